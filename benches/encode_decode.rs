@@ -0,0 +1,54 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use fix_engine_2::clock::Clock;
+use fix_engine_2::message::FixMessage;
+use std::sync::Arc;
+
+struct FixedClock;
+
+impl Clock for FixedClock {
+    fn now(&self) -> String {
+        "20231016-12:30:00.123".to_string()
+    }
+}
+
+fn create_execution_report() -> FixMessage {
+    let mut msg = FixMessage::new();
+    msg.header.insert("8".to_string(), "FIX.4.4".to_string());
+    msg.header.insert("35".to_string(), "8".to_string());
+    msg.header.insert("49".to_string(), "ACCEPTOR".to_string());
+    msg.header.insert("56".to_string(), "INITIATOR".to_string());
+    msg.header.insert("34".to_string(), "2".to_string());
+    msg.header.insert("52".to_string(), "20231016-12:30:00.123".to_string());
+    for (tag, value) in [("37", "ORD-1"), ("11", "CL-1"), ("17", "EXEC-1"), ("150", "F"), ("39", "2"),
+                         ("55", "BTCUSDT"), ("54", "1"), ("38", "100"), ("32", "100"), ("31", "101.25"),
+                         ("151", "0"), ("14", "100"), ("6", "101.25"), ("60", "20231016-12:30:00.120")] {
+        msg.body.insert(tag.to_string(), value.to_string());
+    }
+    msg
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let clock: Arc<dyn Clock> = Arc::new(FixedClock);
+    let mut msg = create_execution_report();
+    c.bench_function("encode_execution_report", |b| b.iter(|| black_box(&mut msg).encode(&clock)));
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let clock: Arc<dyn Clock> = Arc::new(FixedClock);
+    let encoded = create_execution_report().encode(&clock);
+    c.bench_function("decode_execution_report", |b| b.iter(|| FixMessage::decode(black_box(&encoded)).unwrap()));
+}
+
+// Where the checksum pass dominates: a few hundred fields, e.g. a large MarketDataSnapshot
+fn bench_decode_large(c: &mut Criterion) {
+    let clock: Arc<dyn Clock> = Arc::new(FixedClock);
+    let mut msg = create_execution_report();
+    for tag in 5000..5300 {
+        msg.body.insert(tag.to_string(), format!("VALUE-{}", tag));
+    }
+    let encoded = msg.encode(&clock);
+    c.bench_function("decode_large_message", |b| b.iter(|| FixMessage::decode(black_box(&encoded)).unwrap()));
+}
+
+criterion_group!(benches, bench_encode, bench_decode, bench_decode_large);
+criterion_main!(benches);
@@ -0,0 +1,140 @@
+use crate::clock::Clock;
+use crate::engine::FixEngine;
+use crate::engine_factory::FactoryOptions;
+use crate::message::FixMessage;
+use crate::session::{SessionID, SessionState};
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tracing::*;
+
+// How often the accept loop checks for shutdown while no connection is waiting
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(50);
+
+// A session the acceptor started for an incoming connection, with the channels to talk to it
+pub struct NewSession {
+    pub session: SessionHandle,
+    pub peer_addr: SocketAddr,
+    pub sender: Sender<FixMessage>,
+    pub receiver: Receiver<FixMessage>,
+}
+
+// Shared with the acceptor, which shuts every session it started down with it
+#[derive(Clone)]
+pub struct SessionHandle(Arc<Mutex<FixEngine>>);
+
+impl SessionHandle {
+    pub fn state(&self) -> SessionState {
+        self.0.lock().unwrap().state()
+    }
+
+    pub fn session_id(&self) -> SessionID {
+        self.0.lock().unwrap().session_id()
+    }
+
+    pub fn shutdown(&self) {
+        self.0.lock().unwrap().shutdown();
+    }
+}
+
+// Listens until shut down, starting a session for every connection. Each new session is announced on the
+// receiver returned from bind.
+pub struct FixAcceptor {
+    local_addr: SocketAddr,
+    running: Arc<AtomicBool>,
+    sessions: Arc<Mutex<Vec<SessionHandle>>>,
+    accept_thread: Option<thread::JoinHandle<()>>,
+}
+
+impl FixAcceptor {
+    // Binds with the options' socket options and applies them to every connection accepted
+    pub fn bind(address: &str, options: impl Into<FactoryOptions>, clock: Arc<dyn Clock>) -> io::Result<(FixAcceptor, Receiver<NewSession>)> {
+        let options = options.into();
+        let listener = options.engine.bind(address)?;
+        // Non-blocking so the loop notices a shutdown without waiting for one more connection
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+        info!("Acceptor listening on {}", local_addr);
+
+        let running = Arc::new(AtomicBool::new(true));
+        let sessions = Arc::new(Mutex::new(Vec::new()));
+        let (new_sessions, new_session_receiver) = channel();
+        let accept_thread = {
+            let (running, sessions) = (Arc::clone(&running), Arc::clone(&sessions));
+            thread::spawn(move || accept_loop(listener, options, clock, running, sessions, new_sessions))
+        };
+        let acceptor = FixAcceptor { local_addr, running, sessions, accept_thread: Some(accept_thread) };
+        Ok((acceptor, new_session_receiver))
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    // Stops listening, then shuts down every session that is still open
+    pub fn shutdown(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(accept_thread) = self.accept_thread.take() {
+            if let Err(e) = accept_thread.join() {
+                error!("Error joining accept thread: {:?}", e);
+            }
+        }
+        for session in self.sessions.lock().unwrap().drain(..) {
+            session.shutdown();
+        }
+        info!("Acceptor on {} shut down", self.local_addr);
+    }
+}
+
+fn accept_loop(listener: TcpListener, options: FactoryOptions, clock: Arc<dyn Clock>, running: Arc<AtomicBool>,
+               sessions: Arc<Mutex<Vec<SessionHandle>>>, new_sessions: Sender<NewSession>) {
+    while let Some(accepted) = accept_next(&listener, || !running.load(Ordering::SeqCst)) {
+        let (stream, peer_addr) = match accepted {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                error!("Error accepting connection: {:?}", e);
+                thread::sleep(ACCEPT_INTERVAL);
+                continue;
+            }
+        };
+        info!("Acceptor connection from {}", peer_addr);
+        match start_session(stream, &options, Arc::clone(&clock)) {
+            Ok((session, sender, receiver)) => {
+                let mut sessions = sessions.lock().unwrap();
+                // Sessions that have ended on their own no longer need shutting down
+                sessions.retain(|session| session.state() != SessionState::Disconnected);
+                sessions.push(session.clone());
+                if new_sessions.send(NewSession { session, peer_addr, sender, receiver }).is_err() {
+                    warn!("Nobody is taking new sessions; the one from {} runs unattended", peer_addr);
+                }
+            }
+            Err(e) => error!("Failed to start session for {}: {:?}", peer_addr, e),
+        }
+    }
+}
+
+// Waits on a non-blocking listener for the next connection, asking `stop` in between so a shutdown is noticed
+// while nobody connects. None once it says to stop; the stream comes back blocking.
+pub(crate) fn accept_next(listener: &TcpListener, stop: impl Fn() -> bool) -> Option<io::Result<(TcpStream, SocketAddr)>> {
+    while !stop() {
+        match listener.accept() {
+            Ok((stream, peer_addr)) => return Some(stream.set_nonblocking(false).map(|_| (stream, peer_addr))),
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(ACCEPT_INTERVAL),
+            Err(e) => return Some(Err(e)),
+        }
+    }
+    None
+}
+
+fn start_session(stream: TcpStream, options: &FactoryOptions, clock: Arc<dyn Clock>) -> io::Result<(SessionHandle, Sender<FixMessage>, Receiver<FixMessage>)> {
+    options.engine.configure(&stream)?;
+    let (incoming_sender, incoming_receiver) = channel();
+    let mut engine = options.acceptor(clock);
+    let application = options.application(&engine, incoming_sender);
+    let outgoing_sender = engine.start_with_application(stream, application)?;
+    Ok((SessionHandle(Arc::new(Mutex::new(engine))), outgoing_sender, incoming_receiver))
+}
@@ -0,0 +1,98 @@
+use crate::channel::MessageSink;
+use crate::error::DecodeError;
+use crate::message::FixMessage;
+use crate::session::SessionID;
+use crate::tag::MsgType;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::*;
+
+// Returned from FixApplication::to_app to drop an outgoing message; it does not use up a MsgSeqNum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DoNotSend;
+
+// Returned from an incoming interceptor to keep a message from the application
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DoNotDeliver;
+
+// Callbacks are invoked from the engine threads. Admin messages are the session-level ones (Heartbeat,
+// TestRequest, ResendRequest, Reject, SequenceReset, Logout and Logon); everything else goes through the app callbacks.
+#[allow(clippy::wrong_self_convention)] // from_app/from_admin are the usual FIX engine callback names
+pub trait FixApplication: Send + Sync {
+    fn on_logon(&self, _session_id: &SessionID) {}
+    fn on_logout(&self, _session_id: &SessionID) {}
+    fn to_admin(&self, _message: &mut FixMessage, _session_id: &SessionID) {}
+    fn from_admin(&self, _message: &FixMessage, _session_id: &SessionID) {}
+    fn to_app(&self, _message: &mut FixMessage, _session_id: &SessionID) -> Result<(), DoNotSend> {
+        Ok(())
+    }
+    fn from_app(&self, message: &FixMessage, session_id: &SessionID);
+    // Bytes that framed as a message but could not be decoded, e.g. with a bad checksum. They never reach
+    // from_app; the session rejects or discards them itself.
+    fn on_decode_error(&self, _raw: &str, _error: &DecodeError, _session_id: &SessionID) {}
+}
+
+// The channel API of FixEngine::start: application messages are handed on to a receiver
+pub(crate) struct ChannelApplication {
+    incoming: Box<dyn MessageSink>,
+}
+
+impl ChannelApplication {
+    pub(crate) fn new(incoming: impl MessageSink) -> Self {
+        ChannelApplication { incoming: Box::new(incoming) }
+    }
+
+    fn forward(&self, message: &FixMessage) {
+        if !self.incoming.deliver(message.clone()) {
+            error!("Error sending message: the receiver has been dropped or the session stopped");
+        }
+    }
+}
+
+impl FixApplication for ChannelApplication {
+    // Rejects from the peer have always been passed on along with the application messages
+    fn from_admin(&self, message: &FixMessage, _session_id: &SessionID) {
+        if message.header.get("35").map(String::as_str) == Some("3") {
+            self.forward(message);
+        }
+    }
+
+    fn from_app(&self, message: &FixMessage, _session_id: &SessionID) {
+        self.forward(message);
+    }
+}
+
+// A simulated venue for testing clients: every NewOrderSingle is filled in full straight away, the fill being
+// written by the session itself rather than queued behind the application's messages, so the outgoing channel
+// still closes once the application drops its sender. Everything else is passed on like ChannelApplication does.
+pub(crate) struct EchoApplication {
+    channel: ChannelApplication,
+    replies: Box<dyn MessageSink>,
+    fills: AtomicU64,
+}
+
+impl EchoApplication {
+    pub(crate) fn new(incoming: impl MessageSink, replies: impl MessageSink) -> Self {
+        EchoApplication { channel: ChannelApplication::new(incoming), replies: Box::new(replies), fills: AtomicU64::new(0) }
+    }
+}
+
+impl FixApplication for EchoApplication {
+    fn from_admin(&self, message: &FixMessage, session_id: &SessionID) {
+        self.channel.from_admin(message, session_id);
+    }
+
+    fn from_app(&self, message: &FixMessage, session_id: &SessionID) {
+        if message.msg_type_enum() != Some(MsgType::OrderSingle) {
+            return self.channel.from_app(message, session_id);
+        }
+        let fill_number = self.fills.fetch_add(1, Ordering::SeqCst) + 1;
+        match FixMessage::fill(message, &format!("ECHO-{}", fill_number), &format!("ECHO-EXEC-{}", fill_number)) {
+            Ok(fill) => {
+                if !self.replies.deliver(fill) {
+                    error!("Error sending fill: the engine has stopped");
+                }
+            }
+            Err(e) => warn!("Cannot fill order {:?}: {}", message, e),
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use crate::message::FixMessage;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, RecvError, RecvTimeoutError, Sender, TryRecvError, TrySendError};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+// What a send does while the channel is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    // Waits for room, so a consumer that falls behind slows the producer down
+    #[default]
+    Block,
+    // Fails straight away with TrySendError::Full
+    Fail,
+    // Makes room by discarding the oldest message, e.g. for market data where only the latest matters. A
+    // tracked message discarded this way reports SendFailure::Dropped.
+    DropOldest,
+}
+
+// A channel holding at most `capacity` messages; sending blocks while it is full, so a consumer that falls
+// behind slows the producer down instead of letting the queue grow. Both ends report the queue depth.
+pub fn bounded<T>(capacity: usize) -> (BoundedSender<T>, BoundedReceiver<T>) {
+    bounded_with_policy(capacity, OverflowPolicy::Block)
+}
+
+pub fn bounded_with_policy<T>(capacity: usize, policy: OverflowPolicy) -> (BoundedSender<T>, BoundedReceiver<T>) {
+    let queue = Queue { items: VecDeque::new(), senders: 1, receiver: true, waiting: 0, dropped: 0 };
+    let shared = Arc::new(Shared { queue: Mutex::new(queue), changed: Condvar::new(), capacity: capacity.max(1), policy, depth: Arc::default() });
+    (BoundedSender { shared: Arc::clone(&shared) }, BoundedReceiver { shared })
+}
+
+struct Shared<T> {
+    queue: Mutex<Queue<T>>,
+    changed: Condvar, // Signalled whenever a message is added or taken, or either end goes away
+    capacity: usize,
+    policy: OverflowPolicy,
+    depth: Arc<AtomicUsize>, // Queued and waiting messages, for reading without the lock
+}
+
+struct Queue<T> {
+    items: VecDeque<T>,
+    senders: usize,
+    receiver: bool,
+    waiting: usize, // Senders blocked until there is room
+    dropped: usize, // Discarded under DropOldest
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, Queue<T>> {
+        self.queue.lock().unwrap()
+    }
+
+    // Called with the queue still locked after every change to it
+    fn changed(&self, queue: &Queue<T>) {
+        self.depth.store(queue.items.len() + queue.waiting, Ordering::SeqCst);
+        self.changed.notify_all();
+    }
+}
+
+pub struct BoundedSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Clone for BoundedSender<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+        BoundedSender { shared: Arc::clone(&self.shared) }
+    }
+}
+
+impl<T> Drop for BoundedSender<T> {
+    fn drop(&mut self) {
+        let mut queue = self.shared.lock();
+        queue.senders -= 1;
+        self.shared.changed(&queue);
+    }
+}
+
+impl<T> BoundedSender<T> {
+    // While full, blocks, fails or drops the oldest message according to the channel's policy. Fails with
+    // Disconnected once the receiver is gone.
+    pub fn send(&self, value: T) -> Result<(), TrySendError<T>> {
+        self.send_until(value, None)
+    }
+
+    // Like send, but a wait for room under OverflowPolicy::Block gives up after `timeout`, handing the message
+    // back as TrySendError::Full
+    pub fn send_timeout(&self, value: T, timeout: Duration) -> Result<(), TrySendError<T>> {
+        self.send_until(value, Some(Instant::now() + timeout))
+    }
+
+    fn send_until(&self, value: T, deadline: Option<Instant>) -> Result<(), TrySendError<T>> {
+        let mut queue = self.shared.lock();
+        loop {
+            if !queue.receiver {
+                return Err(TrySendError::Disconnected(value));
+            }
+            if queue.items.len() < self.shared.capacity {
+                break;
+            }
+            match self.shared.policy {
+                OverflowPolicy::Block => {
+                    let now = Instant::now();
+                    if deadline.is_some_and(|deadline| now >= deadline) {
+                        // No longer waiting, which the depth has to stop counting
+                        self.shared.depth.store(queue.items.len() + queue.waiting, Ordering::SeqCst);
+                        return Err(TrySendError::Full(value));
+                    }
+                    queue.waiting += 1;
+                    self.shared.changed(&queue);
+                    queue = match deadline {
+                        Some(deadline) => self.shared.changed.wait_timeout(queue, deadline - now).unwrap().0,
+                        None => self.shared.changed.wait(queue).unwrap(),
+                    };
+                    queue.waiting -= 1;
+                }
+                OverflowPolicy::Fail => return Err(TrySendError::Full(value)),
+                OverflowPolicy::DropOldest => {
+                    queue.items.pop_front();
+                    queue.dropped += 1;
+                }
+            }
+        }
+        queue.items.push_back(value);
+        self.shared.changed(&queue);
+        Ok(())
+    }
+
+    // Messages waiting to be received, including any a blocked sender is still handing over
+    pub fn len(&self) -> usize {
+        self.shared.depth.load(Ordering::SeqCst)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Messages discarded to make room under OverflowPolicy::DropOldest
+    pub fn dropped(&self) -> usize {
+        self.shared.lock().dropped
+    }
+}
+
+pub struct BoundedReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Drop for BoundedReceiver<T> {
+    fn drop(&mut self) {
+        let mut queue = self.shared.lock();
+        queue.receiver = false;
+        self.shared.changed(&queue);
+    }
+}
+
+impl<T> BoundedReceiver<T> {
+    pub fn recv(&self) -> Result<T, RecvError> {
+        let mut queue = self.shared.lock();
+        loop {
+            match self.take(&mut queue) {
+                Err(TryRecvError::Empty) => queue = self.shared.changed.wait(queue).unwrap(),
+                result => return result.map_err(|_| RecvError),
+            }
+        }
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut queue = self.shared.lock();
+        loop {
+            match self.take(&mut queue) {
+                Err(TryRecvError::Empty) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(RecvTimeoutError::Timeout);
+                    }
+                    queue = self.shared.changed.wait_timeout(queue, deadline - now).unwrap().0;
+                }
+                Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
+                Ok(value) => return Ok(value),
+            }
+        }
+    }
+
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.take(&mut self.shared.lock())
+    }
+
+    pub fn len(&self) -> usize {
+        self.shared.depth.load(Ordering::SeqCst)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The queue depth, shared so it can still be read once the receiver has been handed to the engine
+    pub(crate) fn depth(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.shared.depth)
+    }
+
+    fn take(&self, queue: &mut Queue<T>) -> Result<T, TryRecvError> {
+        match queue.items.pop_front() {
+            Some(value) => {
+                self.shared.changed(queue);
+                Ok(value)
+            }
+            None if queue.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+}
+
+// Where the engine's send thread takes the application's outgoing messages from
+pub(crate) trait MessageSource: Send + 'static {
+    fn recv_timeout(&self, timeout: Duration) -> Result<FixMessage, RecvTimeoutError>;
+    fn try_recv(&self) -> Result<FixMessage, TryRecvError>;
+    // How many messages are waiting, for a source that keeps count
+    fn depth(&self) -> Option<Arc<AtomicUsize>> {
+        None
+    }
+}
+
+impl MessageSource for Receiver<FixMessage> {
+    fn recv_timeout(&self, timeout: Duration) -> Result<FixMessage, RecvTimeoutError> {
+        Receiver::recv_timeout(self, timeout)
+    }
+
+    fn try_recv(&self) -> Result<FixMessage, TryRecvError> {
+        Receiver::try_recv(self)
+    }
+}
+
+impl MessageSource for BoundedReceiver<FixMessage> {
+    fn recv_timeout(&self, timeout: Duration) -> Result<FixMessage, RecvTimeoutError> {
+        BoundedReceiver::recv_timeout(self, timeout)
+    }
+
+    fn try_recv(&self) -> Result<FixMessage, TryRecvError> {
+        BoundedReceiver::try_recv(self)
+    }
+
+    fn depth(&self) -> Option<Arc<AtomicUsize>> {
+        Some(BoundedReceiver::depth(self))
+    }
+}
+
+// Where inbound application messages are handed on to; a bounded one blocks the receive thread while full.
+// Returns false once the receiving end is gone.
+pub(crate) trait MessageSink: Send + Sync + 'static {
+    fn deliver(&self, message: FixMessage) -> bool;
+}
+
+impl MessageSink for Sender<FixMessage> {
+    fn deliver(&self, message: FixMessage) -> bool {
+        self.send(message).is_ok()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
+
+    #[test]
+    fn test_send_blocks_while_full() {
+        let (sender, receiver) = bounded(1);
+        sender.send(1).unwrap();
+        assert_eq!(receiver.len(), 1);
+
+        let sent = Arc::new(AtomicBool::new(false));
+        let producer = {
+            let sent = Arc::clone(&sent);
+            thread::spawn(move || {
+                sender.send(2).unwrap();
+                sent.store(true, Ordering::SeqCst);
+            })
+        };
+        thread::sleep(Duration::from_millis(100));
+        assert!(!sent.load(Ordering::SeqCst), "second send should wait for room");
+
+        assert_eq!(receiver.recv().unwrap(), 1);
+        producer.join().unwrap();
+        assert!(sent.load(Ordering::SeqCst));
+        assert_eq!(receiver.recv().unwrap(), 2);
+        assert!(receiver.is_empty());
+    }
+
+    #[test]
+    fn test_send_timeout_hands_the_message_back_while_full() {
+        let (sender, receiver) = bounded(1);
+        sender.send(1).unwrap();
+        assert_eq!(sender.send_timeout(2, Duration::from_millis(50)), Err(TrySendError::Full(2)));
+        assert_eq!(sender.len(), 1, "a sender that gave up is no longer counted as waiting");
+        assert_eq!(receiver.recv().unwrap(), 1);
+        sender.send_timeout(2, Duration::from_millis(50)).unwrap();
+        assert_eq!(receiver.recv().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_fail_policy_refuses_while_the_reader_is_stalled() {
+        let (sender, receiver) = bounded_with_policy(2, OverflowPolicy::Fail);
+        sender.send(1).unwrap();
+        sender.send(2).unwrap();
+        assert_eq!(sender.send(3), Err(TrySendError::Full(3)));
+        assert_eq!(sender.len(), 2);
+
+        assert_eq!(receiver.recv().unwrap(), 1);
+        sender.send(3).unwrap();
+        drop(receiver);
+        assert_eq!(sender.send(4), Err(TrySendError::Disconnected(4)));
+    }
+
+    #[test]
+    fn test_drop_oldest_policy_keeps_the_latest_while_the_reader_is_stalled() {
+        let (sender, receiver) = bounded_with_policy(2, OverflowPolicy::DropOldest);
+        for value in 1..=5 {
+            sender.send(value).unwrap();
+        }
+        assert_eq!(sender.dropped(), 3);
+        assert_eq!(receiver.len(), 2);
+
+        drop(sender);
+        assert_eq!(receiver.recv().unwrap(), 4);
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(1)).unwrap(), 5);
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn test_drop_oldest_policy_fails_the_receipt_of_a_tracked_message() {
+        let (sender, receiver) = bounded_with_policy(1, OverflowPolicy::DropOldest);
+        let mut tracked = FixMessage::new();
+        let receipt = tracked.track();
+        sender.send(tracked).unwrap();
+        sender.send(FixMessage::new()).unwrap();
+        assert!(matches!(receipt.try_recv(), Ok(Err(crate::receipt::SendFailure::Dropped))));
+        assert_eq!(receiver.len(), 1);
+    }
+}
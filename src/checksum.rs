@@ -0,0 +1,89 @@
+// Running CheckSum(10): the byte sum modulo 256 of everything before the checksum field.
+// Bytes can be folded in as they arrive, in any split.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Checksum {
+    sum: u8,
+}
+
+impl Checksum {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.sum = bytes.iter().fold(self.sum, |sum, &b| sum.wrapping_add(b));
+    }
+
+    pub fn value(&self) -> u8 {
+        self.sum
+    }
+
+    // Wire form, always three digits, e.g. "009"
+    pub fn finalize(self) -> String {
+        format!("{:03}", self.sum)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChecksumError {
+    // No CheckSum(10) field after an SOH
+    Missing,
+    // Expected is computed over the message; actual is the CheckSum(10) value it carries
+    Mismatch { expected: String, actual: String },
+}
+
+impl std::fmt::Display for ChecksumError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChecksumError::Missing => write!(f, "CheckSum(10) missing"),
+            ChecksumError::Mismatch { expected, actual } => write!(f, "CheckSum(10) mismatch: expected {}, got {}", expected, actual),
+        }
+    }
+}
+
+impl std::error::Error for ChecksumError {}
+
+// Checks the CheckSum(10) of a wire message against its bytes without decoding the rest of it
+pub fn verify_checksum(fix_str: &str) -> Result<(), ChecksumError> {
+    let field_pos = fix_str.rfind("\x0110=").ok_or(ChecksumError::Missing)? + 1;
+    let actual = fix_str[field_pos + 3..].trim_end_matches('\x01');
+    let mut checksum = Checksum::new();
+    checksum.update(&fix_str.as_bytes()[..field_pos]);
+    let expected = checksum.finalize();
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(ChecksumError::Mismatch { expected, actual: actual.to_string() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_folding_halves_matches_one_shot() {
+        let message = b"8=FIX.4.4\x019=59\x0135=A\x0149=SENDER\x0156=TARGET\x0134=1\x0152=20231016-12:30:00.123\x0198=0\x01108=30\x01";
+        let mut one_shot = Checksum::new();
+        one_shot.update(message);
+
+        for split in [0, 1, 37, message.len()] {
+            let (first, second) = message.split_at(split);
+            let mut folded = Checksum::new();
+            folded.update(first);
+            folded.update(second);
+            assert_eq!(folded, one_shot);
+        }
+        assert_eq!(one_shot.finalize(), "119");
+    }
+
+    #[test]
+    fn test_verify_checksum() {
+        let message = "8=FIX.4.4\x019=59\x0135=A\x0149=SENDER\x0156=TARGET\x0134=1\x0152=20231016-12:30:00.123\x0198=0\x01108=30\x0110=119\x01";
+        assert_eq!(verify_checksum(message), Ok(()));
+
+        let tampered = message.replace("108=30", "108=31");
+        assert_eq!(verify_checksum(&tampered), Err(ChecksumError::Mismatch { expected: "120".to_string(), actual: "119".to_string() }));
+        assert_eq!(verify_checksum("8=FIX.4.4\x0135=0\x01"), Err(ChecksumError::Missing));
+    }
+}
@@ -1,5 +1,28 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::thread;
+use std::time::Duration;
+
+// Wire format of UTCTimestamp fields such as SendingTime(52)
+pub const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H:%M:%S%.3f";
+
+// Reads a UTCTimestamp as peers may send it, with any number of fractional digits or none
+pub(crate) const TIMESTAMP_PARSE_FORMAT: &str = "%Y%m%d-%H:%M:%S%.f";
+
 pub trait Clock: Send + Sync {
     fn now(&self) -> String;
+
+    // Used by the session timers; by default derived from `now` so a test clock only has to implement that
+    fn now_utc(&self) -> DateTime<Utc> {
+        NaiveDateTime::parse_from_str(&self.now(), TIMESTAMP_FORMAT)
+            .map(|timestamp| timestamp.and_utc())
+            .unwrap_or_else(|_| Utc::now())
+    }
+
+    // Waits for the duration to pass by this clock, e.g. for the throttle's next token. A clock that is moved by
+    // hand may return as soon as it has been moved far enough.
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
 }
 
 #[derive(Debug)]
@@ -7,7 +30,28 @@ pub struct RealClock;
 
 impl Clock for RealClock {
     fn now(&self) -> String {
-        let now = chrono::Utc::now();
-        format!("{}", now.format("%Y%m%d-%H:%M:%S%.3f"))
+        format!("{}", self.now_utc().format(TIMESTAMP_FORMAT))
+    }
+
+    fn now_utc(&self) -> DateTime<Utc> {
+        Utc::now()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedClock;
+
+    impl Clock for FixedClock {
+        fn now(&self) -> String {
+            "20231016-12:30:00.123".to_string()
+        }
+    }
+
+    #[test]
+    fn test_now_utc_parses_now() {
+        assert_eq!(FixedClock.now_utc().format(TIMESTAMP_FORMAT).to_string(), "20231016-12:30:00.123");
+    }
+}
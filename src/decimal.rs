@@ -0,0 +1,226 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Add;
+use std::str::FromStr;
+
+// Largest number of digits allowed after the decimal point
+pub const MAX_SCALE: u32 = 18;
+
+// A decimal stored as `mantissa * 10^-scale`, so wire values like "0.3" stay exact.
+#[derive(Debug, Clone, Copy)]
+pub struct FixDecimal {
+    mantissa: i64,
+    scale: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecimalFormat {
+    pub trim_trailing_zeros: bool,
+    pub max_precision: u32,
+}
+
+impl Default for DecimalFormat {
+    fn default() -> Self {
+        DecimalFormat {
+            trim_trailing_zeros: true,
+            max_precision: MAX_SCALE,
+        }
+    }
+}
+
+impl FixDecimal {
+    pub fn new(mantissa: i64, scale: u32) -> Result<Self, &'static str> {
+        if scale > MAX_SCALE {
+            return Err("Decimal scale exceeds maximum precision");
+        }
+        Ok(FixDecimal { mantissa, scale })
+    }
+
+    pub fn mantissa(&self) -> i64 {
+        self.mantissa
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    pub fn checked_add(self, other: FixDecimal) -> Option<FixDecimal> {
+        let scale = self.scale.max(other.scale);
+        let lhs = self.mantissa.checked_mul(10i64.pow(scale - self.scale))?;
+        let rhs = other.mantissa.checked_mul(10i64.pow(scale - other.scale))?;
+        Some(FixDecimal { mantissa: lhs.checked_add(rhs)?, scale })
+    }
+
+    // Removes trailing fractional zeros, e.g. 1.2500 becomes 1.25
+    pub fn normalize(self) -> FixDecimal {
+        let mut result = self;
+        while result.scale > 0 && result.mantissa % 10 == 0 {
+            result.mantissa /= 10;
+            result.scale -= 1;
+        }
+        result
+    }
+
+    // Rounds half away from zero to at most `precision` fractional digits
+    pub fn round_dp(self, precision: u32) -> FixDecimal {
+        if self.scale <= precision {
+            return self;
+        }
+        let divisor = 10i64.pow(self.scale - precision);
+        let quotient = self.mantissa / divisor;
+        let remainder = (self.mantissa % divisor).abs();
+        let rounded = if remainder * 2 >= divisor { quotient + self.mantissa.signum() } else { quotient };
+        FixDecimal { mantissa: rounded, scale: precision }
+    }
+
+    pub fn format(&self, format: &DecimalFormat) -> String {
+        let mut value = self.round_dp(format.max_precision);
+        if format.trim_trailing_zeros {
+            value = value.normalize();
+        }
+        value.to_string()
+    }
+
+    fn scaled_to(&self, scale: u32) -> i128 {
+        self.mantissa as i128 * 10i128.pow(scale - self.scale)
+    }
+}
+
+impl FromStr for FixDecimal {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (negative, digits) = match value.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, value),
+        };
+        let (integer, fraction) = match digits.split_once('.') {
+            Some((integer, fraction)) => (integer, fraction),
+            None => (digits, ""),
+        };
+
+        if integer.is_empty() && fraction.is_empty() {
+            return Err("Empty decimal value");
+        }
+        // Only plain digits are allowed; this rejects exponent notation such as "1e-5"
+        if !integer.bytes().chain(fraction.bytes()).all(|b| b.is_ascii_digit()) {
+            return Err("Invalid decimal value");
+        }
+        if fraction.len() as u32 > MAX_SCALE {
+            return Err("Decimal scale exceeds maximum precision");
+        }
+
+        let mut mantissa: i64 = 0;
+        for b in integer.bytes().chain(fraction.bytes()) {
+            mantissa = mantissa
+                .checked_mul(10)
+                .and_then(|m| m.checked_add((b - b'0') as i64))
+                .ok_or("Decimal value out of range")?;
+        }
+
+        Ok(FixDecimal {
+            mantissa: if negative { -mantissa } else { mantissa },
+            scale: fraction.len() as u32,
+        })
+    }
+}
+
+impl fmt::Display for FixDecimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.mantissa < 0 {
+            f.write_str("-")?;
+        }
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return f.write_str(&digits);
+        }
+        if digits.len() > scale {
+            let (integer, fraction) = digits.split_at(digits.len() - scale);
+            write!(f, "{}.{}", integer, fraction)
+        } else {
+            write!(f, "0.{}{}", "0".repeat(scale - digits.len()), digits)
+        }
+    }
+}
+
+impl Add for FixDecimal {
+    type Output = FixDecimal;
+
+    fn add(self, other: FixDecimal) -> FixDecimal {
+        self.checked_add(other).expect("FixDecimal addition overflowed")
+    }
+}
+
+impl PartialEq for FixDecimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for FixDecimal {}
+
+impl PartialOrd for FixDecimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FixDecimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let scale = self.scale.max(other.scale);
+        self.scaled_to(scale).cmp(&other.scaled_to(scale))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(value: &str) -> FixDecimal {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_round_trip_awkward_values() {
+        for value in ["0.00001", "123456789.123456", "0", "-0.5", "100", "0.30", "-42.000001"] {
+            assert_eq!(dec(value).to_string(), value);
+        }
+    }
+
+    #[test]
+    fn test_rejects_invalid_values() {
+        for value in ["1e-5", "1E5", "", ".", "-", "1.2.3", "+1", " 1", "abc", "0.1234567890123456789"] {
+            assert!(value.parse::<FixDecimal>().is_err(), "{} should be rejected", value);
+        }
+        assert!("99999999999999999999".parse::<FixDecimal>().is_err());
+    }
+
+    #[test]
+    fn test_add_avoids_binary_floating_point_error() {
+        let sum = dec("0.1") + dec("0.2");
+        assert_eq!(sum.to_string(), "0.3");
+        assert_eq!(sum, dec("0.30"));
+        assert_eq!((dec("1.5") + dec("-2.25")).to_string(), "-0.75");
+    }
+
+    #[test]
+    fn test_compare_across_scales() {
+        assert!(dec("1.5") > dec("1.49"));
+        assert!(dec("-0.01") < dec("0"));
+        assert_eq!(dec("2.500"), dec("2.5"));
+    }
+
+    #[test]
+    fn test_format_never_uses_exponent_notation() {
+        let tiny = FixDecimal::new(1, 18).unwrap();
+        assert_eq!(tiny.to_string(), "0.000000000000000001");
+
+        let value = dec("101.250000");
+        assert_eq!(value.format(&DecimalFormat::default()), "101.25");
+        assert_eq!(value.format(&DecimalFormat { trim_trailing_zeros: false, max_precision: 4 }), "101.2500");
+        assert_eq!(dec("1.23456").format(&DecimalFormat { trim_trailing_zeros: true, max_precision: 2 }), "1.23");
+        assert_eq!(dec("-1.235").format(&DecimalFormat { trim_trailing_zeros: true, max_precision: 2 }), "-1.24");
+        assert_eq!(dec("100.00").format(&DecimalFormat::default()), "100");
+    }
+}
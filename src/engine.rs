@@ -1,145 +1,679 @@
-use crate::message::FixMessage;
-use std::io::{Read, Write};
-use std::net::TcpStream;
-use std::sync::mpsc::{Receiver, Sender};
-use std::sync::{Arc};
+use crate::acceptor::accept_next;
+use crate::application::{ChannelApplication, EchoApplication, FixApplication};
+use crate::channel::{BoundedReceiver, BoundedSender, MessageSink, MessageSource};
+use crate::message::{DecodeOptions, FixMessage};
+use std::collections::VecDeque;
+use std::io::Read;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender, TrySendError};
+use std::net::{SocketAddr, TcpListener};
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tracing::*;
 use crate::clock::Clock;
-use crate::tag::SOH;
+use crate::engine_config::EngineConfig;
+use crate::error::{DecodeError, EngineError};
+use crate::event::{DisconnectReason, EngineEvent};
+use crate::framer::MessageFramer;
+use crate::message_log::MessageLog;
+use crate::metrics::{MetricsSink, SessionMetricsSnapshot};
+use crate::observer::{EngineObserver, NoopObserver};
+use crate::receipt::SendFailure;
+use crate::reconnect::{QueuePolicy, ReconnectPolicy};
+use crate::throttle::{ThrottleSaturation, TokenBucket};
+use crate::session::{IncomingInterceptor, LogonValidator, OutgoingInterceptor, Session, SessionConfig, SessionID, SessionState};
+use crate::store::MessageStore;
+use crate::transport::Transport;
 
-#[derive(Debug, Clone)]
+// How often the receive thread wakes up to run the heartbeat timers when the line is quiet
+const TIMER_INTERVAL: Duration = Duration::from_millis(100);
+
+// Opens a new connection to the same counterparty when the old one is lost
+type Connector = Box<dyn FnMut() -> std::io::Result<Box<dyn Transport>> + Send>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FixEngineMode {
     Initiator,
     Acceptor
 }
 
+// What became of the messages the application had queued when the engine was shut down
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ShutdownReport {
+    pub flushed: usize, // Sent before the session was logged out
+    pub dropped: usize, // Never sent, because the drain timeout passed or the session could not send them
+}
+
 pub struct FixEngine {
-    clock: Arc<dyn Clock>,
-    engine_mode: FixEngineMode, // No 'static lifetime constraint
-    is_running: Arc<AtomicBool>, // Use AtomicBool instead of Arc<Mutex<bool>>
-    send_thread: Option<thread::JoinHandle<()>>,
-    receive_thread: Option<thread::JoinHandle<()>>,
+    session: Arc<Session>, // Shared with the send and receive threads
+    event_receiver: Option<Receiver<EngineEvent>>,
+    threads: Arc<Mutex<EngineThreads>>, // Filled in by the accept thread when the engine waits for its connection
+    accept: Option<(Arc<AtomicBool>, thread::JoinHandle<()>)>, // Cancel flag and thread while a connection is awaited
+    local_addr: Option<SocketAddr>,
+}
+
+// Hands inbound messages to a bounded channel from the receive thread. While the channel is full it waits in
+// short steps, running the session's heartbeat in between, and gives up once the session stops.
+struct BoundedDelivery {
+    incoming: BoundedSender<FixMessage>,
+    session: Weak<Session>,
+}
+
+impl MessageSink for BoundedDelivery {
+    fn deliver(&self, mut message: FixMessage) -> bool {
+        loop {
+            match self.incoming.send_timeout(message, TIMER_INTERVAL) {
+                Ok(()) => return true,
+                Err(TrySendError::Full(returned)) => {
+                    if !self.session.upgrade().is_some_and(|session| session.keep_alive()) {
+                        warn!("Dropping an inbound message the application had no room for at shutdown");
+                        return false;
+                    }
+                    message = returned;
+                }
+                Err(TrySendError::Disconnected(_)) => return false,
+            }
+        }
+    }
+}
+
+// Writes a reply straight from the application callback that made it, as the session does its own admin replies
+struct SessionReply(Weak<Session>);
+
+impl MessageSink for SessionReply {
+    fn deliver(&self, message: FixMessage) -> bool {
+        self.0.upgrade().is_some_and(|session| session.send(message).is_ok())
+    }
+}
+
+#[derive(Default)]
+struct EngineThreads {
+    send: Option<thread::JoinHandle<ShutdownReport>>,
+    receive: Option<thread::JoinHandle<()>>,
 }
 
 impl FixEngine {
-    pub fn new(clock: Arc<dyn Clock>, engine_mode: FixEngineMode) -> FixEngine {
+    pub fn new(clock: Arc<dyn Clock>, engine_mode: FixEngineMode, config: SessionConfig) -> FixEngine {
+        Self::with_observer(clock, engine_mode, config, Arc::new(NoopObserver))
+    }
+
+    pub fn with_observer(clock: Arc<dyn Clock>, engine_mode: FixEngineMode, config: SessionConfig, observer: Arc<dyn EngineObserver>) -> FixEngine {
+        let (event_sender, event_receiver) = channel();
         FixEngine {
-            clock,
-            engine_mode,
-            is_running: Arc::new(AtomicBool::new(true)), // Use AtomicBool
-            send_thread: None,
-            receive_thread: None,
+            session: Arc::new(Session::new(config, engine_mode, clock, observer, event_sender)),
+            event_receiver: Some(event_receiver),
+            threads: Arc::default(),
+            accept: None,
+            local_addr: None,
         }
     }
 
-    pub fn start(&mut self, mut stream: TcpStream, outgoing_receiver: Receiver<FixMessage>, incoming_sender: Sender<FixMessage>) -> std::io::Result<()> {
+    // Hands the session event stream to the caller; only the first call gets it.
+    pub fn take_events(&mut self) -> Option<Receiver<EngineEvent>> {
+        self.event_receiver.take()
+    }
 
-        // Receiver thread (reads from TCP stream)
-        let clock = Arc::clone(&self.clock);
-        let mode = self.engine_mode.clone();
-        let stream_clone = stream.try_clone()?;
-        let is_running_receive_thread = Arc::clone(&self.is_running);
+    pub fn state(&self) -> SessionState {
+        self.session.state()
+    }
 
-        self.receive_thread = Some(thread::spawn(move || {
-            info!("{:?}: Ready to receive messages.", mode);
-            let mut buffer = vec![];
-            let mut stream_reader = stream_clone;
-            if let Err(e) = stream_reader.set_read_timeout(Some(Duration::from_secs(1))) {
-                error!("{:?}: Error setting read timeout: {:?}", mode, e);
-                return;
-            }
+    pub fn session_id(&self) -> SessionID {
+        self.session.session_id()
+    }
 
-            while is_running_receive_thread.load(Ordering::Relaxed) {
-                let mut tmp_buf = [0; 1024];
-                match stream_reader.read(&mut tmp_buf) {
-                    Ok(size) => {
-                        if size > 0 {
-                            buffer.extend_from_slice(&tmp_buf[..size]);
-
-                            if let Some((message_str, remaining)) = extract_message(&buffer) {
-                                if let Ok(fix_message) = FixMessage::decode(&message_str) {
-                                    info!("{:?}: Received message {:?}", mode, fix_message);
-                                    if let Err(e) = incoming_sender.send(fix_message) {
-                                        error!("{:?}: Error sending message: {:?}", mode, e);
-                                    }
-                                }
-                                buffer = remaining;
-                            }
-                        }
-                    },
-                    Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock || e.kind() == std::io::ErrorKind::TimedOut => {
-                        if !is_running_receive_thread.load(Ordering::Relaxed) {
-                            info!("{:?}: Shutdown signal received, exiting receive thread.", mode);
-                            break;
-                        }
-                    }
-                    Err(e) => {
-                        error!("{:?}: Error reading from stream: {:?}", mode, e);
-                        break;
+    // Acceptor only: a logon the validator refuses is answered with a Logout carrying its reason, and the
+    // connection is dropped without the application seeing anything
+    pub fn set_logon_validator(&self, validator: LogonValidator) {
+        self.session.set_logon_validator(validator);
+    }
+
+    // Stamps or filters every outgoing message, e.g. adding a venue's Account(1) and custom tags, without each
+    // sender remembering to. A message it drops takes no MsgSeqNum and is reported as OutgoingDropped.
+    pub fn set_outgoing_interceptor(&self, interceptor: OutgoingInterceptor) {
+        self.session.set_outgoing_interceptor(interceptor);
+    }
+
+    // Keeps inbound messages from the application, each reported as IncomingDropped instead
+    pub fn set_incoming_interceptor(&self, interceptor: IncomingInterceptor) {
+        self.session.set_incoming_interceptor(interceptor);
+    }
+
+    // MsgSeqNum(34) the next outgoing message will carry; the engine stamps it on every message it sends
+    pub fn next_sender_seq_num(&self) -> u64 {
+        self.session.next_sender_seq_num()
+    }
+
+    // Sends a ResendRequest for begin_seq_no to end_seq_no, or to the latest with an end of 0, e.g. for a gap
+    // reported while SessionConfig::resend_on_gap is unset
+    pub fn request_resend(&self, begin_seq_no: u64, end_seq_no: u64) {
+        self.session.request_resend(begin_seq_no, end_seq_no);
+    }
+
+    // PossDup messages dropped because their MsgSeqNum had already been processed
+    pub fn duplicates_suppressed(&self) -> u64 {
+        self.session.duplicates_suppressed()
+    }
+
+    // Whether the send thread is still taking messages from the application; it stops once the outgoing
+    // channel's senders are all dropped
+    pub fn is_sending(&self) -> bool {
+        self.threads.lock().unwrap().send.as_ref().is_some_and(|send_thread| !send_thread.is_finished())
+    }
+
+    // Whether the receive thread is still reading; it stops once the connection is closed from either end
+    pub fn is_receiving(&self) -> bool {
+        self.threads.lock().unwrap().receive.as_ref().is_some_and(|receive_thread| !receive_thread.is_finished())
+    }
+
+    // Messages waiting in a bounded outgoing channel for the send thread; None for an unbounded one
+    pub fn outgoing_queue_len(&self) -> Option<usize> {
+        self.session.metrics().queue_depth()
+    }
+
+    // Traffic counters and send latencies since the engine was created
+    pub fn metrics(&self) -> SessionMetricsSnapshot {
+        self.session.metrics().snapshot()
+    }
+
+    // Pushes a snapshot of the metrics every SessionConfig::metrics_interval while the engine runs, and a last
+    // one when it shuts down
+    pub fn set_metrics_sink(&self, sink: Box<dyn MetricsSink>) {
+        self.session.set_metrics_sink(sink);
+    }
+
+    // The address of the connection the session is on, or was last on; an initiator with failover addresses
+    // reports which one it reached
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.session.peer_addr()
+    }
+
+    // The address being listened on, for an engine started by the factory to wait for its connection
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    // Replaces the default in-memory store; call before start so the session picks up its sequence numbers
+    pub fn set_message_store(&self, store: Box<dyn MessageStore>) {
+        self.session.set_message_store(store);
+    }
+
+    // The write timeout and other limits the engine applies to its connection itself; call before start. The
+    // factory passes on the one in its FactoryOptions.
+    pub fn set_engine_config(&self, engine_config: EngineConfig) {
+        self.session.set_engine_config(engine_config);
+    }
+
+    // Records every message sent and received, byte for byte; call before start so the logon is logged too
+    pub fn set_message_log(&self, log: Box<dyn MessageLog>) {
+        self.session.set_message_log(log);
+    }
+
+    // A message this engine sent with the given MsgSeqNum, while the store still has it
+    pub fn sent_message(&self, seq_num: u64) -> Option<FixMessage> {
+        self.session.sent_message(seq_num)
+    }
+
+    pub fn reset_sender_seq_num(&self) {
+        self.session.reset_sender_seq_num();
+    }
+
+    // Out-of-band reset agreed with the counterparty: both directions continue from 1 and nothing sent
+    // before the reset can be resent
+    pub fn reset_sequence_numbers(&self) {
+        self.session.reset_seq_nums();
+    }
+
+    // Application messages only flow through the channels once the logon handshake has completed.
+    pub fn start<S: Transport>(&mut self, stream: S, outgoing_receiver: Receiver<FixMessage>, incoming_sender: Sender<FixMessage>) -> std::io::Result<()> {
+        self.run(stream, outgoing_receiver, Arc::new(ChannelApplication::new(incoming_sender)))
+    }
+
+    // Like start, over bounded channels: the receive thread waits while the application has `incoming`'s
+    // capacity of messages still to take, rather than queueing without limit.
+    pub fn start_bounded<S: Transport>(&mut self, stream: S, outgoing_receiver: BoundedReceiver<FixMessage>, incoming_sender: BoundedSender<FixMessage>) -> std::io::Result<()> {
+        let application = Arc::new(ChannelApplication::new(self.bounded_delivery(incoming_sender)));
+        self.run(stream, outgoing_receiver, application)
+    }
+
+    // Passes inbound messages on through `incoming`, waiting for room without holding up the heartbeats or
+    // a shutdown
+    pub(crate) fn bounded_delivery(&self, incoming: BoundedSender<FixMessage>) -> impl MessageSink {
+        BoundedDelivery { incoming, session: Arc::downgrade(&self.session) }
+    }
+
+    // Fills every NewOrderSingle in full and passes everything else on through `incoming`; see EchoApplication
+    pub(crate) fn echo_application(&self, incoming: impl MessageSink) -> Arc<dyn FixApplication> {
+        Arc::new(EchoApplication::new(incoming, SessionReply(Arc::downgrade(&self.session))))
+    }
+
+    // Delivers inbound messages and session changes to the application's callbacks on the engine threads.
+    // Messages to send go into the returned channel.
+    pub fn start_with_application<S: Transport>(&mut self, stream: S, application: Arc<dyn FixApplication>) -> std::io::Result<Sender<FixMessage>> {
+        let (outgoing_sender, outgoing_receiver) = channel();
+        self.run(stream, outgoing_receiver, application)?;
+        Ok(outgoing_sender)
+    }
+
+    // Initiator only: when the connection is lost, `connect` is called again as the config's ReconnectPolicy
+    // allows and the session logs back on from the sequence numbers it stopped at. Without a policy this is start.
+    pub fn start_reconnecting<S, F>(&mut self, stream: S, mut connect: F, outgoing_receiver: Receiver<FixMessage>, incoming_sender: Sender<FixMessage>) -> std::io::Result<()>
+    where
+        S: Transport,
+        F: FnMut() -> std::io::Result<S> + Send + 'static,
+    {
+        let connector: Connector = Box::new(move || connect().map(|stream| Box::new(stream) as Box<dyn Transport>));
+        let application = Arc::new(ChannelApplication::new(incoming_sender));
+        *self.threads.lock().unwrap() = spawn_threads(&self.session, stream, Some(connector), outgoing_receiver, application)?;
+        Ok(())
+    }
+
+    fn run<S: Transport, Q: MessageSource>(&mut self, stream: S, outgoing_receiver: Q, application: Arc<dyn FixApplication>) -> std::io::Result<()> {
+        self.session.metrics().set_queue_depth(outgoing_receiver.depth());
+        *self.threads.lock().unwrap() = spawn_threads(&self.session, stream, None, outgoing_receiver, application)?;
+        Ok(())
+    }
+
+    // Waits for the connection on a background thread and starts the session once it arrives, so the caller
+    // is not held up in accept
+    pub(crate) fn run_on_accept<Q: MessageSource>(&mut self, listener: TcpListener, engine_config: EngineConfig, outgoing_receiver: Q, application: Arc<dyn FixApplication>) -> std::io::Result<()> {
+        // Non-blocking so shutdown is noticed while nobody has connected yet
+        listener.set_nonblocking(true)?;
+        self.local_addr = Some(listener.local_addr()?);
+        self.session.metrics().set_queue_depth(outgoing_receiver.depth());
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let (session, threads) = (Arc::clone(&self.session), Arc::clone(&self.threads));
+        let accept_cancelled = Arc::clone(&cancelled);
+
+        let accept_thread = thread::spawn(move || {
+            let mode = session.mode;
+            let deadline = engine_config.accept_timeout.map(|timeout| (Instant::now() + timeout, timeout));
+            let timed_out = || deadline.is_some_and(|(deadline, _)| Instant::now() >= deadline);
+            let stream = match accept_next(&listener, || accept_cancelled.load(Ordering::SeqCst) || timed_out()) {
+                Some(Ok((stream, peer_addr))) => {
+                    info!("{:?}: Connection from {}", mode, peer_addr);
+                    stream
+                }
+                Some(Err(e)) => {
+                    session.start_failed(EngineError::StartFailed { reason: format!("Error accepting connection: {}", e) });
+                    return;
+                }
+                None => {
+                    if let Some((_, timeout)) = deadline.filter(|_| !accept_cancelled.load(Ordering::SeqCst)) {
+                        session.start_failed(EngineError::AcceptTimeout { timeout });
                     }
+                    return;
                 }
+            };
+            let started = engine_config.configure(&stream)
+                .and_then(|_| spawn_threads(&session, stream, None, outgoing_receiver, application));
+            match started {
+                Ok(started) => *threads.lock().unwrap() = started,
+                Err(e) => session.start_failed(EngineError::StartFailed { reason: e.to_string() }),
             }
-        }));
-
-        // Sender thread (writes to TCP stream)
-        let mode = self.engine_mode.clone();
-        let is_running_send_thread = Arc::clone(&self.is_running);
-
-        self.send_thread = Some(thread::spawn(move || {
-            info!("{:?}: Ready to send messages.", mode);
-            while is_running_send_thread.load(Ordering::Relaxed) {
-                if let Ok(mut message) = outgoing_receiver.recv_timeout(Duration::from_secs(1)) {
-                    info!("{:?}: Sending message {:?}", mode, message);
-                    let message_str = message.encode(&clock);
-                    if let Err(e) = stream.write_all(message_str.as_bytes()) {
-                        error!("{:?}: Error writing to stream: {:?}", mode, e);
+        });
+        self.accept = Some((cancelled, accept_thread));
+        Ok(())
+    }
+
+    pub fn shutdown(&mut self) {
+        self.shutdown_with_timeout(self.session.config.drain_timeout);
+    }
+
+    // Sends what the application queued before asking to stop, for up to drain_timeout, then logs out if the
+    // session was logged on and closes the connection
+    pub fn shutdown_with_timeout(&mut self, drain_timeout: Duration) -> ShutdownReport {
+        let mode = self.session.mode;
+        info!("{:?}: Shutting down.", mode);
+        // Once the accept thread is gone no session can start behind our back
+        if let Some((cancelled, accept_thread)) = self.accept.take() {
+            cancelled.store(true, Ordering::SeqCst);
+            if let Err(e) = accept_thread.join() {
+                error!("{:?}: Error joining accept thread: {:?}", mode, e);
+            }
+        }
+        self.session.stop(drain_timeout);
+
+        let threads = std::mem::take(&mut *self.threads.lock().unwrap());
+        let mut report = ShutdownReport::default();
+        if let Some(tx_thread) = threads.send {
+            match tx_thread.join() {
+                Ok(send_report) => report = send_report,
+                Err(e) => error!("{:?}: Error joining tx_thread: {:?}", mode, e),
+            }
+        }
+
+        if let Some(rx_thread) = threads.receive {
+            if let Err(e) = rx_thread.join() {
+                error!("{:?}: Error joining rx_thread: {:?}", mode, e);
+            }
+        }
+
+        self.session.close(DisconnectReason::Shutdown);
+        self.session.shut_down();
+        info!("{:?}: Fully shut down, {:?}.", mode, report);
+        report
+    }
+}
+
+// Sets the session up on a connected transport and starts its receive and send threads. Either both threads
+// are running afterwards or neither is, with the session left disconnected so the engine can be started again.
+fn spawn_threads<S: Transport, Q: MessageSource>(session: &Arc<Session>, stream: S, connector: Option<Connector>, outgoing_receiver: Q, application: Arc<dyn FixApplication>) -> std::io::Result<EngineThreads> {
+    let stream_reader = stream.try_clone().map_err(|e| setup_error("cloning the transport for the receive thread", e))?;
+    set_read_timeout(stream_reader.as_ref()).map_err(|e| setup_error("setting the read timeout", e))?;
+    session.set_application(application);
+    if let Err(e) = session.on_connected(Box::new(stream)) {
+        session.close(DisconnectReason::Error);
+        return Err(setup_error("opening the session", e));
+    }
+
+    let mode = session.mode;
+    let receive_session = Arc::clone(session);
+    let receive_thread = thread::Builder::new().name(format!("{:?} receive", mode));
+    let receive = match (connector, session.config.reconnect.clone()) {
+        (Some(connector), Some(policy)) if mode == FixEngineMode::Initiator => {
+            session.set_reconnect(true);
+            receive_thread.spawn(move || reconnect_loop(receive_session, stream_reader, connector, policy))
+        }
+        _ => receive_thread.spawn(move || receive_loop(receive_session, stream_reader)),
+    };
+    let receive = receive.inspect_err(|_| session.close(DisconnectReason::Error)).map_err(|e| setup_error("spawning the receive thread", e))?;
+
+    let send_session = Arc::clone(session);
+    match thread::Builder::new().name(format!("{:?} send", mode)).spawn(move || send_loop(send_session, outgoing_receiver)) {
+        Ok(send) => Ok(EngineThreads { send: Some(send), receive: Some(receive) }),
+        Err(e) => {
+            session.stop(Duration::ZERO);
+            session.close(DisconnectReason::Error);
+            if receive.join().is_err() {
+                error!("{:?}: Error joining rx_thread", mode);
+            }
+            Err(setup_error("spawning the send thread", e))
+        }
+    }
+}
+
+fn setup_error(step: &str, error: std::io::Error) -> std::io::Error {
+    std::io::Error::new(error.kind(), format!("Failed {}: {}", step, error))
+}
+
+// Reads wait at most a timer interval, so the receive thread runs its timers and notices a shutdown
+fn set_read_timeout(stream_reader: &dyn Transport) -> std::io::Result<()> {
+    stream_reader.set_read_timeout(Some(TIMER_INTERVAL))
+}
+
+// Reads from the stream, runs the session timers and hands complete messages to the session
+fn receive_loop(session: Arc<Session>, mut stream_reader: Box<dyn Transport>) {
+    let mode = session.mode;
+    info!("{:?}: Ready to receive messages.", mode);
+    let mut framer = MessageFramer::with_framer(session.config.framing.framer(session.config.max_message_size));
+    // Raw bytes are kept so rejected messages can be reported as they arrived
+    let decode_options = DecodeOptions { retain_raw: true, header_layout: Some(&session.config.header_layout), ..DecodeOptions::default() };
+    // An empty buffer would read nothing, which looks just like the peer closing the connection
+    let mut tmp_buf = vec![0; session.read_chunk_size().max(1)];
+
+    'receive: while session.is_receiving() {
+        if let Err(e) = session.check_timers() {
+            session.disconnect(e);
+            break;
+        }
+        session.publish_metrics(false);
+
+        match stream_reader.read(&mut tmp_buf) {
+            Ok(size) => {
+                if size == 0 {
+                    info!("{:?}: Connection closed by peer.", mode);
+                    session.close(DisconnectReason::PeerClosed);
+                    break;
+                }
+                session.log_incoming(&tmp_buf[..size]);
+                session.metrics().record_bytes_in(size);
+                framer.push(&tmp_buf[..size]);
+                // Everything completed by this read arrived together
+                let received_at = session.clock.now_utc();
+
+                // A single read can carry several messages, e.g. a logon followed by an order
+                while let Some(frame) = framer.next_message() {
+                    let result = match std::str::from_utf8(&frame) {
+                        Ok(message_str) => match FixMessage::decode_with_options(message_str, &decode_options) {
+                            Ok(mut fix_message) => {
+                                fix_message.set_received_at(received_at);
+                                info!("{:?}: Received message {:?}", mode, fix_message);
+                                session.handle_incoming(fix_message)
+                            }
+                            Err(e) => session.handle_garbled(message_str, e),
+                        },
+                        Err(_) => session.handle_garbled(&String::from_utf8_lossy(&frame), DecodeError::InvalidUtf8),
+                    };
+                    if let Err(e) = result {
+                        session.disconnect(e);
+                        break 'receive;
                     }
                 }
 
-                if !is_running_send_thread.load(Ordering::Relaxed) {
-                    info!("{:?}: Shutdown signal received, exiting send thread.", mode);
+                // A peer that never finishes a message must not grow the buffer without bound
+                let limit = session.config.max_message_size;
+                if framer.len() > limit {
+                    session.disconnect(EngineError::MessageTooLarge { size: framer.len(), limit });
+                    break;
+                }
+            },
+            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock || e.kind() == std::io::ErrorKind::TimedOut => {
+                if !session.is_receiving() {
+                    info!("{:?}: Shutdown signal received, exiting receive thread.", mode);
                     break;
                 }
             }
-        }));
+            Err(e) => {
+                error!("{:?}: Error reading from stream", mode);
+                session.disconnect_io(e);
+                break;
+            }
+        }
+    }
+}
 
-        Ok(())
+// Runs the receive loop over one connection after another, re-establishing each one that is lost. The
+// backoff only starts over once a connection has got as far as a completed logon, so a venue refusing the
+// logon is not retried at the initial delay forever.
+fn reconnect_loop(session: Arc<Session>, mut stream_reader: Box<dyn Transport>, mut connector: Connector, policy: ReconnectPolicy) {
+    let mut attempt = 0;
+    loop {
+        receive_loop(Arc::clone(&session), stream_reader);
+        if session.logged_on_since_connect() {
+            attempt = 0;
+        }
+        match reconnect(&session, &mut connector, &policy, &mut attempt) {
+            Some(reader) => stream_reader = reader,
+            None => break,
+        }
     }
+    session.set_reconnect(false);
+}
 
-    pub fn shutdown(&mut self) {
-        info!("{:?}: Shutting down.", self.engine_mode);
-        self.is_running.store(false, Ordering::Relaxed);
+// Connects again after the policy's backoff and opens the logon handshake. None once shut down, out of
+// attempts, or the session ended other than by losing the transport.
+fn reconnect(session: &Session, connector: &mut Connector, policy: &ReconnectPolicy, attempt: &mut u32) -> Option<Box<dyn Transport>> {
+    let mode = session.mode;
+    while session.will_reconnect() {
+        if policy.max_attempts.is_some_and(|max_attempts| *attempt >= max_attempts) {
+            warn!("{:?}: Giving up after {} reconnect attempts", mode, attempt);
+            return None;
+        }
+        let delay = policy.backoff.delay(*attempt);
+        *attempt += 1;
+        info!("{:?}: Reconnecting in {:?} (attempt {})", mode, delay, attempt);
+        // Waited out in short steps so a long backoff does not hold up shutdown
+        let deadline = Instant::now() + delay;
+        while session.will_reconnect() && Instant::now() < deadline {
+            thread::sleep(TIMER_INTERVAL.min(deadline - Instant::now()));
+        }
+        if !session.will_reconnect() {
+            break;
+        }
 
-        if let Some(tx_thread) = self.send_thread.take() {
-            if let Err(e) = tx_thread.join() {
-                error!("{:?}: Error joining tx_thread: {:?}", self.engine_mode, e);
+        let connected = connector().and_then(|stream| {
+            let reader = stream.try_clone()?;
+            set_read_timeout(reader.as_ref())?;
+            session.on_connected(stream)?;
+            Ok(reader)
+        });
+        match connected {
+            // Shutdown may have been asked for while the logon went out
+            Ok(reader) if session.will_reconnect() => return Some(reader),
+            Ok(_) => session.close(DisconnectReason::Shutdown),
+            Err(e) => {
+                warn!("{:?}: Reconnect attempt {} failed", mode, attempt);
+                session.disconnect_io(e);
             }
         }
+    }
+    None
+}
 
-        if let Some(rx_thread) = self.receive_thread.take() {
-            if let Err(e) = rx_thread.join() {
-                error!("{:?}: Error joining rx_thread: {:?}", self.engine_mode, e);
+// Writes the application's messages once the session is logged on
+fn send_loop<Q: MessageSource>(session: Arc<Session>, outgoing_receiver: Q) -> ShutdownReport {
+    let mode = session.mode;
+    info!("{:?}: Ready to send messages.", mode);
+    // Taken in while the connection was down, to go out first once the session is back
+    let mut held = VecDeque::new();
+    let mut throttle = session.config.throttle.map(|policy| TokenBucket::new(policy, session.clock.now_utc()));
+    if let Some(bucket) = &throttle {
+        session.metrics().record_throttle(bucket.tokens(), None);
+    }
+    while session.is_running() || session.will_reconnect() {
+        if session.state() == SessionState::Disconnected {
+            hold_while_disconnected(&session, &outgoing_receiver, &mut held);
+            continue;
+        }
+        // Hold application messages back until the session is logged on
+        if !session.state().can_send_application() {
+            thread::sleep(Duration::from_millis(10));
+            continue;
+        }
+
+        let next = held.pop_front().map(Ok).unwrap_or_else(|| outgoing_receiver.recv_timeout(Duration::from_secs(1)));
+        match next {
+            Ok(message) if session.state().can_send_application() => {
+                match await_throttle(&session, throttle.as_mut(), &message, || session.state().can_send_application()) {
+                    Permit::Send => send_application(&session, message),
+                    Permit::Refuse => session.throttle_outgoing(message),
+                    // The connection went while the message waited for a token
+                    Permit::Hold => held.push_front(message),
+                }
+            }
+            // The connection went while we were waiting
+            Ok(message) => held.push_front(message),
+            Err(RecvTimeoutError::Timeout) => {}
+            // Nothing more can come from an application that dropped its sender
+            Err(RecvTimeoutError::Disconnected) => {
+                info!("{:?}: Outgoing channel closed, exiting send thread.", mode);
+                if session.config.logout_on_outgoing_closed {
+                    session.logout("Outgoing channel closed");
+                }
+                return ShutdownReport::default();
             }
         }
+    }
 
-        info!("{:?}: Fully shut down.", self.engine_mode);
+    let mut report = ShutdownReport::default();
+    // Flush whatever the application queued before asking to shut down, while the transport is still open. A
+    // message held here was taken just as the session stopped; ones held while disconnected are never flushed.
+    if let Some(deadline) = session.flush_deadline() {
+        while Instant::now() < deadline {
+            let Some(message) = held.pop_front().or_else(|| outgoing_receiver.try_recv().ok()) else { break };
+            match await_throttle(&session, throttle.as_mut(), &message, || Instant::now() < deadline) {
+                Permit::Send => {}
+                Permit::Refuse => {
+                    session.throttle_outgoing(message);
+                    report.dropped += 1;
+                    continue;
+                }
+                Permit::Hold => {
+                    held.push_front(message);
+                    break;
+                }
+            }
+            match session.send(message) {
+                Ok(()) => report.flushed += 1,
+                Err(e) => {
+                    error!("{:?}: Error writing to stream: {:?}", mode, e);
+                    report.dropped += 1;
+                }
+            }
+        }
+        session.logout("");
     }
+    for mut message in held.into_iter().chain(std::iter::from_fn(|| outgoing_receiver.try_recv().ok())) {
+        message.take_receipt().complete(Err(SendFailure::ShutDown));
+        report.dropped += 1;
+    }
+    if report.dropped > 0 {
+        warn!("{:?}: Dropped {} queued messages at shutdown", mode, report.dropped);
+    }
+    info!("{:?}: Shutdown signal received, exiting send thread.", mode);
+    report
 }
 
-// Extracts a complete FIX message from the buffer and returns the remaining unprocessed data.
-fn extract_message(buffer: &[u8]) -> Option<(String, Vec<u8>)> {
-    let message_str = String::from_utf8_lossy(buffer).to_string();
-
-    if let Some(checksum_pos) = message_str.find("10=") {
+// What the throttle made of an application message
+enum Permit {
+    Send,
+    Refuse, // Saturated under ThrottleSaturation::Reject
+    Hold,   // Waiting for a token was cut short, so the message has to stay queued
+}
 
-        if let Some(end_pos) = message_str[checksum_pos..].find(SOH) {
-            let full_message = &message_str[..checksum_pos + end_pos + 1]; // Include '10=xxx' and SOH
-            let remaining_data = buffer[(checksum_pos + end_pos + 1)..].to_vec(); // Remaining bytes
-            return Some((full_message.to_string(), remaining_data));
+// Takes a throttle token for an application message, waiting for one under ThrottleSaturation::Queue for as
+// long as `may_wait` allows. Admin messages and sessions without a throttle go straight through.
+fn await_throttle(session: &Session, throttle: Option<&mut TokenBucket>, message: &FixMessage, may_wait: impl Fn() -> bool) -> Permit {
+    let Some(bucket) = throttle.filter(|_| !message.msg_type_enum().is_some_and(|msg_type| msg_type.is_admin())) else {
+        return Permit::Send;
+    };
+    let mut delay = match bucket.try_take(session.clock.now_utc()) {
+        Ok(()) => {
+            session.metrics().record_throttle(bucket.tokens(), None);
+            return Permit::Send;
         }
+        Err(delay) => delay,
+    };
+    session.metrics().record_throttle(bucket.tokens(), Some(delay));
+    if bucket.policy().saturation == ThrottleSaturation::Reject {
+        return Permit::Refuse;
+    }
+    // Waited out in short steps so a shutdown or a lost connection is noticed
+    loop {
+        if !may_wait() {
+            return Permit::Hold;
+        }
+        session.clock.sleep(delay.min(TIMER_INTERVAL));
+        match bucket.try_take(session.clock.now_utc()) {
+            Ok(()) => {
+                session.metrics().record_throttle(bucket.tokens(), None);
+                return Permit::Send;
+            }
+            Err(remaining) => delay = remaining,
+        }
+    }
+}
+
+// A failed write means the connection is gone; closing it lets the receive thread notice and reconnect
+fn send_application(session: &Session, message: FixMessage) {
+    if let Err(e) = session.send(message) {
+        error!("{:?}: Error writing to stream", session.mode);
+        session.disconnect_io(e);
+    }
+}
+
+// While reconnecting, the application's messages are held up to the policy's capacity and refused beyond it
+fn hold_while_disconnected<Q: MessageSource>(session: &Session, outgoing_receiver: &Q, held: &mut VecDeque<FixMessage>) {
+    let capacity = match session.config.reconnect.as_ref().map(|policy| policy.queue) {
+        Some(QueuePolicy::Buffer { capacity }) => capacity,
+        _ => 0,
+    };
+    match outgoing_receiver.recv_timeout(TIMER_INTERVAL) {
+        Ok(message) if held.len() < capacity => held.push_back(message),
+        Ok(message) => session.refuse_outgoing(message),
+        Err(RecvTimeoutError::Timeout) => {}
+        // Whatever is held still goes out after the reconnect
+        Err(RecvTimeoutError::Disconnected) => thread::sleep(TIMER_INTERVAL),
     }
-    None
 }
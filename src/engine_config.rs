@@ -0,0 +1,257 @@
+use crate::reconnect::Backoff;
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::Duration;
+use tracing::warn;
+
+// Connections queued by the OS while the engine has not accepted them yet
+const LISTEN_BACKLOG: i32 = 128;
+
+// Socket options the factory applies to the connections and listeners it opens, and the transport limits the
+// engine applies itself; see FixEngine::set_engine_config.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineConfig {
+    // TCP_NODELAY: small messages go out straight away instead of waiting to be coalesced
+    pub nodelay: bool,
+    // SO_KEEPALIVE: the OS probes an idle connection, so a peer that vanished is noticed between heartbeats
+    pub keepalive: bool,
+    // SO_REUSEADDR on listeners, so a restarted acceptor can bind while old connections are in TIME_WAIT
+    pub reuse_address: bool,
+    // Initiator only: how long to wait for the acceptor to answer each connection attempt, so an address that
+    // drops the attempt moves on to the next one rather than waiting out the OS's own connect timeout
+    pub connect_timeout: Duration,
+    // Initiator only: backup gateways, tried in order after the address the initiator is created with, when
+    // first connecting and on every reconnect
+    pub failover_addresses: Vec<String>,
+    // Initiator only: passes over all the addresses before the first connection is given up on, with the
+    // backoff waited out between them. Reconnects make a single pass per attempt of the ReconnectPolicy.
+    pub connect_rounds: u32,
+    pub connect_backoff: Backoff,
+    // Initiator only: the local address to connect from, e.g. to pick the interface a venue expects
+    pub local_address: Option<SocketAddr>,
+    // Acceptor only: how long to wait for the initiator to connect before giving up
+    pub accept_timeout: Option<Duration>,
+    // A write to the connection that makes no progress for this long, e.g. to a peer that has stopped reading,
+    // fails and ends the connection. The engine writes in short steps so it can notice a shutdown meanwhile, so
+    // this is not the socket's own write timeout.
+    pub write_timeout: Duration,
+    // Most bytes taken from the connection by each read
+    pub read_chunk_size: usize,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        EngineConfig {
+            nodelay: true,
+            keepalive: false,
+            reuse_address: true,
+            connect_timeout: Duration::from_secs(10),
+            failover_addresses: Vec::new(),
+            connect_rounds: 1,
+            connect_backoff: Backoff::Fixed(Duration::from_secs(1)),
+            local_address: None,
+            accept_timeout: None,
+            write_timeout: Duration::from_secs(30),
+            read_chunk_size: 1024,
+        }
+    }
+}
+
+impl EngineConfig {
+    pub fn builder() -> EngineConfigBuilder {
+        EngineConfigBuilder::default()
+    }
+
+    // Connects to the first of the address's resolved addresses that answers
+    pub fn connect(&self, address: &str) -> io::Result<TcpStream> {
+        let stream: TcpStream = first_success(address, |addr| {
+            let socket = Socket::new(Domain::for_address(*addr), Type::STREAM, Some(Protocol::TCP))?;
+            if let Some(local_address) = self.local_address {
+                socket.bind(&local_address.into())?;
+            }
+            socket.connect_timeout(&(*addr).into(), self.connect_timeout)?;
+            Ok(socket.into())
+        })?;
+        self.configure(&stream)?;
+        Ok(stream)
+    }
+
+    // Connects to the first of the address and the failover addresses that answers, making up to connect_rounds
+    // passes over them
+    pub fn connect_with_failover(&self, address: &str) -> io::Result<TcpStream> {
+        let mut round = 0;
+        loop {
+            match self.connect_any(address) {
+                Ok(stream) => return Ok(stream),
+                Err(e) if round + 1 >= self.connect_rounds => return Err(e),
+                Err(e) => {
+                    let delay = self.connect_backoff.delay(round);
+                    warn!("No address answered ({}), trying again in {:?}", e, delay);
+                    thread::sleep(delay);
+                    round += 1;
+                }
+            }
+        }
+    }
+
+    // One pass over the address and then the failover addresses, returning the first connection made or else
+    // the last error
+    pub fn connect_any(&self, address: &str) -> io::Result<TcpStream> {
+        let mut last_error = None;
+        for candidate in std::iter::once(address).chain(self.failover_addresses.iter().map(String::as_str)) {
+            match self.connect(candidate) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => {
+                    warn!("Could not connect to {}: {}", candidate, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.expect("the address is always tried"))
+    }
+
+    pub fn bind(&self, address: &str) -> io::Result<TcpListener> {
+        first_success(address, |addr| {
+            let socket = Socket::new(Domain::for_address(*addr), Type::STREAM, Some(Protocol::TCP))?;
+            socket.set_reuse_address(self.reuse_address)?;
+            socket.bind(&(*addr).into())?;
+            socket.listen(LISTEN_BACKLOG)?;
+            Ok(socket.into())
+        })
+    }
+
+    // Applies the per-connection options, e.g. to a stream just accepted
+    pub fn configure(&self, stream: &TcpStream) -> io::Result<()> {
+        stream.set_nodelay(self.nodelay)?;
+        SockRef::from(stream).set_keepalive(self.keepalive)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EngineConfigBuilder {
+    config: EngineConfig,
+}
+
+impl EngineConfigBuilder {
+    pub fn nodelay(mut self, nodelay: bool) -> Self {
+        self.config.nodelay = nodelay;
+        self
+    }
+
+    pub fn keepalive(mut self, keepalive: bool) -> Self {
+        self.config.keepalive = keepalive;
+        self
+    }
+
+    pub fn reuse_address(mut self, reuse_address: bool) -> Self {
+        self.config.reuse_address = reuse_address;
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.config.connect_timeout = timeout;
+        self
+    }
+
+    // Adds a backup gateway after any added before it
+    pub fn failover_address(mut self, address: &str) -> Self {
+        self.config.failover_addresses.push(address.to_string());
+        self
+    }
+
+    pub fn connect_rounds(mut self, rounds: u32, backoff: Backoff) -> Self {
+        self.config.connect_rounds = rounds;
+        self.config.connect_backoff = backoff;
+        self
+    }
+
+    pub fn local_address(mut self, local_address: SocketAddr) -> Self {
+        self.config.local_address = Some(local_address);
+        self
+    }
+
+    pub fn accept_timeout(mut self, timeout: Duration) -> Self {
+        self.config.accept_timeout = Some(timeout);
+        self
+    }
+
+    pub fn write_timeout(mut self, timeout: Duration) -> Self {
+        self.config.write_timeout = timeout;
+        self
+    }
+
+    pub fn read_chunk_size(mut self, read_chunk_size: usize) -> Self {
+        self.config.read_chunk_size = read_chunk_size;
+        self
+    }
+
+    pub fn build(self) -> EngineConfig {
+        self.config
+    }
+}
+
+// Tries each address the string resolves to, returning the first that works or else the last error
+fn first_success<T>(address: &str, mut attempt: impl FnMut(&SocketAddr) -> io::Result<T>) -> io::Result<T> {
+    let mut last_error = None;
+    for addr in address.to_socket_addrs()? {
+        match attempt(&addr) {
+            Ok(value) => return Ok(value),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Address resolved to nothing")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options_are_applied_to_the_stream() {
+        let local_address: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let config = EngineConfig::builder()
+            .nodelay(false)
+            .keepalive(true)
+            .connect_timeout(Duration::from_secs(1))
+            .local_address(local_address)
+            .build();
+        let listener = config.bind("127.0.0.1:0").unwrap();
+        let stream = config.connect(&listener.local_addr().unwrap().to_string()).unwrap();
+        assert!(!stream.nodelay().unwrap());
+        assert!(SockRef::from(&stream).keepalive().unwrap());
+        assert_eq!(stream.local_addr().unwrap().ip(), local_address.ip());
+        let (accepted, _) = listener.accept().unwrap();
+        assert_eq!(accepted.peer_addr().unwrap(), stream.local_addr().unwrap());
+
+        // Nagle's algorithm is off unless asked for, and a connection attempt is never left to hang
+        assert_eq!(EngineConfig::default().connect_timeout, Duration::from_secs(10));
+        let plain = EngineConfig::default().connect(&listener.local_addr().unwrap().to_string()).unwrap();
+        assert!(plain.nodelay().unwrap());
+        assert!(!SockRef::from(&plain).keepalive().unwrap());
+
+        // The engine times its writes itself, leaving the socket's own write timeout alone
+        let config = EngineConfig::builder().write_timeout(Duration::from_secs(2)).build();
+        assert_eq!(config.write_timeout, Duration::from_secs(2));
+        assert_eq!(config.connect(&listener.local_addr().unwrap().to_string()).unwrap().write_timeout().unwrap(), None);
+    }
+
+    #[test]
+    fn test_failover_tries_each_address_for_every_round() {
+        // Nothing listens on a port just given back
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+        let config = EngineConfig::builder()
+            .failover_address(&closed)
+            .connect_rounds(3, Backoff::Fixed(Duration::from_millis(20)))
+            .build();
+        let started = std::time::Instant::now();
+        assert!(config.connect_with_failover(&closed).is_err());
+        assert!(started.elapsed() >= Duration::from_millis(40), "Two waits between three rounds");
+
+        let listener = config.bind("127.0.0.1:0").unwrap();
+        let config = EngineConfig::builder().failover_address(&listener.local_addr().unwrap().to_string()).build();
+        let stream = config.connect_with_failover(&closed).unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), listener.local_addr().unwrap());
+    }
+}
@@ -1,52 +1,213 @@
 use std::net::{TcpListener, TcpStream};
+use std::path::Path;
 use std::sync::{mpsc::{channel, Receiver, Sender}, Arc};
+use crate::acceptor::{FixAcceptor, NewSession};
+use crate::application::{ChannelApplication, FixApplication};
+use crate::channel::{bounded, bounded_with_policy, BoundedReceiver, BoundedSender, MessageSink};
 use crate::engine::{FixEngine, FixEngineMode};
+use crate::engine_config::EngineConfig;
+use crate::error::FixEngineError;
 use crate::message::FixMessage;
-use tracing::{error, info};
+use crate::replay::{self, ReplayControl, ReplaySpeed};
+use tracing::info;
 use crate::clock::{Clock, RealClock};
+use crate::session::{LogonValidator, SessionConfig};
+use crate::testing::duplex;
 
 pub struct FixEngineFactory;
 
+// An engine with the channels the application talks to it through
+type ChannelEngine = (FixEngine, Sender<FixMessage>, Receiver<FixMessage>);
+
+// Everything the factory sets an engine up with besides the address. A SessionConfig on its own converts into
+// options with the defaults for the rest.
+#[derive(Default)]
+pub struct FactoryOptions {
+    pub(crate) session: SessionConfig,
+    pub(crate) engine: EngineConfig,
+    logon_validator: Option<Arc<LogonValidator>>,
+    echo: bool,
+}
+
+impl FactoryOptions {
+    pub fn new(session: SessionConfig) -> Self {
+        FactoryOptions { session, ..FactoryOptions::default() }
+    }
+
+    // Socket options, and for an initiator the failover addresses, which reconnects use as well
+    pub fn engine_config(mut self, engine: EngineConfig) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    // Acceptor only: installed before the engine starts reading, so it sees the very first logon. A listener
+    // installs it on every session.
+    pub fn logon_validator(mut self, validator: LogonValidator) -> Self {
+        self.logon_validator = Some(Arc::new(validator));
+        self
+    }
+
+    // Acceptor only: a fake venue for testing clients against. Each NewOrderSingle is answered with an
+    // ExecutionReport filling it in full, with no application logic needed; other application messages arrive
+    // on the receiver as usual.
+    pub fn echo(mut self) -> Self {
+        self.echo = true;
+        self
+    }
+
+    // An acceptor engine with the logon validator installed, not yet started
+    pub(crate) fn acceptor(&self, clock: Arc<dyn Clock>) -> FixEngine {
+        let engine = FixEngine::new(clock, FixEngineMode::Acceptor, self.session.clone());
+        engine.set_engine_config(self.engine.clone());
+        if let Some(validator) = &self.logon_validator {
+            let validator = Arc::clone(validator);
+            engine.set_logon_validator(Box::new(move |session_id, logon| validator(session_id, logon)));
+        }
+        engine
+    }
+
+    pub(crate) fn application(&self, engine: &FixEngine, incoming: impl MessageSink) -> Arc<dyn FixApplication> {
+        if self.echo {
+            engine.echo_application(incoming)
+        } else {
+            Arc::new(ChannelApplication::new(incoming))
+        }
+    }
+}
+
+impl From<SessionConfig> for FactoryOptions {
+    fn from(session: SessionConfig) -> Self {
+        FactoryOptions::new(session)
+    }
+}
+
 impl FixEngineFactory {
-    pub fn create_initiator(address: &str) -> (FixEngine, Sender<FixMessage>, Receiver<FixMessage>) {
+    pub fn create_initiator(address: &str) -> Result<ChannelEngine, FixEngineError> {
+        Self::create_initiator_with_options(address, SessionConfig::default())
+    }
+
+    // Connects with the options' socket options and failover addresses, which reconnects use as well
+    pub fn create_initiator_with_options(address: &str, options: impl Into<FactoryOptions>) -> Result<ChannelEngine, FixEngineError> {
         info!("Creating Initiator.");
-        let stream = match TcpStream::connect(address) {
-            Ok(s) => s,
-            Err(e) => {
-                error!("Failed to connect to acceptor: {:?}", e);
-                panic!("Connection failed");
-            }
-        };
-        info!("Initiator connected to acceptor at {}", address);
+        let FactoryOptions { session, engine: engine_config, .. } = options.into();
+        let stream = Self::connect(address, &engine_config)?;
 
         let (outgoing_sender, outgoing_receiver) = channel(); // Send Fix Messages
         let (incoming_sender, incoming_receiver) = channel(); // Receive Fix Messages
 
-        let clock: Arc<dyn Clock> = Arc::new(RealClock);
-        let mut engine = FixEngine::new(clock, FixEngineMode::Initiator);
-        engine.start(stream, outgoing_receiver, incoming_sender);
-        (engine, outgoing_sender, incoming_receiver)
+        let mut engine = FixEngine::new(Self::clock(), FixEngineMode::Initiator, session);
+        engine.set_engine_config(engine_config.clone());
+        // Used again to reconnect when the config has a ReconnectPolicy
+        let address = address.to_string();
+        let connect = move || engine_config.connect_any(&address);
+        engine.start_reconnecting(stream, connect, outgoing_receiver, incoming_sender).map_err(FixEngineError::Start)?;
+        Ok((engine, outgoing_sender, incoming_receiver))
     }
 
-    pub fn create_acceptor(address: &str) -> (FixEngine, Sender<FixMessage>, Receiver<FixMessage>) {
-        info!("Creating Acceptor.");
-        let listener = match TcpListener::bind(address) {
-            Ok(l) => l,
-            Err(e) => {
-                error!("Failed to bind to address {}: {:?}", address, e);
-                panic!("Acceptor bind failed");
-            }
-        };
-        info!("Acceptor listening on {}", address);
+    // Each channel holds at most `capacity` messages. The engine blocks while the incoming channel is full, so
+    // an application that cannot keep up holds it back instead of queueing without limit; what the application's
+    // sends do while the outgoing channel is full is up to the config's outgoing_overflow.
+    pub fn create_initiator_with_capacity(address: &str, options: impl Into<FactoryOptions>, capacity: usize) -> Result<(FixEngine, BoundedSender<FixMessage>, BoundedReceiver<FixMessage>), FixEngineError> {
+        info!("Creating Initiator.");
+        let FactoryOptions { session, engine: engine_config, .. } = options.into();
+        let stream = Self::connect(address, &engine_config)?;
+
+        let (outgoing_sender, outgoing_receiver) = bounded_with_policy(capacity, session.outgoing_overflow);
+        let (incoming_sender, incoming_receiver) = bounded(capacity);
 
+        let mut engine = FixEngine::new(Self::clock(), FixEngineMode::Initiator, session);
+        engine.set_engine_config(engine_config);
+        engine.start_bounded(stream, outgoing_receiver, incoming_sender).map_err(FixEngineError::Start)?;
+        Ok((engine, outgoing_sender, incoming_receiver))
+    }
+
+    // Returns once the address is bound; the session starts when the initiator connects. The engine's
+    // local_addr is the address actually bound, e.g. when asked for port 0.
+    pub fn create_acceptor(address: &str) -> Result<ChannelEngine, FixEngineError> {
+        Self::create_acceptor_with_options(address, SessionConfig::default())
+    }
+
+    // Binds and accepts with the options' socket options. When the accept timeout passes without a connection,
+    // the engine reports EngineError::AcceptTimeout and stays disconnected.
+    pub fn create_acceptor_with_options(address: &str, options: impl Into<FactoryOptions>) -> Result<ChannelEngine, FixEngineError> {
+        info!("Creating Acceptor.");
+        let options = options.into();
+        let listener = Self::bind(address, &options.engine)?;
         let (outgoing_sender, outgoing_receiver) = channel(); // Send Fix Messages
         let (incoming_sender, incoming_receiver) = channel(); // Receive Fix Messages
 
-        let stream = listener.accept().unwrap().0;
+        let mut engine = options.acceptor(Self::clock());
+        let application = options.application(&engine, incoming_sender);
+        engine.run_on_accept(listener, options.engine, outgoing_receiver, application).map_err(FixEngineError::Start)?;
+        Ok((engine, outgoing_sender, incoming_receiver))
+    }
+
+    pub fn create_acceptor_with_capacity(address: &str, options: impl Into<FactoryOptions>, capacity: usize) -> Result<(FixEngine, BoundedSender<FixMessage>, BoundedReceiver<FixMessage>), FixEngineError> {
+        info!("Creating Acceptor.");
+        let options = options.into();
+        let listener = Self::bind(address, &options.engine)?;
+        let (outgoing_sender, outgoing_receiver) = bounded_with_policy(capacity, options.session.outgoing_overflow);
+        let (incoming_sender, incoming_receiver) = bounded(capacity);
+
+        let mut engine = options.acceptor(Self::clock());
+        let application = options.application(&engine, engine.bounded_delivery(incoming_sender));
+        engine.run_on_accept(listener, options.engine, outgoing_receiver, application).map_err(FixEngineError::Start)?;
+        Ok((engine, outgoing_sender, incoming_receiver))
+    }
+
+    // Keeps listening after the first connection: every initiator that connects gets a session of its own,
+    // announced on the returned receiver. Shutting the acceptor down ends all of them.
+    pub fn create_acceptor_listener(address: &str, options: impl Into<FactoryOptions>) -> Result<(FixAcceptor, Receiver<NewSession>), FixEngineError> {
+        info!("Creating Acceptor.");
+        FixAcceptor::bind(address, options, Self::clock()).map_err(|source| FixEngineError::Bind { address: address.to_string(), source })
+    }
+
+    // Feeds the inbound messages of a capture, such as a FileMessageLog file, through an engine as they were
+    // originally spaced out, for reproducing a session offline. The application messages the session lets
+    // through arrive on the receiver. See replay::start for the file format.
+    pub fn create_replay(path: &Path) -> Result<(Receiver<FixMessage>, ReplayControl), FixEngineError> {
+        Self::create_replay_with_speed(path, ReplaySpeed::Timed(1.0))
+    }
+
+    pub fn create_replay_with_speed(path: &Path, speed: ReplaySpeed) -> Result<(Receiver<FixMessage>, ReplayControl), FixEngineError> {
+        info!("Replaying {}", path.display());
+        replay::start(path, speed).map_err(|source| FixEngineError::Replay { path: path.to_path_buf(), source })
+    }
+
+    // An initiator and an acceptor wired back to back over an in-memory duplex, with no sockets involved. Both
+    // are started, so the initiator's logon is already on its way.
+    pub fn create_loopback_pair(initiator_options: impl Into<FactoryOptions>, acceptor_options: impl Into<FactoryOptions>) -> Result<(ChannelEngine, ChannelEngine), FixEngineError> {
+        info!("Creating loopback pair.");
+        let (initiator_stream, acceptor_stream) = duplex();
+        let acceptor_options = acceptor_options.into();
+        let mut acceptor = acceptor_options.acceptor(Self::clock());
+        let (incoming_sender, incoming_receiver) = channel();
+        let application = acceptor_options.application(&acceptor, incoming_sender);
+        let outgoing_sender = acceptor.start_with_application(acceptor_stream, application).map_err(FixEngineError::Start)?;
+        let acceptor = (acceptor, outgoing_sender, incoming_receiver);
+
+        let FactoryOptions { session, engine: engine_config, .. } = initiator_options.into();
+        let (outgoing_sender, outgoing_receiver) = channel();
+        let (incoming_sender, incoming_receiver) = channel();
+        let mut initiator = FixEngine::new(Self::clock(), FixEngineMode::Initiator, session);
+        initiator.set_engine_config(engine_config);
+        initiator.start(initiator_stream, outgoing_receiver, incoming_sender).map_err(FixEngineError::Start)?;
+        Ok(((initiator, outgoing_sender, incoming_receiver), acceptor))
+    }
+
+    fn connect(address: &str, engine_config: &EngineConfig) -> Result<TcpStream, FixEngineError> {
+        let stream = engine_config.connect_with_failover(address).map_err(|source| FixEngineError::Connect { address: address.to_string(), source })?;
+        info!("Initiator connected to acceptor at {:?}", stream.peer_addr());
+        Ok(stream)
+    }
+
+    fn bind(address: &str, engine_config: &EngineConfig) -> Result<TcpListener, FixEngineError> {
+        let listener = engine_config.bind(address).map_err(|source| FixEngineError::Bind { address: address.to_string(), source })?;
+        info!("Acceptor listening on {}", address);
+        Ok(listener)
+    }
 
-        let clock: Arc<dyn Clock> = Arc::new(RealClock);
-        let mut engine = FixEngine::new(clock, FixEngineMode::Acceptor);
-        engine.start(stream, outgoing_receiver, incoming_sender);
-        (engine, outgoing_sender, incoming_receiver)
+    fn clock() -> Arc<dyn Clock> {
+        Arc::new(RealClock)
     }
 }
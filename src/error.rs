@@ -0,0 +1,150 @@
+use crate::tag::{numbers, BeginString, FixField};
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineError {
+    BeginStringMismatch { expected: BeginString, received: Option<String> },
+    CompIDMismatch { tag: u32, expected: String, received: Option<String> },
+    TestRequestTimeout { test_req_id: String },
+    MsgSeqNumTooLow { expected: u64, received: u64 },
+    MsgSeqNumMissing { msg_type: Option<String> },
+    AuthenticationFailed { username: Option<String>, reason: String },
+    LogonRejected { reason: String },
+    MessageTooLarge { size: usize, limit: usize },
+    SendingTimeAccuracy { sending_time: String },
+    NextExpectedMsgSeqNumTooHigh { expected: u64, received: u64 },
+    MessageBeforeLogon { msg_type: Option<String> },
+    OutsideSessionTime,
+    AcceptTimeout { timeout: Duration },
+    StartFailed { reason: String }, // The connection could not be accepted or the session set up on it
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::BeginStringMismatch { expected, received } => {
+                write!(f, "Incompatible BeginString: expected {}, received {:?}", expected.value(), received)
+            }
+            EngineError::CompIDMismatch { tag, expected, received } => {
+                write!(f, "CompID mismatch on tag {}: expected {}, received {:?}", tag, expected, received)
+            }
+            EngineError::TestRequestTimeout { test_req_id } => {
+                write!(f, "No heartbeat received in reply to TestRequest {}", test_req_id)
+            }
+            EngineError::MsgSeqNumTooLow { expected, received } => {
+                write!(f, "MsgSeqNum too low: expected {}, received {}", expected, received)
+            }
+            EngineError::MsgSeqNumMissing { msg_type } => {
+                write!(f, "MsgSeqNum(34) missing from a message of type {:?}", msg_type)
+            }
+            EngineError::AuthenticationFailed { username, reason } => {
+                write!(f, "Logon rejected for username {:?}: {}", username, reason)
+            }
+            EngineError::LogonRejected { reason } => {
+                write!(f, "Logon rejected: {}", reason)
+            }
+            EngineError::MessageTooLarge { size, limit } => {
+                write!(f, "Receive buffer holds {} bytes without a complete message, limit is {}", size, limit)
+            }
+            EngineError::SendingTimeAccuracy { sending_time } => {
+                write!(f, "SendingTime {} is too far from the local clock", sending_time)
+            }
+            EngineError::NextExpectedMsgSeqNumTooHigh { expected, received } => {
+                write!(f, "NextExpectedMsgSeqNum too high: expected at most {}, received {}", expected, received)
+            }
+            EngineError::MessageBeforeLogon { msg_type } => {
+                write!(f, "Message of type {:?} received before logon", msg_type)
+            }
+            EngineError::OutsideSessionTime => write!(f, "Logon outside of session time"),
+            EngineError::AcceptTimeout { timeout } => write!(f, "No connection accepted within {:?}", timeout),
+            EngineError::StartFailed { reason } => write!(f, "Session not started: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+// Why bytes framed as a message could not be decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    InvalidUtf8,
+    MissingTrailingSoh,
+    // A field that is not tag=value with a numeric tag
+    InvalidField,
+    EmptyValue { tag: u32 },
+    InvalidDataLength { tag: u32, raw: String }, // tag is the length field, e.g. RawDataLength(95)
+    DataLengthMismatch { tag: u32 }, // tag is the data field, e.g. RawData(96)
+    InvalidBodyLength { raw: String },
+    BodyLengthMismatch { declared: usize, actual: usize },
+    InvalidSeqNum { raw: String },
+    MalformedChecksum { raw: String },
+    ChecksumMismatch { received: u8, calculated: u8 },
+}
+
+impl DecodeError {
+    // The field at fault, for RefTagID(371) on a Reject
+    pub fn ref_tag_id(&self) -> Option<u32> {
+        match self {
+            DecodeError::EmptyValue { tag } | DecodeError::InvalidDataLength { tag, .. } | DecodeError::DataLengthMismatch { tag } => Some(*tag),
+            DecodeError::InvalidBodyLength { .. } | DecodeError::BodyLengthMismatch { .. } => Some(numbers::BODY_LENGTH),
+            DecodeError::InvalidSeqNum { .. } => Some(numbers::MSG_SEQ_NUM),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::InvalidUtf8 => write!(f, "Invalid UTF-8"),
+            DecodeError::MissingTrailingSoh => write!(f, "Message does not end with SOH"),
+            DecodeError::InvalidField => write!(f, "Invalid key-value pair in FIX message"),
+            DecodeError::EmptyValue { tag } => write!(f, "Tag {} specified without a value", tag),
+            DecodeError::InvalidDataLength { tag, raw } => write!(f, "Invalid data field length {:?} in tag {}", raw, tag),
+            DecodeError::DataLengthMismatch { tag } => write!(f, "Data field {} does not match its length field", tag),
+            DecodeError::InvalidBodyLength { raw } => write!(f, "Invalid BodyLength {:?}", raw),
+            DecodeError::BodyLengthMismatch { declared, actual } => {
+                write!(f, "BodyLength {} does not match the message, which has {}", declared, actual)
+            }
+            DecodeError::InvalidSeqNum { raw } => write!(f, "Invalid MsgSeqNum {:?}", raw),
+            DecodeError::MalformedChecksum { raw } => write!(f, "Malformed checksum {:?}", raw),
+            DecodeError::ChecksumMismatch { received, calculated } => {
+                write!(f, "Invalid checksum: received {:03}, calculated {:03}", received, calculated)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+// Why FixEngineFactory could not hand back a running engine
+#[derive(Debug)]
+pub enum FixEngineError {
+    Bind { address: String, source: io::Error },
+    Connect { address: String, source: io::Error },
+    Start(io::Error),
+    Replay { path: PathBuf, source: io::Error },
+}
+
+impl fmt::Display for FixEngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FixEngineError::Bind { address, source } => write!(f, "Failed to bind to {}: {}", address, source),
+            FixEngineError::Connect { address, source } => write!(f, "Failed to connect to {}: {}", address, source),
+            FixEngineError::Start(source) => write!(f, "Failed to start engine: {}", source),
+            FixEngineError::Replay { path, source } => write!(f, "Failed to replay {}: {}", path.display(), source),
+        }
+    }
+}
+
+impl std::error::Error for FixEngineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FixEngineError::Bind { source, .. } | FixEngineError::Connect { source, .. } | FixEngineError::Start(source)
+            | FixEngineError::Replay { source, .. } => Some(source),
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use crate::error::{DecodeError, EngineError};
+use crate::message::FixMessage;
+use crate::session::SessionState;
+use crate::tag::SessionRejectReason;
+use chrono::{DateTime, Utc};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub enum EngineEvent {
+    // Every session state transition, in order; Connected, LoggedOn and Disconnected follow the matching ones
+    StateChanged { from: SessionState, to: SessionState, at: DateTime<Utc> },
+    // peer is the address connected to, e.g. the one an initiator failed over to; None off a socket
+    Connected { peer: Option<SocketAddr> },
+    LoggedOn,
+    Disconnected { reason: DisconnectReason },
+    // The peer ended the session with a Logout; text is its Text(58) reason
+    LoggedOut { text: Option<String> },
+    Error(EngineError),
+    // Reading from or writing to the transport failed; the connection is closed with DisconnectReason::Io
+    IoError(Arc<io::Error>),
+    // Initiator only: the acceptor's logon did not echo the HeartBtInt(108) we proposed
+    HeartBtIntMismatch { proposed: u64, received: Option<u64> },
+    // A message arrived ahead of the expected MsgSeqNum and a resend was requested
+    SequenceGap { expected: u64, received: u64 },
+    // Bytes that framed as a message but could not be decoded
+    DecodeFailed { raw: String, error: DecodeError },
+    // An application message sent while the connection was down that the reconnect policy could not hold
+    OutgoingRejected { message: FixMessage },
+    // An application message refused because the throttle was saturated under ThrottleSaturation::Reject
+    OutgoingThrottled { message: FixMessage },
+    // The outgoing interceptor dropped a message, which took no MsgSeqNum
+    OutgoingDropped { message: FixMessage },
+    // The incoming interceptor kept a message from the application; the session had already processed it
+    IncomingDropped { message: FixMessage },
+    // An inbound message was answered with a session-level Reject; raw is its wire text when available
+    MessageRejected { raw: String, reason: SessionRejectReason, text: String },
+    // The last event, once shutdown has stopped the engine threads
+    ShutDown,
+}
+
+// Why the connection ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisconnectReason {
+    // FixEngine::shutdown
+    Shutdown,
+    // The peer closed the connection without logging out
+    PeerClosed,
+    // A Logout from either side was answered
+    Logout,
+    // The scheduled session closed
+    EndOfSession,
+    // Follows an IoError event
+    Io,
+    // Follows an Error event, or the session could not be opened
+    Error,
+}
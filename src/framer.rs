@@ -0,0 +1,385 @@
+use crate::session::DEFAULT_MAX_MESSAGE_SIZE;
+use crate::tag::SOH;
+use tracing::warn;
+
+// Splits the bytes read from a transport into whole messages
+pub trait Framer: Send {
+    // Takes the next complete message off the front of buf; None until one has fully arrived
+    fn next_frame(&mut self, buf: &mut Vec<u8>) -> Option<Vec<u8>>;
+}
+
+// Tag=value messages, each running for its BodyLength(9) and then up to the SOH after its CheckSum(10)
+#[derive(Debug)]
+pub struct TagValueFramer {
+    scanned: usize, // No BeginString starts ahead of this, so a long run of junk is not rescanned
+    max_message_size: usize, // A BodyLength beyond this is not believed
+}
+
+impl Default for TagValueFramer {
+    fn default() -> Self {
+        TagValueFramer::new(DEFAULT_MAX_MESSAGE_SIZE)
+    }
+}
+
+impl TagValueFramer {
+    pub fn new(max_message_size: usize) -> Self {
+        TagValueFramer { scanned: 0, max_message_size }
+    }
+}
+
+const CHECKSUM_FIELD: &[u8] = &[SOH as u8, b'1', b'0', b'='];
+const BEGIN_STRING_FIELD: &[u8] = b"8=FIX";
+const CHECKSUM_TAG: &[u8] = b"10=";
+const CHECKSUM_VALUE_LEN: usize = 3;
+
+// Where the first message's BeginString(8) starts, e.g. after a capture's timestamp
+pub fn find_message_start(buf: &[u8]) -> Option<usize> {
+    (0..buf.len()).find(|&pos| is_message_start(buf, pos))
+}
+
+// "8=FIX" preceded by a digit is the end of another field, e.g. Text(58) saying "FIX"
+fn is_message_start(buf: &[u8], pos: usize) -> bool {
+    buf[pos..].starts_with(BEGIN_STRING_FIELD) && (pos == 0 || !buf[pos - 1].is_ascii_digit())
+}
+
+// What the BodyLength(9) of a message starting at some position says about it
+#[derive(Debug, PartialEq, Eq)]
+enum FrameEnd {
+    // Just past the SOH after its CheckSum
+    At(usize),
+    // Not enough has arrived to tell
+    Incomplete,
+    // No BodyLength, one larger than a message may be, or one that does not lead to a CheckSum field
+    Invalid,
+}
+
+fn frame_end(buf: &[u8], start: usize, max_message_size: usize) -> FrameEnd {
+    let message = &buf[start..];
+    let Some(begin_string_end) = message.iter().position(|&byte| byte == SOH as u8) else {
+        return FrameEnd::Incomplete;
+    };
+    let length_field = &message[begin_string_end + 1..];
+    let Some(length_end) = length_field.iter().position(|&byte| byte == SOH as u8) else {
+        // Can the bytes so far still become "9=<digits>"?
+        let partial = length_field.iter().enumerate().all(|(i, &byte)| match i {
+            0 => byte == b'9',
+            1 => byte == b'=',
+            _ => byte.is_ascii_digit(),
+        });
+        return if partial { FrameEnd::Incomplete } else { FrameEnd::Invalid };
+    };
+    let Some(body_length) = length_field[..length_end].strip_prefix(b"9=")
+        .filter(|digits| !digits.is_empty() && digits.iter().all(u8::is_ascii_digit))
+        .and_then(|digits| std::str::from_utf8(digits).ok()?.parse::<usize>().ok())
+    else {
+        return FrameEnd::Invalid;
+    };
+
+    // The CheckSum field has to follow the body straight away. The peer chose the BodyLength, so it is not
+    // trusted to keep the sum in range.
+    let checksum_pos = (start + begin_string_end + 1 + length_end + 1).checked_add(body_length);
+    let Some(checksum_pos) = checksum_pos.filter(|_| body_length <= max_message_size) else {
+        return FrameEnd::Invalid;
+    };
+    let Some(field) = buf.get(checksum_pos..) else {
+        return FrameEnd::Incomplete;
+    };
+    let tag_len = field.len().min(CHECKSUM_TAG.len());
+    if field[..tag_len] != CHECKSUM_TAG[..tag_len] {
+        return FrameEnd::Invalid;
+    }
+    let value = &field[tag_len..];
+    match value.iter().take(CHECKSUM_VALUE_LEN + 1).position(|&byte| byte == SOH as u8) {
+        Some(0) => FrameEnd::Invalid,
+        Some(len) => FrameEnd::At(checksum_pos + CHECKSUM_TAG.len() + len + 1),
+        None if value.len() > CHECKSUM_VALUE_LEN => FrameEnd::Invalid,
+        None => FrameEnd::Incomplete,
+    }
+}
+
+// Where the next message starts. Bytes are only skipped while what is at the front is not a message whose
+// BodyLength leads to its CheckSum, e.g. junk or a message cut off by the next one, so a BeginString inside a
+// message's Text(58) or RawData(96) is never taken for the start of another. Failing that, the first
+// BeginString is used and decoding reports what is wrong with it.
+fn message_start(buf: &[u8], from: usize, max_message_size: usize) -> Option<usize> {
+    let mut candidates = (from..buf.len()).filter(|&pos| is_message_start(buf, pos));
+    let first = candidates.next()?;
+    std::iter::once(first).chain(candidates)
+        .find(|&pos| frame_end(buf, pos, max_message_size) != FrameEnd::Invalid)
+        .or(Some(first))
+}
+
+impl Framer for TagValueFramer {
+    fn next_frame(&mut self, buf: &mut Vec<u8>) -> Option<Vec<u8>> {
+        let Some(start) = message_start(buf, self.scanned.min(buf.len()), self.max_message_size) else {
+            // The BeginString may still be completed by the next read
+            self.scanned = buf.len().saturating_sub(BEGIN_STRING_FIELD.len() - 1);
+            return None;
+        };
+        self.scanned = start;
+        let end_pos = match frame_end(buf, start, self.max_message_size) {
+            FrameEnd::At(end_pos) => end_pos,
+            FrameEnd::Incomplete => return None,
+            // With no usable BodyLength the message runs to the first CheckSum field, and decoding rejects it
+            FrameEnd::Invalid => {
+                let field_pos = start + find(&buf[start..], CHECKSUM_FIELD)?;
+                let value_pos = field_pos + CHECKSUM_FIELD.len();
+                value_pos + buf[value_pos..].iter().position(|&byte| byte == SOH as u8)? + 1
+            }
+        };
+        let remaining = buf.split_off(end_pos);
+        self.scanned = 0;
+        let mut frame = std::mem::replace(buf, remaining);
+        if start > 0 {
+            warn!("Discarding {} bytes ahead of the BeginString: {:?}", start, String::from_utf8_lossy(&frame[..start]));
+            frame.drain(..start);
+        }
+        Some(frame)
+    }
+}
+
+// SOFH encoding type for FIX tag=value messages
+pub const SOFH_FIX_TAG_VALUE: u16 = 0xF000;
+const SOFH_LEN: usize = 6;
+
+// Simple Open Framing Header, as used with FIXP: each message is preceded by a 4-byte big-endian length, which
+// counts the header itself, and a 2-byte encoding type. Frames are the messages without their header.
+#[derive(Debug, Default)]
+pub struct SofhFramer;
+
+impl SofhFramer {
+    pub fn encode(message: &[u8]) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(SOFH_LEN + message.len());
+        frame.extend_from_slice(&((SOFH_LEN + message.len()) as u32).to_be_bytes());
+        frame.extend_from_slice(&SOFH_FIX_TAG_VALUE.to_be_bytes());
+        frame.extend_from_slice(message);
+        frame
+    }
+}
+
+impl Framer for SofhFramer {
+    fn next_frame(&mut self, buf: &mut Vec<u8>) -> Option<Vec<u8>> {
+        let header: [u8; 4] = buf.get(..4)?.try_into().unwrap();
+        // A length too short to cover the header yields an empty frame, which then fails to decode
+        let len = (u32::from_be_bytes(header) as usize).max(SOFH_LEN);
+        if buf.len() < len {
+            return None;
+        }
+        let remaining = buf.split_off(len);
+        Some(std::mem::replace(buf, remaining).split_off(SOFH_LEN))
+    }
+}
+
+// How the engine delimits messages on the wire, in both directions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Framing {
+    #[default]
+    TagValue,
+    Sofh,
+}
+
+impl Framing {
+    // A tag=value framer does not believe a BodyLength over max_message_size
+    pub fn framer(&self, max_message_size: usize) -> Box<dyn Framer> {
+        match self {
+            Framing::TagValue => Box::new(TagValueFramer::new(max_message_size)),
+            Framing::Sofh => Box::new(SofhFramer),
+        }
+    }
+
+    // An encoded message as it is written to the transport
+    pub fn wrap(&self, message: &[u8]) -> Vec<u8> {
+        match self {
+            Framing::TagValue => message.to_vec(),
+            Framing::Sofh => SofhFramer::encode(message),
+        }
+    }
+}
+
+// Collects bytes from the transport and splits them into whole messages. Framing works on bytes so a
+// multi-byte character split across two reads is only turned into text once its message is complete.
+pub struct MessageFramer {
+    buffer: Vec<u8>,
+    framer: Box<dyn Framer>,
+}
+
+impl Default for MessageFramer {
+    fn default() -> Self {
+        Self::with_framer(Box::new(TagValueFramer::default()))
+    }
+}
+
+impl MessageFramer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_framer(framer: Box<dyn Framer>) -> Self {
+        MessageFramer { buffer: Vec::new(), framer }
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    pub fn next_message(&mut self) -> Option<Vec<u8>> {
+        self.framer.next_frame(&mut self.buffer)
+    }
+
+    // Bytes held that do not yet make up a whole message
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Clock;
+    use crate::message::FixMessage;
+    use std::sync::Arc;
+
+    struct FixedClock;
+
+    impl Clock for FixedClock {
+        fn now(&self) -> String {
+            "20231016-12:30:00.123".to_string()
+        }
+    }
+
+    fn encoded(text: &str) -> Vec<u8> {
+        let mut message = FixMessage::new();
+        message.header.insert("35".to_string(), "B".to_string());
+        message.header.insert("34".to_string(), "1".to_string());
+        message.body.insert("148".to_string(), "News".to_string());
+        message.body.insert("58".to_string(), text.to_string());
+        let clock: Arc<dyn Clock> = Arc::new(FixedClock);
+        message.encode(&clock).into_bytes()
+    }
+
+    #[test]
+    fn test_multi_byte_value_split_across_pushes() {
+        let bytes = encoded("Zürich");
+        // Split between the two bytes of ü
+        let split = bytes.windows(2).position(|window| window == "ü".as_bytes()).unwrap() + 1;
+        let mut framer = MessageFramer::new();
+        framer.push(&bytes[..split]);
+        assert_eq!(framer.next_message(), None);
+        framer.push(&bytes[split..]);
+
+        let frame = framer.next_message().unwrap();
+        let message = FixMessage::decode(std::str::from_utf8(&frame).unwrap()).unwrap();
+        assert_eq!(message.body.get("58").unwrap(), "Zürich");
+        assert!(framer.is_empty());
+    }
+
+    #[test]
+    fn test_frames_back_to_back_messages() {
+        let (first, second) = (encoded("first"), encoded("second"));
+        let mut framer = MessageFramer::new();
+        framer.push(&first);
+        framer.push(&second);
+        framer.push(b"8=FIX");
+
+        assert_eq!(framer.next_message(), Some(first));
+        assert_eq!(framer.next_message(), Some(second));
+        assert_eq!(framer.next_message(), None);
+        assert_eq!(framer.len(), 5);
+    }
+
+    #[test]
+    fn test_tag_value_framer_extracts_a_message() {
+        let message = encoded("hello");
+        let mut buf = message.clone();
+        buf.extend(b"8=FIX.4.4");
+        let mut framer = TagValueFramer::default();
+
+        assert_eq!(framer.next_frame(&mut buf), Some(message));
+        assert_eq!(buf, b"8=FIX.4.4");
+        assert_eq!(framer.next_frame(&mut buf), None);
+    }
+
+    #[test]
+    fn test_junk_ahead_of_the_begin_string_is_discarded() {
+        let message = encoded("hello");
+        assert_eq!(find_message_start(b"GARBAGE8=FIX.4.4\x01"), Some(7));
+        assert_eq!(find_message_start(b"GARBAGE"), None);
+        assert_eq!(find_message_start(b"58=FIX"), None);
+
+        let mut framer = MessageFramer::new();
+        framer.push(b"GARBAGE");
+        framer.push(&message);
+        assert_eq!(framer.next_message(), Some(message.clone()));
+
+        // A message cut off before its checksum is dropped along with the junk
+        framer.push(&message[..message.len() / 2]);
+        framer.push(&message);
+        assert_eq!(framer.next_message(), Some(message));
+        assert!(framer.is_empty());
+    }
+
+    #[test]
+    fn test_begin_string_inside_a_value_is_not_taken_for_a_message_start() {
+        let message = encoded("copied from 8=FIX.4.4\u{1}9=5\u{1}35=0\u{1}");
+        let mut framer = MessageFramer::new();
+        framer.push(&message);
+        assert_eq!(framer.next_message(), Some(message.clone()));
+
+        // Junk at the front is still skipped, up to the message rather than to the value inside it
+        framer.push(b"GARBAGE");
+        framer.push(&message);
+        assert_eq!(framer.next_message(), Some(message));
+        assert!(framer.is_empty());
+    }
+
+    #[test]
+    fn test_checksum_field_inside_a_value_does_not_end_the_message() {
+        let message = encoded("not the end\u{1}10=000\u{1}");
+        let mut framer = MessageFramer::new();
+        framer.push(&message[..message.len() - 4]);
+        assert_eq!(framer.next_message(), None);
+        framer.push(&message[message.len() - 4..]);
+        assert_eq!(framer.next_message(), Some(message));
+        assert!(framer.is_empty());
+    }
+
+    #[test]
+    fn test_body_length_out_of_range_is_not_believed() {
+        // Would overflow the frame end
+        let mut framer = MessageFramer::new();
+        framer.push(b"8=FIX.4.4\x019=18446744073709551615\x0135=0\x0110=000\x01");
+        assert_eq!(framer.next_message().unwrap(), b"8=FIX.4.4\x019=18446744073709551615\x0135=0\x0110=000\x01");
+        assert!(framer.is_empty());
+
+        // Longer than a message may be, so it runs to its first CheckSum rather than waiting for the rest
+        let mut framer = MessageFramer::with_framer(Framing::TagValue.framer(100));
+        framer.push(b"8=FIX.4.4\x019=101\x0135=0\x0110=000\x01");
+        assert_eq!(framer.next_message().unwrap(), b"8=FIX.4.4\x019=101\x0135=0\x0110=000\x01");
+    }
+
+    #[test]
+    fn test_sofh_framer_extracts_a_message() {
+        let message = encoded("hello");
+        let framed = SofhFramer::encode(&message);
+        assert_eq!(&framed[..6], &[0, 0, 0, (message.len() + 6) as u8, 0xF0, 0x00]);
+
+        let mut framer = SofhFramer;
+        // The length is known from the header, but the message has not all arrived yet
+        let mut buf = framed[..framed.len() - 1].to_vec();
+        assert_eq!(framer.next_frame(&mut buf), None);
+        buf.push(*framed.last().unwrap());
+        buf.extend(&framed[..3]);
+
+        assert_eq!(framer.next_frame(&mut buf), Some(message));
+        assert_eq!(buf, &framed[..3]);
+        assert_eq!(framer.next_frame(&mut buf), None);
+    }
+}
@@ -1,11 +1,33 @@
 // Publicly expose all the modules of the library
 
 pub mod engine;
+pub mod acceptor;
+pub mod application;
 pub mod message;
+pub mod framer;
+pub mod checksum;
 pub mod engine_factory;
+pub mod engine_config;
 pub mod tag;
 pub mod clock;
-mod message_optimised;
+pub mod decimal;
+pub mod session;
+pub mod schedule;
+pub mod reconnect;
+pub mod throttle;
+pub mod store;
+pub mod message_log;
+pub mod receipt;
+pub mod metrics;
+pub mod replay;
+pub mod error;
+pub mod event;
+pub mod observer;
+pub mod router;
+pub mod message_optimised;
+pub mod transport;
+pub mod channel;
+pub mod testing;
 
 // Re-export commonly used items for convenience
 pub use crate::engine::FixEngine;
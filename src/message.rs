@@ -1,161 +1,821 @@
-use crate::clock::Clock;
+use crate::checksum::Checksum;
+use crate::clock::{Clock, TIMESTAMP_PARSE_FORMAT};
+use crate::decimal::FixDecimal;
+use crate::error::DecodeError;
+use crate::receipt::{ReceiptSlot, SendResult};
+use crate::tag::numbers;
+use crate::tag::{BeginString, BusinessRejectReason, DkReason, FixField, FixTag, MsgType, OrdType, SessionRejectReason, Side, BODY_LENGTH_TAG, CHECKSUM_TAG, MSG_SEQ_NUM_TAG, SOH};
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
 use std::collections::HashMap;
 use std::fmt;
 use std::fmt::{Debug, Formatter, Write};
+use std::sync::mpsc::Receiver;
 use std::sync::Arc;
 
+mod fixml;
+#[cfg(feature = "serde")]
+mod json;
+#[cfg(feature = "serde")]
+pub use json::NamedFields;
+
+// A field a message builder needed from the message it was answering
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MissingField {
+    pub tag: u32,
+}
+
+impl fmt::Display for MissingField {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match numbers::field_name(self.tag) {
+            Some(name) => write!(f, "Required field {}({}) missing", name, self.tag),
+            None => write!(f, "Required field {} missing", self.tag),
+        }
+    }
+}
+
+impl std::error::Error for MissingField {}
+
 pub struct FixMessage {
     pub header: HashMap<String, String>,
     pub body: HashMap<String, String>,
     pub trailer: HashMap<String, String>,
+    raw: Option<Vec<u8>>, // Wire bytes as received, only kept when DecodeOptions::retain_raw is set
+    unknown: HashMap<String, String>, // Body tags missing from the tag number table, see DecodeOptions::collect_unknown
+    received_at: Option<DateTime<Utc>>, // When the engine read the message off the connection, by its clock
+    receipt: ReceiptSlot, // Set by track; clones are untracked
+}
+
+// A deep copy, e.g. to keep for a resend while the original goes down the channel. The raw bytes come along,
+// but a clone is untracked, so only the message that is actually sent reports on its receipt.
+impl Clone for FixMessage {
+    fn clone(&self) -> Self {
+        FixMessage {
+            header: self.header.clone(),
+            body: self.body.clone(),
+            trailer: self.trailer.clone(),
+            raw: self.raw.clone(),
+            unknown: self.unknown.clone(),
+            received_at: self.received_at,
+            receipt: ReceiptSlot::default(),
+        }
+    }
 }
 
 impl Debug for FixMessage {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.debug_struct("FixMessage")
             .field("header", &self.header)
-            .field("body", &self.body)
+            .field("body", &Masked(&self.body))
             .field("trailer", &self.trailer)
+            .field("unknown", &self.unknown)
             .finish() // Exclude the `clock` field
     }
 }
 
+// The wire form with SOH shown as '|', for logs. Nothing is added to the message, so one without a
+// SendingTime(52) is shown without it, and passwords are masked as in Debug.
+impl fmt::Display for FixMessage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let wire = self.encode_at(self.header.get("52").map(String::as_str), EncodeOptions::default()).wire;
+        for field in wire.split_terminator(SOH) {
+            match field.split_once('=') {
+                Some((tag, _)) if MASKED_FIELDS.contains(&tag) => write!(f, "{}=****|", tag)?,
+                _ => write!(f, "{}|", field)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// Fields whose values never appear in Debug output, and so never in the logs
+const MASKED_FIELDS: [&str; 2] = ["554", "925"]; // Password, NewPassword
+
+struct Masked<'a>(&'a HashMap<String, String>);
+
+impl Debug for Masked<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|(tag, value)| {
+                (tag, if MASKED_FIELDS.contains(&tag.as_str()) { "****" } else { value.as_str() })
+            }))
+            .finish()
+    }
+}
+
+// Length fields and the data fields whose byte count they give, e.g. RawDataLength(95) and RawData(96)
+const LENGTH_PREFIXED_FIELDS: [(&str, &str); 4] = [("95", "96"), ("90", "91"), ("93", "89"), ("212", "213")];
+
+// Fields that may be sent with nothing after the '=', e.g. an empty Text(58). FIX forbids it for the rest,
+// apart from data fields, whose length field can announce 0 bytes.
+const EMPTY_VALUE_FIELDS: [&str; 2] = ["58", "355"];
+
+// Trailer fields in the order they are sent, all ahead of CheckSum(10): SignatureLength(93) then Signature(89)
+const TRAILER_FIELDS: [&str; 2] = ["93", "89"];
+
+// Messages with up to this many header and body fields take the allocation-light encode path
+const SMALL_MESSAGE_FIELDS: usize = 32;
+
+// Wire form of a message along with the fields derived while encoding it
+struct Encoded {
+    wire: String,
+    body_length: usize,
+    checksum: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DecodeOptions<'a> {
+    // Accept a message whose checksum is followed by CRLF/whitespace or has no trailing SOH
+    pub lenient: bool,
+    // Keep a copy of the original bytes, available through FixMessage::raw
+    pub retain_raw: bool,
+    // Keep body tags the tag number table doesn't know apart, available through FixMessage::unknown_tags
+    pub collect_unknown: bool,
+    // Where the counterparty departs from the BeginString's header layout; None for the standard one
+    pub header_layout: Option<&'a HeaderLayout>,
+}
+
+// How a counterparty splits fields between header and body where it departs from the BeginString's layout,
+// as a venue profile may define it. Encoding and decoding consult it, so a message built or received without
+// it places fields by the standard layout.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeaderLayout {
+    // Tags carried in the header on top of the standard ones, e.g. custom routing fields. They are sent after
+    // the standard header fields, in this order.
+    pub extra: Vec<String>,
+    // Standard header tags the counterparty carries in the body instead; they are sent first in the body.
+    // BeginString(8), BodyLength(9) and MsgType(35) always stay in the header.
+    pub in_body: Vec<String>,
+}
+
+impl HeaderLayout {
+    fn adds(&self, tag: &str) -> bool {
+        self.extra.iter().any(|extra| extra == tag)
+    }
+
+    fn moves_to_body(&self, tag: &str) -> bool {
+        !FIXED_HEADER_FIELDS.contains(&tag) && self.in_body.iter().any(|moved| moved == tag)
+    }
+}
+
+// How a message is put on the wire beyond its own fields: the version to encode when it has no BeginString(8),
+// which a session takes from its config, and the counterparty's header layout
+#[derive(Debug, Clone, Copy)]
+pub struct EncodeOptions<'a> {
+    pub begin_string: BeginString,
+    pub header_layout: &'a HeaderLayout,
+}
+
+impl Default for EncodeOptions<'_> {
+    fn default() -> Self {
+        EncodeOptions { begin_string: BeginString::Fix4_4, header_layout: &STANDARD_LAYOUT }
+    }
+}
+
+static STANDARD_LAYOUT: HeaderLayout = HeaderLayout { extra: Vec::new(), in_body: Vec::new() };
+
+// Header fields every layout keeps where they are
+const FIXED_HEADER_FIELDS: [&str; 3] = ["8", "9", "35"];
+
+#[derive(Debug, Clone)]
+pub struct OrderSingleParams {
+    pub cl_ord_id: String,
+    pub symbol: String,
+    pub side: Side,
+    pub order_qty: FixDecimal,
+    pub ord_type: OrdType,
+    pub price: Option<FixDecimal>,
+}
+
+impl Default for FixMessage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FixMessage {
     pub fn new() -> FixMessage {
         FixMessage {
             header: HashMap::new(),
             body: HashMap::new(),
             trailer: HashMap::new(),
+            raw: None,
+            unknown: HashMap::new(),
+            received_at: None,
+            receipt: ReceiptSlot::default(),
+        }
+    }
+
+    // A message built in one call, e.g. FixMessage::from_pairs(&[("35", "A")], &[("98", "0"), ("108", "30")]).
+    // The header pairs go to the header as given; the others are placed by tag, as set_field does, so
+    // routing fields such as DeliverToCompID(128) or trailer fields may be listed among them.
+    pub fn from_pairs(header: &[(&str, &str)], body: &[(&str, &str)]) -> FixMessage {
+        let mut message = FixMessage::new();
+        for (tag, value) in header {
+            message.header.insert(tag.to_string(), value.to_string());
+        }
+        for (tag, value) in body {
+            message.place_field(tag.to_string(), value);
+        }
+        message
+    }
+
+    // The exact bytes this message was decoded from, if they were retained
+    pub fn raw(&self) -> Option<&[u8]> {
+        self.raw.as_deref()
+    }
+
+    // When the receive thread read this message, by the engine's clock; None for a message not received
+    pub fn received_at(&self) -> Option<DateTime<Utc>> {
+        self.received_at
+    }
+
+    pub(crate) fn set_received_at(&mut self, received_at: DateTime<Utc>) {
+        self.received_at = Some(received_at);
+    }
+
+    // Reports once this message has been written to the connection, with the MsgSeqNum it took, or why it was
+    // not. Call before handing it to the engine; a clone of it is not tracked.
+    pub fn track(&mut self) -> Receiver<SendResult> {
+        let (slot, receiver) = ReceiptSlot::track();
+        self.receipt = slot;
+        receiver
+    }
+
+    pub(crate) fn take_receipt(&mut self) -> ReceiptSlot {
+        std::mem::take(&mut self.receipt)
+    }
+
+    // Time from the peer's SendingTime(52) to receipt. Negative when the peer's clock runs ahead of ours;
+    // None when the message was not received or its SendingTime does not parse.
+    pub fn latency(&self) -> Option<TimeDelta> {
+        let sending_time = NaiveDateTime::parse_from_str(self.header.get("52")?, TIMESTAMP_PARSE_FORMAT).ok()?.and_utc();
+        Some(self.received_at? - sending_time)
+    }
+
+    // Venue-specific tags set aside by a decode with DecodeOptions::collect_unknown; they are still encoded
+    pub fn unknown_tags(&self) -> &HashMap<String, String> {
+        &self.unknown
+    }
+
+    pub fn new_order_single(params: OrderSingleParams) -> FixMessage {
+        let mut message = FixMessage::new();
+        insert_tag(&mut message.header, FixTag::MsgType(MsgType::OrderSingle));
+        insert_tag(&mut message.body, FixTag::ClOrdID(params.cl_ord_id));
+        insert_tag(&mut message.body, FixTag::Symbol(params.symbol));
+        insert_tag(&mut message.body, FixTag::Side(params.side));
+        insert_tag(&mut message.body, FixTag::OrderQty(params.order_qty));
+        insert_tag(&mut message.body, FixTag::OrdType(params.ord_type));
+        if let Some(price) = params.price {
+            insert_tag(&mut message.body, FixTag::Price(price));
+        }
+        message
+    }
+
+    // Logout(35=5), with the reason as Text(58) when there is one
+    pub fn logout(text: Option<&str>) -> FixMessage {
+        let mut message = FixMessage::new();
+        insert_tag(&mut message.header, FixTag::MsgType(MsgType::Logout));
+        message.set_text(text);
+        message
+    }
+
+    // Reject(35=3) refusing a message that failed the session-level checks, with RefTagID(371) naming the
+    // offending field when there is one
+    pub fn reject(ref_msg: &FixMessage, reason: SessionRejectReason, ref_tag_id: Option<u32>, text: Option<&str>) -> FixMessage {
+        let mut message = FixMessage::new();
+        insert_tag(&mut message.header, FixTag::MsgType(MsgType::Reject));
+        message.copy_ref_fields(ref_msg);
+        if let Some(ref_tag_id) = ref_tag_id {
+            message.set_field(numbers::REF_TAG_ID, &ref_tag_id.to_string());
+        }
+        insert_tag(&mut message.body, FixTag::SessionRejectReason(reason));
+        message.set_text(text);
+        message
+    }
+
+    // BusinessMessageReject(35=j) refusing an application message that was received intact
+    pub fn business_reject(ref_msg: &FixMessage, reason: BusinessRejectReason, text: &str) -> FixMessage {
+        let mut message = FixMessage::new();
+        insert_tag(&mut message.header, FixTag::MsgType(MsgType::BusinessMessageReject));
+        message.copy_ref_fields(ref_msg);
+        insert_tag(&mut message.body, FixTag::BusinessRejectReason(reason));
+        message.set_text(Some(text));
+        message
+    }
+
+    // RefSeqNum(45) and RefMsgType(372) pointing back at the message being refused
+    fn copy_ref_fields(&mut self, ref_msg: &FixMessage) {
+        for (tag, ref_tag) in [(numbers::REF_SEQ_NUM, numbers::MSG_SEQ_NUM), (numbers::REF_MSG_TYPE, numbers::MSG_TYPE)] {
+            if let Some(value) = ref_msg.get_field(ref_tag) {
+                self.set_field(tag, value);
+            }
+        }
+    }
+
+    // An empty text is left out, since Text(58) may not be sent without a value
+    fn set_text(&mut self, text: Option<&str>) {
+        if let Some(text) = text.filter(|text| !text.is_empty()) {
+            self.set_field(numbers::TEXT, text);
+        }
+    }
+
+    // DontKnowTrade(35=Q) answering an ExecutionReport that cannot be matched to an order. The identifiers,
+    // Side and quantities are copied from the report and it is addressed back to whoever sent it.
+    pub fn dont_know_trade(exec_report: &FixMessage, reason: DkReason) -> Result<FixMessage, MissingField> {
+        let mut message = FixMessage::new();
+        insert_tag(&mut message.header, FixTag::MsgType(MsgType::DontKnowTrade));
+        for (tag, reply_tag) in [(numbers::SENDER_COMP_ID, numbers::TARGET_COMP_ID), (numbers::TARGET_COMP_ID, numbers::SENDER_COMP_ID),
+                                 (numbers::SENDER_SUB_ID, numbers::TARGET_SUB_ID), (numbers::TARGET_SUB_ID, numbers::SENDER_SUB_ID)] {
+            if let Some(value) = exec_report.get_field(tag) {
+                message.set_field(reply_tag, value);
+            }
+        }
+        for tag in [numbers::ORDER_ID, numbers::EXEC_ID, numbers::SYMBOL, numbers::SIDE, numbers::ORDER_QTY] {
+            message.set_field(tag, exec_report.get_field(tag).ok_or(MissingField { tag })?);
+        }
+        for tag in [numbers::LAST_QTY, numbers::LAST_PX] {
+            if let Some(value) = exec_report.get_field(tag) {
+                message.set_field(tag, value);
+            }
         }
+        insert_tag(&mut message.body, FixTag::DkReason(reason));
+        Ok(message)
+    }
+
+    // ExecutionReport(35=8) filling a NewOrderSingle in full, as a simulated venue would: the echo acceptor answers
+    // with it, and a test venue of one's own can too. The order's identifiers, Side and OrderQty are copied; the
+    // fill is at its Price, or 0 for an order without one.
+    pub fn fill(order: &FixMessage, order_id: &str, exec_id: &str) -> Result<FixMessage, MissingField> {
+        let mut message = FixMessage::new();
+        insert_tag(&mut message.header, FixTag::MsgType(MsgType::ExecutionReport));
+        for tag in [numbers::CL_ORD_ID, numbers::SYMBOL, numbers::SIDE, numbers::ORDER_QTY] {
+            message.set_field(tag, order.get_field(tag).ok_or(MissingField { tag })?);
+        }
+        let qty = order.get_field(numbers::ORDER_QTY).unwrap_or_default().to_string();
+        let price = order.get_field(numbers::PRICE).unwrap_or("0").to_string();
+        for (tag, value) in [(numbers::ORDER_ID, order_id), (numbers::EXEC_ID, exec_id), (numbers::EXEC_TYPE, "F"), (numbers::ORD_STATUS, "2"),
+                             (numbers::LAST_QTY, &qty), (numbers::LAST_PX, &price), (numbers::CUM_QTY, &qty), (numbers::LEAVES_QTY, "0"),
+                             (numbers::AVG_PX, &price)] {
+            message.set_field(tag, value);
+        }
+        Ok(message)
+    }
+
+    // MsgType(35) as the typed enum; None when it is missing or not one we know
+    pub fn msg_type_enum(&self) -> Option<MsgType> {
+        self.header.get("35")?.parse().ok()
+    }
+
+    // Text(58): the free-form explanation a Logout or Reject may carry
+    pub fn text(&self) -> Option<&str> {
+        self.get_field(numbers::TEXT)
+    }
+
+    // ApplVerID(1128): the application version of this message on a FIXT.1.1 session
+    pub fn appl_ver_id(&self) -> Option<&str> {
+        self.get_field(numbers::APPL_VER_ID)
+    }
+
+    // DefaultApplVerID(1137): the application version a FIXT.1.1 Logon sets for the whole session
+    pub fn default_appl_ver_id(&self) -> Option<&str> {
+        self.get_field(numbers::DEFAULT_APPL_VER_ID)
+    }
+
+    pub fn get_field(&self, tag: u32) -> Option<&str> {
+        let key = tag.to_string();
+        self.header.get(&key)
+            .or_else(|| self.body.get(&key))
+            .or_else(|| self.trailer.get(&key))
+            .or_else(|| self.unknown.get(&key))
+            .map(String::as_str)
+    }
+
+    pub fn set_field(&mut self, tag: u32, value: &str) {
+        self.place_field(tag.to_string(), value);
+    }
+
+    // Puts a field in the header, body or trailer according to its tag
+    fn place_field(&mut self, key: String, value: &str) {
+        if key == CHECKSUM_TAG || TRAILER_FIELDS.contains(&key.as_str()) {
+            self.trailer.insert(key, value.to_string());
+        } else if self.is_header_field(&key, EncodeOptions::default()) {
+            self.header.insert(key, value.to_string());
+        } else {
+            self.body.insert(key, value.to_string());
+        }
+    }
+
+    pub fn get_decimal(&self, tag: u32) -> Option<Result<FixDecimal, &'static str>> {
+        self.get_field(tag).map(str::parse)
+    }
+
+    pub fn set_decimal(&mut self, tag: u32, value: FixDecimal) {
+        self.set_field(tag, &value.to_string());
     }
 
     pub fn encode(&mut self, clock: &Arc<dyn Clock>) -> String {
-        // Ensure mandatory fields are populated
+        self.encode_with_options(clock, &EncodeOptions::default())
+    }
+
+    // Like encode, with a missing BeginString(8) taken from the options, and the fields split between header
+    // and body as the counterparty's layout has them, whichever of the two the message holds them in
+    pub fn encode_with_options(&mut self, clock: &Arc<dyn Clock>, options: &EncodeOptions) -> String {
+        self.populate_mandatory_fields(clock, options.begin_string);
+        let encoded = self.encode_fields(clock, *options);
+        // Keep the derived fields as they went out on the wire
+        self.header.insert("9".to_string(), encoded.body_length.to_string());
+        self.trailer.insert("10".to_string(), encoded.checksum);
+        encoded.wire
+    }
+
+    // Like encode, but a given checksum is sent verbatim as CheckSum(10) in place of the computed one, to produce
+    // messages a conformant peer must reject
+    #[cfg(any(test, feature = "testing"))]
+    pub fn encode_with_checksum_override(&mut self, clock: &Arc<dyn Clock>, checksum: Option<String>) -> String {
+        let mut wire = self.encode(clock);
+        if let Some(checksum) = checksum {
+            let computed = self.trailer.insert("10".to_string(), checksum.clone()).unwrap_or_default();
+            wire.truncate(wire.len() - computed.len() - 1);
+            wire.push_str(&checksum);
+            wire.push(SOH);
+        }
+        wire
+    }
+
+    // Encodes without touching the message: BeginString(8) and SendingTime(52) default when missing, and
+    // BodyLength(9) and CheckSum(10) are computed for the output only
+    pub fn encode_ref(&self, clock: &Arc<dyn Clock>) -> String {
+        self.encode_fields(clock, EncodeOptions::default()).wire
+    }
+
+    fn encode_fields(&self, clock: &Arc<dyn Clock>, options: EncodeOptions) -> Encoded {
+        let now;
+        let sending_time = match self.header.get("52") {
+            Some(sending_time) => sending_time.as_str(),
+            None => {
+                now = clock.now();
+                now.as_str()
+            }
+        };
+        self.encode_at(Some(sending_time), options)
+    }
+
+    // Without a SendingTime the field is left out altogether
+    fn encode_at(&self, sending_time: Option<&str>, options: EncodeOptions) -> Encoded {
+        if self.header.len() + self.body.len() + self.unknown.len() <= SMALL_MESSAGE_FIELDS {
+            self.encode_small(sending_time, options)
+        } else {
+            self.encode_general(sending_time, options)
+        }
+    }
+
+    // Checks that every header field is defined for the message's BeginString, e.g. NoHops(627) is not valid in FIX.4.2
+    pub fn validate_header(&self) -> Result<(), &'static str> {
+        let begin_string = BeginString::from_wire(self.header.get("8").ok_or("Missing BeginString")?)?;
+        let header_fields = begin_string.header_fields();
+        if self.header.keys().all(|tag| header_fields.contains(&tag.as_str())) {
+            Ok(())
+        } else {
+            Err("Header field not defined for BeginString")
+        }
+    }
+
+    // Header layout for the message's BeginString; missing or unknown versions use the given default's layout
+    fn header_fields(&self, default: BeginString) -> &'static [&'static str] {
+        self.header.get("8")
+            .and_then(|value| BeginString::from_wire(value).ok())
+            .unwrap_or(default)
+            .header_fields()
+    }
+
+    fn is_header_field(&self, tag: &str, options: EncodeOptions) -> bool {
+        let layout = options.header_layout;
+        (self.header_fields(options.begin_string).contains(&tag) && !layout.moves_to_body(tag)) || layout.adds(tag)
+    }
+
+    // The BeginString's header fields the layout leaves in the header, in wire order
+    fn standard_header_tags<'a>(&self, options: EncodeOptions<'a>) -> impl Iterator<Item = &'static str> + 'a {
+        let layout = options.header_layout;
+        self.header_fields(options.begin_string).iter().copied().filter(|tag| !layout.moves_to_body(tag))
+    }
+
+    // Header fields outside the BeginString's layout, which encode appends to the standard ones
+    fn extra_header_values<'a>(&'a self, options: EncodeOptions<'a>) -> impl Iterator<Item = (&'a str, &'a str)> {
+        let header_fields = self.header_fields(options.begin_string);
+        options.header_layout.extra.iter()
+            .filter(move |tag| !header_fields.contains(&tag.as_str()))
+            .filter_map(|tag| self.header.get_key_value(tag).or_else(|| self.body.get_key_value(tag)))
+            .map(|(tag, value)| (tag.as_str(), value.as_str()))
+    }
+
+    // Standard header fields the layout sends at the start of the body
+    fn moved_header_values<'a>(&'a self, options: EncodeOptions<'a>, sending_time: Option<&'a str>) -> impl Iterator<Item = (&'a str, &'a str)> {
+        let layout = options.header_layout;
+        layout.in_body.iter()
+            .filter(|tag| layout.moves_to_body(tag))
+            .filter_map(move |tag| {
+                let value = self.header_value(tag, sending_time, options).or_else(|| self.body.get(tag).map(String::as_str))?;
+                Some((tag.as_str(), value))
+            })
+    }
+
+    // Body fields in wire order, leaving out those the layout sends in the header
+    fn body_values<'a>(&'a self, options: EncodeOptions<'a>, sending_time: Option<&'a str>) -> impl Iterator<Item = (&'a str, &'a str)> {
+        let layout = options.header_layout;
+        self.moved_header_values(options, sending_time)
+            .chain(body_fields(&self.body).filter(|(tag, _)| !layout.adds(tag) && !layout.moves_to_body(tag)))
+            .chain(unknown_fields(&self.unknown))
+    }
+
+    fn populate_mandatory_fields(&mut self, clock: &Arc<dyn Clock>, begin_string: BeginString) {
         if !self.header.contains_key("8") {
-            self.header.insert("8".to_string(), "FIX.4.4".to_string());
+            self.header.insert("8".to_string(), begin_string.value());
         }
         if !self.header.contains_key("52") {
             self.header.insert("52".to_string(), clock.now());
         }
+    }
+
+    // Value a header field is encoded with; BodyLength(9) is computed separately
+    fn header_value<'a>(&'a self, tag: &str, sending_time: Option<&'a str>, options: EncodeOptions) -> Option<&'a str> {
+        match tag {
+            "8" => Some(self.header.get("8").map_or(options.begin_string.as_str(), String::as_str)),
+            "52" => sending_time,
+            _ => self.header.get(tag).map(String::as_str),
+        }
+    }
+
+    // Trailer fields other than the checksum; they count towards BodyLength and the checksum like the body
+    fn trailer_fields(&self) -> impl Iterator<Item = (&str, &str)> {
+        let ordered = TRAILER_FIELDS.iter().filter_map(|tag| self.trailer.get_key_value(*tag));
+        let others = self.trailer.iter().filter(|(tag, _)| *tag != CHECKSUM_TAG && !TRAILER_FIELDS.contains(&tag.as_str()));
+        ordered.chain(others).map(|(tag, value)| (tag.as_str(), value.as_str()))
+    }
+
+    // Fast path for typical messages: gathers the fields into a stack array and writes the
+    // output into a single pre-sized buffer instead of building intermediate strings.
+    fn encode_small(&self, sending_time: Option<&str>, options: EncodeOptions) -> Encoded {
+        let mut fields: [(&str, &str); SMALL_MESSAGE_FIELDS] = [("", ""); SMALL_MESSAGE_FIELDS];
+        let mut field_count = 0;
+        let mut body_length = 0;
+
+        for tag in self.standard_header_tags(options).filter(|tag| *tag != "9" && *tag != "8") {
+            if let Some(value) = self.header_value(tag, sending_time, options) {
+                body_length += tag.len() + value.len() + 2;
+            }
+        }
+        for (tag, value) in self.extra_header_values(options) {
+            body_length += tag.len() + value.len() + 2;
+        }
+        for (tag, value) in self.body_values(options, sending_time) {
+            fields[field_count] = (tag, value);
+            field_count += 1;
+            body_length += tag.len() + value.len() + 2;
+        }
+        for (tag, value) in self.trailer_fields() {
+            body_length += tag.len() + value.len() + 2;
+        }
+
+        let body_length_value = body_length.to_string();
+        let mut output = String::with_capacity(body_length + 32);
+        for tag in self.standard_header_tags(options) {
+            let value = if tag == "9" { Some(body_length_value.as_str()) } else { self.header_value(tag, sending_time, options) };
+            if let Some(value) = value {
+                push_field(&mut output, tag, value);
+            }
+        }
+        for (tag, value) in self.extra_header_values(options).chain(fields[..field_count].iter().copied()).chain(self.trailer_fields()) {
+            push_field(&mut output, tag, value);
+        }
+
+        let checksum = calculate_checksum(&output);
+        push_field(&mut output, CHECKSUM_TAG, &checksum);
+        Encoded { wire: output, body_length, checksum }
+    }
 
+    fn encode_general(&self, sending_time: Option<&str>, options: EncodeOptions) -> Encoded {
         // Step 1: Concatenate body fields with SOH as the separator
         let mut fix_body = String::new();
-        for (tag, value) in &self.body {
-            write!(fix_body, "{}={}{}", tag, value, '\x01').unwrap();  // Append SOH after each tag-value pair
+        for (tag, value) in self.body_values(options, sending_time).chain(self.trailer_fields()) {
+            write!(fix_body, "{}={}{}", tag, value, SOH).unwrap();  // Append SOH after each tag-value pair
         }
 
         // Step 2: Calculate BodyLength (length of message after "9=" tag, excluding checksum)
-        let body_length_value = {
+        let body_length = {
             // Temporarily create the header without BodyLength (9=) and checksum (10=)
             let mut fix_header = String::new();
-            for (tag, value) in &self.header {
-                if tag != "9" && tag != "8" {
-                    write!(fix_header, "{}={}{}", tag, value, '\x01').unwrap();
+            for tag in self.standard_header_tags(options).filter(|tag| *tag != "9" && *tag != "8") {
+                if let Some(value) = self.header_value(tag, sending_time, options) {
+                    write!(fix_header, "{}={}{}", tag, value, SOH).unwrap();
                 }
             }
+            for (tag, value) in self.extra_header_values(options) {
+                write!(fix_header, "{}={}{}", tag, value, SOH).unwrap();
+            }
             fix_header.len() + fix_body.len()
         };
 
-        // Step 3: Insert BodyLength (Tag 9)
-        self.header.insert("9".to_string(), body_length_value.to_string());
-
-        // Step 4: Rebuild the full header with the BodyLength now included
+        // Step 3: Build the full header with the BodyLength included
+        let body_length_value = body_length.to_string();
         let mut fix_header = String::new();
-        for tag in &["8", "9", "35", "49", "56", "34", "52"] { // Ensure correct order of important tags
-            if let Some(value) = self.header.get(*tag) {
-                write!(fix_header, "{}={}{}", tag, value, '\x01').unwrap();
+        for tag in self.standard_header_tags(options) { // Ensure correct order of header tags for the version
+            let value = if tag == "9" { Some(body_length_value.as_str()) } else { self.header_value(tag, sending_time, options) };
+            if let Some(value) = value {
+                write!(fix_header, "{}={}{}", tag, value, SOH).unwrap();
             }
         }
+        for (tag, value) in self.extra_header_values(options) {
+            write!(fix_header, "{}={}{}", tag, value, SOH).unwrap();
+        }
 
-        // Step 5: Combine header and body
-        let message_without_checksum = format!("{}{}", fix_header, fix_body);
-
-        // Step 6: Calculate checksum (sum of all bytes mod 256)
-        let checksum = calculate_checksum(&message_without_checksum);
-        self.trailer.insert("10".to_string(), checksum);
+        // Step 4: Combine header and body
+        let mut message = format!("{}{}", fix_header, fix_body);
 
-        // Step 7: Concatenate trailer (which contains the checksum) with SOH as the separator
-        let mut fix_trailer = String::new();
-        for (tag, value) in &self.trailer {
-            write!(fix_trailer, "{}={}{}", tag, value, '\x01').unwrap();  // Append SOH after each tag-value pair
-        }
+        // Step 5: Calculate checksum (sum of all bytes mod 256) and append it
+        let checksum = calculate_checksum(&message);
+        push_field(&mut message, CHECKSUM_TAG, &checksum);
+        Encoded { wire: message, body_length, checksum }
+    }
 
-        // Step 8: Final message with SOH at the end
-        format!("{}{}{}", fix_header, fix_body, fix_trailer)
+    pub fn decode(fix_str: &str) -> Result<FixMessage, DecodeError> {
+        Self::decode_with_options(fix_str, &DecodeOptions::default())
     }
 
-    pub fn decode(fix_str: &str) -> Result<FixMessage, &'static str> {
-        // Ensure the message ends with SOH ('\x01')
-        if !fix_str.ends_with('\x01') {
-            return Err("Message does not end with SOH");
-        }
+    pub fn decode_with_options(fix_str: &str, options: &DecodeOptions) -> Result<FixMessage, DecodeError> {
+        let message_without_trailing_soh = if options.lenient {
+            // Some feeds append CRLF after the checksum or omit its trailing SOH
+            let trimmed = fix_str.trim_end_matches(['\r', '\n', ' ', '\t']);
+            trimmed.strip_suffix(SOH).unwrap_or(trimmed)
+        } else {
+            // Ensure the message ends with SOH ('\x01')
+            if !fix_str.ends_with('\x01') {
+                return Err(DecodeError::MissingTrailingSoh);
+            }
 
-        // Remove the trailing SOH before parsing
-        let message_without_trailing_soh = &fix_str[..fix_str.len() - 1];
+            // Remove the trailing SOH before parsing
+            &fix_str[..fix_str.len() - 1]
+        };
 
         let mut message = FixMessage::new();
+        // Fields are placed where encoding would put them, by the message's own BeginString
+        let placement = EncodeOptions { header_layout: options.header_layout.unwrap_or(&STANDARD_LAYOUT), ..EncodeOptions::default() };
 
-        // Split the message into key-value pairs using '\x01' as the field separator
-        let fields: Vec<&str> = message_without_trailing_soh.split('\x01').filter(|&x| !x.is_empty()).collect();
-
-        let mut checksum_input = String::new(); // The portion of the message for checksum calculation
+        let mut remaining = message_without_trailing_soh;
+        let mut data_field: Option<(&str, usize)> = None; // Tag and byte length announced by a length field
+        let mut body_length: Option<(usize, usize)> = None; // BodyLength(9) and where the fields it counts start
 
-        for part in fields {
-            // Split each part by '=' to get the tag and value
-            let key_value: Vec<&str> = part.splitn(2, '=').collect();
-            if key_value.len() != 2 {
-                return Err("Invalid key-value pair in FIX message");
+        while !remaining.is_empty() {
+            // Fields are separated by '\x01'; skip empty ones
+            if let Some(rest) = remaining.strip_prefix(SOH) {
+                remaining = rest;
+                continue;
             }
 
-            let tag = key_value[0];
-            let value = key_value[1];
+            let field_start = message_without_trailing_soh.len() - remaining.len();
 
-            // Skip validation for the "9" tag (BodyLength)
-            if tag == "9" {
-                message.header.insert(tag.to_string(), value.to_string());
-                // continue;
-            }
+            // Split each field by '=' to get the tag and value
+            let (tag, rest) = match remaining.split_once('=') {
+                Some((tag, rest)) if tag.parse::<u32>().is_ok() && tag.bytes().all(|b| b.is_ascii_digit()) => (tag, rest),
+                _ => return Err(DecodeError::InvalidField),
+            };
 
-            if tag == "10" {
-                // Ensure checksum is the last field
-                let received_checksum = value;
-                let calculated_checksum = calculate_checksum(&checksum_input);
-                if received_checksum != calculated_checksum {
-                    return Err("Invalid checksum");
+            // A data field is read by its announced length since its value may contain SOH
+            let (value_len, is_data) = match data_field.take() {
+                Some((data_tag, len)) if data_tag == tag => {
+                    if !rest.is_char_boundary(len) || !(rest.len() == len || rest[len..].starts_with(SOH)) {
+                        return Err(DecodeError::DataLengthMismatch { tag: tag_number(tag) });
+                    }
+                    (len, true)
                 }
-                message.trailer.insert(tag.to_string(), received_checksum.to_string());
-                break;  // Stop processing after checksum
+                _ => (rest.find(SOH).unwrap_or(rest.len()), false),
+            };
+            let value = &rest[..value_len];
+            remaining = &rest[value_len..];
+
+            if let Some(&(_, data_tag)) = LENGTH_PREFIXED_FIELDS.iter().find(|(length_tag, _)| *length_tag == tag) {
+                let len = value.parse().map_err(|_| DecodeError::InvalidDataLength { tag: tag_number(tag), raw: value.to_string() })?;
+                data_field = Some((data_tag, len));
             }
 
-            // Add the part to checksum input before the checksum
-            checksum_input.push_str(part);
-            checksum_input.push('\x01');  // SOH between fields
+            if tag == BODY_LENGTH_TAG {
+                // Counted from just past this field's SOH
+                body_length = Some((parse_body_length(value)?, message_without_trailing_soh.len() - remaining.len() + 1));
+            }
 
-            // Populate the header, body, or trailer based on the tag
-            match tag {
-                "8" | "35" | "49" | "56" | "34" | "52" => {
-                    message.header.insert(tag.to_string(), value.to_string());
+            if tag == CHECKSUM_TAG {
+                if let Some((expected, body_start)) = body_length {
+                    if field_start.checked_sub(body_start) != Some(expected) {
+                        return Err(DecodeError::BodyLengthMismatch { declared: expected, actual: field_start.saturating_sub(body_start) });
+                    }
                 }
-                _ => {
-                    message.body.insert(tag.to_string(), value.to_string());
+                // Summed in one pass over the input as received, which is everything ahead of this field
+                let received_checksum = parse_checksum(value)?;
+                let mut checksum = Checksum::new();
+                checksum.update(&fix_str.as_bytes()[..field_start]);
+                if received_checksum != checksum.value() {
+                    return Err(DecodeError::ChecksumMismatch { received: received_checksum, calculated: checksum.value() });
                 }
+                message.trailer.insert(tag.to_string(), value.to_string());
+                break;  // Stop processing after checksum
+            }
+
+            if tag == MSG_SEQ_NUM_TAG {
+                parse_seq_num(value)?;
+            }
+            if value.is_empty() && !is_data && !EMPTY_VALUE_FIELDS.contains(&tag) {
+                return Err(DecodeError::EmptyValue { tag: tag_number(tag) });
+            }
+
+            // Header fields by the message's BeginString and the layout, then trailer fields, then tags the table
+            // doesn't know when they are collected apart; everything else is body
+            if message.is_header_field(tag, placement) {
+                message.header.insert(tag.to_string(), value.to_string());
+            } else if TRAILER_FIELDS.contains(&tag) {
+                message.trailer.insert(tag.to_string(), value.to_string());
+            } else if options.collect_unknown && tag.parse().ok().and_then(numbers::field_name).is_none() {
+                message.unknown.insert(tag.to_string(), value.to_string());
+            } else {
+                message.body.insert(tag.to_string(), value.to_string());
             }
         }
 
+        if options.retain_raw {
+            message.raw = Some(fix_str.as_bytes().to_vec());
+        }
         Ok(message)
     }
 }
 
+// Body fields in wire order, with each data field placed directly after its length field
+fn body_fields(body: &HashMap<String, String>) -> impl Iterator<Item = (&str, &str)> {
+    body.iter()
+        .filter(|(tag, _)| {
+            !LENGTH_PREFIXED_FIELDS.iter().any(|(length_tag, data_tag)| data_tag == tag && body.contains_key(*length_tag))
+        })
+        .flat_map(move |(tag, value)| {
+            let data_field = LENGTH_PREFIXED_FIELDS.iter()
+                .find(|(length_tag, _)| length_tag == tag)
+                .and_then(|(_, data_tag)| body.get_key_value(*data_tag));
+            std::iter::once((tag, value)).chain(data_field).map(|(tag, value)| (tag.as_str(), value.as_str()))
+        })
+}
+
+fn unknown_fields(unknown: &HashMap<String, String>) -> impl Iterator<Item = (&str, &str)> {
+    unknown.iter().map(|(tag, value)| (tag.as_str(), value.as_str()))
+}
+
+fn push_field(output: &mut String, tag: &str, value: &str) {
+    output.push_str(tag);
+    output.push('=');
+    output.push_str(value);
+    output.push(SOH);
+}
+
+fn insert_tag(fields: &mut HashMap<String, String>, tag: FixTag) {
+    fields.insert(tag.tag_id().to_string(), tag.value());
+}
+
 // Helper function for calculating the checksum (mod 256 sum of all characters)
 fn calculate_checksum(fix_str: &str) -> String {
-    let sum: u32 = fix_str.as_bytes().iter().map(|&b| b as u32).sum();
-    format!("{:03}", sum % 256)
+    let mut checksum = Checksum::new();
+    checksum.update(fix_str.as_bytes());
+    checksum.finalize()
+}
+
+// The checksum is always sent as exactly three digits, e.g. "009"
+fn parse_checksum(value: &str) -> Result<u8, DecodeError> {
+    if value.len() != 3 || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(DecodeError::MalformedChecksum { raw: value.to_string() });
+    }
+    value.parse().map_err(|_| DecodeError::MalformedChecksum { raw: value.to_string() })
+}
+
+// BodyLength is a positive integer written with plain digits; every message has at least a MsgType to count
+fn parse_body_length(value: &str) -> Result<usize, DecodeError> {
+    match value.parse::<usize>() {
+        Ok(body_length) if body_length > 0 && value.bytes().all(|b| b.is_ascii_digit()) => Ok(body_length),
+        _ => Err(DecodeError::InvalidBodyLength { raw: value.to_string() }),
+    }
+}
+
+// MsgSeqNum is a positive integer written with plain digits
+fn parse_seq_num(value: &str) -> Result<u64, DecodeError> {
+    match value.parse::<u64>() {
+        Ok(seq_num) if seq_num > 0 && value.bytes().all(|b| b.is_ascii_digit()) => Ok(seq_num),
+        _ => Err(DecodeError::InvalidSeqNum { raw: value.to_string() }),
+    }
+}
+
+// Tags are checked to be numeric as each field is split off
+fn tag_number(tag: &str) -> u32 {
+    tag.parse().unwrap_or_default()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::clock::Clock;
+    use crate::clock::{Clock, TIMESTAMP_FORMAT};
+    use crate::tag::numbers;
     use std::sync::Arc;
 
     struct FixedClock;
@@ -276,23 +936,628 @@ mod tests {
         assert!(encoded_message.contains("10=")); // Checksum field
     }
 
+    #[test]
+    fn test_new_order_single_limit_order() {
+        let msg = FixMessage::new_order_single(OrderSingleParams {
+            cl_ord_id: "ORD-1".to_string(),
+            symbol: "BTCUSDT".to_string(),
+            side: Side::Buy,
+            order_qty: "100".parse().unwrap(),
+            ord_type: OrdType::Limit,
+            price: Some("101.25".parse().unwrap()),
+        });
+
+        assert_eq!(msg.header.get("35").unwrap(), "D");
+        assert_eq!(msg.body.get("11").unwrap(), "ORD-1");
+        assert_eq!(msg.body.get("55").unwrap(), "BTCUSDT");
+        assert_eq!(msg.body.get("54").unwrap(), "1");
+        assert_eq!(msg.body.get("38").unwrap(), "100");
+        assert_eq!(msg.body.get("40").unwrap(), "2");
+        assert_eq!(msg.body.get("44").unwrap(), "101.25");
+        assert_eq!(msg.body.len(), 6);
+    }
+
+    #[test]
+    fn test_encode_uses_header_layout_of_begin_string() {
+        let fixed_clock = create_fixed_clock();
+        let header_message = |begin_string: BeginString| {
+            let mut msg = FixMessage::new();
+            msg.header.insert("8".to_string(), begin_string.value());
+            msg.header.insert("35".to_string(), "0".to_string());
+            msg.header.insert("34".to_string(), "7".to_string());
+            msg.header.insert("49".to_string(), "SENDER".to_string());
+            msg.header.insert("56".to_string(), "TARGET".to_string());
+            msg.header.insert("43".to_string(), "Y".to_string());
+            msg.header.insert("122".to_string(), "20231016-12:29:00.000".to_string());
+            msg.header.insert("627".to_string(), "0".to_string());
+            msg
+        };
+
+        let mut fix44 = header_message(BeginString::Fix4_4);
+        assert!(fix44.validate_header().is_ok());
+        assert_eq!(
+            fix44.encode(&fixed_clock),
+            "8=FIX.4.4\x019=92\x0135=0\x0149=SENDER\x0156=TARGET\x0134=7\x0143=Y\x0152=20231016-12:30:00.123\x01122=20231016-12:29:00.000\x01627=0\x0110=074\x01"
+        );
+
+        // NoHops(627) only exists from FIX.4.3 onwards
+        let mut fix42 = header_message(BeginString::Fix4_2);
+        assert_eq!(fix42.validate_header(), Err("Header field not defined for BeginString"));
+        assert_eq!(
+            fix42.encode(&fixed_clock),
+            "8=FIX.4.2\x019=86\x0135=0\x0149=SENDER\x0156=TARGET\x0134=7\x0143=Y\x0152=20231016-12:30:00.123\x01122=20231016-12:29:00.000\x0110=062\x01"
+        );
+
+        // Decoding keeps optional header fields in the header
+        let decoded = FixMessage::decode(&fix42.encode(&fixed_clock)).unwrap();
+        assert_eq!(decoded.header.get("43").unwrap(), "Y");
+        assert_eq!(decoded.header.get("122").unwrap(), "20231016-12:29:00.000");
+        assert!(decoded.body.is_empty());
+    }
+
+    #[test]
+    fn test_missing_begin_string_takes_the_configured_default() {
+        let fixed_clock = create_fixed_clock();
+        let mut msg = FixMessage::new();
+        msg.header.insert("35".to_string(), "0".to_string());
+        msg.header.insert("627".to_string(), "0".to_string());
+        let options = EncodeOptions { begin_string: BeginString::Fix4_2, ..EncodeOptions::default() };
+
+        // Laid out as FIX.4.2 too, so without NoHops(627)
+        assert_eq!(msg.encode_with_options(&fixed_clock, &options), "8=FIX.4.2\x019=30\x0135=0\x0152=20231016-12:30:00.123\x0110=142\x01");
+        assert_eq!(msg.header.get("8").unwrap(), "FIX.4.2");
+    }
+
+    #[test]
+    fn test_routing_fields_are_placed_in_the_header() {
+        let mut msg = FixMessage::new();
+        msg.set_field(numbers::MSG_TYPE, "D");
+        msg.set_field(numbers::MSG_SEQ_NUM, "2");
+        msg.set_field(numbers::SENDER_COMP_ID, "BROKER");
+        msg.set_field(numbers::TARGET_COMP_ID, "HUB");
+        msg.set_field(numbers::DELIVER_TO_COMP_ID, "VENUE");
+        msg.set_field(numbers::ON_BEHALF_OF_COMP_ID, "CLIENT");
+        msg.set_field(numbers::CL_ORD_ID, "1");
+        assert!(msg.header.contains_key("115") && msg.header.contains_key("128"));
+
+        let encoded = msg.encode(&create_fixed_clock());
+        assert!(encoded.contains("\x0149=BROKER\x0156=HUB\x01115=CLIENT\x01128=VENUE\x0134=2\x0152="));
+        assert!(encoded.find("128=").unwrap() < encoded.find("11=").unwrap());
+    }
+
+    #[test]
+    fn test_small_encode_matches_general_encode() {
+        let fixed_clock = create_fixed_clock();
+        let mut msg = FixMessage::new();
+        msg.header.insert("35".to_string(), "8".to_string());
+        msg.header.insert("49".to_string(), "SENDER".to_string());
+        msg.header.insert("56".to_string(), "TARGET".to_string());
+        msg.header.insert("34".to_string(), "12".to_string());
+        for (tag, value) in [("37", "ORD-1"), ("17", "EXEC-1"), ("150", "F"), ("39", "2"), ("55", "BTCUSDT"),
+                             ("54", "1"), ("38", "100"), ("32", "100"), ("31", "101.25"), ("151", "0"),
+                             ("14", "100"), ("6", "101.25"), ("11", "CL-1")] {
+            msg.body.insert(tag.to_string(), value.to_string());
+        }
+
+        // Both paths iterate the same maps, so the output must be byte for byte identical
+        let sending_time = fixed_clock.now();
+        let general = msg.encode_general(Some(&sending_time), EncodeOptions::default()).wire;
+        let small = msg.encode_small(Some(&sending_time), EncodeOptions::default()).wire;
+        assert_eq!(small, general);
+        assert_eq!(msg.encode(&fixed_clock), general);
+        assert!(FixMessage::decode(&small).is_ok());
+
+        let mut empty_body = FixMessage::new();
+        empty_body.header.insert("35".to_string(), "0".to_string());
+        assert_eq!(empty_body.encode_small(Some(&sending_time), EncodeOptions::default()).wire, empty_body.encode_general(Some(&sending_time), EncodeOptions::default()).wire);
+    }
+
+    #[test]
+    fn test_from_pairs_builds_the_same_message_as_inserts() {
+        let fixed_clock = create_fixed_clock();
+        let mut msg = FixMessage::new();
+        msg.header.insert("8".to_string(), "FIX.4.4".to_string());
+        msg.header.insert("35".to_string(), "A".to_string());
+        msg.header.insert("49".to_string(), "SENDER".to_string());
+        msg.header.insert("56".to_string(), "TARGET".to_string());
+        msg.header.insert("34".to_string(), "1".to_string());
+        msg.header.insert("52".to_string(), fixed_clock.now());
+        msg.body.insert("98".to_string(), "0".to_string());
+        msg.body.insert("108".to_string(), "30".to_string());
+
+        let from_pairs = FixMessage::from_pairs(
+            &[("8", "FIX.4.4"), ("35", "A"), ("49", "SENDER"), ("56", "TARGET"), ("34", "1"), ("52", "20231016-12:30:00.123")],
+            &[("98", "0"), ("108", "30")],
+        );
+        assert_eq!((&from_pairs.header, &from_pairs.body, &from_pairs.trailer), (&msg.header, &msg.body, &msg.trailer));
+        // Body fields go out in map order, so the wire forms are compared field by field
+        let fields = |wire: String| {
+            let mut fields: Vec<String> = wire.split_terminator(SOH).map(str::to_string).collect();
+            fields.sort();
+            fields
+        };
+        assert_eq!(fields(from_pairs.encode_ref(&fixed_clock)), fields(msg.encode_ref(&fixed_clock)));
+
+        // Fields listed with the body are placed by tag
+        let routed = FixMessage::from_pairs(&[("35", "D")], &[("11", "ORD-1"), ("128", "VENUE")]);
+        assert_eq!(routed.header.get("128").unwrap(), "VENUE");
+        assert_eq!(routed.body.get("11").unwrap(), "ORD-1");
+    }
+
+    #[test]
+    fn test_encode_ref_matches_encode_without_mutating() {
+        let fixed_clock = create_fixed_clock();
+        let mut msg = FixMessage::new();
+        msg.header.insert("35".to_string(), "D".to_string());
+        msg.header.insert("49".to_string(), "SENDER".to_string());
+        msg.header.insert("56".to_string(), "TARGET".to_string());
+        msg.header.insert("34".to_string(), "2".to_string());
+        msg.body.insert("11".to_string(), "ORD-1".to_string());
+        let (header, body, trailer) = (msg.header.clone(), msg.body.clone(), msg.trailer.clone());
+
+        let by_ref = msg.encode_ref(&fixed_clock);
+        assert_eq!((&msg.header, &msg.body, &msg.trailer), (&header, &body, &trailer));
+        assert_eq!(by_ref, msg.encode(&fixed_clock));
+        assert!(msg.header.contains_key("9") && msg.trailer.contains_key("10"));
+        // Encoding again with the derived fields in place gives the same bytes
+        assert_eq!(msg.encode_ref(&fixed_clock), by_ref);
+    }
+
+    #[test]
+    fn test_large_message_uses_general_encode() {
+        let fixed_clock = create_fixed_clock();
+        let mut msg = FixMessage::new();
+        msg.header.insert("35".to_string(), "B".to_string());
+        for tag in 5000..5000 + SMALL_MESSAGE_FIELDS {
+            msg.body.insert(tag.to_string(), "x".to_string());
+        }
+
+        let encoded = msg.encode(&fixed_clock);
+        let decoded = FixMessage::decode(&encoded).unwrap();
+        for tag in 5000..5000 + SMALL_MESSAGE_FIELDS {
+            assert_eq!(decoded.body.get(&tag.to_string()).unwrap(), "x");
+        }
+    }
+
+    #[test]
+    fn test_decimal_accessors() {
+        let mut msg = FixMessage::new();
+        msg.set_decimal(numbers::PRICE, "0.00001".parse().unwrap());
+        msg.set_decimal(numbers::ORDER_QTY, "123456789.123456".parse().unwrap());
+        msg.set_field(numbers::STOP_PX, "1e-5");
+
+        assert_eq!(msg.body.get("44").unwrap(), "0.00001");
+        assert_eq!(msg.get_decimal(numbers::PRICE), Some(Ok("0.00001".parse().unwrap())));
+        assert_eq!(msg.get_decimal(numbers::ORDER_QTY).unwrap().unwrap().to_string(), "123456789.123456");
+        assert!(msg.get_decimal(numbers::STOP_PX).unwrap().is_err());
+        assert_eq!(msg.get_decimal(numbers::LAST_PX), None);
+    }
+
+    #[test]
+    fn test_raw_data_may_contain_soh() {
+        let mut message = FixMessage::new();
+        message.header.insert("35".to_string(), "A".to_string());
+        message.body.insert("95".to_string(), "7".to_string());
+        message.body.insert("96".to_string(), "ab\x01c=d\x01".to_string());
+        message.body.insert("108".to_string(), "30".to_string());
+
+        let clock: Arc<dyn Clock> = Arc::new(FixedClock);
+        let decoded = FixMessage::decode(&message.encode(&clock)).unwrap();
+        assert_eq!(decoded.body.get("96").unwrap(), "ab\x01c=d\x01");
+        assert_eq!(decoded.body.get("108").unwrap(), "30");
+    }
+
+    #[test]
+    fn test_raw_data_must_match_its_length() {
+        for raw_data in ["95=3\x0196=ab\x01", "95=1\x0196=ab\x01", "95=50\x0196=ab\x01"] {
+            let body = format!("35=A\x01{}", raw_data);
+            let fields = format!("8=FIX.4.4\x019={}\x01{}", body.len(), body);
+            let message = format!("{}10={}\x01", fields, calculate_checksum(&fields));
+            assert_eq!(FixMessage::decode(&message).err(), Some(DecodeError::DataLengthMismatch { tag: 96 }), "{:?}", raw_data);
+        }
+
+        let fields = "8=FIX.4.4\x019=16\x0135=A\x0195=x\x0196=ab\x01";
+        let message = format!("{}10={}\x01", fields, calculate_checksum(fields));
+        assert_eq!(FixMessage::decode(&message).err(), Some(DecodeError::InvalidDataLength { tag: 95, raw: "x".to_string() }));
+    }
+
+    #[test]
+    fn test_msg_type_enum_parses_tag_35() {
+        let input = "8=FIX.4.4\x019=67\x0135=A\x0149=SENDER\x0156=TARGET\x0134=1\x0152=20231016-12:30:00.123\x0198=0\x01108=30\x0110=118\x01";
+        assert_eq!(FixMessage::decode(input).unwrap().msg_type_enum(), Some(MsgType::Logon));
+
+        let mut msg = FixMessage::new();
+        assert_eq!(msg.msg_type_enum(), None);
+        msg.set_field(numbers::MSG_TYPE, "ZZ");
+        assert_eq!(msg.msg_type_enum(), None);
+    }
+
+    #[test]
+    fn test_fixt_appl_ver_id_is_a_header_field_and_default_appl_ver_id_a_logon_field() {
+        let fields = "8=FIXT.1.1\x019=36\x0135=A\x011128=9\x0134=1\x011137=9\x0198=0\x01108=30\x01";
+        let mut message = FixMessage::decode(&format!("{}10={}\x01", fields, calculate_checksum(fields))).unwrap();
+        assert_eq!(message.header.get("1128").unwrap(), "9");
+        assert_eq!(message.body.get("1137").unwrap(), "9");
+        assert_eq!((message.appl_ver_id(), message.default_appl_ver_id()), (Some("9"), Some("9")));
+
+        // Encoded after the standard header, not ahead of SenderCompID(49)
+        message.set_field(numbers::SENDER_COMP_ID, "SENDER");
+        let encoded = message.encode(&create_fixed_clock());
+        assert!(encoded.find("\x0149=").unwrap() < encoded.find("\x011137=").unwrap(), "{}", encoded);
+    }
+
+    #[test]
+    fn test_signature_fields_precede_the_checksum() {
+        let mut msg = FixMessage::new();
+        msg.set_field(numbers::MSG_TYPE, "0");
+        msg.set_field(numbers::SIGNATURE, "ab\x01d");
+        msg.set_field(numbers::SIGNATURE_LENGTH, "4");
+        let encoded = msg.encode(&create_fixed_clock());
+
+        let (fields, checksum) = encoded.rsplit_once("10=").unwrap();
+        assert!(fields.ends_with("\x0193=4\x0189=ab\x01d\x01"), "{:?}", encoded);
+        assert_eq!(checksum, format!("{}\x01", calculate_checksum(fields)));
+
+        let decoded = FixMessage::decode(&encoded).unwrap();
+        assert_eq!(decoded.trailer.get("89").unwrap(), "ab\x01d");
+        assert_eq!(decoded.trailer.get("93").unwrap(), "4");
+        assert_eq!(decoded.get_field(numbers::BODY_LENGTH), msg.get_field(numbers::BODY_LENGTH));
+    }
+
+    #[test]
+    fn test_business_reject_refers_to_the_rejected_message() {
+        let fields = "8=FIX.4.4\x019=38\x0135=R\x0149=PEER\x0156=ENGINE\x0134=12\x01131=QR-1\x01";
+        let quote_request = FixMessage::decode(&format!("{}10={}\x01", fields, calculate_checksum(fields))).unwrap();
+
+        let reject = FixMessage::business_reject(&quote_request, BusinessRejectReason::UnsupportedMessageType, "QuoteRequest not supported");
+        assert_eq!(reject.get_field(numbers::MSG_TYPE), Some("j"));
+        assert_eq!(reject.get_field(numbers::REF_MSG_TYPE), Some("R"));
+        assert_eq!(reject.get_field(numbers::BUSINESS_REJECT_REASON), Some("3"));
+        assert_eq!(reject.get_field(numbers::REF_SEQ_NUM), Some("12"));
+        assert_eq!(reject.get_field(numbers::TEXT), Some("QuoteRequest not supported"));
+
+        let reject = FixMessage::business_reject(&FixMessage::new(), BusinessRejectReason::Other, "");
+        assert_eq!((reject.get_field(numbers::REF_SEQ_NUM), reject.get_field(numbers::TEXT)), (None, None));
+    }
+
+    #[test]
+    fn test_logout_and_reject_carry_their_text() {
+        let fixed_clock = create_fixed_clock();
+        let mut logout = FixMessage::logout(Some("End of day"));
+        assert_eq!(logout.text(), Some("End of day"));
+        let encoded = logout.encode(&fixed_clock);
+        assert!(encoded.contains("\x0158=End of day\x01"), "{:?}", encoded);
+        let decoded = FixMessage::decode(&encoded).unwrap();
+        assert_eq!(decoded.msg_type_enum(), Some(MsgType::Logout));
+        assert_eq!(decoded.text(), Some("End of day"));
+        assert_eq!(FixMessage::logout(None).text(), None);
+        assert_eq!(FixMessage::logout(Some("")).text(), None);
+
+        let order = FixMessage::from_pairs(&[("35", "D"), ("34", "7")], &[("11", "ORD-1")]);
+        let reject = FixMessage::reject(&order, SessionRejectReason::ValueIsIncorrect, Some(numbers::CL_ORD_ID), Some("Bad value"));
+        assert_eq!(reject.msg_type_enum(), Some(MsgType::Reject));
+        assert_eq!((reject.get_field(numbers::REF_SEQ_NUM), reject.get_field(numbers::REF_MSG_TYPE)), (Some("7"), Some("D")));
+        assert_eq!(reject.get_field(numbers::REF_TAG_ID), Some("11"));
+        assert_eq!(FixMessage::reject(&order, SessionRejectReason::ValueIsIncorrect, None, None).get_field(numbers::REF_TAG_ID), None);
+        assert_eq!(reject.get_field(numbers::SESSION_REJECT_REASON), Some("5"));
+        assert_eq!(reject.text(), Some("Bad value"));
+    }
+
+    #[test]
+    fn test_dont_know_trade_answers_an_execution_report() {
+        let mut exec_report = FixMessage::new();
+        for (tag, value) in [(35, "8"), (49, "BROKER"), (56, "FUND"), (57, "DESK"), (37, "ORD-7"), (17, "EXEC-9"), (55, "VOD.L"),
+                             (54, "1"), (38, "500"), (32, "200"), (31, "101.5"), (150, "F")] {
+            exec_report.set_field(tag, value);
+        }
+
+        let dk = FixMessage::dont_know_trade(&exec_report, DkReason::NoMatchingOrder).unwrap();
+        assert_eq!(dk.header.get("35").unwrap(), "Q");
+        assert_eq!(dk.header.get("49").unwrap(), "FUND");
+        assert_eq!(dk.header.get("56").unwrap(), "BROKER");
+        assert_eq!(dk.header.get("50").unwrap(), "DESK");
+        assert!(!dk.header.contains_key("57"));
+        for (tag, value) in [(37, "ORD-7"), (17, "EXEC-9"), (55, "VOD.L"), (54, "1"), (38, "500"), (32, "200"), (31, "101.5"), (127, "D")] {
+            assert_eq!(dk.body.get(&tag.to_string()).map(String::as_str), Some(value), "tag {}", tag);
+        }
+        assert!(!dk.body.contains_key("150"));
+
+        exec_report.body.remove("17");
+        let missing = FixMessage::dont_know_trade(&exec_report, DkReason::Other).unwrap_err();
+        assert_eq!(missing, MissingField { tag: 17 });
+        assert_eq!(missing.to_string(), "Required field ExecID(17) missing");
+    }
+
+    #[test]
+    fn test_fill_answers_a_new_order_single() {
+        let mut order = FixMessage::new();
+        for (tag, value) in [(35, "D"), (11, "CL-1"), (55, "VOD.L"), (54, "2"), (38, "300"), (40, "2"), (44, "99.5")] {
+            order.set_field(tag, value);
+        }
+
+        let fill = FixMessage::fill(&order, "ORD-1", "EXEC-1").unwrap();
+        assert_eq!(fill.header.get("35").unwrap(), "8");
+        for (tag, value) in [(11, "CL-1"), (55, "VOD.L"), (54, "2"), (38, "300"), (37, "ORD-1"), (17, "EXEC-1"), (150, "F"), (39, "2"),
+                             (32, "300"), (31, "99.5"), (14, "300"), (151, "0"), (6, "99.5")] {
+            assert_eq!(fill.get_field(tag), Some(value), "tag {}", tag);
+        }
+
+        order.body.remove("44");
+        assert_eq!(FixMessage::fill(&order, "ORD-2", "EXEC-2").unwrap().get_field(numbers::AVG_PX), Some("0"));
+        order.body.remove("55");
+        assert_eq!(FixMessage::fill(&order, "ORD-3", "EXEC-3").unwrap_err(), MissingField { tag: 55 });
+    }
+
+    #[test]
+    fn test_msg_seq_num_must_be_a_positive_integer() {
+        let decode = |seq_num: &str| {
+            let body = format!("35=0\x0134={}\x01", seq_num);
+            let fields = format!("8=FIX.4.4\x019={}\x01{}", body.len(), body);
+            FixMessage::decode(&format!("{}10={}\x01", fields, calculate_checksum(&fields)))
+        };
+        for seq_num in ["abc", "-1", "0", "+7", ""] {
+            assert_eq!(decode(seq_num).err(), Some(DecodeError::InvalidSeqNum { raw: seq_num.to_string() }), "{:?}", seq_num);
+        }
+        assert_eq!(decode("7").unwrap().header.get("34").unwrap(), "7");
+        assert_eq!(decode("abc").unwrap_err().ref_tag_id(), Some(34));
+    }
+
+    #[test]
+    fn test_body_length_must_count_the_fields_after_it() {
+        let decode = |body_length: &str| {
+            let fields = format!("8=FIX.4.4\x019={}\x0135=0\x0134=2\x01", body_length);
+            FixMessage::decode(&format!("{}10={}\x01", fields, calculate_checksum(&fields)))
+        };
+        for body_length in ["abc", "0", "-10", "+10", ""] {
+            assert_eq!(decode(body_length).err(), Some(DecodeError::InvalidBodyLength { raw: body_length.to_string() }), "{:?}", body_length);
+        }
+        assert_eq!(decode("9").err(), Some(DecodeError::BodyLengthMismatch { declared: 9, actual: 10 }));
+        assert_eq!(decode("11").err(), Some(DecodeError::BodyLengthMismatch { declared: 11, actual: 10 }));
+        assert_eq!(decode("+10").unwrap_err().ref_tag_id(), Some(9));
+        assert_eq!(decode("10").unwrap().header.get("9").unwrap(), "10");
+    }
+
+    #[test]
+    fn test_only_some_fields_may_have_an_empty_value() {
+        let decode = |field: &str| {
+            let body = format!("35=D\x0134=2\x01{}\x01", field);
+            let fields = format!("8=FIX.4.4\x019={}\x01{}", body.len(), body);
+            FixMessage::decode(&format!("{}10={}\x01", fields, calculate_checksum(&fields)))
+        };
+        assert_eq!(decode("58=").unwrap().body.get("58").unwrap(), "");
+        assert_eq!(decode("55=").err(), Some(DecodeError::EmptyValue { tag: 55 }));
+        assert_eq!(DecodeError::EmptyValue { tag: 55 }.ref_tag_id(), Some(55));
+        // A data field announced as empty by its length field
+        assert_eq!(decode("95=0\x0196=").unwrap().body.get("96").unwrap(), "");
+    }
+
+    #[test]
+    fn test_decode_can_retain_raw_bytes() {
+        let input = "8=FIX.4.4\x019=67\x0135=A\x0149=SENDER\x0156=TARGET\x0134=1\x0152=20231016-12:30:00.123\x0198=0\x01108=30\x0110=118\x01";
+
+        let decoded = FixMessage::decode_with_options(input, &DecodeOptions { retain_raw: true, ..DecodeOptions::default() }).unwrap();
+        assert_eq!(decoded.raw(), Some(input.as_bytes()));
+
+        // Not retained unless asked for
+        assert_eq!(FixMessage::decode(input).unwrap().raw(), None);
+    }
+
+    #[test]
+    fn test_clone_is_independent_of_the_original() {
+        let input = "8=FIX.4.4\x019=67\x0135=A\x0149=SENDER\x0156=TARGET\x0134=1\x0152=20231016-12:30:00.123\x0198=0\x01108=30\x0110=118\x01";
+        let original = FixMessage::decode_with_options(input, &DecodeOptions { retain_raw: true, ..DecodeOptions::default() }).unwrap();
+
+        let mut copy = original.clone();
+        assert_eq!(copy.raw(), Some(input.as_bytes()));
+        copy.body.insert("108".to_string(), "60".to_string());
+        copy.body.insert("58".to_string(), "changed".to_string());
+        assert_eq!(original.body.get("108").unwrap(), "30");
+        assert!(!original.body.contains_key("58"));
+
+        // Only the tracked original answers its receipt
+        let mut tracked = FixMessage::new();
+        let receipt = tracked.track();
+        drop(tracked.clone());
+        assert!(receipt.try_recv().is_err());
+        drop(tracked);
+        assert!(matches!(receipt.try_recv(), Ok(Err(crate::receipt::SendFailure::Dropped))));
+    }
+
+    #[test]
+    fn test_header_layout_moves_fields_between_header_and_body() {
+        // A custom routing tag in the header, and SenderSubID(50) carried in the body
+        let layout = HeaderLayout { extra: vec!["5001".to_string()], in_body: vec!["50".to_string()] };
+        let mut msg = FixMessage::new();
+        msg.set_field(numbers::MSG_TYPE, "D");
+        msg.set_field(5001, "ROUTE-1");
+        msg.set_field(numbers::SENDER_SUB_ID, "DESK");
+        msg.set_field(numbers::CL_ORD_ID, "ORDER1");
+        assert!(msg.body.contains_key("5001") && msg.header.contains_key("50"), "Built by the standard layout");
+        let encode_options = EncodeOptions { header_layout: &layout, ..EncodeOptions::default() };
+        let encoded = msg.encode_with_options(&create_fixed_clock(), &encode_options);
+        assert!(encoded.contains("\x0152=20231016-12:30:00.123\x015001=ROUTE-1\x0150=DESK\x0111=ORDER1\x01"), "{:?}", encoded);
+
+        let standard = FixMessage::decode(&encoded).unwrap();
+        assert!(standard.body.contains_key("5001") && standard.header.contains_key("50"));
+        let options = DecodeOptions { header_layout: Some(&layout), ..DecodeOptions::default() };
+        let mut decoded = FixMessage::decode_with_options(&encoded, &options).unwrap();
+        assert_eq!(decoded.header.get("5001").unwrap(), "ROUTE-1");
+        assert_eq!(decoded.body.get("50").unwrap(), "DESK");
+        assert!(!decoded.body.contains_key("5001") && !decoded.header.contains_key("50"));
+        assert_eq!(decoded.encode_with_options(&create_fixed_clock(), &encode_options), encoded);
+
+        // MsgType(35) cannot be moved out of the header
+        let layout = HeaderLayout { in_body: vec!["35".to_string()], ..HeaderLayout::default() };
+        assert!(FixMessage::decode_with_options(&encoded, &DecodeOptions { header_layout: Some(&layout), ..DecodeOptions::default() }).unwrap().header.contains_key("35"));
+    }
+
+    #[test]
+    fn test_latency_runs_from_sending_time_to_receipt() {
+        let mut msg = FixMessage::new();
+        msg.header.insert("52".to_string(), "20231016-12:30:00.123".to_string());
+        assert_eq!(msg.latency(), None);
+
+        let received_at = NaiveDateTime::parse_from_str("20231016-12:30:01.000", TIMESTAMP_FORMAT).unwrap().and_utc();
+        msg.set_received_at(received_at);
+        assert_eq!(msg.latency(), Some(TimeDelta::milliseconds(877)));
+        // Any precision the peer sends is read, and a peer clock running ahead gives a negative latency
+        msg.header.insert("52".to_string(), "20231016-12:30:01.000250".to_string());
+        assert_eq!(msg.latency(), Some(TimeDelta::microseconds(-250)));
+        msg.header.insert("52".to_string(), "20231016-12:30:00".to_string());
+        assert_eq!(msg.latency(), Some(TimeDelta::seconds(1)));
+        msg.header.insert("52".to_string(), "not a time".to_string());
+        assert_eq!(msg.latency(), None);
+    }
+
+    #[test]
+    fn test_decode_can_collect_unknown_tags() {
+        let mut msg = FixMessage::new();
+        msg.header.insert("35".to_string(), "D".to_string());
+        msg.body.insert("11".to_string(), "ORDER1".to_string());
+        msg.body.insert("9999".to_string(), "x".to_string());
+        let encoded = msg.encode(&create_fixed_clock());
+
+        // By default unknown tags are ordinary body fields
+        assert_eq!(FixMessage::decode(&encoded).unwrap().body.get("9999").unwrap(), "x");
+
+        let options = DecodeOptions { collect_unknown: true, ..DecodeOptions::default() };
+        let mut decoded = FixMessage::decode_with_options(&encoded, &options).unwrap();
+        assert_eq!(decoded.unknown_tags().get("9999").unwrap(), "x");
+        assert!(!decoded.body.contains_key("9999"));
+        assert_eq!(decoded.body.get("11").unwrap(), "ORDER1");
+        assert_eq!(decoded.get_field(9999), Some("x"));
+        let reencoded = FixMessage::decode(&decoded.encode(&create_fixed_clock())).unwrap();
+        assert_eq!(reencoded.body, msg.body);
+    }
+
+    #[test]
+    fn test_debug_output_masks_passwords() {
+        let mut logon = FixMessage::new();
+        logon.set_field(numbers::USERNAME, "trader");
+        logon.set_field(numbers::PASSWORD, "secret");
+        logon.set_field(numbers::NEW_PASSWORD, "secret2");
+        let output = format!("{:?}", logon);
+        assert!(output.contains("\"trader\""));
+        assert!(!output.contains("secret"));
+        assert_eq!(logon.get_field(numbers::PASSWORD), Some("secret"));
+    }
+
+    #[test]
+    fn test_display_shows_the_wire_form_with_visible_soh() {
+        let input = "8=FIX.4.4\x019=67\x0135=A\x0149=SENDER\x0156=TARGET\x0134=1\x0152=20231016-12:30:00.123\x0198=0\x01108=30\x0110=118\x01";
+        let mut logon = FixMessage::decode(input).unwrap();
+        let output = logon.to_string();
+        assert!(output.starts_with("8=FIX.4.4|9="), "{}", output);
+        assert!(output.contains("|35=A|"));
+        assert!(output.contains("|52=20231016-12:30:00.123|"));
+        assert!(output.contains("|10=") && output.ends_with('|'));
+        assert!(!output.contains(SOH));
+
+        logon.set_field(numbers::PASSWORD, "secret");
+        assert!(logon.to_string().contains("|554=****|"));
+        assert!(!logon.to_string().contains("secret"));
+    }
+
+    #[test]
+    fn test_lenient_decode_accepts_relaxed_trailers() {
+        let message = "8=FIX.4.4\x019=67\x0135=A\x0149=SENDER\x0156=TARGET\x0134=1\x0152=20231016-12:30:00.123\x0198=0\x01108=30\x01";
+        let options = DecodeOptions { lenient: true, ..DecodeOptions::default() };
+
+        for trailer in ["10=118\r\n", "10=118", "10=118\x01"] {
+            let decoded = FixMessage::decode_with_options(&format!("{}{}", message, trailer), &options).unwrap();
+            assert_eq!(decoded.header.get("35").unwrap(), "A");
+            assert_eq!(decoded.body.get("108").unwrap(), "30");
+            assert_eq!(decoded.trailer.get("10").unwrap(), "118");
+        }
+
+        // Strict decoding is still the default
+        assert_eq!(FixMessage::decode(&format!("{}10=118\r\n", message)).err().unwrap(), DecodeError::MissingTrailingSoh);
+        assert_eq!(FixMessage::decode(&format!("{}10=118", message)).err().unwrap(), DecodeError::MissingTrailingSoh);
+    }
+
+    #[test]
+    fn test_typed_accessors_accept_tag_number_constants() {
+        let mut msg = FixMessage::new();
+        msg.set_field(numbers::MSG_TYPE, "A");
+        msg.set_field(numbers::HEART_BT_INT, "30");
+
+        assert_eq!(msg.header.get("35").unwrap(), "A");
+        assert_eq!(msg.body.get("108").unwrap(), "30");
+        assert_eq!(msg.get_field(numbers::MSG_TYPE), Some("A"));
+        assert_eq!(msg.get_field(numbers::HEART_BT_INT), Some("30"));
+        assert_eq!(msg.get_field(numbers::CL_ORD_ID), None);
+    }
+
     #[test]
     fn test_checksum_is_calculated_correctly() {
-        let message_without_checksum = "8=FIX.4.4\x019=59\x0135=A\x0149=SENDER\x0156=TARGET\x0134=1\x0152=20231016-12:30:00.123\x0198=0\x01108=30\x01";
+        let message_without_checksum = "8=FIX.4.4\x019=67\x0135=A\x0149=SENDER\x0156=TARGET\x0134=1\x0152=20231016-12:30:00.123\x0198=0\x01108=30\x01";
 
         let calculated_checksum = calculate_checksum(message_without_checksum);
-        let expected_checksum = "119";  // This is the checksum for the above message
+        let expected_checksum = "118";  // This is the checksum for the above message
 
         assert_eq!(calculated_checksum, expected_checksum);
     }
 
     #[test]
     fn test_invalid_checksum_throws_err() {
-        let invalid_message = "8=FIX.4.4\x019=59\x0135=A\x0149=SENDER\x0156=TARGET\x0134=1\x0152=20231016-12:30:00.123\x0198=0\x01108=30\x0110=999\x01"; // Invalid checksum
+        let invalid_message = "8=FIX.4.4\x019=67\x0135=A\x0149=SENDER\x0156=TARGET\x0134=1\x0152=20231016-12:30:00.123\x0198=0\x01108=30\x0110=120\x01"; // Invalid checksum
 
         let result = FixMessage::decode(invalid_message);
         assert!(result.is_err());
-        assert_eq!(result.err().unwrap(), "Invalid checksum");
+        assert!(matches!(result.err().unwrap(), DecodeError::ChecksumMismatch { received: 120, .. }));
+    }
+
+    #[test]
+    fn test_checksum_of_a_message_with_many_fields() {
+        let mut msg = FixMessage::new();
+        msg.header.insert("35".to_string(), "8".to_string());
+        msg.header.insert("34".to_string(), "2".to_string());
+        for tag in 5000..5300 {
+            msg.body.insert(tag.to_string(), format!("VALUE-{}", tag));
+        }
+        msg.body.insert("95".to_string(), "5".to_string());
+        msg.body.insert("96".to_string(), "a\x01b=c".to_string());
+        let encoded = msg.encode(&create_fixed_clock());
+
+        let checksum_pos = encoded.rfind("\x0110=").unwrap() + 1;
+        let decoded = FixMessage::decode(&encoded).unwrap();
+        assert_eq!(decoded.trailer.get("10").unwrap(), &calculate_checksum(&encoded[..checksum_pos]));
+        assert_eq!(decoded.body.len(), 302);
+
+        // Any byte changed ahead of the checksum is noticed
+        let tampered = encoded.replacen("VALUE-5150", "VALUE-5151", 1);
+        assert!(matches!(FixMessage::decode(&tampered).err(), Some(DecodeError::ChecksumMismatch { .. })));
+    }
+
+    #[test]
+    fn test_overridden_checksum_is_sent_verbatim_and_rejected() {
+        let fixed_clock = create_fixed_clock();
+        let mut msg = FixMessage::logout(Some("End of day"));
+        let valid = msg.clone().encode_with_checksum_override(&fixed_clock, None);
+        assert!(FixMessage::decode(&valid).is_ok());
+
+        let checksum = calculate_checksum(&valid[..valid.rfind("\x0110=").unwrap() + 1]);
+        let wrong = format!("{:03}", (checksum.parse::<u32>().unwrap() + 1) % 256);
+        let encoded = msg.encode_with_checksum_override(&fixed_clock, Some(wrong.clone()));
+        assert!(encoded.ends_with(&format!("\x0110={}\x01", wrong)), "{:?}", encoded);
+        assert_eq!(msg.trailer.get("10"), Some(&wrong));
+        let calculated = checksum.parse().unwrap();
+        assert_eq!(FixMessage::decode(&encoded).err(), Some(DecodeError::ChecksumMismatch { received: wrong.parse().unwrap(), calculated }));
+    }
+
+    #[test]
+    fn test_checksum_must_be_three_digits() {
+        // The fields below sum to a checksum of 9
+        let fields = "8=FIX.4.4\x019=87\x0135=0\x0149=SENDER\x0156=TARGET\x0134=2\x0152=20231016-12:30:00.123\x01112=7BBBBBBBBBBBBBBBBBBBBBBBAAA\x01";
+        assert_eq!(calculate_checksum(fields), "009");
+
+        let message = FixMessage::decode(&format!("{}10=009\x01", fields)).unwrap();
+        assert_eq!(message.trailer.get("10").unwrap(), "009");
+
+        for checksum in ["9", "09", "0009", "1009", "+09", "256"] {
+            let result = FixMessage::decode(&format!("{}10={}\x01", fields, checksum));
+            assert_eq!(result.err(), Some(DecodeError::MalformedChecksum { raw: checksum.to_string() }), "10={} should be rejected", checksum);
+        }
     }
 
     #[test]
@@ -310,6 +1575,6 @@ mod tests {
 
         let result = FixMessage::decode(invalid_message);
         assert!(result.is_err());
-        assert_eq!(result.err().unwrap(), "Message does not end with SOH");
+        assert_eq!(result.err().unwrap(), DecodeError::MissingTrailingSoh);
     }
 }
\ No newline at end of file
@@ -0,0 +1,119 @@
+use super::FixMessage;
+use crate::tag::{numbers, BeginString, FixField, MsgType};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+// Tags carried by the FIXML structure itself rather than as attributes
+const STRUCTURAL_TAGS: [&str; 4] = ["8", "9", "35", "10"];
+
+impl FixMessage {
+    // Renders the message as FIXML, e.g. <FIXML v="4.4"><Order ClOrdID="1" ...><Hdr SenderCompID="A" .../></Order></FIXML>.
+    // Fields are attributes named from the tag number table; tags missing from it are left out.
+    pub fn to_fixml(&self) -> String {
+        let version = self.header.get("8")
+            .and_then(|value| BeginString::from_wire(value).ok())
+            .unwrap_or(BeginString::Fix4_4);
+        let element = self.header.get("35")
+            .and_then(|value| value.parse().ok())
+            .map_or("Message", element_name);
+
+        let mut xml = String::new();
+        let _ = write!(xml, "<FIXML v=\"{}\"><{}", version.value().trim_start_matches("FIXT.").trim_start_matches("FIX."), element);
+        write_attributes(&mut xml, &self.body);
+        xml.push_str("><Hdr");
+        write_attributes(&mut xml, &self.header);
+        let _ = write!(xml, "/></{}></FIXML>", element);
+        xml
+    }
+}
+
+// FIXML element names for the common admin and order messages
+fn element_name(msg_type: MsgType) -> &'static str {
+    match msg_type {
+        MsgType::Heartbeat => "Heartbeat",
+        MsgType::TestRequest => "TestReq",
+        MsgType::ResendRequest => "ResendReq",
+        MsgType::Reject => "Reject",
+        MsgType::SequenceReset => "SeqReset",
+        MsgType::Logout => "Logout",
+        MsgType::Logon => "Logon",
+        MsgType::OrderSingle => "Order",
+        MsgType::ExecutionReport => "ExecRpt",
+        MsgType::OrderCancelRequest => "OrdCxlReq",
+        MsgType::OrderCancelReplaceRequest => "OrdCxlRplcReq",
+        MsgType::OrderCancelReject => "OrdCxlRej",
+        MsgType::OrderStatusRequest => "OrdStatReq",
+        MsgType::MarketDataRequest => "MktDataReq",
+        MsgType::MarketDataSnapshotFullRefresh => "MktDataFull",
+        MsgType::MarketDataIncrementalRefresh => "MktDataInc",
+        _ => "Message",
+    }
+}
+
+fn write_attributes(xml: &mut String, fields: &HashMap<String, String>) {
+    let mut attributes: Vec<(u32, &'static str, &str)> = fields.iter()
+        .filter(|(tag, _)| !STRUCTURAL_TAGS.contains(&tag.as_str()))
+        .filter_map(|(tag, value)| {
+            let number = tag.parse().ok()?;
+            Some((number, numbers::field_name(number)?, value.as_str()))
+        })
+        .collect();
+    attributes.sort_by_key(|(tag, _, _)| *tag);
+
+    for (_, name, value) in attributes {
+        let _ = write!(xml, " {}=\"{}\"", name, escape(value));
+    }
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decimal::FixDecimal;
+    use crate::message::OrderSingleParams;
+    use crate::tag::{OrdType, Side};
+
+    #[test]
+    fn test_new_order_single_fixml() {
+        let mut msg = FixMessage::new_order_single(OrderSingleParams {
+            cl_ord_id: "ORD<1>".to_string(),
+            symbol: "AAPL".to_string(),
+            side: Side::Buy,
+            order_qty: "100".parse::<FixDecimal>().unwrap(),
+            ord_type: OrdType::Limit,
+            price: Some("150.25".parse().unwrap()),
+        });
+        msg.header.insert("8".to_string(), "FIX.4.4".to_string());
+        msg.header.insert("49".to_string(), "SENDER".to_string());
+        msg.header.insert("56".to_string(), "TARGET".to_string());
+        msg.header.insert("34".to_string(), "2".to_string());
+        msg.body.insert("58".to_string(), "Fill \"or\" kill".to_string());
+
+        let xml = msg.to_fixml();
+        assert!(xml.starts_with("<FIXML v=\"4.4\"><Order "));
+        assert!(xml.ends_with("</Order></FIXML>"));
+        assert!(xml.contains(" ClOrdID=\"ORD&lt;1&gt;\""));
+        assert!(xml.contains(" Symbol=\"AAPL\""));
+        assert!(xml.contains(" Side=\"1\""));
+        assert!(xml.contains(" OrderQty=\"100\""));
+        assert!(xml.contains(" OrdType=\"2\""));
+        assert!(xml.contains(" Price=\"150.25\""));
+        assert!(xml.contains("<Hdr MsgSeqNum=\"2\" SenderCompID=\"SENDER\" TargetCompID=\"TARGET\"/>"));
+        assert!(xml.contains(" Text=\"Fill &quot;or&quot; kill\""));
+        assert!(!xml.contains("MsgType"));
+    }
+}
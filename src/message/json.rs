@@ -0,0 +1,135 @@
+use super::FixMessage;
+use crate::tag::numbers;
+use serde::ser::{SerializeMap, SerializeStruct};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+
+// JSON shape: {"header": {"8": "FIX.4.4", "35": "A", ...}, "body": {...}, "trailer": {"10": "..."}}.
+// Unknown tags are written as part of the body.
+impl Serialize for FixMessage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_sections(self, false, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FixMessage {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let sections = Sections::deserialize(deserializer)?;
+        let mut message = FixMessage::new();
+        message.header = sections.header;
+        message.body = sections.body;
+        message.trailer = sections.trailer;
+        Ok(message)
+    }
+}
+
+#[derive(Deserialize)]
+struct Sections {
+    #[serde(default)]
+    header: HashMap<String, String>,
+    #[serde(default)]
+    body: HashMap<String, String>,
+    #[serde(default)]
+    trailer: HashMap<String, String>,
+}
+
+// Serializes like FixMessage but keyed by field name, e.g. "MsgType" instead of "35".
+// Output only: tags missing from the tag number table keep their number.
+pub struct NamedFields<'a>(&'a FixMessage);
+
+impl FixMessage {
+    pub fn with_field_names(&self) -> NamedFields<'_> {
+        NamedFields(self)
+    }
+}
+
+impl Serialize for NamedFields<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_sections(self.0, true, serializer)
+    }
+}
+
+fn serialize_sections<S: Serializer>(message: &FixMessage, named: bool, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut state = serializer.serialize_struct("FixMessage", 3)?;
+    state.serialize_field("header", &Section { fields: vec![&message.header], named })?;
+    state.serialize_field("body", &Section { fields: vec![&message.body, &message.unknown], named })?;
+    state.serialize_field("trailer", &Section { fields: vec![&message.trailer], named })?;
+    state.end()
+}
+
+struct Section<'a> {
+    fields: Vec<&'a HashMap<String, String>>,
+    named: bool,
+}
+
+impl Serialize for Section<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // Numeric tag order keeps the output stable between runs
+        let mut fields: Vec<(&String, &String)> = self.fields.iter().flat_map(|fields| fields.iter()).collect();
+        fields.sort_by_key(|(tag, _)| tag.parse::<u32>().unwrap_or(u32::MAX));
+
+        let mut map = serializer.serialize_map(Some(fields.len()))?;
+        for (tag, value) in fields {
+            match tag.parse().ok().and_then(numbers::field_name) {
+                Some(name) if self.named => map.serialize_entry(name, value)?,
+                _ => map.serialize_entry(tag, value)?,
+            }
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Clock;
+    use std::sync::Arc;
+
+    struct FixedClock;
+
+    impl Clock for FixedClock {
+        fn now(&self) -> String {
+            "20231016-12:30:00.123".to_string()
+        }
+    }
+
+    fn logon() -> FixMessage {
+        let clock: Arc<dyn Clock> = Arc::new(FixedClock);
+        let mut msg = FixMessage::new();
+        msg.header.insert("35".to_string(), "A".to_string());
+        msg.header.insert("49".to_string(), "SENDER".to_string());
+        msg.header.insert("56".to_string(), "TARGET".to_string());
+        msg.header.insert("34".to_string(), "1".to_string());
+        msg.body.insert("98".to_string(), "0".to_string());
+        msg.body.insert("108".to_string(), "30".to_string());
+        FixMessage::decode(&msg.encode(&clock)).unwrap()
+    }
+
+    #[test]
+    fn test_logon_json_round_trip() {
+        let original = logon();
+        let json = serde_json::to_string(&original).unwrap();
+        assert!(json.starts_with(r#"{"header":{"8":"FIX.4.4","9":"#));
+        assert!(json.contains(r#""body":{"98":"0","108":"30"}"#));
+
+        let clock: Arc<dyn Clock> = Arc::new(FixedClock);
+        let mut restored: FixMessage = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.header, original.header);
+        assert_eq!(restored.body, original.body);
+        assert_eq!(restored.trailer, original.trailer);
+
+        let wire = restored.encode(&clock);
+        let decoded = FixMessage::decode(&wire).unwrap();
+        assert_eq!(decoded.trailer, original.trailer);
+    }
+
+    #[test]
+    fn test_json_keyed_by_field_name() {
+        let msg = logon();
+        let json = serde_json::to_value(msg.with_field_names()).unwrap();
+        assert_eq!(json["header"]["MsgType"], "A");
+        assert_eq!(json["header"]["SenderCompID"], "SENDER");
+        assert_eq!(json["body"]["HeartBtInt"], "30");
+        assert_eq!(json["trailer"]["CheckSum"], msg.trailer["10"].as_str());
+    }
+}
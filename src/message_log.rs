@@ -0,0 +1,193 @@
+use crate::clock::{Clock, RealClock};
+use crate::store::file_name;
+use crate::tag::{numbers, SOH};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::warn;
+
+// A byte-level record of a session's traffic, e.g. for settling disputes with a counterparty. Traffic is
+// logged exactly as it crosses the wire: incoming bytes as each read takes them off the transport, whether or
+// not they make up whole messages, and outgoing messages once the transport has taken them. A write that
+// fails is logged as an event instead. Called from both engine threads.
+pub trait MessageLog: Send {
+    fn log_incoming(&mut self, raw: &[u8]);
+    fn log_outgoing(&mut self, raw: &[u8]);
+    fn log_event(&mut self, text: &str);
+    // The engine hands over its clock when the log is installed, so timestamps follow the session's time
+    fn set_clock(&mut self, _clock: Arc<dyn Clock>) {}
+    // Writes out anything still buffered; the engine calls it when shutting down
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Fields whose values are masked unless set_masked_tags says otherwise
+const DEFAULT_MASKED_TAGS: [u32; 2] = [numbers::PASSWORD, numbers::NEW_PASSWORD];
+
+// Appends one line per message or event to SENDER-TARGET.log under the directory, e.g.
+//   20231016-12:30:00.123456 IN  8=FIX.4.4|9=67|35=A|...|10=118|
+//   20231016-12:30:00.123789 OUT 8=FIX.4.4|9=61|35=A|...|10=042|
+//   20231016-12:30:01.000000 EVENT Session state LoggedOn -> Disconnecting
+// SOH is shown as '|' and credentials are masked. Incoming bytes are put on a line of their own for each
+// message they complete, timestamped by the read that completed it, with anything between messages on a line
+// of its own. Lines are buffered until flush.
+pub struct FileMessageLog {
+    file: BufWriter<File>,
+    masked_tags: Vec<String>,
+    clock: Arc<dyn Clock>,
+    // Incoming bytes read since the end of the last message
+    incoming: Vec<u8>,
+}
+
+impl FileMessageLog {
+    pub fn open(directory: impl AsRef<Path>, sender_comp_id: &str, target_comp_id: &str) -> io::Result<Self> {
+        let directory = directory.as_ref();
+        fs::create_dir_all(directory)?;
+        let name = format!("{}-{}.log", file_name(sender_comp_id), file_name(target_comp_id));
+        let file = OpenOptions::new().append(true).create(true).open(directory.join(name))?;
+        Ok(FileMessageLog {
+            file: BufWriter::new(file),
+            masked_tags: DEFAULT_MASKED_TAGS.iter().map(u32::to_string).collect(),
+            clock: Arc::new(RealClock),
+            incoming: Vec::new(),
+        })
+    }
+
+    // Replaces the fields whose values are written as ****, e.g. to add a venue's own credential tags
+    pub fn set_masked_tags(&mut self, tags: &[u32]) {
+        self.masked_tags = tags.iter().map(u32::to_string).collect();
+    }
+
+    fn write_line(&mut self, kind: &str, text: &str) {
+        let timestamp = self.clock.now_utc().format("%Y%m%d-%H:%M:%S%.6f");
+        if let Err(e) = writeln!(self.file, "{} {} {}", timestamp, kind, text) {
+            warn!("Failed writing to the message log: {:?}", e);
+        }
+    }
+
+    fn render(&self, raw: &[u8]) -> String {
+        let mut line = String::with_capacity(raw.len());
+        for field in String::from_utf8_lossy(raw).split_terminator(SOH) {
+            match field.split_once('=') {
+                Some((tag, _)) if self.masked_tags.iter().any(|masked| masked == tag) => {
+                    line.push_str(tag);
+                    line.push_str("=****");
+                }
+                _ => line.push_str(field),
+            }
+            line.push('|');
+        }
+        line
+    }
+}
+
+// Where the first message in the bytes ends: just past the SOH after its CheckSum(10)
+fn message_end(bytes: &[u8]) -> Option<usize> {
+    let checksum = bytes.windows(4).position(|window| window == b"\x0110=")? + 4;
+    let end = bytes[checksum..].iter().position(|&byte| byte == SOH as u8)?;
+    Some(checksum + end + 1)
+}
+
+impl MessageLog for FileMessageLog {
+    fn log_incoming(&mut self, raw: &[u8]) {
+        self.incoming.extend_from_slice(raw);
+        while let Some(end) = message_end(&self.incoming) {
+            let message: Vec<u8> = self.incoming.drain(..end).collect();
+            let line = self.render(&message);
+            self.write_line("IN ", &line);
+        }
+    }
+
+    fn log_outgoing(&mut self, raw: &[u8]) {
+        let line = self.render(raw);
+        self.write_line("OUT", &line);
+    }
+
+    fn log_event(&mut self, text: &str) {
+        self.write_line("EVENT", text);
+    }
+
+    fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    // A message cut off by the connection closing is written as far as it got
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.incoming.is_empty() {
+            let partial = std::mem::take(&mut self.incoming);
+            let line = self.render(&partial);
+            self.write_line("IN ", &line);
+        }
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_message_log_masks_credentials() {
+        let directory = std::env::temp_dir().join(format!("fix_engine_file_message_log_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let mut log = FileMessageLog::open(&directory, "SENDER", "TARGET").unwrap();
+        log.log_outgoing(b"8=FIX.4.4\x0135=A\x01553=trader\x01554=secret\x0110=000\x01");
+        log.set_masked_tags(&[numbers::USERNAME]);
+        log.log_incoming(b"8=FIX.4.4\x0135=A\x01553=trader\x01554=secret\x0110=000\x01");
+        log.log_event("Disconnected");
+        log.flush().unwrap();
+
+        let contents = fs::read_to_string(directory.join("SENDER-TARGET.log")).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with(" OUT 8=FIX.4.4|35=A|553=trader|554=****|10=000|"), "{}", lines[0]);
+        assert!(lines[1].ends_with(" IN  8=FIX.4.4|35=A|553=****|554=secret|10=000|"), "{}", lines[1]);
+        assert!(lines[2].ends_with(" EVENT Disconnected"));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_file_message_log_splits_reads_into_messages() {
+        let directory = std::env::temp_dir().join(format!("fix_engine_file_message_log_reads_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let mut log = FileMessageLog::open(&directory, "SENDER", "TARGET").unwrap();
+        let start = chrono::NaiveDateTime::parse_from_str("20231016-12:30:00.123", crate::clock::TIMESTAMP_FORMAT).unwrap().and_utc();
+        let clock = Arc::new(crate::testing::ManualClock::new(start));
+        log.set_clock(Arc::clone(&clock) as Arc<dyn Clock>);
+        // Two messages in one read, with junk ahead of them and the second one's checksum split off
+        log.log_incoming(b"xx8=FIX.4.4\x0135=0\x0110=001\x018=FIX.4.4\x0135=1\x0110=0");
+        clock.advance(std::time::Duration::from_millis(5));
+        log.log_incoming(b"02\x018=FIX.4.4\x0135=2");
+        log.flush().unwrap();
+
+        let contents = fs::read_to_string(directory.join("SENDER-TARGET.log")).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines, [
+            "20231016-12:30:00.123000 IN  xx8=FIX.4.4|35=0|10=001|",
+            "20231016-12:30:00.128000 IN  8=FIX.4.4|35=1|10=002|",
+            "20231016-12:30:00.128000 IN  8=FIX.4.4|35=2|",
+        ]);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_comp_ids_are_escaped_in_file_names() {
+        let directory = std::env::temp_dir().join(format!("fix_engine_file_message_log_escaped_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let mut log = FileMessageLog::open(&directory, "../A", "B/C").unwrap();
+        log.log_event("Connected");
+        log.flush().unwrap();
+        let names: Vec<_> = fs::read_dir(&directory).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(names, ["%2E%2E%2FA-B%2FC.log"]);
+
+        // The '-' between the comp IDs is the only one left, so different pairs never share a file
+        let mut other = FileMessageLog::open(&directory, "A-B", "C").unwrap();
+        other.log_event("Connected");
+        other.flush().unwrap();
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 2);
+        assert!(directory.join("A%2DB-C.log").exists());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
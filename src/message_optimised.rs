@@ -1,6 +1,5 @@
-use crate::clock::Clock;
+use crate::message::FixMessage;
 use crate::tag::*;
-use std::sync::Arc;
 
 pub struct FixMessage2 {
     pub header: [Option<FixTag>; 10],
@@ -9,7 +8,7 @@ pub struct FixMessage2 {
 }
 
 impl FixMessage2 {
-    pub(crate) fn encode(&mut self) -> String {
+    pub fn encode(&mut self) -> String {
 
         // The message length must be specified in the BodyLength(9) field. The length must be calculatedpub pub  by counting the number of octets
         // in the message following the end of field delimiter (<SOH>) of BodyLength(9), up to and including the end of field delimiter (<SOH>)
@@ -72,6 +71,63 @@ fn int_to_str_no_alloc(n: usize, buffer: &mut [u8]) -> &str {
     core::str::from_utf8(&buffer[pos..]).unwrap()
 }
 
+impl Default for FixMessage2 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Header slots 0 and 1 hold BeginString and BodyLength; the other header fields follow in the order the
+// version defines them and body fields go in tag number order. Fields without a FixTag variant, or that
+// do not fit in the arrays, are dropped.
+impl From<&FixMessage> for FixMessage2 {
+    fn from(message: &FixMessage) -> Self {
+        let mut result = FixMessage2::new();
+        let parse = |(tag, value): (&str, &String)| FixTag::parse(tag, value).ok();
+
+        let begin_string = message.header.get("8").and_then(|value| BeginString::from_wire(value).ok());
+        result.header[0] = begin_string.map(FixTag::BeginString);
+        result.header[1] = message.header.get("9").map(|length| FixTag::BodyLength(length.clone()));
+        let header_fields = begin_string.unwrap_or(BeginString::Fix4_4).header_fields();
+        let header = header_fields.iter()
+            .filter(|tag| **tag != "8" && **tag != "9")
+            .filter_map(|tag| message.header.get(*tag).map(|value| (*tag, value)))
+            .filter_map(parse);
+        fill(&mut result.header[2..], header);
+
+        let mut body: Vec<(&str, &String)> = message.body.iter().map(|(tag, value)| (tag.as_str(), value)).collect();
+        body.sort_by_key(|(tag, _)| tag.parse::<u32>().unwrap_or(u32::MAX));
+        fill(&mut result.body, body.into_iter().filter_map(parse));
+
+        result.trailer[0] = message.trailer.get("10").map(|checksum| FixTag::Checksum(checksum.clone()));
+        result
+    }
+}
+
+// Repeated tags such as the entries of a repeating group collapse to the last value
+impl From<&FixMessage2> for FixMessage {
+    fn from(message: &FixMessage2) -> Self {
+        let mut result = FixMessage::new();
+        let sections = [
+            (&mut result.header, &message.header[..]),
+            (&mut result.body, &message.body[..]),
+            (&mut result.trailer, &message.trailer[..]),
+        ];
+        for (fields, tags) in sections {
+            for tag in tags.iter().flatten() {
+                fields.insert(tag.tag_id().to_string(), tag.value());
+            }
+        }
+        result
+    }
+}
+
+fn fill(slots: &mut [Option<FixTag>], tags: impl Iterator<Item = FixTag>) {
+    for (slot, tag) in slots.iter_mut().zip(tags) {
+        *slot = Some(tag);
+    }
+}
+
 impl FixMessage2 {
     pub fn new() -> Self {
         Self {
@@ -133,12 +189,27 @@ mod tests {
         msg.header[2] = Some(FixTag::MsgType(MsgType::Logon));
         msg.header[3] = Some(FixTag::MsgSeqNum("1".to_string()));
         msg.header[4] = Some(FixTag::SendingTime(fixed_clock.now()));
-        msg.header[5] = Some(FixTag::SenderCompID(CompID("SENDER".to_string())));
-        msg.header[6] = Some(FixTag::TargetCompID(CompID("TARGET".to_string())));
+        msg.header[5] = Some(FixTag::SenderCompID(CompID::new("SENDER".to_string()).unwrap()));
+        msg.header[6] = Some(FixTag::TargetCompID(CompID::new("TARGET".to_string()).unwrap()));
         msg.body[0] = Some(FixTag::Symbol("BTCUSDT".to_string()));
         msg
     }
 
+    #[test]
+    fn test_logon_round_trips_through_both_representations() {
+        let logon = "8=FIX.4.4\x019=67\x0135=A\x0149=SENDER\x0156=TARGET\x0134=1\x0152=20231016-12:30:00.123\x0198=0\x01108=30\x0110=118\x01";
+        let decoded = FixMessage::decode(logon).unwrap();
+
+        let mut optimised = FixMessage2::from(&decoded);
+        assert!(matches!(optimised.header[2], Some(FixTag::MsgType(MsgType::Logon))));
+        assert_eq!(optimised.encode(), logon);
+
+        let round_tripped = FixMessage::from(&optimised);
+        assert_eq!(round_tripped.header, decoded.header);
+        assert_eq!(round_tripped.body, decoded.body);
+        assert_eq!(round_tripped.trailer, decoded.trailer);
+    }
+
     #[test]
     fn test_calculate_checksum_correctly() {
         let message_without_checksum = "8=FIX.4.4\x019=59\x0135=A\x0149=SENDER\x0156=TARGET\x0134=1\x0152=20231016-12:30:00.123\x0198=0\x01108=30\x01".to_string();
@@ -156,4 +227,35 @@ mod tests {
 
         assert_eq!(calculated_checksum, expected_checksum);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_encode_market_data_request_for_two_symbols() {
+        let fixed_clock = create_fixed_clock();
+        let mut msg = FixMessage2::new();
+        msg.header[0] = Some(FixTag::BeginString(BeginString::Fix4_4));
+        msg.header[2] = Some(FixTag::MsgType(MsgType::MarketDataRequest));
+        msg.header[3] = Some(FixTag::MsgSeqNum("2".to_string()));
+        msg.header[4] = Some(FixTag::SendingTime(fixed_clock.now()));
+        msg.header[5] = Some(FixTag::SenderCompID(CompID::new("SENDER".to_string()).unwrap()));
+        msg.header[6] = Some(FixTag::TargetCompID(CompID::new("TARGET".to_string()).unwrap()));
+        msg.body[0] = Some(FixTag::MDReqID("MD-1".to_string()));
+        msg.body[1] = Some(FixTag::SubscriptionRequestType(SubscriptionRequestType::SnapshotPlusUpdates));
+        msg.body[2] = Some(FixTag::MarketDepth("1".to_string()));
+        msg.body[3] = Some(FixTag::NoMDEntryTypes("2".to_string()));
+        msg.body[4] = Some(FixTag::MDEntryType(MDEntryType::Bid));
+        msg.body[5] = Some(FixTag::MDEntryType(MDEntryType::Offer));
+        msg.body[6] = Some(FixTag::NoRelatedSym("2".to_string()));
+        msg.body[7] = Some(FixTag::Symbol("BTCUSDT".to_string()));
+        msg.body[8] = Some(FixTag::Symbol("ETHUSDT".to_string()));
+
+        let encoded_message = msg.encode();
+
+        assert!(encoded_message.starts_with("8=FIX.4.4\x019="));
+        assert!(encoded_message.contains("\x0135=V\x01"));
+        assert!(encoded_message.contains(
+            "262=MD-1\x01263=1\x01264=1\x01267=2\x01269=0\x01269=1\x01146=2\x0155=BTCUSDT\x0155=ETHUSDT\x01"
+        ));
+        assert!(encoded_message.ends_with("\x01"));
+        assert!(encoded_message.contains("\x0110="));
+    }
+}
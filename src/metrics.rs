@@ -0,0 +1,157 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Upper bounds of the send latency buckets, measured from encoding a message to the end of its write. The
+// last bucket of a snapshot counts everything slower than the final bound.
+pub const SEND_LATENCY_BUCKETS: [Duration; 8] = [
+    Duration::from_micros(10),
+    Duration::from_micros(50),
+    Duration::from_micros(100),
+    Duration::from_micros(500),
+    Duration::from_millis(1),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(50),
+];
+
+const LATENCY_BUCKET_COUNT: usize = SEND_LATENCY_BUCKETS.len() + 1;
+
+// Counters kept by the engine threads as they go, so reading them never waits on the session
+#[derive(Debug, Default)]
+pub struct SessionMetrics {
+    admin_sent: AtomicU64,
+    app_sent: AtomicU64,
+    admin_received: AtomicU64,
+    app_received: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    decode_errors: AtomicU64,
+    resends_serviced: AtomicU64,
+    duplicates_dropped: AtomicU64,
+    send_latency: [AtomicU64; LATENCY_BUCKET_COUNT],
+    queue_depth: Mutex<Option<Arc<AtomicUsize>>>, // Shared with a bounded outgoing channel, which keeps it up to date
+    throttled: AtomicBool, // A ThrottlePolicy is in force, so the throttle fields mean something
+    throttle_tokens: AtomicU64,
+    throttle_delay_micros: AtomicU64,
+    messages_throttled: AtomicU64,
+}
+
+// The counters at one moment; totals since the engine was created, across reconnects
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionMetricsSnapshot {
+    pub admin_messages_sent: u64,
+    pub app_messages_sent: u64,
+    pub admin_messages_received: u64,
+    pub app_messages_received: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub decode_errors: u64,
+    // ResendRequests from the peer that were answered
+    pub resends_serviced: u64,
+    // Possible duplicates dropped because they had already been processed
+    pub duplicates_dropped: u64,
+    // Messages waiting in a bounded outgoing channel; None for an unbounded one
+    pub queue_depth: Option<usize>,
+    // Messages sent per SEND_LATENCY_BUCKETS bucket, with one more for those slower than all of them
+    pub send_latency: [u64; LATENCY_BUCKET_COUNT],
+    // Whole tokens left in the throttle's bucket; None without a ThrottlePolicy
+    pub throttle_tokens: Option<u64>,
+    // How long the last application message the throttle held back had to wait for a token
+    pub throttle_delay: Duration,
+    // Application messages the throttle held back or refused
+    pub messages_throttled: u64,
+}
+
+// Receives a snapshot every SessionConfig::metrics_interval and once more at shutdown, for pushing to a
+// metrics system. Called from the receive thread, so it should hand the snapshot off rather than block.
+pub trait MetricsSink: Send {
+    fn publish(&mut self, snapshot: &SessionMetricsSnapshot);
+}
+
+impl SessionMetrics {
+    pub fn snapshot(&self) -> SessionMetricsSnapshot {
+        SessionMetricsSnapshot {
+            admin_messages_sent: self.admin_sent.load(Ordering::Relaxed),
+            app_messages_sent: self.app_sent.load(Ordering::Relaxed),
+            admin_messages_received: self.admin_received.load(Ordering::Relaxed),
+            app_messages_received: self.app_received.load(Ordering::Relaxed),
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+            decode_errors: self.decode_errors.load(Ordering::Relaxed),
+            resends_serviced: self.resends_serviced.load(Ordering::Relaxed),
+            duplicates_dropped: self.duplicates_dropped.load(Ordering::Relaxed),
+            queue_depth: self.queue_depth(),
+            send_latency: std::array::from_fn(|bucket| self.send_latency[bucket].load(Ordering::Relaxed)),
+            throttle_tokens: self.throttled.load(Ordering::Relaxed).then(|| self.throttle_tokens.load(Ordering::Relaxed)),
+            throttle_delay: Duration::from_micros(self.throttle_delay_micros.load(Ordering::Relaxed)),
+            messages_throttled: self.messages_throttled.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn queue_depth(&self) -> Option<usize> {
+        self.queue_depth.lock().unwrap().as_ref().map(|depth| depth.load(Ordering::SeqCst))
+    }
+
+    pub(crate) fn set_queue_depth(&self, depth: Option<Arc<AtomicUsize>>) {
+        *self.queue_depth.lock().unwrap() = depth;
+    }
+
+    pub(crate) fn record_sent(&self, admin: bool, bytes: usize, latency: Duration) {
+        let count = if admin { &self.admin_sent } else { &self.app_sent };
+        count.fetch_add(1, Ordering::Relaxed);
+        self.bytes_out.fetch_add(bytes as u64, Ordering::Relaxed);
+        let bucket = SEND_LATENCY_BUCKETS.iter().position(|bound| latency <= *bound).unwrap_or(SEND_LATENCY_BUCKETS.len());
+        self.send_latency[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_received(&self, admin: bool) {
+        let count = if admin { &self.admin_received } else { &self.app_received };
+        count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_bytes_in(&self, bytes: usize) {
+        self.bytes_in.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_decode_error(&self) {
+        self.decode_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_resend(&self) {
+        self.resends_serviced.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_duplicate(&self) {
+        self.duplicates_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // The throttle's bucket after an application message took a token, or found none with `delay` to wait
+    pub(crate) fn record_throttle(&self, tokens: u64, delay: Option<Duration>) {
+        self.throttled.store(true, Ordering::Relaxed);
+        self.throttle_tokens.store(tokens, Ordering::Relaxed);
+        if let Some(delay) = delay {
+            self.throttle_delay_micros.store(delay.as_micros() as u64, Ordering::Relaxed);
+            self.messages_throttled.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_send_latency_falls_into_the_first_bucket_that_holds_it() {
+        let metrics = SessionMetrics::default();
+        metrics.record_sent(true, 60, Duration::from_micros(5));
+        metrics.record_sent(false, 100, Duration::from_micros(50));
+        metrics.record_sent(false, 100, Duration::from_micros(51));
+        metrics.record_sent(false, 100, Duration::from_secs(1));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!((snapshot.admin_messages_sent, snapshot.app_messages_sent, snapshot.bytes_out), (1, 3, 360));
+        assert_eq!(snapshot.send_latency, [1, 1, 1, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(snapshot.queue_depth, None);
+    }
+}
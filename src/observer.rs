@@ -0,0 +1,16 @@
+use crate::error::EngineError;
+use crate::message::FixMessage;
+use crate::session::SessionState;
+
+// Callbacks are invoked from the engine threads, so implementations must be cheap and thread safe.
+pub trait EngineObserver: Send + Sync {
+    fn on_sent(&self, _message: &FixMessage) {}
+    fn on_received(&self, _message: &FixMessage) {}
+    fn on_error(&self, _error: &EngineError) {}
+    fn on_state_change(&self, _state: SessionState) {}
+}
+
+#[derive(Debug, Default)]
+pub struct NoopObserver;
+
+impl EngineObserver for NoopObserver {}
@@ -0,0 +1,72 @@
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::io;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+
+// What the send thread reports for a message tracked with FixMessage::track
+pub type SendResult = Result<SendReceipt, SendFailure>;
+
+// A tracked message as it went out on the wire
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SendReceipt {
+    pub seq_num: u64,
+    pub bytes: usize, // Of the encoded message, without any framing around it
+    pub sent_at: DateTime<Utc>, // Taken from the engine's clock as soon as the write returned
+}
+
+// Why a tracked message was never written
+#[derive(Debug, Clone)]
+pub enum SendFailure {
+    // Sent while the connection was down, and the reconnect policy could not hold it
+    NotConnected,
+    // Refused by a saturated throttle under ThrottleSaturation::Reject
+    Throttled,
+    // Kept back by FixApplication::to_app or the outgoing interceptor; it took no MsgSeqNum
+    Refused,
+    // Storing or writing it failed
+    Io(Arc<io::Error>),
+    // Still queued when the engine shut down
+    ShutDown,
+    // Discarded before the engine took it, e.g. by a full channel under OverflowPolicy::DropOldest
+    Dropped,
+}
+
+impl fmt::Display for SendFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendFailure::NotConnected => write!(f, "Not connected"),
+            SendFailure::Throttled => write!(f, "Throttled"),
+            SendFailure::Refused => write!(f, "Refused before sending"),
+            SendFailure::Io(error) => write!(f, "Failed to send: {}", error),
+            SendFailure::ShutDown => write!(f, "Dropped at shutdown"),
+            SendFailure::Dropped => write!(f, "Discarded before sending"),
+        }
+    }
+}
+
+impl std::error::Error for SendFailure {}
+
+// Where a tracked message's result goes; reports back only once. A tracked message dropped without a result,
+// e.g. by a channel making room, reports SendFailure::Dropped.
+#[derive(Debug, Default)]
+pub(crate) struct ReceiptSlot(Option<Sender<SendResult>>);
+
+impl Drop for ReceiptSlot {
+    fn drop(&mut self) {
+        self.complete(Err(SendFailure::Dropped));
+    }
+}
+
+impl ReceiptSlot {
+    pub(crate) fn track() -> (ReceiptSlot, Receiver<SendResult>) {
+        let (sender, receiver) = channel();
+        (ReceiptSlot(Some(sender)), receiver)
+    }
+
+    pub(crate) fn complete(&mut self, result: SendResult) {
+        if let Some(sender) = self.0.take() {
+            let _ = sender.send(result);
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::time::Duration;
+
+// How long an initiator waits before each attempt to re-establish a lost connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backoff {
+    Fixed(Duration),
+    // Doubles from initial with every failed attempt, up to max
+    Exponential { initial: Duration, max: Duration },
+}
+
+impl Backoff {
+    // Delay before the given attempt, counting from 0
+    pub fn delay(&self, attempt: u32) -> Duration {
+        match *self {
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential { initial, max } => initial.saturating_mul(2u32.saturating_pow(attempt)).min(max),
+        }
+    }
+}
+
+// What happens to application messages sent while the connection is down
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueuePolicy {
+    // Held and sent once the session is logged on again; any beyond capacity are refused
+    Buffer { capacity: usize },
+    // Refused straight away
+    Reject,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    pub backoff: Backoff,
+    // Attempts without a completed logon in between before giving up; None keeps trying until shutdown
+    pub max_attempts: Option<u32>,
+    pub queue: QueuePolicy,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            backoff: Backoff::Exponential { initial: Duration::from_secs(1), max: Duration::from_secs(30) },
+            max_attempts: None,
+            queue: QueuePolicy::Buffer { capacity: 1024 },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exponential_backoff_doubles_up_to_max() {
+        let backoff = Backoff::Exponential { initial: Duration::from_millis(100), max: Duration::from_secs(1) };
+        let delays: Vec<u64> = (0..6).map(|attempt| backoff.delay(attempt).as_millis() as u64).collect();
+        assert_eq!(delays, [100, 200, 400, 800, 1000, 1000]);
+        assert_eq!(backoff.delay(u32::MAX), Duration::from_secs(1));
+        assert_eq!(Backoff::Fixed(Duration::from_millis(250)).delay(7), Duration::from_millis(250));
+    }
+}
@@ -0,0 +1,232 @@
+use crate::clock::TIMESTAMP_PARSE_FORMAT;
+use crate::engine::{FixEngine, FixEngineMode};
+use crate::error::DecodeError;
+use crate::event::EngineEvent;
+use crate::framer::find_message_start;
+use crate::message::FixMessage;
+use crate::session::SessionConfig;
+use crate::tag::{BeginString, SOH};
+use crate::testing::{duplex, ManualClock, MemoryStream};
+use chrono::{NaiveDateTime, Utc};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+// How fast a replay hands out its messages
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    // One after another with no waiting
+    FullSpeed,
+    // Spaced out as their SendingTime(52)s were, divided by the factor: 1.0 as captured, 2.0 twice as fast
+    Timed(f64),
+}
+
+// A line of the capture that held a message which could not be decoded; the replay carries on past it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayError {
+    pub line: usize,
+    pub error: DecodeError,
+}
+
+struct ReplayState {
+    paused: bool,
+    speed: ReplaySpeed,
+    stopped: bool,
+    finished: bool,
+    errors: Vec<ReplayError>,
+}
+
+// Steers a replay running on its own thread. Dropping it ends the replay.
+pub struct ReplayControl {
+    state: Arc<(Mutex<ReplayState>, Condvar)>,
+}
+
+impl ReplayControl {
+    pub fn pause(&self) {
+        self.update(|state| state.paused = true);
+    }
+
+    pub fn resume(&self) {
+        self.update(|state| state.paused = false);
+    }
+
+    // Applies straight away, to the wait for the next message as well
+    pub fn set_speed(&self, speed: ReplaySpeed) {
+        self.update(|state| state.speed = speed);
+    }
+
+    // Every message has been fed to the engine and the engine has shut down, so the receiver holds all it
+    // is going to get
+    pub fn is_finished(&self) -> bool {
+        self.state.0.lock().unwrap().finished
+    }
+
+    // Lines that could not be decoded so far, in file order
+    pub fn errors(&self) -> Vec<ReplayError> {
+        self.state.0.lock().unwrap().errors.clone()
+    }
+
+    fn update(&self, change: impl FnOnce(&mut ReplayState)) {
+        let (state, changed) = &*self.state;
+        change(&mut state.lock().unwrap());
+        changed.notify_all();
+    }
+}
+
+impl Drop for ReplayControl {
+    fn drop(&mut self) {
+        self.update(|state| state.stopped = true);
+    }
+}
+
+// An inbound message of the capture, with the line it was on
+struct Captured {
+    line: usize,
+    message: String,
+}
+
+// Reads a capture and feeds its inbound messages, byte for byte, to an acceptor engine over an in-memory
+// connection. The session takes them just as it took them off the wire: sequence checks, gap handling and
+// admin messages all apply, and its answers are thrown away. Application messages come out on the returned
+// receiver, which ends once the whole capture has been fed and the engine has shut down.
+//
+// Each line holds at most one message, from its BeginString(8) on, so prefixes such as a timestamp are
+// skipped. Lines marked OUT, as written by FileMessageLog, and lines without a message are passed over. SOH
+// may be shown as '|'. The engine's clock follows the SendingTime(52)s of the capture.
+pub fn start(path: &Path, speed: ReplaySpeed) -> io::Result<(Receiver<FixMessage>, ReplayControl)> {
+    let contents = fs::read(path)?;
+    let captured: Vec<Captured> = contents
+        .split(|&byte| byte == b'\n')
+        .enumerate()
+        .filter_map(|(index, line)| inbound_message(line).map(|message| Captured { line: index + 1, message }))
+        .collect();
+
+    let first = captured.first().and_then(|captured| FixMessage::decode(&captured.message).ok());
+    let begin_string = first.as_ref().and_then(|message| message.header.get("8")).and_then(|value| BeginString::from_wire(value).ok());
+    let config = SessionConfig {
+        begin_string: begin_string.unwrap_or(BeginString::Fix4_4),
+        // The clock jumps to each message's SendingTime as it is fed, which a fast replay runs well ahead of
+        max_clock_skew: Duration::MAX,
+        ..SessionConfig::default()
+    };
+    let clock = Arc::new(ManualClock::new(first.as_ref().and_then(sending_time).map_or_else(Utc::now, |time| time.and_utc())));
+    let mut engine = FixEngine::new(Arc::clone(&clock) as _, FixEngineMode::Acceptor, config);
+
+    let (capture, connection) = duplex();
+    let (outgoing_sender, outgoing_receiver) = channel();
+    let (sender, receiver) = channel();
+    let events = engine.take_events().expect("a new engine has its events");
+    engine.start(connection, outgoing_receiver, sender)?;
+    let mut answers = capture.clone();
+    thread::Builder::new()
+        .name("fix-replay-answers".to_string())
+        .spawn(move || while matches!(answers.read(&mut [0; 1024]), Ok(size) if size > 0) {})?;
+
+    let state = Arc::new((Mutex::new(ReplayState { paused: false, speed, stopped: false, finished: false, errors: Vec::new() }), Condvar::new()));
+    let replay_state = Arc::clone(&state);
+    thread::Builder::new().name("fix-replay".to_string()).spawn(move || {
+        if replay(&captured, &replay_state, capture, &clock) {
+            // Shutting down any sooner would cut off the messages the engine has still to read
+            let _ = events.iter().find(|event| matches!(event, EngineEvent::Disconnected { .. }));
+        }
+        engine.shutdown();
+        replay_state.0.lock().unwrap().finished = true;
+        // The engine holds the sender, so the receiver ends as it goes
+        drop((engine, outgoing_sender));
+    })?;
+    Ok((receiver, ReplayControl { state }))
+}
+
+// Writes each message into the engine's connection in turn, then closes it. Returns false if the replay was
+// stopped first.
+fn replay(captured: &[Captured], state: &(Mutex<ReplayState>, Condvar), mut capture: MemoryStream, clock: &ManualClock) -> bool {
+    let mut previous_sending_time: Option<NaiveDateTime> = None;
+    for Captured { line, message } in captured {
+        // The engine is handed the bytes either way, and decides for itself what to make of a broken message
+        let sending_time = match FixMessage::decode(message) {
+            Ok(decoded) => sending_time(&decoded),
+            Err(error) => {
+                warn!("Replay line {}: {}", line, error);
+                state.0.lock().unwrap().errors.push(ReplayError { line: *line, error });
+                None
+            }
+        };
+
+        let gap = previous_sending_time.zip(sending_time).and_then(|(previous, current)| (current - previous).to_std().ok());
+        previous_sending_time = sending_time.or(previous_sending_time);
+        if !wait_turn(state, gap.unwrap_or_default()) {
+            return false;
+        }
+        if let Some(sending_time) = sending_time {
+            clock.set(sending_time.and_utc());
+        }
+        if let Err(e) = capture.write_all(message.as_bytes()) {
+            warn!("Replay stopped at line {}, the engine dropped the connection: {}", line, e);
+            break;
+        }
+    }
+    // What is already written is still read before the engine sees the connection close, and its answers
+    // until then still go through
+    capture.shutdown_write();
+    true
+}
+
+fn sending_time(message: &FixMessage) -> Option<NaiveDateTime> {
+    message.header.get("52").and_then(|value| NaiveDateTime::parse_from_str(value, TIMESTAMP_PARSE_FORMAT).ok())
+}
+
+// The message on a line of the capture, with SOH restored; None for outbound messages and other lines
+fn inbound_message(line: &[u8]) -> Option<String> {
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let start = find_message_start(line)?;
+    let prefix = String::from_utf8_lossy(&line[..start]);
+    if prefix.split_whitespace().any(|word| word == "OUT") {
+        return None;
+    }
+    let message = String::from_utf8_lossy(&line[start..]);
+    Some(if message.contains(SOH) { message.into_owned() } else { message.replace('|', &SOH.to_string()) })
+}
+
+// Waits out the gap to the previous message at the current speed, and for as long as the replay is paused.
+// Returns false once the replay has been stopped.
+fn wait_turn(state: &(Mutex<ReplayState>, Condvar), gap: Duration) -> bool {
+    let (state, changed) = state;
+    let mut guard = state.lock().unwrap();
+    let waiting_since = Instant::now();
+    loop {
+        if guard.stopped {
+            return false;
+        }
+        let due = match guard.speed {
+            ReplaySpeed::Timed(factor) if factor > 0.0 => waiting_since + gap.div_f64(factor),
+            _ => waiting_since,
+        };
+        let now = Instant::now();
+        if guard.paused {
+            guard = changed.wait(guard).unwrap();
+        } else if now < due {
+            guard = changed.wait_timeout(guard, due - now).unwrap().0;
+        } else {
+            return true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inbound_message_skips_prefixes_and_outbound_lines() {
+        assert_eq!(inbound_message(b"20231016-12:30:00.000100 IN  8=FIX.4.4|9=5|35=0|10=000|").unwrap(), "8=FIX.4.4\x019=5\x0135=0\x0110=000\x01");
+        assert_eq!(inbound_message(b"8=FIX.4.4\x019=5\x0158=a|b\x0110=000\x01").unwrap(), "8=FIX.4.4\x019=5\x0158=a|b\x0110=000\x01");
+        assert_eq!(inbound_message(b"20231016-12:30:00.000100 OUT 8=FIX.4.4|9=5|35=0|10=000|"), None);
+        assert_eq!(inbound_message(b"20231016-12:30:00.000100 EVENT Session state Connected -> LoggedOn"), None);
+        assert_eq!(inbound_message(b""), None);
+    }
+}
@@ -0,0 +1,103 @@
+use crate::message::FixMessage;
+use crate::tag::{FixField, MsgType};
+use std::collections::HashMap;
+use std::sync::mpsc::Receiver;
+
+type Handler = Box<dyn FnMut(&FixMessage) + Send>;
+
+// Dispatches application messages to handlers registered per MsgType(35).
+#[derive(Default)]
+pub struct MessageRouter {
+    handlers: HashMap<String, Handler>, // Keyed by the MsgType wire value
+    fallback: Option<Handler>,
+}
+
+impl MessageRouter {
+    pub fn new() -> MessageRouter {
+        MessageRouter::default()
+    }
+
+    pub fn on<F>(&mut self, msg_type: MsgType, handler: F) -> &mut Self
+    where
+        F: FnMut(&FixMessage) + Send + 'static,
+    {
+        self.handlers.insert(msg_type.value(), Box::new(handler));
+        self
+    }
+
+    // Receives every message that has no handler of its own, including ones without a MsgType
+    pub fn fallback<F>(&mut self, handler: F) -> &mut Self
+    where
+        F: FnMut(&FixMessage) + Send + 'static,
+    {
+        self.fallback = Some(Box::new(handler));
+        self
+    }
+
+    // Returns false when neither a handler nor a fallback took the message
+    pub fn dispatch(&mut self, message: &FixMessage) -> bool {
+        let handler = message.header.get("35").and_then(|msg_type| self.handlers.get_mut(msg_type));
+        match handler.or(self.fallback.as_mut()) {
+            Some(handler) => {
+                handler(message);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Dispatches everything from the engine's incoming channel until it is closed
+    pub fn run(&mut self, incoming: &Receiver<FixMessage>) {
+        for message in incoming.iter() {
+            self.dispatch(&message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use std::sync::{Arc, Mutex};
+
+    fn message_of_type(msg_type: &str) -> FixMessage {
+        let mut message = FixMessage::new();
+        message.header.insert("35".to_string(), msg_type.to_string());
+        message
+    }
+
+    #[test]
+    fn test_dispatch_invokes_matching_handler() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut router = MessageRouter::new();
+        let logons = Arc::clone(&calls);
+        let reports = Arc::clone(&calls);
+        router
+            .on(MsgType::Logon, move |_| logons.lock().unwrap().push("logon"))
+            .on(MsgType::ExecutionReport, move |_| reports.lock().unwrap().push("execution report"));
+
+        assert!(router.dispatch(&message_of_type("8")));
+        assert!(router.dispatch(&message_of_type("A")));
+        assert!(!router.dispatch(&message_of_type("D")));
+        assert_eq!(*calls.lock().unwrap(), vec!["execution report", "logon"]);
+    }
+
+    #[test]
+    fn test_unmatched_messages_go_to_fallback() {
+        let unmatched = Arc::new(Mutex::new(Vec::new()));
+        let mut router = MessageRouter::new();
+        let fallback = Arc::clone(&unmatched);
+        router
+            .on(MsgType::Logon, |_| {})
+            .fallback(move |message| fallback.lock().unwrap().push(message.header.get("35").cloned()));
+
+        let (sender, receiver) = channel();
+        sender.send(message_of_type("A")).unwrap();
+        sender.send(message_of_type("D")).unwrap();
+        sender.send(FixMessage::new()).unwrap();
+        drop(sender);
+        router.run(&receiver);
+
+        assert_eq!(*unmatched.lock().unwrap(), vec![Some("D".to_string()), None]);
+    }
+}
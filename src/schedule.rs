@@ -0,0 +1,87 @@
+use chrono::{DateTime, Datelike, FixedOffset, NaiveTime, TimeDelta, Utc, Weekday};
+
+// When a session is open, e.g. 07:00 to 17:30 Monday to Friday. Sequence numbers start again from 1 each time
+// the session opens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionSchedule {
+    pub start_time: NaiveTime,
+    // An end time not after the start time closes the session on the following day
+    pub end_time: NaiveTime,
+    // Days the session opens on; empty means every day
+    pub days: Vec<Weekday>,
+    // Start and end times are local to this offset
+    pub time_zone: FixedOffset,
+}
+
+impl SessionSchedule {
+    // Open every day, with times in UTC
+    pub fn new(start_time: NaiveTime, end_time: NaiveTime) -> Self {
+        SessionSchedule {
+            start_time,
+            end_time,
+            days: Vec::new(),
+            time_zone: FixedOffset::east_opt(0).unwrap(),
+        }
+    }
+
+    // When the session open at the given instant started, or None outside the schedule
+    pub fn session_start(&self, at: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let local = at.with_timezone(&self.time_zone).naive_local();
+        // A session that runs past midnight may have opened the day before
+        [local.date(), local.date() - TimeDelta::days(1)].into_iter().find_map(|date| {
+            if !self.days.is_empty() && !self.days.contains(&date.weekday()) {
+                return None;
+            }
+            let start = date.and_time(self.start_time);
+            let end = if self.end_time > self.start_time {
+                date.and_time(self.end_time)
+            } else {
+                (date + TimeDelta::days(1)).and_time(self.end_time)
+            };
+            (start <= local && local < end).then(|| (start - self.time_zone).and_utc())
+        })
+    }
+
+    pub fn is_session_time(&self, at: DateTime<Utc>) -> bool {
+        self.session_start(at).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+
+    fn utc(timestamp: &str) -> DateTime<Utc> {
+        NaiveDateTime::parse_from_str(timestamp, "%Y%m%d-%H:%M:%S").unwrap().and_utc()
+    }
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_session_start_within_a_weekday_window() {
+        let schedule = SessionSchedule {
+            days: vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri],
+            ..SessionSchedule::new(time(7, 0), time(17, 30))
+        };
+        // 2023-10-16 is a Monday
+        assert_eq!(schedule.session_start(utc("20231016-12:00:00")), Some(utc("20231016-07:00:00")));
+        assert_eq!(schedule.session_start(utc("20231016-17:30:00")), None);
+        assert_eq!(schedule.session_start(utc("20231016-06:59:59")), None);
+        assert_eq!(schedule.session_start(utc("20231015-12:00:00")), None); // Sunday
+    }
+
+    #[test]
+    fn test_session_past_midnight_in_another_time_zone() {
+        // 22:00 to 06:00 New York standard time, five hours behind UTC
+        let schedule = SessionSchedule {
+            time_zone: FixedOffset::west_opt(5 * 3600).unwrap(),
+            ..SessionSchedule::new(time(22, 0), time(6, 0))
+        };
+        assert_eq!(schedule.session_start(utc("20231017-04:00:00")), Some(utc("20231017-03:00:00")));
+        assert_eq!(schedule.session_start(utc("20231017-10:00:00")), Some(utc("20231017-03:00:00")));
+        assert!(!schedule.is_session_time(utc("20231017-11:00:00")));
+    }
+}
@@ -0,0 +1,1685 @@
+use crate::application::{DoNotDeliver, DoNotSend, FixApplication};
+use crate::channel::OverflowPolicy;
+use crate::clock::{Clock, TIMESTAMP_FORMAT};
+use crate::engine::FixEngineMode;
+use crate::engine_config::EngineConfig;
+use crate::error::{DecodeError, EngineError};
+use crate::event::{DisconnectReason, EngineEvent};
+use crate::framer::Framing;
+use crate::message::{EncodeOptions, FixMessage, HeaderLayout};
+use crate::message_log::MessageLog;
+use crate::metrics::{MetricsSink, SessionMetrics};
+use crate::observer::EngineObserver;
+use crate::receipt::{SendFailure, SendReceipt};
+use crate::reconnect::ReconnectPolicy;
+use crate::schedule::SessionSchedule;
+use crate::store::{MemoryMessageStore, MessageStore};
+use crate::tag::{numbers, BeginString, BusinessRejectReason, EncryptMethod, FixField, MsgType, ResetSeqNumFlag, SessionRejectReason, SOH};
+use crate::throttle::ThrottlePolicy;
+use crate::transport::Transport;
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::*;
+
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+
+// How long a write waits for the peer to take more bytes before checking whether to keep trying
+const WRITE_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    pub begin_string: BeginString,
+    // Empty comp IDs are not validated; an acceptor then adopts the ones from the peer's logon
+    pub sender_comp_id: String,
+    pub target_comp_id: String,
+    // What our own logon proposes
+    pub logon: LogonConfig,
+    // Acceptor only: a logon whose HeartBtInt falls outside these bounds is refused with a Logout
+    pub min_heart_bt_int: Option<u64>,
+    pub max_heart_bt_int: Option<u64>,
+    // Sent as DefaultApplVerID(1137) on our logon; FIXT.1.1 carries the application version there instead of
+    // in BeginString
+    pub default_appl_ver_id: Option<String>,
+    // Sent by an initiator as Username(553), Password(554) and NewPassword(925) on its logon
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub new_password: Option<String>,
+    // When set, stamped as SenderSubID(50) and TargetSubID(57) and required on every inbound message
+    pub sender_sub_id: Option<String>,
+    pub target_sub_id: Option<String>,
+    // How messages are delimited on the wire; both sides of a session must agree
+    pub framing: Framing,
+    // Where the counterparty splits header and body differently from the BeginString, e.g. its own routing
+    // tags in the header. Messages are decoded and sent by it.
+    pub header_layout: HeaderLayout,
+    // Bytes the receive buffer may hold without completing a message before the connection is dropped, and the
+    // largest BodyLength(9) the framer believes
+    pub max_message_size: usize,
+    // Largest difference between an inbound SendingTime(52) and our clock before the session is ended
+    pub max_clock_skew: Duration,
+    // Send NextExpectedMsgSeqNum(789) on our logon and act on the peer's: messages it is missing are resent
+    // straight after the logon instead of waiting for a ResendRequest
+    pub send_next_expected_msg_seq_num: bool,
+    // Application message types answered with a BusinessMessageReject instead of being delivered
+    pub reject_unsupported_msg_types: Vec<MsgType>,
+    // A Logon with ResetSeqNumFlag(141)=Y while logged on restarts both directions from 1 and is answered with
+    // our own; when unset it is rejected like any other Logon received after the handshake
+    pub accept_in_session_reset: bool,
+    // Log out once the application has dropped every sender of outgoing messages, instead of keeping the
+    // session up with heartbeats alone
+    pub logout_on_outgoing_closed: bool,
+    // Logons are only made and accepted inside the schedule; the session is logged out when it closes and
+    // sequence numbers start again from 1 when it next opens. None keeps the session open at all times.
+    pub schedule: Option<SessionSchedule>,
+    // Initiator only: a lost connection is re-established and logged back on, carrying on from the same
+    // sequence numbers. Needs an engine started with a way to connect, as the factory does.
+    pub reconnect: Option<ReconnectPolicy>,
+    // How long shutdown keeps sending the messages the application queued before it; any left are dropped
+    pub drain_timeout: Duration,
+    // How long shutdown waits for the peer to answer our Logout before closing the connection regardless
+    pub logout_timeout: Duration,
+    // What sending does while a bounded outgoing channel from the factory is full
+    pub outgoing_overflow: OverflowPolicy,
+    // Send a ResendRequest for the missing range as soon as an inbound gap is seen. When unset the gap is only
+    // reported, as EngineEvent::SequenceGap, and later messages are held until the application has it resent
+    // with FixEngine::request_resend.
+    pub resend_on_gap: bool,
+    // How often a MetricsSink set on the engine is handed a snapshot
+    pub metrics_interval: Duration,
+    // Keeps application messages under the counterparty's rate limit; None sends them as fast as they come
+    pub throttle: Option<ThrottlePolicy>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogonConfig {
+    // Sent as EncryptMethod(98). The engine never encrypts, so a session configured with anything but
+    // EncryptMethod::None fails to start rather than advertise it.
+    pub encrypt_method: EncryptMethod,
+    // Initiator only: logon with ResetSeqNumFlag(141)=Y, starting both directions again from 1
+    pub reset_seq_num: bool,
+    // Seconds; 0 disables heartbeats and TestRequests. An acceptor falls back on it when the peer's logon
+    // has no HeartBtInt(108).
+    pub heart_bt_int: u64,
+}
+
+impl Default for LogonConfig {
+    fn default() -> Self {
+        LogonConfig { encrypt_method: EncryptMethod::None, reset_seq_num: false, heart_bt_int: 30 }
+    }
+}
+
+impl SessionConfig {
+    pub fn new(sender_comp_id: &str, target_comp_id: &str) -> Self {
+        SessionConfig {
+            sender_comp_id: sender_comp_id.to_string(),
+            target_comp_id: target_comp_id.to_string(),
+            ..SessionConfig::default()
+        }
+    }
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            begin_string: BeginString::Fix4_4,
+            sender_comp_id: String::new(),
+            target_comp_id: String::new(),
+            logon: LogonConfig::default(),
+            min_heart_bt_int: None,
+            max_heart_bt_int: None,
+            default_appl_ver_id: None,
+            username: None,
+            password: None,
+            new_password: None,
+            sender_sub_id: None,
+            target_sub_id: None,
+            framing: Framing::TagValue,
+            header_layout: HeaderLayout::default(),
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            max_clock_skew: Duration::from_secs(120),
+            send_next_expected_msg_seq_num: false,
+            reject_unsupported_msg_types: Vec::new(),
+            accept_in_session_reset: false,
+            logout_on_outgoing_closed: false,
+            schedule: None,
+            reconnect: None,
+            drain_timeout: Duration::from_secs(5),
+            logout_timeout: Duration::from_secs(2),
+            outgoing_overflow: OverflowPolicy::Block,
+            resend_on_gap: true,
+            metrics_interval: Duration::from_secs(10),
+            throttle: None,
+        }
+    }
+}
+
+// Identifies a session from our side: sender is our CompID and target is the counterparty's
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SessionID {
+    pub begin_string: BeginString,
+    pub sender_comp_id: String,
+    pub target_comp_id: String,
+}
+
+// Decides whether an acceptor lets a logon in, e.g. by the Username(553) and Password(554) it carried, given the
+// session it would open. An Err is sent back as the Logout's Text(58).
+pub type LogonValidator = Box<dyn Fn(&SessionID, &FixMessage) -> Result<(), LogoutReason> + Send + Sync>;
+
+// Sees every new outgoing message once its header is stamped, after FixApplication::to_app or to_admin, and
+// may change it or drop it. Runs on whichever engine thread is sending, so it must not call back into the engine.
+pub type OutgoingInterceptor = Box<dyn FnMut(&mut FixMessage) -> Result<(), DoNotSend> + Send>;
+
+// Sees every inbound message that passed the session checks before the application does, and may keep it away
+pub type IncomingInterceptor = Box<dyn FnMut(&FixMessage) -> Result<(), DoNotDeliver> + Send>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogoutReason(pub String);
+
+impl LogoutReason {
+    pub fn new(text: &str) -> Self {
+        LogoutReason(text.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionState {
+    Disconnected,
+    Connected, // Transport up, logon not yet exchanged
+    LogonSent,
+    LoggedOn,
+    AwaitingResend, // Logged on, with a ResendRequest of ours still being answered
+    LogoutSent,     // Our Logout is out and the peer's confirmation has not arrived
+    Disconnecting,  // Shutting down; only Disconnected can follow
+}
+
+impl SessionState {
+    // Past the logon handshake and not yet torn down, so inbound messages are processed
+    pub fn is_logged_on(&self) -> bool {
+        matches!(self, SessionState::LoggedOn | SessionState::AwaitingResend | SessionState::LogoutSent)
+    }
+
+    // Application messages only go out in these states
+    pub fn can_send_application(&self) -> bool {
+        matches!(self, SessionState::LoggedOn | SessionState::AwaitingResend)
+    }
+}
+
+// Why an inbound message was refused with a session-level Reject(35=3)
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rejection {
+    reason: SessionRejectReason,
+    ref_tag_id: Option<u32>,
+    text: String,
+}
+
+// Session layer shared by the engine's send and receive threads.
+pub(crate) struct Session {
+    pub(crate) config: SessionConfig,
+    pub(crate) mode: FixEngineMode,
+    pub(crate) clock: Arc<dyn Clock>,
+    engine_config: Mutex<EngineConfig>,
+    observer: Arc<dyn EngineObserver>,
+    events: Sender<EngineEvent>,
+    inner: Mutex<SessionInner>,
+    writer: Mutex<Option<Box<dyn Transport>>>,
+    logon_validator: Mutex<Option<LogonValidator>>,
+    application: Mutex<Option<Arc<dyn FixApplication>>>,
+    message_log: Mutex<Option<Box<dyn MessageLog>>>,
+    outgoing_interceptor: Mutex<Option<OutgoingInterceptor>>,
+    incoming_interceptor: Mutex<Option<IncomingInterceptor>>,
+    metrics: SessionMetrics,
+    metrics_sink: Mutex<Option<(Box<dyn MetricsSink>, Instant)>>, // With when it was last published to
+}
+
+struct SessionInner {
+    state: SessionState,
+    sender_comp_id: String,
+    target_comp_id: String,
+    heart_bt_int: u64,
+    last_sent: DateTime<Utc>,
+    last_received: DateTime<Utc>,
+    pending_test_request: Option<(String, DateTime<Utc>)>, // TestReqID and when it was sent
+    test_request_count: u64,
+    queued: BTreeMap<u64, Option<FixMessage>>, // Arrived ahead of a gap; None marks an already handled message
+    resend_requested: bool,
+    processing: Option<u64>, // MsgSeqNum of the in-order message being handled; taken away by a reset meanwhile
+    store: Box<dyn MessageStore>, // Sequence numbers, and the encoded outgoing messages for answering ResendRequests
+    logout_sent: bool, // A Logout from the peer then confirms ours rather than needing a reply
+    logout_until: Option<Instant>, // When to stop waiting for the peer to confirm our Logout
+    flush_until: Option<Instant>, // Stopped while application messages could go out, so the queued ones are still sent until then
+    session_start: Option<DateTime<Utc>>, // Opening of the scheduled session the sequence numbers belong to
+    reconnect: bool, // A lost connection is re-established, until shutdown or the attempts run out
+    logged_on_since_connect: bool, // The current or last connection got as far as a completed logon
+    peer_addr: Option<SocketAddr>, // Of the current or last connection
+}
+
+impl Session {
+    pub(crate) fn new(config: SessionConfig, mode: FixEngineMode, clock: Arc<dyn Clock>, observer: Arc<dyn EngineObserver>, events: Sender<EngineEvent>) -> Session {
+        let inner = SessionInner {
+            state: SessionState::Disconnected,
+            sender_comp_id: config.sender_comp_id.clone(),
+            target_comp_id: config.target_comp_id.clone(),
+            heart_bt_int: config.logon.heart_bt_int,
+            last_sent: clock.now_utc(),
+            last_received: clock.now_utc(),
+            pending_test_request: None,
+            test_request_count: 0,
+            queued: BTreeMap::new(),
+            resend_requested: false,
+            processing: None,
+            store: Box::new(MemoryMessageStore::new()),
+            logout_sent: false,
+            logout_until: None,
+            flush_until: None,
+            session_start: None,
+            reconnect: false,
+            logged_on_since_connect: false,
+            peer_addr: None,
+        };
+        Session {
+            config,
+            mode,
+            clock,
+            engine_config: Mutex::new(EngineConfig::default()),
+            observer,
+            events,
+            inner: Mutex::new(inner),
+            writer: Mutex::new(None),
+            logon_validator: Mutex::new(None),
+            application: Mutex::new(None),
+            message_log: Mutex::new(None),
+            outgoing_interceptor: Mutex::new(None),
+            incoming_interceptor: Mutex::new(None),
+            metrics: SessionMetrics::default(),
+            metrics_sink: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn state(&self) -> SessionState {
+        self.inner.lock().unwrap().state
+    }
+
+    // Every change of state goes through here. Once Disconnecting, nothing but Disconnected is taken, so a
+    // message still being handled cannot bring a session that is shutting down back to life.
+    // Returns whether the state changed.
+    fn set_state(&self, state: SessionState) -> bool {
+        let previous = {
+            let mut inner = self.inner.lock().unwrap();
+            if inner.state == SessionState::Disconnecting && state != SessionState::Disconnected {
+                return false;
+            }
+            inner.logged_on_since_connect |= state.is_logged_on();
+            std::mem::replace(&mut inner.state, state)
+        };
+        if previous == state {
+            return false;
+        }
+        info!("{:?}: Session state {:?} -> {:?}", self.mode, previous, state);
+        self.log(|log| log.log_event(&format!("Session state {:?} -> {:?}", previous, state)));
+        self.observer.on_state_change(state);
+        let _ = self.events.send(EngineEvent::StateChanged { from: previous, to: state, at: self.clock.now_utc() });
+        let logged_on = state.is_logged_on() && !previous.is_logged_on();
+        let logged_out = previous.is_logged_on() && !state.is_logged_on();
+        let event = match state {
+            SessionState::Connected => Some(EngineEvent::Connected { peer: self.peer_addr() }),
+            _ if logged_on => Some(EngineEvent::LoggedOn),
+            _ => None,
+        };
+        if let Some(event) = event {
+            let _ = self.events.send(event);
+        }
+        if let Some(application) = self.application() {
+            if logged_on {
+                application.on_logon(&self.session_id());
+            } else if logged_out {
+                application.on_logout(&self.session_id());
+            }
+        }
+        true
+    }
+
+    // Moves between LoggedOn and AwaitingResend as our ResendRequests are sent and answered
+    fn sync_resend_state(&self) {
+        let resend_requested = self.inner.lock().unwrap().resend_requested;
+        match (self.state(), resend_requested) {
+            (SessionState::LoggedOn, true) => {
+                self.set_state(SessionState::AwaitingResend);
+            }
+            (SessionState::AwaitingResend, false) => {
+                self.set_state(SessionState::LoggedOn);
+            }
+            _ => {}
+        }
+    }
+
+    pub(crate) fn session_id(&self) -> SessionID {
+        let inner = self.inner.lock().unwrap();
+        SessionID {
+            begin_string: self.config.begin_string,
+            sender_comp_id: inner.sender_comp_id.clone(),
+            target_comp_id: inner.target_comp_id.clone(),
+        }
+    }
+
+    pub(crate) fn set_logon_validator(&self, validator: LogonValidator) {
+        *self.logon_validator.lock().unwrap() = Some(validator);
+    }
+
+    pub(crate) fn set_outgoing_interceptor(&self, interceptor: OutgoingInterceptor) {
+        *self.outgoing_interceptor.lock().unwrap() = Some(interceptor);
+    }
+
+    pub(crate) fn set_incoming_interceptor(&self, interceptor: IncomingInterceptor) {
+        *self.incoming_interceptor.lock().unwrap() = Some(interceptor);
+    }
+
+    pub(crate) fn set_application(&self, application: Arc<dyn FixApplication>) {
+        *self.application.lock().unwrap() = Some(application);
+    }
+
+    fn application(&self) -> Option<Arc<dyn FixApplication>> {
+        self.application.lock().unwrap().clone()
+    }
+
+    // Hands an inbound message that passed the session checks to the application
+    fn deliver(&self, message: &FixMessage) {
+        if let Some(interceptor) = self.incoming_interceptor.lock().unwrap().as_mut() {
+            if interceptor(message).is_err() {
+                info!("{:?}: Incoming interceptor kept back {:?}", self.mode, message);
+                let _ = self.events.send(EngineEvent::IncomingDropped { message: message.clone() });
+                return;
+            }
+        }
+        if let Some(application) = self.application() {
+            if is_admin(message) {
+                application.from_admin(message, &self.session_id());
+            } else {
+                application.from_app(message, &self.session_id());
+            }
+        }
+    }
+
+    // The session continues from the store's sequence numbers
+    pub(crate) fn set_message_store(&self, store: Box<dyn MessageStore>) {
+        self.inner.lock().unwrap().store = store;
+    }
+
+    pub(crate) fn set_engine_config(&self, engine_config: EngineConfig) {
+        *self.engine_config.lock().unwrap() = engine_config;
+    }
+
+    pub(crate) fn read_chunk_size(&self) -> usize {
+        self.engine_config.lock().unwrap().read_chunk_size
+    }
+
+    pub(crate) fn set_message_log(&self, mut log: Box<dyn MessageLog>) {
+        log.set_clock(Arc::clone(&self.clock));
+        *self.message_log.lock().unwrap() = Some(log);
+    }
+
+    // The log's lock is never held while taking another, so either thread can log at any point
+    fn log(&self, entry: impl FnOnce(&mut dyn MessageLog)) {
+        if let Some(log) = self.message_log.lock().unwrap().as_mut() {
+            entry(log.as_mut());
+        }
+    }
+
+    // Bytes as a read took them off the transport, before they are framed or decoded
+    pub(crate) fn log_incoming(&self, raw: &[u8]) {
+        self.log(|log| log.log_incoming(raw));
+    }
+
+    pub(crate) fn next_sender_seq_num(&self) -> u64 {
+        self.inner.lock().unwrap().store.next_sender_seq()
+    }
+
+    pub(crate) fn peer_addr(&self) -> Option<SocketAddr> {
+        self.inner.lock().unwrap().peer_addr
+    }
+
+    pub(crate) fn duplicates_suppressed(&self) -> u64 {
+        self.metrics.snapshot().duplicates_dropped
+    }
+
+    pub(crate) fn metrics(&self) -> &SessionMetrics {
+        &self.metrics
+    }
+
+    pub(crate) fn set_metrics_sink(&self, sink: Box<dyn MetricsSink>) {
+        *self.metrics_sink.lock().unwrap() = Some((sink, Instant::now()));
+    }
+
+    // Hands the sink a snapshot once metrics_interval has passed since the last one, or straight away when forced
+    pub(crate) fn publish_metrics(&self, force: bool) {
+        if let Some((sink, published)) = self.metrics_sink.lock().unwrap().as_mut() {
+            if force || published.elapsed() >= self.config.metrics_interval {
+                sink.publish(&self.metrics.snapshot());
+                *published = Instant::now();
+            }
+        }
+    }
+
+    // A previously sent message, as it was first written
+    pub(crate) fn sent_message(&self, seq_num: u64) -> Option<FixMessage> {
+        let raw = self.inner.lock().unwrap().store.get_range(seq_num, seq_num).pop()?;
+        FixMessage::decode(&String::from_utf8_lossy(&raw)).ok()
+    }
+
+    pub(crate) fn reset_sender_seq_num(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Err(e) = inner.store.set_next_sender_seq(1) {
+            error!("{:?}: Error storing sequence numbers: {:?}", self.mode, e);
+        }
+    }
+
+    // Starts both directions again from 1 and forgets the messages kept for resends
+    pub(crate) fn reset_seq_nums(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Err(e) = inner.store.reset() {
+            error!("{:?}: Error resetting message store: {:?}", self.mode, e);
+        }
+        inner.queued.clear();
+        inner.resend_requested = false;
+        inner.processing = None;
+        drop(inner);
+        self.sync_resend_state();
+    }
+
+    // The MsgSeqNum expected from the peer, counting the message being handled as received
+    fn next_target_seq_num(inner: &SessionInner) -> u64 {
+        inner.processing.map_or_else(|| inner.store.next_target_seq(), |seq_num| seq_num + 1)
+    }
+
+    fn set_next_target_seq_num(&self, inner: &mut SessionInner, seq_num: u64) {
+        if let Err(e) = inner.store.set_next_target_seq(seq_num) {
+            error!("{:?}: Error storing sequence numbers: {:?}", self.mode, e);
+        }
+    }
+
+    pub(crate) fn is_running(&self) -> bool {
+        !matches!(self.state(), SessionState::Disconnecting | SessionState::Disconnected)
+    }
+
+    // Whether the receive thread should keep reading. A session stopped while logged on is still read from
+    // until the peer has confirmed the Logout shutdown sends, or logout_timeout after that Logout.
+    pub(crate) fn is_receiving(&self) -> bool {
+        let inner = self.inner.lock().unwrap();
+        match inner.state {
+            SessionState::Disconnected => false,
+            SessionState::Disconnecting => {
+                let now = Instant::now();
+                match (inner.logout_until, inner.flush_until) {
+                    (Some(logout_until), _) => now < logout_until,
+                    // The Logout goes out once the queued messages have been sent
+                    (None, Some(flush_until)) => now < flush_until + self.config.logout_timeout,
+                    (None, None) => false,
+                }
+            }
+            _ => true,
+        }
+    }
+
+    pub(crate) fn set_reconnect(&self, reconnect: bool) {
+        self.inner.lock().unwrap().reconnect = reconnect;
+    }
+
+    pub(crate) fn will_reconnect(&self) -> bool {
+        self.inner.lock().unwrap().reconnect
+    }
+
+    pub(crate) fn logged_on_since_connect(&self) -> bool {
+        self.inner.lock().unwrap().logged_on_since_connect
+    }
+
+    // Asks the engine threads to finish; the transport stays open until close
+    pub(crate) fn stop(&self, drain_timeout: Duration) {
+        self.set_reconnect(false);
+        let state = self.state();
+        if state != SessionState::Disconnected {
+            self.inner.lock().unwrap().flush_until = state.can_send_application().then(|| Instant::now() + drain_timeout);
+            self.set_state(SessionState::Disconnecting);
+        }
+    }
+
+    // When the queued application messages must have gone out by, if they are to be sent at all
+    pub(crate) fn flush_deadline(&self) -> Option<Instant> {
+        let inner = self.inner.lock().unwrap();
+        if inner.state == SessionState::Disconnecting { inner.flush_until } else { None }
+    }
+
+    // Called once the transport is up; the initiator opens the logon handshake straight away.
+    pub(crate) fn on_connected(&self, stream: Box<dyn Transport>) -> std::io::Result<()> {
+        let encrypt_method = self.config.logon.encrypt_method;
+        if encrypt_method != EncryptMethod::None {
+            let text = format!("EncryptMethod {} is not supported", encrypt_method.value());
+            return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, text));
+        }
+        {
+            // Only the sequence numbers carry over from an earlier connection
+            let now = self.clock.now_utc();
+            let mut inner = self.inner.lock().unwrap();
+            inner.last_sent = now;
+            inner.last_received = now;
+            inner.pending_test_request = None;
+            inner.queued.clear();
+            inner.resend_requested = false;
+            inner.logout_sent = false;
+            inner.logout_until = None;
+            inner.logged_on_since_connect = false;
+            inner.peer_addr = stream.peer_addr();
+        }
+        // Writes wait in short steps, so a stalled one still notices a shutdown
+        stream.set_write_timeout(Some(WRITE_INTERVAL))?;
+        *self.writer.lock().unwrap() = Some(stream);
+        self.set_state(SessionState::Connected);
+
+        if self.mode == FixEngineMode::Initiator {
+            if let Err(e) = self.open_scheduled_session() {
+                self.close(DisconnectReason::Error);
+                return Err(std::io::Error::other(e.to_string()));
+            }
+            if self.config.logon.reset_seq_num {
+                self.reset_seq_nums();
+            }
+            self.send(self.logon_message(self.config.logon.heart_bt_int, self.config.logon.reset_seq_num))?;
+            self.set_state(SessionState::LogonSent);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn send(&self, mut message: FixMessage) -> std::io::Result<()> {
+        let is_logout = is_msg_type(&message, MsgType::Logout);
+        let mut receipt = message.take_receipt();
+        // Holding the writer while numbering keeps MsgSeqNum in wire order across both threads
+        let written = self.write(&mut self.writer.lock().unwrap(), message, None);
+        match &written {
+            Ok(Some(sent)) => receipt.complete(Ok(sent.clone())),
+            Ok(None) => receipt.complete(Err(SendFailure::Refused)),
+            Err(e) => receipt.complete(Err(SendFailure::Io(Arc::new(std::io::Error::new(e.kind(), e.to_string()))))),
+        }
+        written?;
+        if is_logout && self.state().is_logged_on() {
+            self.set_state(SessionState::LogoutSent);
+        }
+        Ok(())
+    }
+
+    // Stamps the session header and writes the message. New messages take the next MsgSeqNum and are kept
+    // for resends; a resent message passes the number it was originally sent with. None when the application
+    // or the outgoing interceptor kept the message back.
+    fn write(&self, writer: &mut Option<Box<dyn Transport>>, mut message: FixMessage, resend_seq_num: Option<u64>) -> std::io::Result<Option<SendReceipt>> {
+        let seq_num = {
+            let inner = self.inner.lock().unwrap();
+            let seq_num = resend_seq_num.unwrap_or(inner.store.next_sender_seq());
+            message.header.insert("34".to_string(), seq_num.to_string());
+            message.header.insert("52".to_string(), self.clock.now());
+            message.header.insert("8".to_string(), self.config.begin_string.value());
+            if !inner.sender_comp_id.is_empty() {
+                message.header.insert("49".to_string(), inner.sender_comp_id.clone());
+            }
+            if !inner.target_comp_id.is_empty() {
+                message.header.insert("56".to_string(), inner.target_comp_id.clone());
+            }
+            if let Some(sender_sub_id) = &self.config.sender_sub_id {
+                message.header.insert("50".to_string(), sender_sub_id.clone());
+            }
+            if let Some(target_sub_id) = &self.config.target_sub_id {
+                message.header.insert("57".to_string(), target_sub_id.clone());
+            }
+            seq_num
+        };
+
+        // The application sees new messages once the header is filled in; one it refuses never takes a MsgSeqNum
+        if resend_seq_num.is_none() {
+            if let Some(application) = self.application() {
+                let session_id = self.session_id();
+                if is_admin(&message) {
+                    application.to_admin(&mut message, &session_id);
+                } else if application.to_app(&mut message, &session_id).is_err() {
+                    info!("{:?}: Application refused to send {:?}", self.mode, message);
+                    return Ok(None);
+                }
+            }
+            if let Some(interceptor) = self.outgoing_interceptor.lock().unwrap().as_mut() {
+                if interceptor(&mut message).is_err() {
+                    info!("{:?}: Outgoing interceptor dropped {:?}", self.mode, message);
+                    let _ = self.events.send(EngineEvent::OutgoingDropped { message });
+                    return Ok(None);
+                }
+            }
+            let mut inner = self.inner.lock().unwrap();
+            inner.store.set_next_sender_seq(seq_num + 1)?;
+            if is_msg_type(&message, MsgType::Logout) {
+                inner.logout_sent = true;
+                inner.logout_until = Some(Instant::now() + self.config.logout_timeout);
+            }
+        }
+
+        info!("{:?}: Sending message {:?}", self.mode, message);
+        let encode_started = Instant::now();
+        let encode_options = EncodeOptions { begin_string: self.config.begin_string, header_layout: &self.config.header_layout };
+        let message_str = message.encode_with_options(&self.clock, &encode_options);
+        // Kept before it goes out, so anything the peer may have seen can be resent
+        if resend_seq_num.is_none() {
+            self.inner.lock().unwrap().store.store(seq_num, message_str.as_bytes())?;
+        }
+        let stream = writer.as_mut().ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotConnected))?;
+        let framed = self.config.framing.wrap(message_str.as_bytes());
+        // Only what the transport took is logged as sent
+        if let Err(e) = self.write_all(stream.as_mut(), &framed) {
+            self.log(|log| log.log_event(&format!("Failed to send MsgSeqNum {}: {}", seq_num, e)));
+            return Err(e);
+        }
+        self.log(|log| log.log_outgoing(message_str.as_bytes()));
+        let sent_at = self.clock.now_utc();
+        self.metrics.record_sent(is_admin(&message), framed.len(), encode_started.elapsed());
+        self.inner.lock().unwrap().last_sent = sent_at;
+        self.observer.on_sent(&message);
+        Ok(Some(SendReceipt { seq_num, bytes: message_str.len(), sent_at }))
+    }
+
+    // Like Write::write_all, retrying while the peer is not taking bytes. Gives up once no progress has been
+    // made for the write timeout, or as soon as a shutdown no longer wants the bytes sent.
+    fn write_all(&self, stream: &mut dyn Transport, mut bytes: &[u8]) -> std::io::Result<()> {
+        let write_timeout = self.engine_config.lock().unwrap().write_timeout;
+        let mut stalled_since = None;
+        while !bytes.is_empty() {
+            match stream.write(bytes) {
+                Ok(0) => return Err(std::io::ErrorKind::WriteZero.into()),
+                Ok(written) => {
+                    bytes = &bytes[written..];
+                    stalled_since = None;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock || e.kind() == std::io::ErrorKind::TimedOut => {
+                    let stalled = stalled_since.get_or_insert_with(Instant::now).elapsed();
+                    if stalled >= write_timeout {
+                        return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, format!("Write stalled for {:?}", stalled)));
+                    }
+                    if self.abandons_writes() {
+                        return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "Write stalled while shutting down"));
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    // Shutting down, and past the point where queued messages are still flushed
+    fn abandons_writes(&self) -> bool {
+        let inner = self.inner.lock().unwrap();
+        inner.state == SessionState::Disconnecting && inner.flush_until.is_none_or(|deadline| Instant::now() >= deadline)
+    }
+
+    // Replays application messages in the requested range as possible duplicates. Admin messages and
+    // anything no longer available are skipped over with SequenceReset-GapFill.
+    fn handle_resend_request(&self, request: &FixMessage) {
+        let range = request.get_field(numbers::BEGIN_SEQ_NO).and_then(|value| value.parse::<u64>().ok())
+            .zip(request.get_field(numbers::END_SEQ_NO).and_then(|value| value.parse::<u64>().ok()));
+        let Some((begin_seq_no, end_seq_no)) = range else {
+            warn!("{:?}: Ignoring malformed ResendRequest {:?}", self.mode, request);
+            return;
+        };
+
+        self.metrics.record_resend();
+        self.resend(begin_seq_no, end_seq_no);
+    }
+
+    fn resend(&self, begin_seq_no: u64, end_seq_no: u64) {
+        let mut writer = self.writer.lock().unwrap();
+        let (end_seq_no, stored) = {
+            let inner = self.inner.lock().unwrap();
+            // EndSeqNo 0 means everything sent so far
+            let last_sent = inner.store.next_sender_seq() - 1;
+            let end_seq_no = if end_seq_no == 0 { last_sent } else { end_seq_no.min(last_sent) };
+            let stored: BTreeMap<u64, String> = inner.store.get_range(begin_seq_no, end_seq_no).into_iter()
+                .map(|raw| String::from_utf8_lossy(&raw).into_owned())
+                .filter_map(|raw| Some((raw_field(&raw, "34")?.parse().ok()?, raw)))
+                .collect();
+            (end_seq_no, stored)
+        };
+        info!("{:?}: Resending messages {} to {}", self.mode, begin_seq_no, end_seq_no);
+        if let Err(e) = self.replay(&mut writer, begin_seq_no, end_seq_no, &stored) {
+            error!("{:?}: Error answering ResendRequest: {:?}", self.mode, e);
+        }
+    }
+
+    fn replay(&self, writer: &mut Option<Box<dyn Transport>>, begin_seq_no: u64, end_seq_no: u64, stored: &BTreeMap<u64, String>) -> std::io::Result<()> {
+        let mut gap_start = None;
+        for seq_num in begin_seq_no..=end_seq_no {
+            match stored.get(&seq_num).and_then(|raw| possible_duplicate(raw)) {
+                Some(message) => {
+                    if let Some(start) = gap_start.take() {
+                        self.write(writer, gap_fill_message(seq_num), Some(start))?;
+                    }
+                    self.write(writer, message, Some(seq_num))?;
+                }
+                None => {
+                    gap_start.get_or_insert(seq_num);
+                }
+            }
+        }
+        if let Some(start) = gap_start {
+            self.write(writer, gap_fill_message(end_seq_no + 1), Some(start))?;
+        }
+        Ok(())
+    }
+
+    // Runs the session layer over a decoded message; an error is fatal to the connection.
+    pub(crate) fn handle_incoming(&self, message: FixMessage) -> Result<(), EngineError> {
+        let result = self.receive(message);
+        self.sync_resend_state();
+        result
+    }
+
+    fn receive(&self, message: FixMessage) -> Result<(), EngineError> {
+        self.observer.on_received(&message);
+        self.metrics.record_received(is_admin(&message));
+        self.inner.lock().unwrap().last_received = self.clock.now_utc();
+        // A peer speaking another version is told so with a Logout before the connection is dropped
+        if let Err(e) = validate_begin_string(&message, self.config.begin_string) {
+            if let Err(e) = self.send(logout_message("Incompatible BeginString")) {
+                error!("{:?}: Error sending logout: {:?}", self.mode, e);
+            }
+            return Err(e);
+        }
+
+        // Only a Logout, which is how a peer refuses our logon, may come ahead of the Logon
+        let is_logon = is_msg_type(&message, MsgType::Logon);
+        // Stopped while logged on, the session still takes what the peer sends until it confirms our Logout
+        let logged_on = self.state().is_logged_on() || self.flush_deadline().is_some();
+        if !is_logon && !logged_on {
+            if is_msg_type(&message, MsgType::Logout) {
+                let text = message.get_field(numbers::TEXT).map(str::to_string);
+                let _ = self.events.send(EngineEvent::LoggedOut { text });
+                self.close(DisconnectReason::Logout);
+                return Ok(());
+            }
+            return Err(EngineError::MessageBeforeLogon { msg_type: message.get_field(numbers::MSG_TYPE).map(str::to_string) });
+        }
+
+        // Decoding has already refused a MsgSeqNum that is not a number. Without one at all there is no telling
+        // where the message belongs in the sequence, which ends the session.
+        let Some(seq_num) = message.get_field(numbers::MSG_SEQ_NUM).and_then(|value| value.parse::<u64>().ok()) else {
+            if let Err(e) = self.send(logout_message("MsgSeqNum(34) missing")) {
+                error!("{:?}: Error sending logout: {:?}", self.mode, e);
+            }
+            return Err(EngineError::MsgSeqNumMissing { msg_type: message.get_field(numbers::MSG_TYPE).map(str::to_string) });
+        };
+        // A logon outside the schedule is refused; inside it, one opening a new session starts from 1
+        if is_logon && self.mode == FixEngineMode::Acceptor && self.state() == SessionState::Connected {
+            if let Err(e) = self.open_scheduled_session() {
+                if let Err(e) = self.send(logout_message(&e.to_string())) {
+                    error!("{:?}: Error sending logout: {:?}", self.mode, e);
+                }
+                return Err(e);
+            }
+        }
+
+        let (expected, sender_comp_id, target_comp_id) = {
+            let inner = self.inner.lock().unwrap();
+            (inner.store.next_target_seq(), inner.sender_comp_id.clone(), inner.target_comp_id.clone())
+        };
+
+        // A message from the wrong counterparty is rejected and ends the session; so is a second Logon from one
+        if !is_logon || logged_on {
+            if let Err(e) = validate_comp_ids(&message, &sender_comp_id, &target_comp_id, &self.config) {
+                let ref_tag_id = match &e {
+                    EngineError::CompIDMismatch { tag, .. } => Some(*tag),
+                    _ => None,
+                };
+                let raw = raw_text(&message);
+                let rejection = Rejection { reason: SessionRejectReason::CompIDProblem, ref_tag_id, text: e.to_string() };
+                self.reject(seq_num, message.header.get("35").map(String::as_str), raw, rejection);
+                if let Err(e) = self.send(logout_message("CompID problem")) {
+                    error!("{:?}: Error sending logout: {:?}", self.mode, e);
+                }
+                return Err(e);
+            }
+        }
+
+        // A logon asking for a reset is not held to the old sequence numbers. handle_logon only resets them once
+        // the logon has passed its checks, so a refused one leaves a live session's numbers alone.
+        let in_session_reset = logged_on && self.config.accept_in_session_reset;
+        let reset_allowed = in_session_reset || (self.mode == FixEngineMode::Acceptor && self.state() == SessionState::Connected);
+        if is_logon && is_reset_requested(&message) && reset_allowed {
+            self.handle_logon(&message)?;
+            self.set_next_target_seq_num(&mut self.inner.lock().unwrap(), seq_num + 1);
+            return Ok(());
+        }
+
+        // A hard SequenceReset applies whatever its own MsgSeqNum is
+        if is_msg_type(&message, MsgType::SequenceReset) && message.get_field(numbers::GAP_FILL_FLAG) != Some("Y") {
+            self.deliver(&message);
+            self.handle_sequence_reset(&message, seq_num);
+            return Ok(());
+        }
+
+        if seq_num < expected {
+            if is_possible_duplicate(&message) {
+                self.suppress_duplicate(message, seq_num);
+                return Ok(());
+            }
+            let text = format!("MsgSeqNum too low, expecting {} but received {}", expected, seq_num);
+            if let Err(e) = self.send(logout_message(&text)) {
+                error!("{:?}: Error sending logout: {:?}", self.mode, e);
+            }
+            return Err(EngineError::MsgSeqNumTooLow { expected, received: seq_num });
+        }
+
+        if seq_num > expected {
+            // A peer that sent NextExpectedMsgSeqNum on its logon resends the gap without being asked
+            let peer_resends = is_logon && self.uses_next_expected(&message);
+            // Hold the message back until the gap has been resent; a logon still completes the handshake
+            let queued = if is_logon {
+                self.handle_logon(&message)?;
+                None
+            } else if is_msg_type(&message, MsgType::ResendRequest) {
+                // Answered straight away, otherwise both sides could end up waiting on each other
+                self.handle_resend_request(&message);
+                None
+            } else if is_possible_duplicate(&message) && self.inner.lock().unwrap().queued.contains_key(&seq_num) {
+                // Already waiting for the gap to close; the copy held back is the one delivered
+                self.suppress_duplicate(message, seq_num);
+                return Ok(());
+            } else {
+                Some(message)
+            };
+            // Only the numbers between this message and the last one already held are newly missing
+            let missing = {
+                let mut inner = self.inner.lock().unwrap();
+                let last_held = inner.queued.keys().next_back().copied().unwrap_or(expected - 1);
+                inner.queued.insert(seq_num, queued);
+                let first_gap = !std::mem::replace(&mut inner.resend_requested, true);
+                let missing = last_held + 1..seq_num;
+                if missing.is_empty() || (first_gap && peer_resends) { None } else { Some(missing) }
+            };
+            if let Some(missing) = missing {
+                warn!("{:?}: MsgSeqNum gap, expecting {} but received {}", self.mode, missing.start, seq_num);
+                let _ = self.events.send(EngineEvent::SequenceGap { expected: missing.start, received: seq_num });
+                if self.config.resend_on_gap {
+                    self.request_resend(missing.start, missing.end - 1);
+                }
+            }
+            return Ok(());
+        }
+
+        self.process_in_order(Some(message), seq_num)
+    }
+
+    // Asks the peer to send begin_seq_no to end_seq_no again; an end of 0 asks for everything from begin_seq_no on
+    pub(crate) fn request_resend(&self, begin_seq_no: u64, end_seq_no: u64) {
+        if let Err(e) = self.send(resend_request_message(begin_seq_no, end_seq_no)) {
+            error!("{:?}: Error sending ResendRequest: {:?}", self.mode, e);
+        }
+    }
+
+    // A resent message we already have is dropped unseen by the application, unless its OrigSendingTime shows it
+    // was tampered with, which is rejected
+    fn suppress_duplicate(&self, message: FixMessage, seq_num: u64) {
+        if let Err(rejection) = validate_orig_sending_time(&message) {
+            let raw = raw_text(&message);
+            self.reject(seq_num, message.header.get("35").map(String::as_str), raw, rejection);
+            return;
+        }
+        info!("{:?}: Ignoring possible duplicate with MsgSeqNum {}", self.mode, seq_num);
+        self.metrics.record_duplicate();
+    }
+
+    // Processes the expected message, then anything queued behind it that is now in sequence. None stands for
+    // a message that has already been dealt with and only moves the expected number on.
+    fn process_in_order(&self, message: Option<FixMessage>, seq_num: u64) -> Result<(), EngineError> {
+        let mut next = message;
+        let mut seq_num = seq_num;
+        loop {
+            // Counted as received while it is handled, but the store is only updated once it is done
+            self.inner.lock().unwrap().processing = Some(seq_num);
+            let new_seq_no = match next {
+                Some(message) => self.process(message, seq_num),
+                None => Ok(None),
+            };
+            let mut inner = self.inner.lock().unwrap();
+            // A reset made once the application has seen the message sticks
+            let reset = inner.processing.take().is_none();
+            match new_seq_no {
+                Ok(Some(new_seq_no)) => self.set_next_target_seq_num(&mut inner, new_seq_no),
+                Ok(None) if !reset => self.set_next_target_seq_num(&mut inner, seq_num + 1),
+                Ok(None) => {}
+                // A message that ends the session is not taken as received
+                Err(e) => return Err(e),
+            }
+            seq_num = inner.store.next_target_seq();
+            // A GapFill may jump past messages that were queued
+            inner.queued = inner.queued.split_off(&seq_num);
+            match inner.queued.remove(&seq_num) {
+                Some(queued) => next = queued,
+                None => {
+                    if inner.queued.is_empty() {
+                        inner.resend_requested = false;
+                    }
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    // A message that failed to decode is rejected as IncorrectDataFormat when its MsgSeqNum can still be read and
+    // is the expected one, so the sequence numbers stay in step. Anything else is dropped and left to gap detection.
+    pub(crate) fn handle_garbled(&self, raw: &str, error: DecodeError) -> Result<(), EngineError> {
+        self.metrics.record_decode_error();
+        let _ = self.events.send(EngineEvent::DecodeFailed { raw: raw.to_string(), error: error.clone() });
+        if let Some(application) = self.application() {
+            application.on_decode_error(raw, &error, &self.session_id());
+        }
+        let seq_num = raw_field(raw, "34").and_then(|value| value.parse::<u64>().ok());
+        let expected = self.inner.lock().unwrap().store.next_target_seq();
+        if !self.state().is_logged_on() || seq_num != Some(expected) {
+            warn!("{:?}: Discarding undecodable message ({}) {:?}", self.mode, error, raw);
+            return Ok(());
+        }
+
+        let reason = match error {
+            DecodeError::EmptyValue { .. } => SessionRejectReason::TagSpecifiedWithoutValue,
+            _ => SessionRejectReason::IncorrectDataFormat,
+        };
+        let rejection = Rejection { reason, ref_tag_id: error.ref_tag_id(), text: error.to_string() };
+        self.reject(expected, raw_field(raw, "35"), raw.to_string(), rejection);
+        let result = self.process_in_order(None, expected);
+        self.sync_resend_state();
+        result
+    }
+
+    // Handles an in-sequence message, returning the next expected inbound MsgSeqNum when it is not simply the following one
+    fn process(&self, message: FixMessage, seq_num: u64) -> Result<Option<u64>, EngineError> {
+        if is_msg_type(&message, MsgType::Logon) {
+            return self.handle_logon(&message).map(|_| None);
+        }
+
+        // A rejected message still uses up its MsgSeqNum
+        if let Err(rejection) = validate_message(&message) {
+            let raw = raw_text(&message);
+            self.reject(seq_num, message.header.get("35").map(String::as_str), raw, rejection);
+            return Ok(None);
+        }
+
+        // A SendingTime too far from our clock may be a replay, so the session is ended
+        if let Err(e) = self.check_sending_time(&message) {
+            let raw = raw_text(&message);
+            let rejection = Rejection { reason: SessionRejectReason::SendingTimeAccuracyProblem, ref_tag_id: Some(numbers::SENDING_TIME), text: e.to_string() };
+            self.reject(seq_num, message.header.get("35").map(String::as_str), raw, rejection);
+            if let Err(e) = self.send(logout_message("SendingTime accuracy problem")) {
+                error!("{:?}: Error sending logout: {:?}", self.mode, e);
+            }
+            return Err(e);
+        }
+
+        if let Some(msg_type) = message.msg_type_enum().filter(|msg_type| self.config.reject_unsupported_msg_types.contains(msg_type)) {
+            warn!("{:?}: Rejecting unsupported MsgType {} with MsgSeqNum {}", self.mode, msg_type.value(), seq_num);
+            let text = format!("Unsupported MsgType {}", msg_type.value());
+            if let Err(e) = self.send(FixMessage::business_reject(&message, BusinessRejectReason::UnsupportedMessageType, &text)) {
+                error!("{:?}: Error sending BusinessMessageReject: {:?}", self.mode, e);
+            }
+            return Ok(None);
+        }
+        self.deliver(&message);
+
+        if is_msg_type(&message, MsgType::SequenceReset) {
+            return Ok(self.handle_gap_fill(&message, seq_num));
+        }
+
+        if is_msg_type(&message, MsgType::ResendRequest) {
+            self.handle_resend_request(&message);
+            return Ok(None);
+        }
+
+        if is_msg_type(&message, MsgType::Logout) {
+            self.handle_logout(&message);
+            return Ok(None);
+        }
+
+        // Heartbeats and TestRequests are answered here; the application only gets to see them
+        if is_msg_type(&message, MsgType::Heartbeat) {
+            let mut inner = self.inner.lock().unwrap();
+            if inner.pending_test_request.as_ref().map(|(id, _)| id.as_str()) == message.get_field(numbers::TEST_REQ_ID) {
+                inner.pending_test_request = None;
+            }
+            return Ok(None);
+        }
+        if is_msg_type(&message, MsgType::TestRequest) {
+            if let Err(e) = self.send(heartbeat_message(message.get_field(numbers::TEST_REQ_ID))) {
+                error!("{:?}: Error answering TestRequest: {:?}", self.mode, e);
+            }
+            return Ok(None);
+        }
+
+        Ok(None)
+    }
+
+    // A GapFill moves the expected inbound number forward to NewSeqNo(36); it can never move it back
+    fn handle_gap_fill(&self, gap_fill: &FixMessage, seq_num: u64) -> Option<u64> {
+        match gap_fill.get_field(numbers::NEW_SEQ_NO).and_then(|value| value.parse::<u64>().ok()) {
+            Some(new_seq_no) if new_seq_no > seq_num => Some(new_seq_no),
+            new_seq_no => {
+                let text = format!("GapFill NewSeqNo {:?} must be greater than MsgSeqNum {}", new_seq_no, seq_num);
+                let raw = raw_text(gap_fill);
+                let rejection = Rejection { reason: SessionRejectReason::ValueIsIncorrect, ref_tag_id: Some(numbers::NEW_SEQ_NO), text };
+                self.reject(seq_num, Some(&MsgType::SequenceReset.value()), raw, rejection);
+                None
+            }
+        }
+    }
+
+    fn check_sending_time(&self, message: &FixMessage) -> Result<(), EngineError> {
+        let sending_time = message.get_field(numbers::SENDING_TIME).unwrap_or_default();
+        let Ok(parsed) = NaiveDateTime::parse_from_str(sending_time, TIMESTAMP_FORMAT) else {
+            return Ok(()); // Already rejected as IncorrectDataFormat by validate_message
+        };
+        let skew = (self.clock.now_utc() - parsed.and_utc()).abs();
+        if skew.to_std().map_or(true, |skew| skew > self.config.max_clock_skew) {
+            return Err(EngineError::SendingTimeAccuracy { sending_time: sending_time.to_string() });
+        }
+        Ok(())
+    }
+
+    // Answers with a session-level Reject and reports the refused message to the application
+    fn reject(&self, ref_seq_num: u64, ref_msg_type: Option<&str>, raw: String, rejection: Rejection) {
+        warn!("{:?}: Rejecting MsgSeqNum {}: {}", self.mode, ref_seq_num, rejection.text);
+        // Answering a malformed Reject with another could bounce Rejects between the two sides without end
+        if ref_msg_type == Some(MsgType::Reject.value().as_str()) {
+            warn!("{:?}: Not answering the malformed Reject {} with a Reject", self.mode, ref_seq_num);
+        } else if let Err(e) = self.send(reject_message(ref_seq_num, ref_msg_type, &rejection)) {
+            error!("{:?}: Error sending reject: {:?}", self.mode, e);
+        }
+        let _ = self.events.send(EngineEvent::MessageRejected { raw, reason: rejection.reason, text: rejection.text });
+    }
+
+    // Hands an application message the session could not hold back to the application as an event
+    pub(crate) fn refuse_outgoing(&self, mut message: FixMessage) {
+        warn!("{:?}: Not connected, refusing to send {:?}", self.mode, message);
+        message.take_receipt().complete(Err(SendFailure::NotConnected));
+        let _ = self.events.send(EngineEvent::OutgoingRejected { message });
+    }
+
+    pub(crate) fn throttle_outgoing(&self, mut message: FixMessage) {
+        warn!("{:?}: Throttled, refusing to send {:?}", self.mode, message);
+        message.take_receipt().complete(Err(SendFailure::Throttled));
+        let _ = self.events.send(EngineEvent::OutgoingThrottled { message });
+    }
+
+    // Starts a logout of our own; the session ends when the peer's confirmation arrives
+    pub(crate) fn logout(&self, text: &str) {
+        if let Err(e) = self.send(logout_message(text)) {
+            error!("{:?}: Error sending logout: {:?}", self.mode, e);
+        }
+    }
+
+    // A Logout we did not start is confirmed with one of our own; either way the session ends here
+    fn handle_logout(&self, logout: &FixMessage) {
+        let text = logout.get_field(numbers::TEXT).map(str::to_string);
+        info!("{:?}: Logout received ({:?})", self.mode, text);
+        let _ = self.events.send(EngineEvent::LoggedOut { text });
+        if !self.inner.lock().unwrap().logout_sent {
+            if let Err(e) = self.send(logout_message("")) {
+                error!("{:?}: Error confirming logout: {:?}", self.mode, e);
+            }
+        }
+        self.close(DisconnectReason::Logout);
+    }
+
+    // A hard reset sets the expected inbound number outright, even backwards
+    fn handle_sequence_reset(&self, reset: &FixMessage, seq_num: u64) {
+        let Some(new_seq_no) = reset.get_field(numbers::NEW_SEQ_NO).and_then(|value| value.parse::<u64>().ok()) else {
+            warn!("{:?}: Ignoring SequenceReset without a valid NewSeqNo {:?}", self.mode, reset);
+            return;
+        };
+        let mut inner = self.inner.lock().unwrap();
+        warn!("{:?}: SequenceReset (MsgSeqNum {}) moves the expected MsgSeqNum from {} to {}", self.mode, seq_num, inner.store.next_target_seq(), new_seq_no);
+        self.set_next_target_seq_num(&mut inner, new_seq_no);
+        inner.queued = inner.queued.split_off(&new_seq_no);
+        if inner.queued.is_empty() {
+            inner.resend_requested = false;
+        }
+    }
+
+    fn handle_logon(&self, logon: &FixMessage) -> Result<(), EngineError> {
+        match (self.mode, self.state()) {
+            (FixEngineMode::Acceptor, SessionState::Connected) => {
+                validate_comp_ids(logon, &self.config.sender_comp_id, &self.config.target_comp_id, &self.config)?;
+                let heart_bt_int = {
+                    let mut inner = self.inner.lock().unwrap();
+                    // Address the reply back to whoever logged on
+                    inner.sender_comp_id = logon.header.get("56").cloned().unwrap_or_default();
+                    inner.target_comp_id = logon.header.get("49").cloned().unwrap_or_default();
+                    inner.heart_bt_int = logon.get_field(numbers::HEART_BT_INT)
+                        .and_then(|value| value.parse().ok())
+                        .unwrap_or(self.config.logon.heart_bt_int);
+                    inner.heart_bt_int
+                };
+                if let Err(LogoutReason(reason)) = self.validate_logon(logon) {
+                    if let Err(e) = self.send(logout_message(&reason)) {
+                        error!("{:?}: Error sending logout: {:?}", self.mode, e);
+                    }
+                    return Err(EngineError::AuthenticationFailed { username: logon.get_field(numbers::USERNAME).map(str::to_string), reason });
+                }
+                if let Err(reason) = self.check_heart_bt_int(heart_bt_int) {
+                    if let Err(e) = self.send(logout_message(&reason)) {
+                        error!("{:?}: Error sending logout: {:?}", self.mode, e);
+                    }
+                    return Err(EngineError::LogonRejected { reason });
+                }
+                if is_reset_requested(logon) {
+                    info!("{:?}: Logon with ResetSeqNumFlag, resetting sequence numbers", self.mode);
+                    self.reset_seq_nums();
+                }
+                // Our reply takes the next number, so that is what the peer should be expecting
+                let reply_seq_num = self.inner.lock().unwrap().store.next_sender_seq();
+                let next_expected = self.check_next_expected(logon, reply_seq_num)?;
+                self.deliver(logon);
+                if let Err(e) = self.send(self.logon_message(heart_bt_int, is_reset_requested(logon))) {
+                    error!("{:?}: Error sending logon response: {:?}", self.mode, e);
+                }
+                self.set_state(SessionState::LoggedOn);
+                if let Some(next_expected) = next_expected.filter(|next_expected| *next_expected < reply_seq_num) {
+                    self.resend(next_expected, reply_seq_num - 1);
+                }
+            }
+            (FixEngineMode::Initiator, SessionState::LogonSent) => {
+                validate_comp_ids(logon, &self.config.sender_comp_id, &self.config.target_comp_id, &self.config)?;
+                // The peer has had our logon, which took the number before the next one
+                let next_sender_seq_num = self.inner.lock().unwrap().store.next_sender_seq();
+                let next_expected = self.check_next_expected(logon, next_sender_seq_num)?;
+                // The acceptor should echo our HeartBtInt; our timers keep using it either way
+                let received = logon.get_field(numbers::HEART_BT_INT).and_then(|value| value.parse::<u64>().ok());
+                let proposed = self.config.logon.heart_bt_int;
+                if received != Some(proposed) {
+                    warn!("{:?}: Proposed HeartBtInt {} but the acceptor answered with {:?}", self.mode, proposed, received);
+                    let _ = self.events.send(EngineEvent::HeartBtIntMismatch { proposed, received });
+                }
+                self.deliver(logon);
+                self.set_state(SessionState::LoggedOn);
+                // Our logon itself has already been taken in by the peer, so it is not part of the resend
+                if let Some(next_expected) = next_expected.filter(|next_expected| *next_expected < next_sender_seq_num - 1) {
+                    self.resend(next_expected, next_sender_seq_num - 2);
+                }
+            }
+            (_, state) if state.is_logged_on() => self.handle_logon_while_logged_on(logon),
+            (_, state) => warn!("{:?}: Ignoring unexpected logon in state {:?}", self.mode, state),
+        }
+        Ok(())
+    }
+
+    // A reset Logon from the counterparty already validated as logged on restarts both directions from 1;
+    // anything else is refused
+    fn handle_logon_while_logged_on(&self, logon: &FixMessage) {
+        let seq_num = logon.get_field(numbers::MSG_SEQ_NUM).and_then(|value| value.parse().ok()).unwrap_or_default();
+        if self.config.accept_in_session_reset && is_reset_requested(logon) {
+            info!("{:?}: In-session reset requested, sequence numbers start again from 1", self.mode);
+            self.reset_seq_nums();
+            self.deliver(logon);
+            let heart_bt_int = self.inner.lock().unwrap().heart_bt_int;
+            if let Err(e) = self.send(self.logon_message(heart_bt_int, true)) {
+                error!("{:?}: Error answering in-session reset: {:?}", self.mode, e);
+            }
+            return;
+        }
+        let rejection = Rejection { reason: SessionRejectReason::Other, ref_tag_id: None, text: "Logon received while already logged on".to_string() };
+        self.reject(seq_num, Some(&MsgType::Logon.value()), raw_text(logon), rejection);
+    }
+
+    // The acceptor only takes a HeartBtInt(108) within its configured bounds
+    fn check_heart_bt_int(&self, heart_bt_int: u64) -> Result<(), String> {
+        if self.config.min_heart_bt_int.is_some_and(|min| heart_bt_int < min) || self.config.max_heart_bt_int.is_some_and(|max| heart_bt_int > max) {
+            let (min, max) = (self.config.min_heart_bt_int.unwrap_or(0), self.config.max_heart_bt_int.unwrap_or(u64::MAX));
+            return Err(format!("HeartBtInt {} outside the allowed range {} to {}", heart_bt_int, min, max));
+        }
+        Ok(())
+    }
+
+    fn uses_next_expected(&self, logon: &FixMessage) -> bool {
+        self.config.send_next_expected_msg_seq_num && logon.get_field(numbers::NEXT_EXPECTED_MSG_SEQ_NUM).is_some()
+    }
+
+    // Reads the peer's NextExpectedMsgSeqNum(789) when both sides use it. A peer expecting more than we have
+    // sent cannot be brought back in step by resending, so the session is ended.
+    fn check_next_expected(&self, logon: &FixMessage, in_sync: u64) -> Result<Option<u64>, EngineError> {
+        if !self.uses_next_expected(logon) {
+            return Ok(None);
+        }
+        let Some(next_expected) = logon.get_field(numbers::NEXT_EXPECTED_MSG_SEQ_NUM).and_then(|value| value.parse::<u64>().ok()) else {
+            warn!("{:?}: Ignoring malformed NextExpectedMsgSeqNum on {:?}", self.mode, logon);
+            return Ok(None);
+        };
+        if next_expected > in_sync {
+            let text = format!("NextExpectedMsgSeqNum too high, expecting at most {} but received {}", in_sync, next_expected);
+            if let Err(e) = self.send(logout_message(&text)) {
+                error!("{:?}: Error sending logout: {:?}", self.mode, e);
+            }
+            return Err(EngineError::NextExpectedMsgSeqNumTooHigh { expected: in_sync, received: next_expected });
+        }
+        Ok(Some(next_expected))
+    }
+
+    // Sends a Heartbeat after HeartBtInt of outbound silence and a TestRequest after HeartBtInt plus 20% of
+    // inbound silence. An unanswered TestRequest fails the session once another HeartBtInt has passed.
+    pub(crate) fn check_timers(&self) -> Result<(), EngineError> {
+        if !self.state().is_logged_on() {
+            return Ok(());
+        }
+
+        let now = self.clock.now_utc();
+        if self.is_session_closed(now) {
+            self.close_scheduled_session();
+            return Ok(());
+        }
+
+        let message = {
+            let mut inner = self.inner.lock().unwrap();
+            if inner.heart_bt_int == 0 {
+                return Ok(());
+            }
+            let interval = TimeDelta::seconds(inner.heart_bt_int as i64);
+
+            if let Some((test_req_id, sent_at)) = &inner.pending_test_request {
+                if now - *sent_at >= interval {
+                    return Err(EngineError::TestRequestTimeout { test_req_id: test_req_id.clone() });
+                }
+                None
+            } else if now - inner.last_received >= interval + interval / 5 {
+                inner.test_request_count += 1;
+                let test_req_id = format!("TEST{}", inner.test_request_count);
+                inner.pending_test_request = Some((test_req_id.clone(), now));
+                Some(test_request_message(&test_req_id))
+            } else if now - inner.last_sent >= interval {
+                Some(heartbeat_message(None))
+            } else {
+                None
+            }
+        };
+
+        if let Some(message) = message {
+            if let Err(e) = self.send(message) {
+                error!("{:?}: Error sending heartbeat: {:?}", self.mode, e);
+            }
+        }
+        Ok(())
+    }
+
+    // For the receive thread while it waits on the application to take a message: keeps our side of the
+    // heartbeat going, though not the TestRequest, as the peer's messages are only waiting to be read.
+    // False once the session is stopping and the wait should be given up.
+    pub(crate) fn keep_alive(&self) -> bool {
+        if !self.is_running() {
+            return false;
+        }
+        if self.state().is_logged_on() {
+            let due = {
+                let inner = self.inner.lock().unwrap();
+                inner.heart_bt_int > 0 && self.clock.now_utc() - inner.last_sent >= TimeDelta::seconds(inner.heart_bt_int as i64)
+            };
+            if due {
+                if let Err(e) = self.send(heartbeat_message(None)) {
+                    error!("{:?}: Error sending heartbeat: {:?}", self.mode, e);
+                }
+            }
+        }
+        true
+    }
+
+    // Called before a logon is made or accepted. Sequence numbers left from an earlier session, including one
+    // the store was created in before a restart, are reset so the new session starts from 1.
+    fn open_scheduled_session(&self) -> Result<(), EngineError> {
+        let Some(schedule) = &self.config.schedule else {
+            return Ok(());
+        };
+        let Some(session_start) = schedule.session_start(self.clock.now_utc()) else {
+            return Err(EngineError::OutsideSessionTime);
+        };
+        let stale = {
+            let mut inner = self.inner.lock().unwrap();
+            let stale = match inner.session_start {
+                Some(previous) => previous != session_start,
+                None => inner.store.creation_time() < session_start,
+            };
+            inner.session_start = Some(session_start);
+            stale
+        };
+        if stale {
+            info!("{:?}: New session opened at {}, resetting sequence numbers", self.mode, session_start);
+            self.reset_seq_nums();
+        }
+        Ok(())
+    }
+
+    // The scheduled session we logged on in is over, whether or not the next one has already opened
+    fn is_session_closed(&self, now: DateTime<Utc>) -> bool {
+        let Some(schedule) = &self.config.schedule else {
+            return false;
+        };
+        schedule.session_start(now) != self.inner.lock().unwrap().session_start
+    }
+
+    fn close_scheduled_session(&self) {
+        info!("{:?}: End of session, logging out", self.mode);
+        if let Err(e) = self.send(logout_message("End of session")) {
+            error!("{:?}: Error sending logout: {:?}", self.mode, e);
+        }
+        self.reset_seq_nums();
+        self.close(DisconnectReason::EndOfSession);
+    }
+
+    fn validate_logon(&self, logon: &FixMessage) -> Result<(), LogoutReason> {
+        match self.logon_validator.lock().unwrap().as_ref() {
+            Some(validator) => validator(&self.session_id(), logon),
+            None => Ok(()),
+        }
+    }
+
+    fn logon_message(&self, heart_bt_int: u64, reset_seq_num: bool) -> FixMessage {
+        let mut logon = FixMessage::new();
+        logon.set_field(numbers::MSG_TYPE, &MsgType::Logon.value());
+        logon.set_field(numbers::ENCRYPT_METHOD, &self.config.logon.encrypt_method.value());
+        logon.set_field(numbers::HEART_BT_INT, &heart_bt_int.to_string());
+        if let Some(default_appl_ver_id) = &self.config.default_appl_ver_id {
+            logon.set_field(numbers::DEFAULT_APPL_VER_ID, default_appl_ver_id);
+        }
+        if reset_seq_num {
+            logon.set_field(numbers::RESET_SEQ_NUM_FLAG, &ResetSeqNumFlag::Yes.value());
+        }
+        if self.config.send_next_expected_msg_seq_num {
+            logon.set_field(numbers::NEXT_EXPECTED_MSG_SEQ_NUM, &Self::next_target_seq_num(&self.inner.lock().unwrap()).to_string());
+        }
+        if self.mode == FixEngineMode::Initiator {
+            let credentials = [
+                (numbers::USERNAME, &self.config.username),
+                (numbers::PASSWORD, &self.config.password),
+                (numbers::NEW_PASSWORD, &self.config.new_password),
+            ];
+            for (tag, value) in credentials {
+                if let Some(value) = value {
+                    logon.set_field(tag, value);
+                }
+            }
+        }
+        logon
+    }
+
+    // Tears the connection down after a session-level failure.
+    pub(crate) fn disconnect(&self, error: EngineError) {
+        self.report_error(error);
+        self.close(DisconnectReason::Error);
+    }
+
+    // For a session whose connection never arrived or could not be set up. One that never had a connection has
+    // nothing to close, so its Disconnected is sent here; any other was closed, and reported, as it failed.
+    pub(crate) fn start_failed(&self, error: EngineError) {
+        self.report_error(error);
+        if self.peer_addr().is_none() {
+            let _ = self.events.send(EngineEvent::Disconnected { reason: DisconnectReason::Error });
+        }
+    }
+
+    fn report_error(&self, error: EngineError) {
+        error!("{:?}: {}", self.mode, error);
+        self.log(|log| log.log_event(&error.to_string()));
+        self.observer.on_error(&error);
+        let _ = self.events.send(EngineEvent::Error(error));
+    }
+
+    // The transport failed under us, so the connection is no use any more
+    pub(crate) fn disconnect_io(&self, error: std::io::Error) {
+        error!("{:?}: {:?}", self.mode, error);
+        self.log(|log| log.log_event(&format!("I/O error: {}", error)));
+        let _ = self.events.send(EngineEvent::IoError(Arc::new(error)));
+        self.close(DisconnectReason::Io);
+    }
+
+    pub(crate) fn close(&self, reason: DisconnectReason) {
+        if self.state() == SessionState::Disconnected {
+            return;
+        }
+        // Only a lost transport is re-established; a Logout, the end of the session or an error ends it for good
+        if !matches!(reason, DisconnectReason::PeerClosed | DisconnectReason::Io) {
+            self.set_reconnect(false);
+        }
+        self.set_state(SessionState::Disconnecting);
+        if let Some(stream) = self.writer.lock().unwrap().as_ref() {
+            let _ = stream.shutdown();
+        }
+        // Only the caller that makes the change reports it, should two threads close at once
+        if self.set_state(SessionState::Disconnected) {
+            let _ = self.events.send(EngineEvent::Disconnected { reason });
+        }
+    }
+
+    pub(crate) fn shut_down(&self) {
+        self.publish_metrics(true);
+        self.log(|log| {
+            if let Err(e) = log.flush() {
+                error!("Failed flushing the message log: {:?}", e);
+            }
+        });
+        let _ = self.events.send(EngineEvent::ShutDown);
+    }
+}
+
+fn heartbeat_message(test_req_id: Option<&str>) -> FixMessage {
+    let mut heartbeat = FixMessage::new();
+    heartbeat.set_field(numbers::MSG_TYPE, &MsgType::Heartbeat.value());
+    if let Some(test_req_id) = test_req_id {
+        heartbeat.set_field(numbers::TEST_REQ_ID, test_req_id);
+    }
+    heartbeat
+}
+
+fn test_request_message(test_req_id: &str) -> FixMessage {
+    let mut test_request = FixMessage::new();
+    test_request.set_field(numbers::MSG_TYPE, &MsgType::TestRequest.value());
+    test_request.set_field(numbers::TEST_REQ_ID, test_req_id);
+    test_request
+}
+
+// Admin messages that are never resent; a GapFill covers them instead
+const GAP_FILLED_MSG_TYPES: [&str; 6] = ["0", "1", "2", "4", "5", "A"];
+
+// Prepares a stored message for replay: PossDupFlag(43)=Y and OrigSendingTime(122) set to its original
+// SendingTime(52), which encode then refreshes. Returns None for messages that should be gap filled.
+fn possible_duplicate(raw: &str) -> Option<FixMessage> {
+    let mut message = FixMessage::decode(raw).ok()?;
+    if GAP_FILLED_MSG_TYPES.contains(&message.header.get("35")?.as_str()) {
+        return None;
+    }
+    if let Some(orig_sending_time) = message.header.remove("52") {
+        message.header.insert("122".to_string(), orig_sending_time);
+    }
+    message.header.insert("43".to_string(), "Y".to_string());
+    Some(message)
+}
+
+fn gap_fill_message(new_seq_no: u64) -> FixMessage {
+    let mut gap_fill = FixMessage::new();
+    gap_fill.set_field(numbers::MSG_TYPE, &MsgType::SequenceReset.value());
+    gap_fill.header.insert("43".to_string(), "Y".to_string());
+    gap_fill.set_field(numbers::GAP_FILL_FLAG, "Y");
+    gap_fill.set_field(numbers::NEW_SEQ_NO, &new_seq_no.to_string());
+    gap_fill
+}
+
+fn resend_request_message(begin_seq_no: u64, end_seq_no: u64) -> FixMessage {
+    let mut resend_request = FixMessage::new();
+    resend_request.set_field(numbers::MSG_TYPE, &MsgType::ResendRequest.value());
+    resend_request.set_field(numbers::BEGIN_SEQ_NO, &begin_seq_no.to_string());
+    resend_request.set_field(numbers::END_SEQ_NO, &end_seq_no.to_string());
+    resend_request
+}
+
+fn logout_message(text: &str) -> FixMessage {
+    FixMessage::logout(Some(text))
+}
+
+fn reject_message(ref_seq_num: u64, ref_msg_type: Option<&str>, rejection: &Rejection) -> FixMessage {
+    // Only what the Reject refers back to, since the refused message may not have decoded at all
+    let mut ref_msg = FixMessage::new();
+    ref_msg.set_field(numbers::MSG_SEQ_NUM, &ref_seq_num.to_string());
+    if let Some(ref_msg_type) = ref_msg_type {
+        ref_msg.set_field(numbers::MSG_TYPE, ref_msg_type);
+    }
+    FixMessage::reject(&ref_msg, rejection.reason, rejection.ref_tag_id, Some(&rejection.text))
+}
+
+// Header fields every message after the logon must carry. BeginString and BodyLength are checked when decoding,
+// and a missing MsgSeqNum ends the session before this.
+const REQUIRED_TAGS: [u32; 4] = [numbers::MSG_TYPE, numbers::SENDER_COMP_ID, numbers::TARGET_COMP_ID, numbers::SENDING_TIME];
+
+fn validate_message(message: &FixMessage) -> Result<(), Rejection> {
+    for tag in REQUIRED_TAGS {
+        if message.get_field(tag).is_none() {
+            return Err(Rejection { reason: SessionRejectReason::RequiredTagMissing, ref_tag_id: Some(tag), text: format!("Required tag {} missing", tag) });
+        }
+    }
+
+    // Types the engine has no name for, e.g. venue-specific ones, are the application's to deal with
+    let msg_type = message.get_field(numbers::MSG_TYPE).unwrap_or_default();
+    if msg_type.is_empty() || !msg_type.bytes().all(|byte| byte.is_ascii_alphanumeric()) {
+        return Err(Rejection { reason: SessionRejectReason::InvalidMsgType, ref_tag_id: Some(numbers::MSG_TYPE), text: format!("Invalid MsgType {}", msg_type) });
+    }
+
+    let sending_time = message.get_field(numbers::SENDING_TIME).unwrap_or_default();
+    if NaiveDateTime::parse_from_str(sending_time, TIMESTAMP_FORMAT).is_err() {
+        return Err(Rejection { reason: SessionRejectReason::IncorrectDataFormat, ref_tag_id: Some(numbers::SENDING_TIME), text: format!("Invalid SendingTime {}", sending_time) });
+    }
+    validate_orig_sending_time(message)
+}
+
+fn is_possible_duplicate(message: &FixMessage) -> bool {
+    message.get_field(numbers::POSS_DUP_FLAG) == Some("Y")
+}
+
+// A resent message cannot have been first sent after it was resent
+fn validate_orig_sending_time(message: &FixMessage) -> Result<(), Rejection> {
+    let parse = |tag| message.get_field(tag).and_then(|value| NaiveDateTime::parse_from_str(value, TIMESTAMP_FORMAT).ok());
+    match (parse(numbers::ORIG_SENDING_TIME), parse(numbers::SENDING_TIME)) {
+        (Some(orig_sending_time), Some(sending_time)) if is_possible_duplicate(message) && orig_sending_time > sending_time => {
+            let text = format!("OrigSendingTime {} is later than SendingTime {}", orig_sending_time, sending_time);
+            Err(Rejection { reason: SessionRejectReason::SendingTimeAccuracyProblem, ref_tag_id: Some(numbers::ORIG_SENDING_TIME), text })
+        }
+        _ => Ok(()),
+    }
+}
+
+// Wire text of a received message for reporting it to the application
+fn raw_text(message: &FixMessage) -> String {
+    message.raw().map(|raw| String::from_utf8_lossy(raw).into_owned()).unwrap_or_default()
+}
+
+// Reads a field straight from the wire text of a message that could not be decoded
+fn raw_field<'a>(raw: &'a str, tag: &str) -> Option<&'a str> {
+    raw.split(SOH).find_map(|field| field.split_once('=').filter(|(field_tag, _)| *field_tag == tag).map(|(_, value)| value))
+}
+
+fn is_reset_requested(logon: &FixMessage) -> bool {
+    logon.get_field(numbers::RESET_SEQ_NUM_FLAG) == Some(ResetSeqNumFlag::Yes.value().as_str())
+}
+
+// Session-level messages go through to_admin/from_admin rather than to_app/from_app
+fn is_admin(message: &FixMessage) -> bool {
+    message.msg_type_enum().is_some_and(|msg_type| msg_type.is_admin())
+}
+
+fn is_msg_type(message: &FixMessage, msg_type: MsgType) -> bool {
+    message.msg_type_enum() == Some(msg_type)
+}
+
+fn validate_begin_string(message: &FixMessage, expected: BeginString) -> Result<(), EngineError> {
+    match message.header.get("8") {
+        Some(received) if BeginString::from_wire(received) == Ok(expected) => Ok(()),
+        received => Err(EngineError::BeginStringMismatch { expected, received: received.cloned() }),
+    }
+}
+
+// The peer's SenderCompID must be our target and its TargetCompID must be us, and likewise for configured sub IDs.
+// Empty values are not checked.
+fn validate_comp_ids(message: &FixMessage, sender_comp_id: &str, target_comp_id: &str, config: &SessionConfig) -> Result<(), EngineError> {
+    let expected_fields = [
+        (numbers::SENDER_COMP_ID, target_comp_id),
+        (numbers::TARGET_COMP_ID, sender_comp_id),
+        (numbers::SENDER_SUB_ID, config.target_sub_id.as_deref().unwrap_or_default()),
+        (numbers::TARGET_SUB_ID, config.sender_sub_id.as_deref().unwrap_or_default()),
+    ];
+    for (tag, expected) in expected_fields {
+        let received = message.get_field(tag);
+        if !expected.is_empty() && received != Some(expected) {
+            return Err(EngineError::CompIDMismatch { tag, expected: expected.to_string(), received: received.map(str::to_string) });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logon_from(sender: &str, target: &str) -> FixMessage {
+        let mut logon = FixMessage::new();
+        logon.header.insert("49".to_string(), sender.to_string());
+        logon.header.insert("56".to_string(), target.to_string());
+        logon
+    }
+
+    #[test]
+    fn test_validate_comp_ids() {
+        let validate = |message: &FixMessage, config: &SessionConfig| {
+            validate_comp_ids(message, &config.sender_comp_id, &config.target_comp_id, config)
+        };
+        let mut config = SessionConfig::new("ACCEPTOR", "INITIATOR");
+        assert!(validate(&logon_from("INITIATOR", "ACCEPTOR"), &config).is_ok());
+        assert_eq!(
+            validate(&logon_from("INITIATOR", "OTHER"), &config),
+            Err(EngineError::CompIDMismatch { tag: 56, expected: "ACCEPTOR".to_string(), received: Some("OTHER".to_string()) })
+        );
+        assert!(validate(&FixMessage::new(), &config).is_err());
+
+        // Sub IDs are only checked once configured
+        config.sender_sub_id = Some("DESK".to_string());
+        let mut message = logon_from("INITIATOR", "ACCEPTOR");
+        assert_eq!(validate(&message, &config).unwrap_err(), EngineError::CompIDMismatch { tag: 57, expected: "DESK".to_string(), received: None });
+        message.header.insert("57".to_string(), "DESK".to_string());
+        assert!(validate(&message, &config).is_ok());
+
+        // Unset comp IDs accept any peer
+        assert!(validate(&logon_from("ANYONE", "ANYONE"), &SessionConfig::default()).is_ok());
+    }
+
+    #[test]
+    fn test_validate_message() {
+        let mut message = logon_from("INITIATOR", "ACCEPTOR");
+        message.header.insert("35".to_string(), "D".to_string());
+        message.header.insert("52".to_string(), "20231016-12:30:00".to_string());
+        assert_eq!(validate_message(&message), Ok(()));
+
+        message.header.insert("52".to_string(), "2023-10-16 12:30".to_string());
+        assert_eq!(validate_message(&message).unwrap_err().reason, SessionRejectReason::IncorrectDataFormat);
+        message.header.insert("52".to_string(), "20231016-12:30:00".to_string());
+        for msg_type in ["E", "AB", "s", "r", "U1"] {
+            message.header.insert("35".to_string(), msg_type.to_string());
+            assert_eq!(validate_message(&message), Ok(()), "35={} is well formed", msg_type);
+        }
+        for msg_type in ["", "D!", "A B"] {
+            message.header.insert("35".to_string(), msg_type.to_string());
+            assert_eq!(validate_message(&message).unwrap_err().reason, SessionRejectReason::InvalidMsgType, "35={:?}", msg_type);
+        }
+        message.header.remove("49");
+        assert_eq!(validate_message(&message).unwrap_err().ref_tag_id, Some(numbers::SENDER_COMP_ID));
+    }
+
+    #[test]
+    fn test_raw_field() {
+        let raw = "8=FIX.4.4\x019=5\x0135=D\x0134=7\x0110=999\x01";
+        assert_eq!(raw_field(raw, "34"), Some("7"));
+        assert_eq!(raw_field(raw, "4"), None);
+    }
+}
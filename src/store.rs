@@ -0,0 +1,145 @@
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::io;
+
+mod file;
+pub use file::FileMessageStore;
+pub(crate) use file::file_name;
+
+// Where a session keeps its sequence numbers and the encoded messages it has sent, so ResendRequests can be
+// answered. Updates return an error when they could not be persisted.
+pub trait MessageStore: Send {
+    fn store(&mut self, seq_num: u64, raw: &[u8]) -> io::Result<()>;
+    // Messages from begin to end inclusive in MsgSeqNum order; numbers never stored are skipped
+    fn get_range(&self, begin: u64, end: u64) -> Vec<Vec<u8>>;
+    fn next_sender_seq(&self) -> u64;
+    fn next_target_seq(&self) -> u64;
+    fn set_next_sender_seq(&mut self, seq_num: u64) -> io::Result<()>;
+    fn set_next_target_seq(&mut self, seq_num: u64) -> io::Result<()>;
+    // Both sequence numbers back to 1 and every stored message dropped
+    fn reset(&mut self) -> io::Result<()>;
+    // When the store was created or last reset
+    fn creation_time(&self) -> DateTime<Utc>;
+}
+
+// The next inbound (expected from the peer) and outbound MsgSeqNum, as a session resumes from them after a
+// restart. Every MessageStore is one, so the numbers have a single home: FileMessageStore keeps them on disk and
+// MemoryMessageStore, the default, for the life of the process.
+pub trait SeqNumStore {
+    fn load(&mut self) -> io::Result<(u64, u64)>; // (inbound, outbound)
+    fn store(&mut self, inbound: u64, outbound: u64) -> io::Result<()>;
+}
+
+impl<T: MessageStore + ?Sized> SeqNumStore for T {
+    fn load(&mut self) -> io::Result<(u64, u64)> {
+        Ok((self.next_target_seq(), self.next_sender_seq()))
+    }
+
+    fn store(&mut self, inbound: u64, outbound: u64) -> io::Result<()> {
+        self.set_next_target_seq(inbound)?;
+        self.set_next_sender_seq(outbound)
+    }
+}
+
+// The default store; nothing survives the process
+#[derive(Debug, Clone)]
+pub struct MemoryMessageStore {
+    messages: BTreeMap<u64, Vec<u8>>,
+    next_sender_seq: u64,
+    next_target_seq: u64,
+    creation_time: DateTime<Utc>,
+}
+
+impl MemoryMessageStore {
+    pub fn new() -> Self {
+        MemoryMessageStore {
+            messages: BTreeMap::new(),
+            next_sender_seq: 1,
+            next_target_seq: 1,
+            creation_time: Utc::now(),
+        }
+    }
+}
+
+impl Default for MemoryMessageStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MessageStore for MemoryMessageStore {
+    fn store(&mut self, seq_num: u64, raw: &[u8]) -> io::Result<()> {
+        self.messages.insert(seq_num, raw.to_vec());
+        Ok(())
+    }
+
+    fn get_range(&self, begin: u64, end: u64) -> Vec<Vec<u8>> {
+        if begin > end {
+            return Vec::new();
+        }
+        self.messages.range(begin..=end).map(|(_, raw)| raw.clone()).collect()
+    }
+
+    fn next_sender_seq(&self) -> u64 {
+        self.next_sender_seq
+    }
+
+    fn next_target_seq(&self) -> u64 {
+        self.next_target_seq
+    }
+
+    fn set_next_sender_seq(&mut self, seq_num: u64) -> io::Result<()> {
+        self.next_sender_seq = seq_num;
+        Ok(())
+    }
+
+    fn set_next_target_seq(&mut self, seq_num: u64) -> io::Result<()> {
+        self.next_target_seq = seq_num;
+        Ok(())
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        *self = Self::new();
+        Ok(())
+    }
+
+    fn creation_time(&self) -> DateTime<Utc> {
+        self.creation_time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_store_range_and_reset() {
+        let mut store = MemoryMessageStore::new();
+        assert_eq!((store.next_sender_seq(), store.next_target_seq()), (1, 1));
+        for seq_num in [1, 2, 4] {
+            MessageStore::store(&mut store, seq_num, format!("34={}", seq_num).as_bytes()).unwrap();
+        }
+        store.set_next_sender_seq(5).unwrap();
+        store.set_next_target_seq(3).unwrap();
+
+        assert_eq!(store.get_range(2, 4), [b"34=2".to_vec(), b"34=4".to_vec()]);
+        assert_eq!(store.get_range(3, 3), Vec::<Vec<u8>>::new());
+        assert!(store.get_range(4, 1).is_empty());
+        assert_eq!((store.next_sender_seq(), store.next_target_seq()), (5, 3));
+
+        let created = store.creation_time();
+        store.reset().unwrap();
+        assert!(store.get_range(1, 10).is_empty());
+        assert_eq!((store.next_sender_seq(), store.next_target_seq()), (1, 1));
+        assert!(store.creation_time() >= created);
+    }
+
+    #[test]
+    fn test_seq_num_store_is_backed_by_the_message_store() {
+        let mut store: Box<dyn MessageStore> = Box::new(MemoryMessageStore::new());
+        assert_eq!(SeqNumStore::load(&mut *store).unwrap(), (1, 1));
+        SeqNumStore::store(&mut *store, 7, 12).unwrap();
+        assert_eq!((store.next_target_seq(), store.next_sender_seq()), (7, 12));
+        assert_eq!(SeqNumStore::load(&mut *store).unwrap(), (7, 12));
+    }
+}
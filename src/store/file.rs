@@ -0,0 +1,283 @@
+use super::MessageStore;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+// MsgSeqNum to the offset and length of its raw bytes in the messages file
+type Index = BTreeMap<u64, (u64, usize)>;
+
+// Keeps a session's outgoing messages and sequence numbers on disk so a restarted process carries on where it
+// stopped. Each session gets two files under the directory, named after its comp IDs (see file_name):
+//   SENDER-TARGET.messages  append-only records of "<MsgSeqNum> <length>\n<raw bytes>\n"
+//   SENDER-TARGET.seqnums   "<next sender>,<next target>,<creation time>", replaced whole on every update
+// Every update is synced to disk before it returns.
+#[derive(Debug)]
+pub struct FileMessageStore {
+    directory: PathBuf,
+    messages: File,
+    seq_nums_path: PathBuf,
+    index: Index,
+    next_sender_seq: u64,
+    next_target_seq: u64,
+    creation_time: DateTime<Utc>,
+}
+
+impl FileMessageStore {
+    pub fn open(directory: impl AsRef<Path>, sender_comp_id: &str, target_comp_id: &str) -> io::Result<Self> {
+        let directory = directory.as_ref();
+        fs::create_dir_all(directory)?;
+        let name = format!("{}-{}", file_name(sender_comp_id), file_name(target_comp_id));
+        let messages = OpenOptions::new().read(true).append(true).create(true).open(directory.join(format!("{}.messages", name)))?;
+        sync_directory(directory)?;
+        let (index, length) = read_index(&messages)?;
+        if length < messages.metadata()?.len() {
+            messages.set_len(length)?;
+        }
+
+        let mut store = FileMessageStore {
+            directory: directory.to_path_buf(),
+            index,
+            messages,
+            seq_nums_path: directory.join(format!("{}.seqnums", name)),
+            next_sender_seq: 1,
+            next_target_seq: 1,
+            creation_time: Utc::now(),
+        };
+        match fs::read_to_string(&store.seq_nums_path) {
+            Ok(contents) => store.parse_seq_nums(&contents)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => store.write_seq_nums()?,
+            Err(e) => return Err(e),
+        }
+        Ok(store)
+    }
+
+    fn parse_seq_nums(&mut self, contents: &str) -> io::Result<()> {
+        let malformed = || io::Error::new(io::ErrorKind::InvalidData, "Malformed sequence number file");
+        let mut fields = contents.trim().splitn(3, ',');
+        let (Some(sender), Some(target), Some(created)) = (fields.next(), fields.next(), fields.next()) else {
+            return Err(malformed());
+        };
+        self.next_sender_seq = sender.parse().map_err(|_| malformed())?;
+        self.next_target_seq = target.parse().map_err(|_| malformed())?;
+        self.creation_time = DateTime::parse_from_rfc3339(created).map_err(|_| malformed())?.with_timezone(&Utc);
+        Ok(())
+    }
+
+    // Written aside and renamed over the old file, so a crash leaves either the old numbers or the new ones
+    fn write_seq_nums(&self) -> io::Result<()> {
+        let temp = self.seq_nums_path.with_extension("tmp");
+        let mut file = File::create(&temp)?;
+        write!(file, "{},{},{}", self.next_sender_seq, self.next_target_seq, self.creation_time.to_rfc3339())?;
+        file.sync_all()?;
+        fs::rename(&temp, &self.seq_nums_path)?;
+        sync_directory(&self.directory)
+    }
+
+    fn read_message(&self, offset: u64, length: usize) -> io::Result<Vec<u8>> {
+        let mut file = &self.messages;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut raw = vec![0; length];
+        file.read_exact(&mut raw)?;
+        Ok(raw)
+    }
+}
+
+// A comp ID as it appears in a file name. Anything but ASCII letters, digits and '_' is written as %XX, so a comp ID
+// can neither reach outside the directory nor run into the '-' between the two. The message log names its files
+// the same way.
+pub(crate) fn file_name(comp_id: &str) -> String {
+    let mut name = String::with_capacity(comp_id.len());
+    for byte in comp_id.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'_' {
+            name.push(byte as char);
+        } else {
+            name.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    name
+}
+
+// A new or renamed file only survives a crash once the directory entry pointing at it is on disk as well
+#[cfg(unix)]
+fn sync_directory(directory: &Path) -> io::Result<()> {
+    File::open(directory)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_directory(_directory: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+// Rebuilds the index from the messages file and returns it with the length of the complete records. A record
+// cut short by a crash ends the scan; it never made it onto the wire, as messages are stored before they are sent.
+fn read_index(messages: &File) -> io::Result<(Index, u64)> {
+    let mut index = BTreeMap::new();
+    let file_length = messages.metadata()?.len();
+    let mut reader = BufReader::new(messages);
+    reader.seek(SeekFrom::Start(0))?;
+    let mut offset = 0;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let header_length = reader.read_line(&mut line)?;
+        let Some((seq_num, length)) = line.strip_suffix('\n')
+            .and_then(|header| header.split_once(' '))
+            .and_then(|(seq_num, length)| Some((seq_num.parse::<u64>().ok()?, length.parse::<usize>().ok()?))) else {
+            break;
+        };
+        // A length running past the end of the file is a cut-off or corrupt record, and is not allocated for
+        let record_end = (length as u64).checked_add(offset + header_length as u64 + 1);
+        if record_end.is_none_or(|record_end| record_end > file_length) {
+            break;
+        }
+        let mut record = vec![0; length + 1];
+        if reader.read_exact(&mut record).is_err() {
+            break;
+        }
+        offset += header_length as u64;
+        index.insert(seq_num, (offset, length));
+        offset += record.len() as u64;
+    }
+    Ok((index, offset))
+}
+
+impl MessageStore for FileMessageStore {
+    fn store(&mut self, seq_num: u64, raw: &[u8]) -> io::Result<()> {
+        let offset = self.messages.seek(SeekFrom::End(0))?;
+        let header = format!("{} {}\n", seq_num, raw.len());
+        let mut record = Vec::with_capacity(header.len() + raw.len() + 1);
+        record.extend_from_slice(header.as_bytes());
+        record.extend_from_slice(raw);
+        record.push(b'\n');
+        self.messages.write_all(&record)?;
+        self.messages.sync_data()?;
+        self.index.insert(seq_num, (offset + header.len() as u64, raw.len()));
+        Ok(())
+    }
+
+    fn get_range(&self, begin: u64, end: u64) -> Vec<Vec<u8>> {
+        if begin > end {
+            return Vec::new();
+        }
+        self.index.range(begin..=end)
+            .filter_map(|(_, (offset, length))| self.read_message(*offset, *length).ok())
+            .collect()
+    }
+
+    fn next_sender_seq(&self) -> u64 {
+        self.next_sender_seq
+    }
+
+    fn next_target_seq(&self) -> u64 {
+        self.next_target_seq
+    }
+
+    fn set_next_sender_seq(&mut self, seq_num: u64) -> io::Result<()> {
+        self.next_sender_seq = seq_num;
+        self.write_seq_nums()
+    }
+
+    fn set_next_target_seq(&mut self, seq_num: u64) -> io::Result<()> {
+        self.next_target_seq = seq_num;
+        self.write_seq_nums()
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        self.messages.set_len(0)?;
+        self.messages.sync_all()?;
+        self.index.clear();
+        self.next_sender_seq = 1;
+        self.next_target_seq = 1;
+        self.creation_time = Utc::now();
+        self.write_seq_nums()
+    }
+
+    fn creation_time(&self) -> DateTime<Utc> {
+        self.creation_time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("fix_engine_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    #[test]
+    fn test_reopened_store_continues_where_it_stopped() {
+        let directory = directory("file_store");
+        let created = {
+            let mut store = FileMessageStore::open(&directory, "SENDER", "TARGET").unwrap();
+            store.store(1, b"8=FIX.4.4\x0134=1\x0135=A\x01").unwrap();
+            store.store(2, b"8=FIX.4.4\x0134=2\x0135=D\n\x01").unwrap();
+            store.set_next_sender_seq(3).unwrap();
+            store.set_next_target_seq(7).unwrap();
+            store.creation_time()
+        };
+
+        let mut store = FileMessageStore::open(&directory, "SENDER", "TARGET").unwrap();
+        assert_eq!((store.next_sender_seq(), store.next_target_seq()), (3, 7));
+        assert_eq!(crate::store::SeqNumStore::load(&mut store).unwrap(), (7, 3));
+        assert_eq!(store.creation_time(), created);
+        store.store(3, b"8=FIX.4.4\x0134=3\x0135=D\x01").unwrap();
+        assert_eq!(store.get_range(2, 3), [b"8=FIX.4.4\x0134=2\x0135=D\n\x01".to_vec(), b"8=FIX.4.4\x0134=3\x0135=D\x01".to_vec()]);
+
+        // Another session under the same directory starts from scratch
+        let other = FileMessageStore::open(&directory, "SENDER", "OTHER").unwrap();
+        assert_eq!(other.next_sender_seq(), 1);
+        assert!(other.get_range(1, 3).is_empty());
+
+        store.reset().unwrap();
+        let store = FileMessageStore::open(&directory, "SENDER", "TARGET").unwrap();
+        assert_eq!((store.next_sender_seq(), store.next_target_seq()), (1, 1));
+        assert!(store.get_range(1, 3).is_empty());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_truncated_record_is_dropped_on_open() {
+        let directory = directory("file_store_truncated");
+        {
+            let mut store = FileMessageStore::open(&directory, "SENDER", "TARGET").unwrap();
+            store.store(1, b"34=1\x01").unwrap();
+        }
+        let mut messages = OpenOptions::new().append(true).open(directory.join("SENDER-TARGET.messages")).unwrap();
+        messages.write_all(b"2 40\n34=2").unwrap();
+
+        let mut store = FileMessageStore::open(&directory, "SENDER", "TARGET").unwrap();
+        assert_eq!(store.get_range(1, 2), [b"34=1\x01".to_vec()]);
+        store.store(2, b"34=2\x01").unwrap();
+        let store = FileMessageStore::open(&directory, "SENDER", "TARGET").unwrap();
+        assert_eq!(store.get_range(1, 2).len(), 2, "The cut-off record is not left in front of later ones");
+
+        // A corrupt length is never allocated for
+        let mut messages = OpenOptions::new().append(true).open(directory.join("SENDER-TARGET.messages")).unwrap();
+        messages.write_all(format!("3 {}\n34=3", usize::MAX).as_bytes()).unwrap();
+        let store = FileMessageStore::open(&directory, "SENDER", "TARGET").unwrap();
+        assert_eq!(store.get_range(1, 3).len(), 2);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_comp_ids_are_escaped_in_file_names() {
+        assert_eq!(file_name("CLIENT_1"), "CLIENT_1");
+        assert_eq!(file_name("../A-B"), "%2E%2E%2FA%2DB");
+
+        let directory = directory("file_store_escaped");
+        let mut store = FileMessageStore::open(&directory, "../A", "B/C").unwrap();
+        store.store(1, b"34=1\x01").unwrap();
+        let mut names: Vec<_> = fs::read_dir(&directory).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        names.sort();
+        assert_eq!(names, ["%2E%2E%2FA-B%2FC.messages", "%2E%2E%2FA-B%2FC.seqnums"]);
+        // The '-' between the comp IDs is the only one left, so different pairs never share a file
+        let other = FileMessageStore::open(&directory, "..", "A-B/C").unwrap();
+        assert!(other.get_range(1, 1).is_empty());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
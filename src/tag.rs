@@ -1,6 +1,31 @@
+use crate::decimal::FixDecimal;
+use std::fmt;
+use std::str::FromStr;
+
+pub mod numbers;
+
 pub const SOH: char = '\x01';
-const CHECKSUM_TAG: &str = "10";
-const REQUIRED_HEADER_FIELDS: [&str; 7] = ["8", "9", "35", "49", "56", "34", "52"];
+pub(crate) const CHECKSUM_TAG: &str = "10";
+pub(crate) const BODY_LENGTH_TAG: &str = "9";
+pub(crate) const MSG_SEQ_NUM_TAG: &str = "34";
+
+// Standard header fields in wire order for each protocol version (repeating groups are not supported)
+const FIX_4_1_HEADER_FIELDS: [&str; 22] = [
+    "8", "9", "35", "49", "56", "115", "128", "90", "91", "34", "50", "142", "57", "143", "116", "144", "129", "145",
+    "43", "97", "52", "122",
+];
+const FIX_4_2_HEADER_FIELDS: [&str; 27] = [
+    "8", "9", "35", "49", "56", "115", "128", "90", "91", "34", "50", "142", "57", "143", "116", "144", "129", "145",
+    "43", "97", "52", "122", "212", "213", "347", "369", "370",
+];
+const FIX_4_4_HEADER_FIELDS: [&str; 28] = [
+    "8", "9", "35", "49", "56", "115", "128", "90", "91", "34", "50", "142", "57", "143", "116", "144", "129", "145",
+    "43", "97", "52", "122", "212", "213", "347", "369", "370", "627",
+];
+const FIXT_1_1_HEADER_FIELDS: [&str; 30] = [
+    "8", "9", "35", "1128", "1129", "49", "56", "115", "128", "90", "91", "34", "50", "142", "57", "143", "116", "144",
+    "129", "145", "43", "97", "52", "122", "212", "213", "347", "369", "370", "627",
+];
 
 pub trait FixField {
     fn tag_id(&self) -> &'static str;
@@ -8,12 +33,28 @@ pub trait FixField {
     fn value(&self) -> String; // Use &'static str to avoid heap allocation.
 }
 
-#[derive(Debug, Clone)]
-pub(crate) struct CompID(pub String); // Use &'static str instead of String.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompID(String);
 
 impl CompID {
-    fn new(id: String) -> Self {
-        CompID(id) // No allocation, just a reference to a static string.
+    pub fn new(id: String) -> Result<Self, &'static str> {
+        if id.is_empty() {
+            return Err("CompID must not be empty");
+        }
+        if id.contains(SOH) || id.contains('=') {
+            return Err("CompID must not contain SOH or '='");
+        }
+        Ok(CompID(id))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for CompID {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
     }
 }
 
@@ -31,14 +72,16 @@ impl FixField for CompID {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-enum PossDupFlag {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PossDupFlag {
     Yes,
     No,
 }
 
-impl PossDupFlag {
-    fn from_str(value: &str) -> Result<Self, &'static str> {
+impl FromStr for PossDupFlag {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
         match value {
             "Y" => Ok(PossDupFlag::Yes),
             "N" => Ok(PossDupFlag::No),
@@ -47,6 +90,12 @@ impl PossDupFlag {
     }
 }
 
+impl fmt::Display for PossDupFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.value())
+    }
+}
+
 impl FixField for PossDupFlag {
     fn tag_id(&self) -> &'static str {
         "43"
@@ -64,10 +113,52 @@ impl FixField for PossDupFlag {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum BeginString {
+    Fix4_0,
+    Fix4_1,
     Fix4_2,
+    Fix4_3,
     Fix4_4,
+    Fix5_0,
+    FixT1_1,
+}
+
+impl BeginString {
+    pub fn from_wire(value: &str) -> Result<Self, &'static str> {
+        match value {
+            "FIX.4.0" => Ok(BeginString::Fix4_0),
+            "FIX.4.1" => Ok(BeginString::Fix4_1),
+            "FIX.4.2" => Ok(BeginString::Fix4_2),
+            "FIX.4.3" => Ok(BeginString::Fix4_3),
+            "FIX.4.4" => Ok(BeginString::Fix4_4),
+            "FIX.5.0" => Ok(BeginString::Fix5_0),
+            "FIXT.1.1" => Ok(BeginString::FixT1_1),
+            _ => Err("Invalid BeginString value"),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BeginString::Fix4_0 => "FIX.4.0",
+            BeginString::Fix4_1 => "FIX.4.1",
+            BeginString::Fix4_2 => "FIX.4.2",
+            BeginString::Fix4_3 => "FIX.4.3",
+            BeginString::Fix4_4 => "FIX.4.4",
+            BeginString::Fix5_0 => "FIX.5.0",
+            BeginString::FixT1_1 => "FIXT.1.1",
+        }
+    }
+
+    // The header fields this version defines, in the order they go on the wire
+    pub fn header_fields(&self) -> &'static [&'static str] {
+        match self {
+            BeginString::Fix4_0 | BeginString::Fix4_1 => &FIX_4_1_HEADER_FIELDS,
+            BeginString::Fix4_2 => &FIX_4_2_HEADER_FIELDS,
+            BeginString::Fix4_3 | BeginString::Fix4_4 => &FIX_4_4_HEADER_FIELDS,
+            BeginString::Fix5_0 | BeginString::FixT1_1 => &FIXT_1_1_HEADER_FIELDS,
+        }
+    }
 }
 
 impl FixField for BeginString {
@@ -80,14 +171,11 @@ impl FixField for BeginString {
     }
 
     fn value(&self) -> String {
-        match self {
-            BeginString::Fix4_2 => "FIX.4.2".to_string(),
-            BeginString::Fix4_4 => "FIX.4.4".to_string(),
-        }
+        self.as_str().to_string()
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MsgType {
     Heartbeat,
     TestRequest,
@@ -116,6 +204,45 @@ pub enum MsgType {
     TradeCaptureReportRequest,
     TradeCaptureReport,
     TradeCaptureReportRequestAck,
+    BusinessMessageReject,
+}
+
+impl FromStr for MsgType {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "0" => Ok(MsgType::Heartbeat),
+            "1" => Ok(MsgType::TestRequest),
+            "2" => Ok(MsgType::ResendRequest),
+            "3" => Ok(MsgType::Reject),
+            "4" => Ok(MsgType::SequenceReset),
+            "5" => Ok(MsgType::Logout),
+            "8" => Ok(MsgType::ExecutionReport),
+            "9" => Ok(MsgType::OrderCancelReject),
+            "A" => Ok(MsgType::Logon),
+            "B" => Ok(MsgType::News),
+            "c" => Ok(MsgType::SecurityDefinitionRequest),
+            "D" => Ok(MsgType::OrderSingle),
+            "d" => Ok(MsgType::SecurityDefinition),
+            "e" => Ok(MsgType::SecurityStatusRequest),
+            "f" => Ok(MsgType::SecurityStatus),
+            "F" => Ok(MsgType::OrderCancelRequest),
+            "G" => Ok(MsgType::OrderCancelReplaceRequest),
+            "H" => Ok(MsgType::OrderStatusRequest),
+            "Q" => Ok(MsgType::DontKnowTrade),
+            "R" => Ok(MsgType::QuoteRequest),
+            "V" => Ok(MsgType::MarketDataRequest),
+            "W" => Ok(MsgType::MarketDataSnapshotFullRefresh),
+            "X" => Ok(MsgType::MarketDataIncrementalRefresh),
+            "Y" => Ok(MsgType::MarketDataRequestReject),
+            "AD" => Ok(MsgType::TradeCaptureReportRequest),
+            "AE" => Ok(MsgType::TradeCaptureReport),
+            "AQ" => Ok(MsgType::TradeCaptureReportRequestAck),
+            "j" => Ok(MsgType::BusinessMessageReject),
+            _ => Err("Invalid MsgType value"),
+        }
+    }
 }
 
 impl FixField for MsgType {
@@ -156,6 +283,634 @@ impl FixField for MsgType {
             MsgType::TradeCaptureReportRequest => "AD".to_string(),
             MsgType::TradeCaptureReport => "AE".to_string(),
             MsgType::TradeCaptureReportRequestAck => "AQ".to_string(),
+            MsgType::BusinessMessageReject => "j".to_string(),
+        }
+    }
+}
+
+impl MsgType {
+    // Session-level message types, handled by the engine itself
+    pub fn is_admin(&self) -> bool {
+        matches!(self, MsgType::Heartbeat | MsgType::TestRequest | MsgType::ResendRequest | MsgType::Reject
+            | MsgType::SequenceReset | MsgType::Logout | MsgType::Logon)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Buy,
+    Sell,
+    BuyMinus,
+    SellPlus,
+    SellShort,
+    SellShortExempt,
+    Undisclosed,
+    Cross,
+    CrossShort,
+}
+
+impl FromStr for Side {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "1" => Ok(Side::Buy),
+            "2" => Ok(Side::Sell),
+            "3" => Ok(Side::BuyMinus),
+            "4" => Ok(Side::SellPlus),
+            "5" => Ok(Side::SellShort),
+            "6" => Ok(Side::SellShortExempt),
+            "7" => Ok(Side::Undisclosed),
+            "8" => Ok(Side::Cross),
+            "9" => Ok(Side::CrossShort),
+            _ => Err("Invalid Side value"),
+        }
+    }
+}
+
+impl FixField for Side {
+    fn tag_id(&self) -> &'static str {
+        "54"
+    }
+
+    fn field_name(&self) -> &'static str {
+        "Side"
+    }
+
+    fn value(&self) -> String {
+        match self {
+            Side::Buy => "1".to_string(),
+            Side::Sell => "2".to_string(),
+            Side::BuyMinus => "3".to_string(),
+            Side::SellPlus => "4".to_string(),
+            Side::SellShort => "5".to_string(),
+            Side::SellShortExempt => "6".to_string(),
+            Side::Undisclosed => "7".to_string(),
+            Side::Cross => "8".to_string(),
+            Side::CrossShort => "9".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrdType {
+    Market,
+    Limit,
+    Stop,
+    StopLimit,
+}
+
+impl FromStr for OrdType {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "1" => Ok(OrdType::Market),
+            "2" => Ok(OrdType::Limit),
+            "3" => Ok(OrdType::Stop),
+            "4" => Ok(OrdType::StopLimit),
+            _ => Err("Invalid OrdType value"),
+        }
+    }
+}
+
+impl FixField for OrdType {
+    fn tag_id(&self) -> &'static str {
+        "40"
+    }
+
+    fn field_name(&self) -> &'static str {
+        "OrdType"
+    }
+
+    fn value(&self) -> String {
+        match self {
+            OrdType::Market => "1".to_string(),
+            OrdType::Limit => "2".to_string(),
+            OrdType::Stop => "3".to_string(),
+            OrdType::StopLimit => "4".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeInForce {
+    Day,
+    GoodTillCancel,
+    AtTheOpening,
+    ImmediateOrCancel,
+    FillOrKill,
+    GoodTillDate,
+}
+
+impl FromStr for TimeInForce {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "0" => Ok(TimeInForce::Day),
+            "1" => Ok(TimeInForce::GoodTillCancel),
+            "2" => Ok(TimeInForce::AtTheOpening),
+            "3" => Ok(TimeInForce::ImmediateOrCancel),
+            "4" => Ok(TimeInForce::FillOrKill),
+            "6" => Ok(TimeInForce::GoodTillDate),
+            _ => Err("Invalid TimeInForce value"),
+        }
+    }
+}
+
+impl FixField for TimeInForce {
+    fn tag_id(&self) -> &'static str {
+        "59"
+    }
+
+    fn field_name(&self) -> &'static str {
+        "TimeInForce"
+    }
+
+    fn value(&self) -> String {
+        match self {
+            TimeInForce::Day => "0".to_string(),
+            TimeInForce::GoodTillCancel => "1".to_string(),
+            TimeInForce::AtTheOpening => "2".to_string(),
+            TimeInForce::ImmediateOrCancel => "3".to_string(),
+            TimeInForce::FillOrKill => "4".to_string(),
+            TimeInForce::GoodTillDate => "6".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncryptMethod {
+    None,
+    Pkcs,
+    Des,
+    PkcsDes,
+    PgpDes,
+    PgpDesMd5,
+    PemDesMd5,
+}
+
+impl FromStr for EncryptMethod {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "0" => Ok(EncryptMethod::None),
+            "1" => Ok(EncryptMethod::Pkcs),
+            "2" => Ok(EncryptMethod::Des),
+            "3" => Ok(EncryptMethod::PkcsDes),
+            "4" => Ok(EncryptMethod::PgpDes),
+            "5" => Ok(EncryptMethod::PgpDesMd5),
+            "6" => Ok(EncryptMethod::PemDesMd5),
+            _ => Err("Invalid EncryptMethod value"),
+        }
+    }
+}
+
+impl FixField for EncryptMethod {
+    fn tag_id(&self) -> &'static str {
+        "98"
+    }
+
+    fn field_name(&self) -> &'static str {
+        "EncryptMethod"
+    }
+
+    fn value(&self) -> String {
+        match self {
+            EncryptMethod::None => "0".to_string(),
+            EncryptMethod::Pkcs => "1".to_string(),
+            EncryptMethod::Des => "2".to_string(),
+            EncryptMethod::PkcsDes => "3".to_string(),
+            EncryptMethod::PgpDes => "4".to_string(),
+            EncryptMethod::PgpDesMd5 => "5".to_string(),
+            EncryptMethod::PemDesMd5 => "6".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionRejectReason {
+    InvalidTagNumber,
+    RequiredTagMissing,
+    TagNotDefinedForMessageType,
+    UndefinedTag,
+    TagSpecifiedWithoutValue,
+    ValueIsIncorrect,
+    IncorrectDataFormat,
+    DecryptionProblem,
+    SignatureProblem,
+    CompIDProblem,
+    SendingTimeAccuracyProblem,
+    InvalidMsgType,
+    XmlValidationError,
+    TagAppearsMoreThanOnce,
+    TagSpecifiedOutOfRequiredOrder,
+    RepeatingGroupFieldsOutOfOrder,
+    IncorrectNumInGroupCount,
+    NonDataValueIncludesFieldDelimiter,
+    Other,
+}
+
+impl FromStr for SessionRejectReason {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "0" => Ok(SessionRejectReason::InvalidTagNumber),
+            "1" => Ok(SessionRejectReason::RequiredTagMissing),
+            "2" => Ok(SessionRejectReason::TagNotDefinedForMessageType),
+            "3" => Ok(SessionRejectReason::UndefinedTag),
+            "4" => Ok(SessionRejectReason::TagSpecifiedWithoutValue),
+            "5" => Ok(SessionRejectReason::ValueIsIncorrect),
+            "6" => Ok(SessionRejectReason::IncorrectDataFormat),
+            "7" => Ok(SessionRejectReason::DecryptionProblem),
+            "8" => Ok(SessionRejectReason::SignatureProblem),
+            "9" => Ok(SessionRejectReason::CompIDProblem),
+            "10" => Ok(SessionRejectReason::SendingTimeAccuracyProblem),
+            "11" => Ok(SessionRejectReason::InvalidMsgType),
+            "12" => Ok(SessionRejectReason::XmlValidationError),
+            "13" => Ok(SessionRejectReason::TagAppearsMoreThanOnce),
+            "14" => Ok(SessionRejectReason::TagSpecifiedOutOfRequiredOrder),
+            "15" => Ok(SessionRejectReason::RepeatingGroupFieldsOutOfOrder),
+            "16" => Ok(SessionRejectReason::IncorrectNumInGroupCount),
+            "17" => Ok(SessionRejectReason::NonDataValueIncludesFieldDelimiter),
+            "99" => Ok(SessionRejectReason::Other),
+            _ => Err("Invalid SessionRejectReason value"),
+        }
+    }
+}
+
+impl FixField for SessionRejectReason {
+    fn tag_id(&self) -> &'static str {
+        "373"
+    }
+
+    fn field_name(&self) -> &'static str {
+        "SessionRejectReason"
+    }
+
+    fn value(&self) -> String {
+        match self {
+            SessionRejectReason::InvalidTagNumber => "0".to_string(),
+            SessionRejectReason::RequiredTagMissing => "1".to_string(),
+            SessionRejectReason::TagNotDefinedForMessageType => "2".to_string(),
+            SessionRejectReason::UndefinedTag => "3".to_string(),
+            SessionRejectReason::TagSpecifiedWithoutValue => "4".to_string(),
+            SessionRejectReason::ValueIsIncorrect => "5".to_string(),
+            SessionRejectReason::IncorrectDataFormat => "6".to_string(),
+            SessionRejectReason::DecryptionProblem => "7".to_string(),
+            SessionRejectReason::SignatureProblem => "8".to_string(),
+            SessionRejectReason::CompIDProblem => "9".to_string(),
+            SessionRejectReason::SendingTimeAccuracyProblem => "10".to_string(),
+            SessionRejectReason::InvalidMsgType => "11".to_string(),
+            SessionRejectReason::XmlValidationError => "12".to_string(),
+            SessionRejectReason::TagAppearsMoreThanOnce => "13".to_string(),
+            SessionRejectReason::TagSpecifiedOutOfRequiredOrder => "14".to_string(),
+            SessionRejectReason::RepeatingGroupFieldsOutOfOrder => "15".to_string(),
+            SessionRejectReason::IncorrectNumInGroupCount => "16".to_string(),
+            SessionRejectReason::NonDataValueIncludesFieldDelimiter => "17".to_string(),
+            SessionRejectReason::Other => "99".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CxlRejReason {
+    TooLateToCancel,
+    UnknownOrder,
+    BrokerOption,
+    OrderAlreadyPending,
+    UnableToProcessMassCancel,
+    OrigOrdModTimeMismatch,
+    DuplicateClOrdID,
+    Other,
+}
+
+impl FromStr for CxlRejReason {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "0" => Ok(CxlRejReason::TooLateToCancel),
+            "1" => Ok(CxlRejReason::UnknownOrder),
+            "2" => Ok(CxlRejReason::BrokerOption),
+            "3" => Ok(CxlRejReason::OrderAlreadyPending),
+            "4" => Ok(CxlRejReason::UnableToProcessMassCancel),
+            "5" => Ok(CxlRejReason::OrigOrdModTimeMismatch),
+            "6" => Ok(CxlRejReason::DuplicateClOrdID),
+            "99" => Ok(CxlRejReason::Other),
+            _ => Err("Invalid CxlRejReason value"),
+        }
+    }
+}
+
+impl FixField for CxlRejReason {
+    fn tag_id(&self) -> &'static str {
+        "102"
+    }
+
+    fn field_name(&self) -> &'static str {
+        "CxlRejReason"
+    }
+
+    fn value(&self) -> String {
+        match self {
+            CxlRejReason::TooLateToCancel => "0".to_string(),
+            CxlRejReason::UnknownOrder => "1".to_string(),
+            CxlRejReason::BrokerOption => "2".to_string(),
+            CxlRejReason::OrderAlreadyPending => "3".to_string(),
+            CxlRejReason::UnableToProcessMassCancel => "4".to_string(),
+            CxlRejReason::OrigOrdModTimeMismatch => "5".to_string(),
+            CxlRejReason::DuplicateClOrdID => "6".to_string(),
+            CxlRejReason::Other => "99".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusinessRejectReason {
+    Other,
+    UnknownID,
+    UnknownSecurity,
+    UnsupportedMessageType,
+    ApplicationNotAvailable,
+    ConditionallyRequiredFieldMissing,
+    NotAuthorized,
+    DeliverToFirmNotAvailable,
+    InvalidPriceIncrement,
+}
+
+impl FromStr for BusinessRejectReason {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "0" => Ok(BusinessRejectReason::Other),
+            "1" => Ok(BusinessRejectReason::UnknownID),
+            "2" => Ok(BusinessRejectReason::UnknownSecurity),
+            "3" => Ok(BusinessRejectReason::UnsupportedMessageType),
+            "4" => Ok(BusinessRejectReason::ApplicationNotAvailable),
+            "5" => Ok(BusinessRejectReason::ConditionallyRequiredFieldMissing),
+            "6" => Ok(BusinessRejectReason::NotAuthorized),
+            "7" => Ok(BusinessRejectReason::DeliverToFirmNotAvailable),
+            "18" => Ok(BusinessRejectReason::InvalidPriceIncrement),
+            _ => Err("Invalid BusinessRejectReason value"),
+        }
+    }
+}
+
+impl FixField for BusinessRejectReason {
+    fn tag_id(&self) -> &'static str {
+        "380"
+    }
+
+    fn field_name(&self) -> &'static str {
+        "BusinessRejectReason"
+    }
+
+    fn value(&self) -> String {
+        match self {
+            BusinessRejectReason::Other => "0".to_string(),
+            BusinessRejectReason::UnknownID => "1".to_string(),
+            BusinessRejectReason::UnknownSecurity => "2".to_string(),
+            BusinessRejectReason::UnsupportedMessageType => "3".to_string(),
+            BusinessRejectReason::ApplicationNotAvailable => "4".to_string(),
+            BusinessRejectReason::ConditionallyRequiredFieldMissing => "5".to_string(),
+            BusinessRejectReason::NotAuthorized => "6".to_string(),
+            BusinessRejectReason::DeliverToFirmNotAvailable => "7".to_string(),
+            BusinessRejectReason::InvalidPriceIncrement => "18".to_string(),
+        }
+    }
+}
+
+// DKReason(127): why a DontKnowTrade refuses an execution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DkReason {
+    UnknownSymbol,
+    WrongSide,
+    QuantityExceedsOrder,
+    NoMatchingOrder,
+    PriceExceedsLimit,
+    CalculationDifference,
+    Other,
+}
+
+impl FromStr for DkReason {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "A" => Ok(DkReason::UnknownSymbol),
+            "B" => Ok(DkReason::WrongSide),
+            "C" => Ok(DkReason::QuantityExceedsOrder),
+            "D" => Ok(DkReason::NoMatchingOrder),
+            "E" => Ok(DkReason::PriceExceedsLimit),
+            "F" => Ok(DkReason::CalculationDifference),
+            "Z" => Ok(DkReason::Other),
+            _ => Err("Invalid DKReason value"),
+        }
+    }
+}
+
+impl FixField for DkReason {
+    fn tag_id(&self) -> &'static str {
+        "127"
+    }
+
+    fn field_name(&self) -> &'static str {
+        "DKReason"
+    }
+
+    fn value(&self) -> String {
+        match self {
+            DkReason::UnknownSymbol => "A".to_string(),
+            DkReason::WrongSide => "B".to_string(),
+            DkReason::QuantityExceedsOrder => "C".to_string(),
+            DkReason::NoMatchingOrder => "D".to_string(),
+            DkReason::PriceExceedsLimit => "E".to_string(),
+            DkReason::CalculationDifference => "F".to_string(),
+            DkReason::Other => "Z".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetSeqNumFlag {
+    Yes,
+    No,
+}
+
+impl FromStr for ResetSeqNumFlag {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "Y" => Ok(ResetSeqNumFlag::Yes),
+            "N" => Ok(ResetSeqNumFlag::No),
+            _ => Err("Invalid ResetSeqNumFlag value"),
+        }
+    }
+}
+
+impl FixField for ResetSeqNumFlag {
+    fn tag_id(&self) -> &'static str {
+        "141"
+    }
+
+    fn field_name(&self) -> &'static str {
+        "ResetSeqNumFlag"
+    }
+
+    fn value(&self) -> String {
+        match self {
+            ResetSeqNumFlag::Yes => "Y".to_string(),
+            ResetSeqNumFlag::No => "N".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MDEntryType {
+    Bid,
+    Offer,
+    Trade,
+    IndexValue,
+    OpeningPrice,
+    ClosingPrice,
+    SettlementPrice,
+    TradingSessionHighPrice,
+    TradingSessionLowPrice,
+    TradingSessionVwapPrice,
+    Imbalance,
+    TradeVolume,
+    OpenInterest,
+}
+
+impl FromStr for MDEntryType {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "0" => Ok(MDEntryType::Bid),
+            "1" => Ok(MDEntryType::Offer),
+            "2" => Ok(MDEntryType::Trade),
+            "3" => Ok(MDEntryType::IndexValue),
+            "4" => Ok(MDEntryType::OpeningPrice),
+            "5" => Ok(MDEntryType::ClosingPrice),
+            "6" => Ok(MDEntryType::SettlementPrice),
+            "7" => Ok(MDEntryType::TradingSessionHighPrice),
+            "8" => Ok(MDEntryType::TradingSessionLowPrice),
+            "9" => Ok(MDEntryType::TradingSessionVwapPrice),
+            "A" => Ok(MDEntryType::Imbalance),
+            "B" => Ok(MDEntryType::TradeVolume),
+            "C" => Ok(MDEntryType::OpenInterest),
+            _ => Err("Invalid MDEntryType value"),
+        }
+    }
+}
+
+impl FixField for MDEntryType {
+    fn tag_id(&self) -> &'static str {
+        "269"
+    }
+
+    fn field_name(&self) -> &'static str {
+        "MDEntryType"
+    }
+
+    fn value(&self) -> String {
+        match self {
+            MDEntryType::Bid => "0".to_string(),
+            MDEntryType::Offer => "1".to_string(),
+            MDEntryType::Trade => "2".to_string(),
+            MDEntryType::IndexValue => "3".to_string(),
+            MDEntryType::OpeningPrice => "4".to_string(),
+            MDEntryType::ClosingPrice => "5".to_string(),
+            MDEntryType::SettlementPrice => "6".to_string(),
+            MDEntryType::TradingSessionHighPrice => "7".to_string(),
+            MDEntryType::TradingSessionLowPrice => "8".to_string(),
+            MDEntryType::TradingSessionVwapPrice => "9".to_string(),
+            MDEntryType::Imbalance => "A".to_string(),
+            MDEntryType::TradeVolume => "B".to_string(),
+            MDEntryType::OpenInterest => "C".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MDUpdateAction {
+    New,
+    Change,
+    Delete,
+}
+
+impl FromStr for MDUpdateAction {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "0" => Ok(MDUpdateAction::New),
+            "1" => Ok(MDUpdateAction::Change),
+            "2" => Ok(MDUpdateAction::Delete),
+            _ => Err("Invalid MDUpdateAction value"),
+        }
+    }
+}
+
+impl FixField for MDUpdateAction {
+    fn tag_id(&self) -> &'static str {
+        "279"
+    }
+
+    fn field_name(&self) -> &'static str {
+        "MDUpdateAction"
+    }
+
+    fn value(&self) -> String {
+        match self {
+            MDUpdateAction::New => "0".to_string(),
+            MDUpdateAction::Change => "1".to_string(),
+            MDUpdateAction::Delete => "2".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscriptionRequestType {
+    Snapshot,
+    SnapshotPlusUpdates,
+    DisablePreviousSnapshot,
+}
+
+impl FromStr for SubscriptionRequestType {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "0" => Ok(SubscriptionRequestType::Snapshot),
+            "1" => Ok(SubscriptionRequestType::SnapshotPlusUpdates),
+            "2" => Ok(SubscriptionRequestType::DisablePreviousSnapshot),
+            _ => Err("Invalid SubscriptionRequestType value"),
+        }
+    }
+}
+
+impl FixField for SubscriptionRequestType {
+    fn tag_id(&self) -> &'static str {
+        "263"
+    }
+
+    fn field_name(&self) -> &'static str {
+        "SubscriptionRequestType"
+    }
+
+    fn value(&self) -> String {
+        match self {
+            SubscriptionRequestType::Snapshot => "0".to_string(),
+            SubscriptionRequestType::SnapshotPlusUpdates => "1".to_string(),
+            SubscriptionRequestType::DisablePreviousSnapshot => "2".to_string(),
         }
     }
 }
@@ -169,14 +924,101 @@ pub enum FixTag {
     TargetCompID(CompID),
     SenderSubID(String),
     TargetSubID(String),
+    OnBehalfOfCompID(String),
     OnBehalfOfSubID(String),
+    DeliverToCompID(String),
+    DeliverToSubID(String),
     MsgSeqNum(String),
     SenderLocationID(String),
     PossDupFlag(PossDupFlag),
     OrigSendingTime(String),
     SendingTime(String),
     Checksum(String),
-    Symbol(String)
+    Symbol(String),
+    ClOrdID(String),
+    Side(Side),
+    OrderQty(FixDecimal),
+    OrdType(OrdType),
+    Price(FixDecimal),
+    TimeInForce(TimeInForce),
+    EncryptMethod(EncryptMethod),
+    SessionRejectReason(SessionRejectReason),
+    CxlRejReason(CxlRejReason),
+    BusinessRejectReason(BusinessRejectReason),
+    DkReason(DkReason),
+    ResetSeqNumFlag(ResetSeqNumFlag),
+    MDReqID(String),
+    SubscriptionRequestType(SubscriptionRequestType),
+    MarketDepth(String),
+    NoMDEntryTypes(String),
+    NoRelatedSym(String),
+    MDEntryType(MDEntryType),
+    MDUpdateAction(MDUpdateAction),
+    MDEntryPx(String),
+    MDEntrySize(String),
+    HeartBtInt(String),
+    ApplVerID(String),
+    DefaultApplVerID(String),
+}
+
+impl FixTag {
+    pub fn md_entry_px(price: &str) -> FixTag {
+        FixTag::MDEntryPx(price.to_string())
+    }
+
+    pub fn md_entry_size(qty: &str) -> FixTag {
+        FixTag::MDEntrySize(qty.to_string())
+    }
+
+    // Builds the typed tag for a tag=value pair; tags without a FixTag variant are rejected
+    pub fn parse(tag: &str, value: &str) -> Result<FixTag, &'static str> {
+        let text = value.to_string();
+        match tag {
+            "8" => BeginString::from_wire(value).map(FixTag::BeginString),
+            "35" => value.parse().map(FixTag::MsgType),
+            "9" => Ok(FixTag::BodyLength(text)),
+            "49" => CompID::new(text).map(FixTag::SenderCompID),
+            "56" => CompID::new(text).map(FixTag::TargetCompID),
+            "50" => Ok(FixTag::SenderSubID(text)),
+            "57" => Ok(FixTag::TargetSubID(text)),
+            "115" => Ok(FixTag::OnBehalfOfCompID(text)),
+            "116" => Ok(FixTag::OnBehalfOfSubID(text)),
+            "128" => Ok(FixTag::DeliverToCompID(text)),
+            "129" => Ok(FixTag::DeliverToSubID(text)),
+            "34" => Ok(FixTag::MsgSeqNum(text)),
+            "142" => Ok(FixTag::SenderLocationID(text)),
+            "43" => value.parse().map(FixTag::PossDupFlag),
+            "122" => Ok(FixTag::OrigSendingTime(text)),
+            "52" => Ok(FixTag::SendingTime(text)),
+            "10" => Ok(FixTag::Checksum(text)),
+            "55" => Ok(FixTag::Symbol(text)),
+            "11" => Ok(FixTag::ClOrdID(text)),
+            "54" => value.parse().map(FixTag::Side),
+            "38" => value.parse().map(FixTag::OrderQty),
+            "40" => value.parse().map(FixTag::OrdType),
+            "44" => value.parse().map(FixTag::Price),
+            "59" => value.parse().map(FixTag::TimeInForce),
+            "98" => value.parse().map(FixTag::EncryptMethod),
+            "373" => value.parse().map(FixTag::SessionRejectReason),
+            "102" => value.parse().map(FixTag::CxlRejReason),
+            "380" => value.parse().map(FixTag::BusinessRejectReason),
+            "127" => value.parse().map(FixTag::DkReason),
+            "141" => value.parse().map(FixTag::ResetSeqNumFlag),
+            "262" => Ok(FixTag::MDReqID(text)),
+            "263" => value.parse().map(FixTag::SubscriptionRequestType),
+            "264" => Ok(FixTag::MarketDepth(text)),
+            "267" => Ok(FixTag::NoMDEntryTypes(text)),
+            "146" => Ok(FixTag::NoRelatedSym(text)),
+            "269" => value.parse().map(FixTag::MDEntryType),
+            "279" => value.parse().map(FixTag::MDUpdateAction),
+            "270" => Ok(FixTag::MDEntryPx(text)),
+            "271" => Ok(FixTag::MDEntrySize(text)),
+            "108" => Ok(FixTag::HeartBtInt(text)),
+            "1128" => Ok(FixTag::ApplVerID(text)),
+            "1137" => Ok(FixTag::DefaultApplVerID(text)),
+            _ => Err("Unsupported tag"),
+        }
+    }
 }
 
 impl FixField for FixTag {
@@ -189,14 +1031,41 @@ impl FixField for FixTag {
             FixTag::TargetCompID(_) => "56",
             FixTag::SenderSubID(_) => "50",
             FixTag::TargetSubID(_) => "57",
+            FixTag::OnBehalfOfCompID(_) => "115",
             FixTag::OnBehalfOfSubID(_) => "116",
+            FixTag::DeliverToCompID(_) => "128",
+            FixTag::DeliverToSubID(_) => "129",
             FixTag::MsgSeqNum(_) => "34",
             FixTag::SenderLocationID(_) => "142",
             FixTag::PossDupFlag(f) => f.tag_id(),
             FixTag::OrigSendingTime(_) => "122",
             FixTag::SendingTime(_) => "52",
             FixTag::Checksum(_) => "10",
-            FixTag::Symbol(_) => "55"
+            FixTag::Symbol(_) => "55",
+            FixTag::ClOrdID(_) => "11",
+            FixTag::Side(f) => f.tag_id(),
+            FixTag::OrderQty(_) => "38",
+            FixTag::OrdType(f) => f.tag_id(),
+            FixTag::Price(_) => "44",
+            FixTag::TimeInForce(f) => f.tag_id(),
+            FixTag::EncryptMethod(f) => f.tag_id(),
+            FixTag::SessionRejectReason(f) => f.tag_id(),
+            FixTag::CxlRejReason(f) => f.tag_id(),
+            FixTag::BusinessRejectReason(f) => f.tag_id(),
+            FixTag::DkReason(f) => f.tag_id(),
+            FixTag::ResetSeqNumFlag(f) => f.tag_id(),
+            FixTag::MDReqID(_) => "262",
+            FixTag::SubscriptionRequestType(f) => f.tag_id(),
+            FixTag::MarketDepth(_) => "264",
+            FixTag::NoMDEntryTypes(_) => "267",
+            FixTag::NoRelatedSym(_) => "146",
+            FixTag::MDEntryType(f) => f.tag_id(),
+            FixTag::MDUpdateAction(f) => f.tag_id(),
+            FixTag::MDEntryPx(_) => "270",
+            FixTag::MDEntrySize(_) => "271",
+            FixTag::HeartBtInt(_) => "108",
+            FixTag::ApplVerID(_) => "1128",
+            FixTag::DefaultApplVerID(_) => "1137",
         }
     }
 
@@ -209,14 +1078,41 @@ impl FixField for FixTag {
             FixTag::TargetCompID(_) => "TargetCompID",
             FixTag::SenderSubID(_) => "SenderSubID",
             FixTag::TargetSubID(_) => "TargetSubID",
+            FixTag::OnBehalfOfCompID(_) => "OnBehalfOfCompID",
             FixTag::OnBehalfOfSubID(_) => "OnBehalfOfSubID",
+            FixTag::DeliverToCompID(_) => "DeliverToCompID",
+            FixTag::DeliverToSubID(_) => "DeliverToSubID",
             FixTag::MsgSeqNum(_) => "MsgSeqNum",
             FixTag::SenderLocationID(_) => "SenderLocationID",
             FixTag::PossDupFlag(f) => f.field_name(),
             FixTag::OrigSendingTime(_) => "OrigSendingTime",
             FixTag::SendingTime(_) => "SendingTime",
-            FixTag::Checksum(_) => "Checksum",
-            FixTag::Symbol(_) => "Symbol"
+            FixTag::Checksum(_) => "CheckSum",
+            FixTag::Symbol(_) => "Symbol",
+            FixTag::ClOrdID(_) => "ClOrdID",
+            FixTag::Side(f) => f.field_name(),
+            FixTag::OrderQty(_) => "OrderQty",
+            FixTag::OrdType(f) => f.field_name(),
+            FixTag::Price(_) => "Price",
+            FixTag::TimeInForce(f) => f.field_name(),
+            FixTag::EncryptMethod(f) => f.field_name(),
+            FixTag::SessionRejectReason(f) => f.field_name(),
+            FixTag::CxlRejReason(f) => f.field_name(),
+            FixTag::BusinessRejectReason(f) => f.field_name(),
+            FixTag::DkReason(f) => f.field_name(),
+            FixTag::ResetSeqNumFlag(f) => f.field_name(),
+            FixTag::MDReqID(_) => "MDReqID",
+            FixTag::SubscriptionRequestType(f) => f.field_name(),
+            FixTag::MarketDepth(_) => "MarketDepth",
+            FixTag::NoMDEntryTypes(_) => "NoMDEntryTypes",
+            FixTag::NoRelatedSym(_) => "NoRelatedSym",
+            FixTag::MDEntryType(f) => f.field_name(),
+            FixTag::MDUpdateAction(f) => f.field_name(),
+            FixTag::MDEntryPx(_) => "MDEntryPx",
+            FixTag::MDEntrySize(_) => "MDEntrySize",
+            FixTag::HeartBtInt(_) => "HeartBtInt",
+            FixTag::ApplVerID(_) => "ApplVerID",
+            FixTag::DefaultApplVerID(_) => "DefaultApplVerID",
         }
     }
 
@@ -229,14 +1125,41 @@ impl FixField for FixTag {
             FixTag::TargetCompID(f) => f.value(),
             FixTag::SenderSubID(sub_id) => sub_id.to_string(),
             FixTag::TargetSubID(sub_id) => sub_id.to_string(),
+            FixTag::OnBehalfOfCompID(comp_id) => comp_id.to_string(),
             FixTag::OnBehalfOfSubID(sub_id) => sub_id.to_string(),
+            FixTag::DeliverToCompID(comp_id) => comp_id.to_string(),
+            FixTag::DeliverToSubID(sub_id) => sub_id.to_string(),
             FixTag::MsgSeqNum(seq_num) => seq_num.to_string(),
             FixTag::SenderLocationID(location_id) => location_id.to_string(),
             FixTag::PossDupFlag(f) => f.value(),
             FixTag::OrigSendingTime(orig_time) => orig_time.to_string(),
             FixTag::SendingTime(time) => time.to_string(),
             FixTag::Checksum(checksum) => checksum.to_string(),
-            FixTag::Symbol(symbol) => symbol.to_string()
+            FixTag::Symbol(symbol) => symbol.to_string(),
+            FixTag::ClOrdID(cl_ord_id) => cl_ord_id.to_string(),
+            FixTag::Side(f) => f.value(),
+            FixTag::OrderQty(qty) => qty.to_string(),
+            FixTag::OrdType(f) => f.value(),
+            FixTag::Price(price) => price.to_string(),
+            FixTag::TimeInForce(f) => f.value(),
+            FixTag::EncryptMethod(f) => f.value(),
+            FixTag::SessionRejectReason(f) => f.value(),
+            FixTag::CxlRejReason(f) => f.value(),
+            FixTag::BusinessRejectReason(f) => f.value(),
+            FixTag::DkReason(f) => f.value(),
+            FixTag::ResetSeqNumFlag(f) => f.value(),
+            FixTag::MDReqID(req_id) => req_id.to_string(),
+            FixTag::SubscriptionRequestType(f) => f.value(),
+            FixTag::MarketDepth(depth) => depth.to_string(),
+            FixTag::NoMDEntryTypes(count) => count.to_string(),
+            FixTag::NoRelatedSym(count) => count.to_string(),
+            FixTag::MDEntryType(f) => f.value(),
+            FixTag::MDUpdateAction(f) => f.value(),
+            FixTag::MDEntryPx(price) => price.to_string(),
+            FixTag::MDEntrySize(size) => size.to_string(),
+            FixTag::HeartBtInt(interval) => interval.to_string(),
+            FixTag::ApplVerID(version) => version.to_string(),
+            FixTag::DefaultApplVerID(version) => version.to_string(),
         }
     }
 }
@@ -258,12 +1181,12 @@ mod tests {
         assert_eq!(poss_dup_tag.field_name(), "PossDupFlag");
         assert_eq!(poss_dup_tag.value(), "Y");
 
-        let sender_comp_id_tag = FixTag::SenderCompID(CompID::new("Sender123".to_string()));
+        let sender_comp_id_tag = FixTag::SenderCompID(CompID::new("Sender123".to_string()).unwrap());
         assert_eq!(sender_comp_id_tag.tag_id(), "49");
         assert_eq!(sender_comp_id_tag.field_name(), "SenderCompID");
         assert_eq!(sender_comp_id_tag.value(), "Sender123");
 
-        let target_comp_id_tag = FixTag::TargetCompID(CompID::new("Target123".to_string()));
+        let target_comp_id_tag = FixTag::TargetCompID(CompID::new("Target123".to_string()).unwrap());
         assert_eq!(target_comp_id_tag.tag_id(), "56");
         assert_eq!(target_comp_id_tag.field_name(), "TargetCompID");
         assert_eq!(target_comp_id_tag.value(), "Target123");
@@ -273,4 +1196,296 @@ mod tests {
         assert_eq!(msg_seq_num_tag.field_name(), "MsgSeqNum");
         assert_eq!(msg_seq_num_tag.value(), "0");
     }
+
+    #[test]
+    fn test_begin_string_from_wire_round_trip() {
+        let versions = [
+            BeginString::Fix4_0,
+            BeginString::Fix4_1,
+            BeginString::Fix4_2,
+            BeginString::Fix4_3,
+            BeginString::Fix4_4,
+            BeginString::Fix5_0,
+            BeginString::FixT1_1,
+        ];
+        for version in versions {
+            assert_eq!(BeginString::from_wire(&version.value()), Ok(version));
+        }
+
+        assert_eq!(BeginString::FixT1_1.value(), "FIXT.1.1");
+        assert!(BeginString::from_wire("FIX.9.9").is_err());
+        assert!(BeginString::from_wire("").is_err());
+    }
+
+    #[test]
+    fn test_side_round_trip() {
+        assert_eq!(Side::Buy.tag_id(), "54");
+        assert_eq!(Side::Buy.field_name(), "Side");
+        assert_eq!(Side::Buy.value(), "1");
+        assert_eq!(Side::Sell.value(), "2");
+        assert_eq!(Side::SellShort.value(), "5");
+
+        let sides = [
+            Side::Buy,
+            Side::Sell,
+            Side::BuyMinus,
+            Side::SellPlus,
+            Side::SellShort,
+            Side::SellShortExempt,
+            Side::Undisclosed,
+            Side::Cross,
+            Side::CrossShort,
+        ];
+        for side in sides {
+            assert_eq!(Side::from_str(&side.value()), Ok(side));
+        }
+        assert!(Side::from_str("0").is_err());
+    }
+
+    #[test]
+    fn test_ord_type_round_trip() {
+        assert_eq!(OrdType::Limit.tag_id(), "40");
+        assert_eq!(OrdType::Limit.field_name(), "OrdType");
+        assert_eq!(OrdType::Market.value(), "1");
+        assert_eq!(OrdType::Limit.value(), "2");
+        assert_eq!(OrdType::Stop.value(), "3");
+        assert_eq!(OrdType::StopLimit.value(), "4");
+
+        for ord_type in [OrdType::Market, OrdType::Limit, OrdType::Stop, OrdType::StopLimit] {
+            assert_eq!(OrdType::from_str(&ord_type.value()), Ok(ord_type));
+        }
+        assert!(OrdType::from_str("Z").is_err());
+    }
+
+    #[test]
+    fn test_time_in_force_round_trip() {
+        assert_eq!(TimeInForce::Day.tag_id(), "59");
+        assert_eq!(TimeInForce::Day.field_name(), "TimeInForce");
+        assert_eq!(TimeInForce::Day.value(), "0");
+        assert_eq!(TimeInForce::GoodTillCancel.value(), "1");
+        assert_eq!(TimeInForce::ImmediateOrCancel.value(), "3");
+        assert_eq!(TimeInForce::FillOrKill.value(), "4");
+
+        let values = [
+            TimeInForce::Day,
+            TimeInForce::GoodTillCancel,
+            TimeInForce::AtTheOpening,
+            TimeInForce::ImmediateOrCancel,
+            TimeInForce::FillOrKill,
+            TimeInForce::GoodTillDate,
+        ];
+        for tif in values {
+            assert_eq!(TimeInForce::from_str(&tif.value()), Ok(tif));
+        }
+        assert!(TimeInForce::from_str("5").is_err());
+    }
+
+    fn assert_wire_values<T>(tag_id: &str, expected: &[(T, &str)])
+    where
+        T: FixField + FromStr + PartialEq + std::fmt::Debug,
+        <T as FromStr>::Err: std::fmt::Debug,
+    {
+        for (field, wire) in expected {
+            assert_eq!(field.tag_id(), tag_id);
+            assert_eq!(field.value(), *wire);
+            assert_eq!(&T::from_str(wire).unwrap(), field);
+        }
+    }
+
+    #[test]
+    fn test_encrypt_method_values() {
+        assert_wire_values("98", &[
+            (EncryptMethod::None, "0"),
+            (EncryptMethod::Pkcs, "1"),
+            (EncryptMethod::Des, "2"),
+            (EncryptMethod::PkcsDes, "3"),
+            (EncryptMethod::PgpDes, "4"),
+            (EncryptMethod::PgpDesMd5, "5"),
+            (EncryptMethod::PemDesMd5, "6"),
+        ]);
+        assert!(EncryptMethod::from_str("7").is_err());
+        assert!(EncryptMethod::from_str("").is_err());
+    }
+
+    #[test]
+    fn test_session_reject_reason_values() {
+        assert_wire_values("373", &[
+            (SessionRejectReason::InvalidTagNumber, "0"),
+            (SessionRejectReason::RequiredTagMissing, "1"),
+            (SessionRejectReason::TagNotDefinedForMessageType, "2"),
+            (SessionRejectReason::UndefinedTag, "3"),
+            (SessionRejectReason::TagSpecifiedWithoutValue, "4"),
+            (SessionRejectReason::ValueIsIncorrect, "5"),
+            (SessionRejectReason::IncorrectDataFormat, "6"),
+            (SessionRejectReason::DecryptionProblem, "7"),
+            (SessionRejectReason::SignatureProblem, "8"),
+            (SessionRejectReason::CompIDProblem, "9"),
+            (SessionRejectReason::SendingTimeAccuracyProblem, "10"),
+            (SessionRejectReason::InvalidMsgType, "11"),
+            (SessionRejectReason::XmlValidationError, "12"),
+            (SessionRejectReason::TagAppearsMoreThanOnce, "13"),
+            (SessionRejectReason::TagSpecifiedOutOfRequiredOrder, "14"),
+            (SessionRejectReason::RepeatingGroupFieldsOutOfOrder, "15"),
+            (SessionRejectReason::IncorrectNumInGroupCount, "16"),
+            (SessionRejectReason::NonDataValueIncludesFieldDelimiter, "17"),
+            (SessionRejectReason::Other, "99"),
+        ]);
+        assert!(SessionRejectReason::from_str("18").is_err());
+        assert!(SessionRejectReason::from_str("-1").is_err());
+        assert!(SessionRejectReason::from_str("abc").is_err());
+    }
+
+    #[test]
+    fn test_cxl_rej_reason_values() {
+        assert_wire_values("102", &[
+            (CxlRejReason::TooLateToCancel, "0"),
+            (CxlRejReason::UnknownOrder, "1"),
+            (CxlRejReason::BrokerOption, "2"),
+            (CxlRejReason::OrderAlreadyPending, "3"),
+            (CxlRejReason::UnableToProcessMassCancel, "4"),
+            (CxlRejReason::OrigOrdModTimeMismatch, "5"),
+            (CxlRejReason::DuplicateClOrdID, "6"),
+            (CxlRejReason::Other, "99"),
+        ]);
+        assert!(CxlRejReason::from_str("7").is_err());
+        assert!(CxlRejReason::from_str("98").is_err());
+    }
+
+    #[test]
+    fn test_business_reject_reason_values() {
+        assert_wire_values("380", &[
+            (BusinessRejectReason::Other, "0"),
+            (BusinessRejectReason::UnknownID, "1"),
+            (BusinessRejectReason::UnknownSecurity, "2"),
+            (BusinessRejectReason::UnsupportedMessageType, "3"),
+            (BusinessRejectReason::ApplicationNotAvailable, "4"),
+            (BusinessRejectReason::ConditionallyRequiredFieldMissing, "5"),
+            (BusinessRejectReason::NotAuthorized, "6"),
+            (BusinessRejectReason::DeliverToFirmNotAvailable, "7"),
+            (BusinessRejectReason::InvalidPriceIncrement, "18"),
+        ]);
+        assert!(BusinessRejectReason::from_str("8").is_err());
+        assert!(BusinessRejectReason::from_str("99").is_err());
+    }
+
+    #[test]
+    fn test_dk_reason_values() {
+        assert_wire_values("127", &[
+            (DkReason::UnknownSymbol, "A"),
+            (DkReason::WrongSide, "B"),
+            (DkReason::QuantityExceedsOrder, "C"),
+            (DkReason::NoMatchingOrder, "D"),
+            (DkReason::PriceExceedsLimit, "E"),
+            (DkReason::CalculationDifference, "F"),
+            (DkReason::Other, "Z"),
+        ]);
+        assert!(DkReason::from_str("G").is_err());
+    }
+
+    #[test]
+    fn test_reset_seq_num_flag_values() {
+        assert_wire_values("141", &[
+            (ResetSeqNumFlag::Yes, "Y"),
+            (ResetSeqNumFlag::No, "N"),
+        ]);
+        assert!(ResetSeqNumFlag::from_str("y").is_err());
+        assert!(ResetSeqNumFlag::from_str("1").is_err());
+        assert_eq!(FixTag::ResetSeqNumFlag(ResetSeqNumFlag::Yes).field_name(), "ResetSeqNumFlag");
+    }
+
+    #[test]
+    fn test_md_entry_type_values() {
+        assert_wire_values("269", &[
+            (MDEntryType::Bid, "0"),
+            (MDEntryType::Offer, "1"),
+            (MDEntryType::Trade, "2"),
+            (MDEntryType::IndexValue, "3"),
+            (MDEntryType::OpeningPrice, "4"),
+            (MDEntryType::ClosingPrice, "5"),
+            (MDEntryType::SettlementPrice, "6"),
+            (MDEntryType::TradingSessionHighPrice, "7"),
+            (MDEntryType::TradingSessionLowPrice, "8"),
+            (MDEntryType::TradingSessionVwapPrice, "9"),
+            (MDEntryType::Imbalance, "A"),
+            (MDEntryType::TradeVolume, "B"),
+            (MDEntryType::OpenInterest, "C"),
+        ]);
+        assert!(MDEntryType::from_str("D").is_err());
+    }
+
+    #[test]
+    fn test_md_update_action_values() {
+        assert_wire_values("279", &[
+            (MDUpdateAction::New, "0"),
+            (MDUpdateAction::Change, "1"),
+            (MDUpdateAction::Delete, "2"),
+        ]);
+        assert!(MDUpdateAction::from_str("3").is_err());
+    }
+
+    #[test]
+    fn test_subscription_request_type_values() {
+        assert_wire_values("263", &[
+            (SubscriptionRequestType::Snapshot, "0"),
+            (SubscriptionRequestType::SnapshotPlusUpdates, "1"),
+            (SubscriptionRequestType::DisablePreviousSnapshot, "2"),
+        ]);
+        assert!(SubscriptionRequestType::from_str("3").is_err());
+    }
+
+    #[test]
+    fn test_md_entry_px_and_size_helpers() {
+        let px = FixTag::md_entry_px("101.5");
+        assert_eq!(px.tag_id(), "270");
+        assert_eq!(px.field_name(), "MDEntryPx");
+        assert_eq!(px.value(), "101.5");
+
+        let size = FixTag::md_entry_size("250");
+        assert_eq!(size.tag_id(), "271");
+        assert_eq!(size.field_name(), "MDEntrySize");
+        assert_eq!(size.value(), "250");
+    }
+
+    #[test]
+    fn test_tag_numbers_match_fix_tags() {
+        let samples = [
+            (numbers::BEGIN_STRING, FixTag::BeginString(BeginString::Fix4_4)),
+            (numbers::MSG_TYPE, FixTag::MsgType(MsgType::Logon)),
+            (numbers::SENDER_COMP_ID, FixTag::SenderCompID(CompID::new("S".to_string()).unwrap())),
+            (numbers::TARGET_COMP_ID, FixTag::TargetCompID(CompID::new("T".to_string()).unwrap())),
+            (numbers::ON_BEHALF_OF_COMP_ID, FixTag::OnBehalfOfCompID("CLIENT".to_string())),
+            (numbers::ON_BEHALF_OF_SUB_ID, FixTag::OnBehalfOfSubID("SUB".to_string())),
+            (numbers::DELIVER_TO_COMP_ID, FixTag::DeliverToCompID("VENUE".to_string())),
+            (numbers::MSG_SEQ_NUM, FixTag::MsgSeqNum("1".to_string())),
+            (numbers::CHECK_SUM, FixTag::Checksum("000".to_string())),
+            (numbers::CL_ORD_ID, FixTag::ClOrdID("1".to_string())),
+            (numbers::ORDER_QTY, FixTag::OrderQty("1".parse().unwrap())),
+            (numbers::TIME_IN_FORCE, FixTag::TimeInForce(TimeInForce::Day)),
+            (numbers::RESET_SEQ_NUM_FLAG, FixTag::ResetSeqNumFlag(ResetSeqNumFlag::Yes)),
+            (numbers::NO_MD_ENTRY_TYPES, FixTag::NoMDEntryTypes("2".to_string())),
+            (numbers::MD_ENTRY_PX, FixTag::md_entry_px("1")),
+            (numbers::APPL_VER_ID, FixTag::ApplVerID("9".to_string())),
+            (numbers::DEFAULT_APPL_VER_ID, FixTag::DefaultApplVerID("9".to_string())),
+        ];
+        for (number, tag) in samples {
+            assert_eq!(number.to_string(), tag.tag_id());
+            assert_eq!(numbers::field_name(number), Some(tag.field_name()));
+            assert_eq!(numbers::tag_number(tag.field_name()), Some(number));
+        }
+
+        assert_eq!(numbers::HEART_BT_INT, 108);
+        assert!(numbers::FIELDS.len() >= 150);
+        assert_eq!(numbers::field_name(99999), None);
+    }
+
+    #[test]
+    fn test_parse_builds_typed_tags() {
+        for (tag, value) in [("8", "FIX.4.2"), ("35", "AE"), ("49", "SENDER"), ("54", "2"), ("44", "101.25"), ("108", "30"), ("269", "B")] {
+            let parsed = FixTag::parse(tag, value).unwrap();
+            assert_eq!((parsed.tag_id(), parsed.value().as_str()), (tag, value));
+        }
+        assert!(matches!(FixTag::parse("35", "A"), Ok(FixTag::MsgType(MsgType::Logon))));
+        assert_eq!(FixTag::parse("35", "ZZ").err(), Some("Invalid MsgType value"));
+        assert_eq!(FixTag::parse("9999", "x").err(), Some("Unsupported tag"));
+    }
 }
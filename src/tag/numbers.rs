@@ -0,0 +1,228 @@
+// Tag numbers for the most common FIX 4.4 fields. Each entry also carries the field name used by
+// `FixField::field_name`, so lookups in both directions come from the same table.
+
+macro_rules! fix_tag_numbers {
+    ($($constant:ident = $number:literal, $name:literal;)*) => {
+        $(pub const $constant: u32 = $number;)*
+
+        pub const FIELDS: &[(u32, &str)] = &[$(($number, $name)),*];
+
+        pub fn field_name(tag: u32) -> Option<&'static str> {
+            match tag {
+                $($number => Some($name),)*
+                _ => None,
+            }
+        }
+
+        pub fn tag_number(name: &str) -> Option<u32> {
+            match name {
+                $($name => Some($number),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+fix_tag_numbers! {
+    ACCOUNT = 1, "Account";
+    AVG_PX = 6, "AvgPx";
+    BEGIN_SEQ_NO = 7, "BeginSeqNo";
+    BEGIN_STRING = 8, "BeginString";
+    BODY_LENGTH = 9, "BodyLength";
+    CHECK_SUM = 10, "CheckSum";
+    CL_ORD_ID = 11, "ClOrdID";
+    COMMISSION = 12, "Commission";
+    COMM_TYPE = 13, "CommType";
+    CUM_QTY = 14, "CumQty";
+    CURRENCY = 15, "Currency";
+    END_SEQ_NO = 16, "EndSeqNo";
+    EXEC_ID = 17, "ExecID";
+    EXEC_INST = 18, "ExecInst";
+    EXEC_REF_ID = 19, "ExecRefID";
+    HANDL_INST = 21, "HandlInst";
+    SECURITY_ID_SOURCE = 22, "SecurityIDSource";
+    IOI_ID = 23, "IOIID";
+    IOI_QLTY_IND = 25, "IOIQltyInd";
+    IOI_REF_ID = 26, "IOIRefID";
+    IOI_QTY = 27, "IOIQty";
+    IOI_TRANS_TYPE = 28, "IOITransType";
+    LAST_CAPACITY = 29, "LastCapacity";
+    LAST_MKT = 30, "LastMkt";
+    LAST_PX = 31, "LastPx";
+    LAST_QTY = 32, "LastQty";
+    MSG_SEQ_NUM = 34, "MsgSeqNum";
+    MSG_TYPE = 35, "MsgType";
+    NEW_SEQ_NO = 36, "NewSeqNo";
+    ORDER_ID = 37, "OrderID";
+    ORDER_QTY = 38, "OrderQty";
+    ORD_STATUS = 39, "OrdStatus";
+    ORD_TYPE = 40, "OrdType";
+    ORIG_CL_ORD_ID = 41, "OrigClOrdID";
+    POSS_DUP_FLAG = 43, "PossDupFlag";
+    PRICE = 44, "Price";
+    REF_SEQ_NUM = 45, "RefSeqNum";
+    SECURITY_ID = 48, "SecurityID";
+    SENDER_COMP_ID = 49, "SenderCompID";
+    SENDER_SUB_ID = 50, "SenderSubID";
+    SENDING_TIME = 52, "SendingTime";
+    QUANTITY = 53, "Quantity";
+    SIDE = 54, "Side";
+    SYMBOL = 55, "Symbol";
+    TARGET_COMP_ID = 56, "TargetCompID";
+    TARGET_SUB_ID = 57, "TargetSubID";
+    TEXT = 58, "Text";
+    TIME_IN_FORCE = 59, "TimeInForce";
+    TRANSACT_TIME = 60, "TransactTime";
+    URGENCY = 61, "Urgency";
+    VALID_UNTIL_TIME = 62, "ValidUntilTime";
+    SETTL_TYPE = 63, "SettlType";
+    SETTL_DATE = 64, "SettlDate";
+    SYMBOL_SFX = 65, "SymbolSfx";
+    LIST_ID = 66, "ListID";
+    LIST_SEQ_NO = 67, "ListSeqNo";
+    TOT_NO_ORDERS = 68, "TotNoOrders";
+    LIST_EXEC_INST = 69, "ListExecInst";
+    ALLOC_ID = 70, "AllocID";
+    ALLOC_TRANS_TYPE = 71, "AllocTransType";
+    REF_ALLOC_ID = 72, "RefAllocID";
+    NO_ORDERS = 73, "NoOrders";
+    AVG_PX_PRECISION = 74, "AvgPxPrecision";
+    TRADE_DATE = 75, "TradeDate";
+    POSITION_EFFECT = 77, "PositionEffect";
+    NO_ALLOCS = 78, "NoAllocs";
+    ALLOC_ACCOUNT = 79, "AllocAccount";
+    ALLOC_QTY = 80, "AllocQty";
+    PROCESS_CODE = 81, "ProcessCode";
+    NO_RPTS = 82, "NoRpts";
+    RPT_SEQ = 83, "RptSeq";
+    CXL_QTY = 84, "CxlQty";
+    ALLOC_STATUS = 87, "AllocStatus";
+    ALLOC_REJ_CODE = 88, "AllocRejCode";
+    SIGNATURE = 89, "Signature";
+    SECURE_DATA_LEN = 90, "SecureDataLen";
+    SECURE_DATA = 91, "SecureData";
+    SIGNATURE_LENGTH = 93, "SignatureLength";
+    EMAIL_TYPE = 94, "EmailType";
+    RAW_DATA_LENGTH = 95, "RawDataLength";
+    RAW_DATA = 96, "RawData";
+    POSS_RESEND = 97, "PossResend";
+    ENCRYPT_METHOD = 98, "EncryptMethod";
+    STOP_PX = 99, "StopPx";
+    EX_DESTINATION = 100, "ExDestination";
+    CXL_REJ_REASON = 102, "CxlRejReason";
+    ORD_REJ_REASON = 103, "OrdRejReason";
+    IOI_QUALIFIER = 104, "IOIQualifier";
+    ISSUER = 106, "Issuer";
+    SECURITY_DESC = 107, "SecurityDesc";
+    HEART_BT_INT = 108, "HeartBtInt";
+    MIN_QTY = 110, "MinQty";
+    MAX_FLOOR = 111, "MaxFloor";
+    TEST_REQ_ID = 112, "TestReqID";
+    LOCATE_REQD = 114, "LocateReqd";
+    ON_BEHALF_OF_COMP_ID = 115, "OnBehalfOfCompID";
+    ON_BEHALF_OF_SUB_ID = 116, "OnBehalfOfSubID";
+    QUOTE_ID = 117, "QuoteID";
+    NET_MONEY = 118, "NetMoney";
+    SETTL_CURR_AMT = 119, "SettlCurrAmt";
+    SETTL_CURRENCY = 120, "SettlCurrency";
+    FOREX_REQ = 121, "ForexReq";
+    ORIG_SENDING_TIME = 122, "OrigSendingTime";
+    GAP_FILL_FLAG = 123, "GapFillFlag";
+    NO_EXECS = 124, "NoExecs";
+    EXPIRE_TIME = 126, "ExpireTime";
+    DK_REASON = 127, "DKReason";
+    DELIVER_TO_COMP_ID = 128, "DeliverToCompID";
+    DELIVER_TO_SUB_ID = 129, "DeliverToSubID";
+    IOI_NATURAL_FLAG = 130, "IOINaturalFlag";
+    QUOTE_REQ_ID = 131, "QuoteReqID";
+    BID_PX = 132, "BidPx";
+    OFFER_PX = 133, "OfferPx";
+    BID_SIZE = 134, "BidSize";
+    OFFER_SIZE = 135, "OfferSize";
+    NO_MISC_FEES = 136, "NoMiscFees";
+    MISC_FEE_AMT = 137, "MiscFeeAmt";
+    MISC_FEE_CURR = 138, "MiscFeeCurr";
+    MISC_FEE_TYPE = 139, "MiscFeeType";
+    PREV_CLOSE_PX = 140, "PrevClosePx";
+    RESET_SEQ_NUM_FLAG = 141, "ResetSeqNumFlag";
+    SENDER_LOCATION_ID = 142, "SenderLocationID";
+    TARGET_LOCATION_ID = 143, "TargetLocationID";
+    ON_BEHALF_OF_LOCATION_ID = 144, "OnBehalfOfLocationID";
+    DELIVER_TO_LOCATION_ID = 145, "DeliverToLocationID";
+    NO_RELATED_SYM = 146, "NoRelatedSym";
+    SUBJECT = 147, "Subject";
+    HEADLINE = 148, "Headline";
+    URL_LINK = 149, "URLLink";
+    EXEC_TYPE = 150, "ExecType";
+    LEAVES_QTY = 151, "LeavesQty";
+    CASH_ORDER_QTY = 152, "CashOrderQty";
+    ALLOC_AVG_PX = 153, "AllocAvgPx";
+    ALLOC_NET_MONEY = 154, "AllocNetMoney";
+    SETTL_CURR_FX_RATE = 155, "SettlCurrFxRate";
+    SETTL_CURR_FX_RATE_CALC = 156, "SettlCurrFxRateCalc";
+    SECURITY_TYPE = 167, "SecurityType";
+    EFFECTIVE_TIME = 168, "EffectiveTime";
+    MATURITY_MONTH_YEAR = 200, "MaturityMonthYear";
+    STRIKE_PRICE = 202, "StrikePrice";
+    SECURITY_EXCHANGE = 207, "SecurityExchange";
+    PEG_OFFSET_VALUE = 211, "PegOffsetValue";
+    MD_REQ_ID = 262, "MDReqID";
+    SUBSCRIPTION_REQUEST_TYPE = 263, "SubscriptionRequestType";
+    MARKET_DEPTH = 264, "MarketDepth";
+    MD_UPDATE_TYPE = 265, "MDUpdateType";
+    NO_MD_ENTRY_TYPES = 267, "NoMDEntryTypes";
+    NO_MD_ENTRIES = 268, "NoMDEntries";
+    MD_ENTRY_TYPE = 269, "MDEntryType";
+    MD_ENTRY_PX = 270, "MDEntryPx";
+    MD_ENTRY_SIZE = 271, "MDEntrySize";
+    MD_ENTRY_DATE = 272, "MDEntryDate";
+    MD_ENTRY_TIME = 273, "MDEntryTime";
+    TICK_DIRECTION = 274, "TickDirection";
+    MD_MKT = 275, "MDMkt";
+    QUOTE_CONDITION = 276, "QuoteCondition";
+    TRADE_CONDITION = 277, "TradeCondition";
+    MD_ENTRY_ID = 278, "MDEntryID";
+    MD_UPDATE_ACTION = 279, "MDUpdateAction";
+    MD_ENTRY_REF_ID = 280, "MDEntryRefID";
+    MD_REQ_REJ_REASON = 281, "MDReqRejReason";
+    MD_ENTRY_ORIGINATOR = 282, "MDEntryOriginator";
+    LOCATION_ID = 283, "LocationID";
+    DESK_ID = 284, "DeskID";
+    DELETE_REASON = 285, "DeleteReason";
+    OPEN_CLOSE_SETTL_FLAG = 286, "OpenCloseSettlFlag";
+    MD_ENTRY_POSITION_NO = 290, "MDEntryPositionNo";
+    LAST_MSG_SEQ_NUM_PROCESSED = 369, "LastMsgSeqNumProcessed";
+    REF_TAG_ID = 371, "RefTagID";
+    REF_MSG_TYPE = 372, "RefMsgType";
+    SESSION_REJECT_REASON = 373, "SessionRejectReason";
+    BUSINESS_REJECT_REASON = 380, "BusinessRejectReason";
+    MAX_MESSAGE_SIZE = 383, "MaxMessageSize";
+    NO_MSG_TYPES = 384, "NoMsgTypes";
+    MSG_DIRECTION = 385, "MsgDirection";
+    PRICE_TYPE = 423, "PriceType";
+    CXL_REJ_RESPONSE_TO = 434, "CxlRejResponseTo";
+    PARTY_ID_SOURCE = 447, "PartyIDSource";
+    PARTY_ID = 448, "PartyID";
+    PARTY_ROLE = 452, "PartyRole";
+    NO_PARTY_IDS = 453, "NoPartyIDs";
+    NO_SECURITY_ALT_ID = 454, "NoSecurityAltID";
+    SECURITY_ALT_ID = 455, "SecurityAltID";
+    SECURITY_ALT_ID_SOURCE = 456, "SecurityAltIDSource";
+    PRODUCT = 460, "Product";
+    CFI_CODE = 461, "CFICode";
+    TEST_MESSAGE_INDICATOR = 464, "TestMessageIndicator";
+    SECONDARY_CL_ORD_ID = 526, "SecondaryClOrdID";
+    SECONDARY_EXEC_ID = 527, "SecondaryExecID";
+    USERNAME = 553, "Username";
+    PASSWORD = 554, "Password";
+    NO_LEGS = 555, "NoLegs";
+    TRADE_REQUEST_ID = 568, "TradeRequestID";
+    TRADE_REQUEST_TYPE = 569, "TradeRequestType";
+    TRADE_REPORT_ID = 571, "TradeReportID";
+    NO_DATES = 580, "NoDates";
+    WORKING_INDICATOR = 636, "WorkingIndicator";
+    NEXT_EXPECTED_MSG_SEQ_NUM = 789, "NextExpectedMsgSeqNum";
+    NEW_PASSWORD = 925, "NewPassword";
+    APPL_VER_ID = 1128, "ApplVerID";
+    DEFAULT_APPL_VER_ID = 1137, "DefaultApplVerID";
+}
@@ -0,0 +1,222 @@
+use crate::clock::{Clock, TIMESTAMP_FORMAT};
+use crate::transport::Transport;
+use chrono::{DateTime, TimeDelta, Utc};
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+// Two connected in-memory endpoints, so an initiator and an acceptor can be wired together without sockets.
+// Bytes written to one are read from the other.
+pub fn duplex() -> (MemoryStream, MemoryStream) {
+    let (a_to_b, b_to_a) = (Arc::new(Pipe::default()), Arc::new(Pipe::default()));
+    let a = MemoryStream { incoming: Arc::clone(&b_to_a), outgoing: Arc::clone(&a_to_b), read_timeout: Arc::default() };
+    let b = MemoryStream { incoming: a_to_b, outgoing: b_to_a, read_timeout: Arc::default() };
+    (a, b)
+}
+
+// One end of a duplex(); clones share the read timeout, like a socket's handles do
+#[derive(Clone)]
+pub struct MemoryStream {
+    incoming: Arc<Pipe>,
+    outgoing: Arc<Pipe>,
+    read_timeout: Arc<Mutex<Option<Duration>>>,
+}
+
+#[derive(Default)]
+struct Pipe {
+    state: Mutex<PipeState>,
+    readable: Condvar,
+}
+
+#[derive(Default)]
+struct PipeState {
+    bytes: VecDeque<u8>,
+    closed: bool,
+}
+
+impl Pipe {
+    fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.readable.notify_all();
+    }
+}
+
+impl MemoryStream {
+    // Closes only the direction this end writes in, like a TCP half-close: the other end reads what is
+    // buffered and then end of stream, while its answers still come through
+    pub fn shutdown_write(&self) {
+        self.outgoing.close();
+    }
+}
+
+impl Read for MemoryStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let deadline = self.read_timeout.lock().unwrap().map(|timeout| Instant::now() + timeout);
+        let mut state = self.incoming.state.lock().unwrap();
+        // Whatever is buffered is still delivered after the pipe is closed, then end of stream
+        while state.bytes.is_empty() && !state.closed {
+            state = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        return Err(io::ErrorKind::WouldBlock.into());
+                    }
+                    self.incoming.readable.wait_timeout(state, remaining).unwrap().0
+                }
+                None => self.incoming.readable.wait(state).unwrap(),
+            };
+        }
+        let size = buf.len().min(state.bytes.len());
+        for (slot, byte) in buf.iter_mut().zip(state.bytes.drain(..size)) {
+            *slot = byte;
+        }
+        Ok(size)
+    }
+}
+
+impl Write for MemoryStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.outgoing.state.lock().unwrap();
+        if state.closed {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+        state.bytes.extend(buf);
+        self.outgoing.readable.notify_all();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for MemoryStream {
+    fn try_clone(&self) -> io::Result<Box<dyn Transport>> {
+        Ok(Box::new(self.clone()))
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        *self.read_timeout.lock().unwrap() = timeout;
+        Ok(())
+    }
+
+    // Writes never wait: a pipe holds whatever is written to it
+    fn set_write_timeout(&self, _timeout: Option<Duration>) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        self.incoming.close();
+        self.outgoing.close();
+        Ok(())
+    }
+}
+
+// A clock that only moves when told to, so heartbeat, timeout, throttle and schedule logic can be driven step
+// by step
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<DateTime<Utc>>,
+    moved: Condvar,
+}
+
+impl ManualClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        ManualClock { now: Mutex::new(start), moved: Condvar::new() }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap() += TimeDelta::from_std(by).expect("duration out of range");
+        self.moved.notify_all();
+    }
+
+    pub fn set(&self, at: DateTime<Utc>) {
+        *self.now.lock().unwrap() = at;
+        self.moved.notify_all();
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> String {
+        self.now_utc().format(TIMESTAMP_FORMAT).to_string()
+    }
+
+    fn now_utc(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+
+    // Returns once the clock has been moved past the deadline, or after the duration in real time at the
+    // latest, so a caller waiting in steps still notices a shutdown
+    fn sleep(&self, duration: Duration) {
+        let started = Instant::now();
+        let mut now = self.now.lock().unwrap();
+        let deadline = *now + TimeDelta::from_std(duration).expect("duration out of range");
+        while *now < deadline {
+            let Some(remaining) = duration.checked_sub(started.elapsed()).filter(|remaining| !remaining.is_zero()) else { break };
+            now = self.moved.wait_timeout(now, remaining).unwrap().0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+
+    #[test]
+    fn test_manual_clock_moves_only_when_told() {
+        let start = NaiveDateTime::parse_from_str("20231016-12:30:00.123", TIMESTAMP_FORMAT).unwrap().and_utc();
+        let clock = ManualClock::new(start);
+        assert_eq!(clock.now(), "20231016-12:30:00.123");
+
+        clock.advance(Duration::from_millis(90_500));
+        assert_eq!(clock.now(), "20231016-12:31:30.623");
+        assert_eq!(clock.now_utc() - start, TimeDelta::milliseconds(90_500));
+
+        clock.set(start - TimeDelta::days(1));
+        assert_eq!(clock.now(), "20231015-12:30:00.123");
+
+        // A sleep ends once the clock is moved far enough, or after the real duration
+        let clock = Arc::new(clock);
+        let mover = Arc::clone(&clock);
+        let moving = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            mover.advance(Duration::from_secs(60));
+        });
+        let started = Instant::now();
+        clock.sleep(Duration::from_secs(30));
+        assert!(started.elapsed() < Duration::from_secs(10));
+        moving.join().unwrap();
+        let started = Instant::now();
+        clock.sleep(Duration::from_millis(20));
+        assert!(started.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_duplex_carries_bytes_both_ways_until_shutdown() {
+        let (mut a, mut b) = duplex();
+        let mut buf = [0; 8];
+
+        a.write_all(b"8=FIX").unwrap();
+        assert_eq!(b.read(&mut buf).unwrap(), 5);
+        assert_eq!(&buf[..5], b"8=FIX");
+        b.write_all(b"10=").unwrap();
+        assert_eq!(a.read(&mut buf).unwrap(), 3);
+
+        b.set_read_timeout(Some(Duration::from_millis(10))).unwrap();
+        assert_eq!(b.read(&mut buf).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+
+        a.write_all(b"x").unwrap();
+        a.shutdown().unwrap();
+        assert_eq!(b.read(&mut buf).unwrap(), 1, "Buffered bytes outlive the shutdown");
+        assert_eq!(b.read(&mut buf).unwrap(), 0);
+        assert_eq!(b.write(b"y").unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+
+        let (a, mut b) = duplex();
+        a.shutdown_write();
+        assert_eq!(b.read(&mut buf).unwrap(), 0);
+        b.write_all(b"z").unwrap();
+        assert_eq!(a.clone().read(&mut buf).unwrap(), 1, "The other direction stays open");
+    }
+}
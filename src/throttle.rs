@@ -0,0 +1,91 @@
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+// What the send thread does with an application message the throttle has no room for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ThrottleSaturation {
+    // Waits for the next token, leaving later messages in the outgoing channel meanwhile
+    #[default]
+    Queue,
+    // Refuses the message straight away: a tracked message's receipt gets SendFailure::Throttled, and every
+    // refusal is also reported as EngineEvent::OutgoingThrottled
+    Reject,
+}
+
+// Limits the application messages sent to `rate` a second, letting up to `burst` go out back to back after a
+// quiet spell. Admin messages such as heartbeats are never held back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThrottlePolicy {
+    pub rate: u32,  // Messages a second; at least 1
+    pub burst: u32, // At least 1
+    pub saturation: ThrottleSaturation,
+}
+
+impl ThrottlePolicy {
+    pub fn new(rate: u32, burst: u32) -> Self {
+        ThrottlePolicy { rate, burst, saturation: ThrottleSaturation::Queue }
+    }
+}
+
+// Token bucket behind a ThrottlePolicy; starts full. Refilled by the times it is given, which the send thread
+// takes from the engine's clock.
+#[derive(Debug)]
+pub(crate) struct TokenBucket {
+    policy: ThrottlePolicy,
+    tokens: f64,
+    refilled: DateTime<Utc>,
+}
+
+impl TokenBucket {
+    pub(crate) fn new(policy: ThrottlePolicy, now: DateTime<Utc>) -> Self {
+        TokenBucket { policy, tokens: policy.burst.max(1) as f64, refilled: now }
+    }
+
+    pub(crate) fn policy(&self) -> &ThrottlePolicy {
+        &self.policy
+    }
+
+    // Whole tokens left, as of the last call to try_take
+    pub(crate) fn tokens(&self) -> u64 {
+        self.tokens as u64
+    }
+
+    // Takes a token for one message, or says how long until there is one
+    pub(crate) fn try_take(&mut self, now: DateTime<Utc>) -> Result<(), Duration> {
+        let rate = self.policy.rate.max(1) as f64;
+        // A clock set back refills nothing
+        let elapsed = (now - self.refilled).to_std().unwrap_or_default().as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(self.policy.burst.max(1) as f64);
+        self.refilled = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / rate))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_allows_a_burst_then_refills_at_the_rate() {
+        let start = Utc::now();
+        let mut bucket = TokenBucket::new(ThrottlePolicy::new(10, 3), start);
+        for _ in 0..3 {
+            assert_eq!(bucket.try_take(start), Ok(()));
+        }
+        assert_eq!(bucket.try_take(start), Err(Duration::from_millis(100)));
+        assert_eq!(bucket.tokens(), 0);
+
+        let later = start + chrono::TimeDelta::milliseconds(150);
+        assert_eq!(bucket.try_take(later), Ok(()));
+        assert!(bucket.try_take(later).is_err());
+        // A long quiet spell fills the bucket no further than the burst
+        let idle = later + chrono::TimeDelta::seconds(60);
+        assert_eq!(bucket.try_take(idle), Ok(()));
+        assert_eq!(bucket.tokens(), 2);
+    }
+}
@@ -0,0 +1,41 @@
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::time::Duration;
+
+// A connected byte stream an engine can run a session over. The receive thread reads through its own handle
+// while the send thread writes, so a transport has to be able to hand out a second handle to itself.
+pub trait Transport: Read + Write + Send + 'static {
+    fn try_clone(&self) -> io::Result<Box<dyn Transport>>;
+    // Reads that wait longer than this fail with WouldBlock or TimedOut; None blocks until data arrives
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+    // Writes that cannot make progress for this long fail with WouldBlock or TimedOut
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+    // Closes both directions for every handle; a blocked read returns end of stream
+    fn shutdown(&self) -> io::Result<()>;
+    // The address at the other end, for transports that have one
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+}
+
+impl Transport for TcpStream {
+    fn try_clone(&self) -> io::Result<Box<dyn Transport>> {
+        Ok(Box::new(TcpStream::try_clone(self)?))
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_write_timeout(self, timeout)
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        TcpStream::shutdown(self, Shutdown::Both)
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        TcpStream::peer_addr(self).ok()
+    }
+}
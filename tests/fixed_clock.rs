@@ -1,5 +1,7 @@
 use std::sync::Arc;
-use fix_engine_2::clock::Clock;
+use chrono::NaiveDateTime;
+use fix_engine_2::clock::{Clock, TIMESTAMP_FORMAT};
+use fix_engine_2::testing::ManualClock;
 
 // A FixedClock for testing purposes
 pub struct FixedClock;
@@ -14,3 +16,9 @@ impl Clock for FixedClock {
 pub fn create_fixed_clock() -> Arc<dyn Clock> {
     Arc::new(FixedClock)
 }
+
+// A ManualClock starting at the same instant as the FixedClock
+pub fn create_manual_clock() -> Arc<ManualClock> {
+    let start = NaiveDateTime::parse_from_str("20231016-12:30:00.123", TIMESTAMP_FORMAT).unwrap().and_utc();
+    Arc::new(ManualClock::new(start))
+}
@@ -1,52 +1,2973 @@
 mod fixed_clock;
 
-use crate::fixed_clock::create_fixed_clock;
-use fix_engine_2::engine_factory::FixEngineFactory;
-use fix_engine_2::message::FixMessage;
+use crate::fixed_clock::{create_fixed_clock, create_manual_clock};
+use chrono::{NaiveTime, TimeDelta, Utc};
+use fix_engine_2::application::{DoNotDeliver, DoNotSend, FixApplication};
+use fix_engine_2::channel::{bounded, OverflowPolicy};
+use fix_engine_2::clock::Clock;
+use fix_engine_2::engine::{FixEngine, FixEngineMode, ShutdownReport};
+use fix_engine_2::engine_config::EngineConfig;
+use fix_engine_2::engine_factory::{FactoryOptions, FixEngineFactory};
+use fix_engine_2::error::{DecodeError, EngineError, FixEngineError};
+use fix_engine_2::event::{DisconnectReason, EngineEvent};
+use fix_engine_2::framer::Framing;
+use fix_engine_2::message::{FixMessage, OrderSingleParams};
+use fix_engine_2::message_log::{FileMessageLog, MessageLog};
+use fix_engine_2::metrics::{MetricsSink, SessionMetricsSnapshot};
+use fix_engine_2::observer::EngineObserver;
+use fix_engine_2::receipt::SendFailure;
+use fix_engine_2::reconnect::{Backoff, QueuePolicy, ReconnectPolicy};
+use fix_engine_2::replay::{ReplayError, ReplaySpeed};
+use fix_engine_2::schedule::SessionSchedule;
+use fix_engine_2::session::{LogonConfig, LogoutReason, SessionConfig, SessionID, SessionState};
+use fix_engine_2::store::{FileMessageStore, MemoryMessageStore, MessageStore};
+use fix_engine_2::tag::{BeginString, EncryptMethod, MsgType, OrdType, Side};
+use fix_engine_2::testing::duplex;
+use fix_engine_2::throttle::{ThrottlePolicy, ThrottleSaturation};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, TrySendError};
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 #[test]
 fn test_initiator_acceptor_can_exchange_messages() {
-    let address = "127.0.0.1:12345";
+    // Returns straight away; the session starts once the initiator connects
+    let config = SessionConfig::new("ACCEPTOR", "INITIATOR");
+    let (mut acceptor, acceptor_sender, acceptor_receiver) = FixEngineFactory::create_acceptor_with_options("127.0.0.1:0", config).unwrap();
+    let address = acceptor.local_addr().unwrap().to_string();
 
-    // Start the acceptor in a separate thread
+    // Start the initiator; it logs on by itself
+    let config = SessionConfig::new("INITIATOR", "ACCEPTOR");
+    let (mut engine, sender, receiver) = FixEngineFactory::create_initiator_with_options(&address, config).unwrap();
+    sender.send(create_new_order_single()).unwrap();
+
+    // The logon is consumed by the engine, so the first message delivered is the order
+    let message = acceptor_receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(message.header.get("35").unwrap(), "D"); // NewOrderSingle message type
+    assert_eq!(acceptor.state(), SessionState::LoggedOn);
+
+    // Send execution report; it still goes out when the acceptor shuts down straight after
+    acceptor_sender.send(create_execution_report()).unwrap();
+    acceptor.shutdown();
+
+    // Receive execution report from acceptor
+    let response = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(response.header.get("35").unwrap(), "8"); // Execution Report message type
+    assert_eq!(response.header.get("49").unwrap(), "ACCEPTOR");
+
+    engine.shutdown();
+    assert_eq!(engine.state(), SessionState::Disconnected);
+}
+
+#[test]
+fn test_factory_reports_bind_and_connect_failures() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    match FixEngineFactory::create_acceptor(&address) {
+        Err(FixEngineError::Bind { address: failed, .. }) => assert_eq!(failed, address),
+        other => panic!("Unexpected result {:?}", other.map(|_| ())),
+    }
+
+    // Nothing listens once the listener is dropped
+    drop(listener);
+    match FixEngineFactory::create_initiator(&address) {
+        Err(FixEngineError::Connect { address: failed, .. }) => assert_eq!(failed, address),
+        other => panic!("Unexpected result {:?}", other.map(|_| ())),
+    }
+}
+
+// A local address whose connection attempts go unanswered: its listener never accepts and its backlog is
+// full, so further SYNs are dropped. Kept alive by the returned sockets.
+fn black_hole() -> ((socket2::Socket, Vec<TcpStream>), std::net::SocketAddr) {
+    let listener = socket2::Socket::new(socket2::Domain::IPV4, socket2::Type::STREAM, None).unwrap();
+    listener.bind(&"127.0.0.1:0".parse::<std::net::SocketAddr>().unwrap().into()).unwrap();
+    listener.listen(0).unwrap();
+    let address = listener.local_addr().unwrap().as_socket().unwrap();
+    let mut queued = Vec::new();
+    while let Ok(stream) = TcpStream::connect_timeout(&address, Duration::from_millis(50)) {
+        queued.push(stream);
+    }
+    ((listener, queued), address)
+}
+
+#[test]
+fn test_initiator_fails_over_to_the_next_address() {
+    let config = SessionConfig::new("ACCEPTOR", "INITIATOR");
+    let (mut acceptor, _acceptor_sender, _acceptor_receiver) = FixEngineFactory::create_acceptor_with_options("127.0.0.1:0", config).unwrap();
+    let live = acceptor.local_addr().unwrap();
+
+    // The first address never answers, so the attempt runs into the connect timeout
+    let (_black_hole, black_hole_address) = black_hole();
+    let engine_config = EngineConfig::builder()
+        .connect_timeout(Duration::from_millis(200))
+        .failover_address(&live.to_string())
+        .build();
+    let started = Instant::now();
+    let (mut initiator, _sender, _receiver) =
+        FixEngineFactory::create_initiator_with_options(&black_hole_address.to_string(), FactoryOptions::new(SessionConfig::new("INITIATOR", "ACCEPTOR")).engine_config(engine_config)).unwrap();
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_millis(150) && elapsed < Duration::from_secs(1), "Took {:?}", elapsed);
+
+    let events = initiator.take_events().unwrap();
+    let connected = loop {
+        if let EngineEvent::Connected { peer } = events.recv_timeout(Duration::from_secs(5)).unwrap() {
+            break peer;
+        }
+    };
+    assert_eq!(connected, Some(live));
+    assert_eq!(initiator.peer_addr(), Some(live));
+    wait_for_state(&initiator, SessionState::LoggedOn);
+    initiator.shutdown();
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_acceptor_gives_up_after_its_accept_timeout() {
+    let engine_config = EngineConfig::builder().accept_timeout(Duration::from_millis(200)).build();
+    let (mut acceptor, _sender, _receiver) =
+        FixEngineFactory::create_acceptor_with_options("127.0.0.1:0", FactoryOptions::new(SessionConfig::default()).engine_config(engine_config)).unwrap();
+    let events = acceptor.take_events().unwrap();
+    match events.recv_timeout(Duration::from_secs(5)).unwrap() {
+        EngineEvent::Error(EngineError::AcceptTimeout { timeout }) => assert_eq!(timeout, Duration::from_millis(200)),
+        other => panic!("Unexpected event {:?}", other),
+    }
+    assert!(matches!(events.recv_timeout(Duration::from_secs(5)).unwrap(), EngineEvent::Disconnected { reason: DisconnectReason::Error }));
+    assert_eq!(acceptor.state(), SessionState::Disconnected);
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_bounded_engines_use_the_engine_config() {
+    let engine_config = EngineConfig::builder().accept_timeout(Duration::from_millis(200)).build();
+    let options = FactoryOptions::new(SessionConfig::default()).engine_config(engine_config);
+    let (mut acceptor, _sender, _receiver) = FixEngineFactory::create_acceptor_with_capacity("127.0.0.1:0", options, 2).unwrap();
+    let events = acceptor.take_events().unwrap();
+    assert!(matches!(events.recv_timeout(Duration::from_secs(5)).unwrap(), EngineEvent::Error(EngineError::AcceptTimeout { .. })));
+    acceptor.shutdown();
+
+    // Nothing listens on a port just given back, so only the failover address gets the initiator connected
+    let (mut acceptor, _acceptor_sender, _acceptor_receiver) =
+        FixEngineFactory::create_acceptor_with_options("127.0.0.1:0", SessionConfig::new("ACCEPTOR", "INITIATOR")).unwrap();
+    let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+    let engine_config = EngineConfig::builder().failover_address(&acceptor.local_addr().unwrap().to_string()).build();
+    let options = FactoryOptions::new(SessionConfig::new("INITIATOR", "ACCEPTOR")).engine_config(engine_config);
+    let (mut initiator, _sender, _receiver) = FixEngineFactory::create_initiator_with_capacity(&closed, options, 2).unwrap();
+    wait_for_state(&initiator, SessionState::LoggedOn);
+    initiator.shutdown();
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_listener_sessions_get_the_logon_validator_and_echo() {
+    let options = FactoryOptions::new(SessionConfig::new("VENUE", ""))
+        .logon_validator(Box::new(|_, logon| match logon.get_field(554) {
+            Some("secret") => Ok(()),
+            _ => Err(LogoutReason::new("Wrong password")),
+        }))
+        .echo();
+    let (mut acceptor, new_sessions) = FixEngineFactory::create_acceptor_listener("127.0.0.1:0", options).unwrap();
+    let address = acceptor.local_addr().to_string();
+
+    let config = |password: &str| SessionConfig { password: Some(password.to_string()), ..SessionConfig::new("CLIENT", "VENUE") };
+    let (mut refused, _sender, _receiver) = FixEngineFactory::create_initiator_with_options(&address, config("wrong")).unwrap();
+    wait_for_state(&refused, SessionState::Disconnected);
+    refused.shutdown();
+
+    let (mut initiator, sender, receiver) = FixEngineFactory::create_initiator_with_options(&address, config("secret")).unwrap();
+    sender.send(FixMessage::new_order_single(OrderSingleParams {
+        cl_ord_id: "CL-1".to_string(),
+        symbol: "VOD.L".to_string(),
+        side: Side::Buy,
+        order_qty: "300".parse().unwrap(),
+        ord_type: OrdType::Market,
+        price: None,
+    })).unwrap();
+    assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap().body.get("150").unwrap(), "F");
+    drop(new_sessions);
+    initiator.shutdown();
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_acceptor_serves_several_initiators_at_once() {
+    let (mut acceptor, new_sessions) = FixEngineFactory::create_acceptor_listener("127.0.0.1:0", SessionConfig::new("ACCEPTOR", "")).unwrap();
+    let address = acceptor.local_addr().to_string();
+
+    let mut initiators: Vec<_> = ["FIRST", "SECOND"].iter()
+        .map(|name| FixEngineFactory::create_initiator_with_options(&address, SessionConfig::new(name, "ACCEPTOR")).unwrap())
+        .collect();
+    let sessions: Vec<_> = (0..2).map(|_| new_sessions.recv_timeout(Duration::from_secs(5)).unwrap()).collect();
+
+    for (_, sender, _) in &initiators {
+        sender.send(create_new_order_single()).unwrap();
+    }
+    for session in &sessions {
+        let order = session.receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(order.header.get("35").unwrap(), "D");
+        // Each session answers its own initiator
+        assert_eq!(session.session.session_id().target_comp_id, *order.header.get("49").unwrap());
+        session.sender.send(create_execution_report()).unwrap();
+    }
+    for (engine, _, receiver) in &initiators {
+        let report = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(report.header.get("35").unwrap(), "8");
+        assert_eq!(report.header.get("56").unwrap(), &engine.session_id().sender_comp_id);
+    }
+
+    acceptor.shutdown();
+    for session in &sessions {
+        assert_eq!(session.session.state(), SessionState::Disconnected);
+    }
+    for (engine, _, _) in &mut initiators {
+        wait_for_state(engine, SessionState::Disconnected);
+        engine.shutdown();
+    }
+}
+
+#[test]
+fn test_initiator_acceptor_exchange_messages_in_memory() {
+    let initiator_config = SessionConfig::new("INITIATOR", "ACCEPTOR");
+    let acceptor_config = SessionConfig::new("ACCEPTOR", "INITIATOR");
+    let ((mut initiator, initiator_sender, initiator_receiver), (mut acceptor, acceptor_sender, acceptor_receiver)) =
+        FixEngineFactory::create_loopback_pair(initiator_config, acceptor_config).unwrap();
+
+    initiator_sender.send(create_new_order_single()).unwrap();
+    let order = acceptor_receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(order.header.get("35").unwrap(), "D");
+    assert_eq!(acceptor.state(), SessionState::LoggedOn);
+
+    acceptor_sender.send(create_execution_report()).unwrap();
+    let report = initiator_receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(report.header.get("35").unwrap(), "8");
+    assert_eq!(report.header.get("49").unwrap(), "ACCEPTOR");
+
+    initiator.shutdown();
+    wait_for_state(&acceptor, SessionState::Disconnected);
+    acceptor.shutdown();
+}
+
+// Hands each published snapshot to the test
+struct ChannelSink(std::sync::mpsc::Sender<SessionMetricsSnapshot>);
+
+impl MetricsSink for ChannelSink {
+    fn publish(&mut self, snapshot: &SessionMetricsSnapshot) {
+        let _ = self.0.send(snapshot.clone());
+    }
+}
+
+#[test]
+fn test_metrics_count_the_messages_exchanged_on_both_sides() {
+    const ORDERS: u64 = 20;
+    let initiator_config = SessionConfig::new("INITIATOR", "ACCEPTOR");
+    let acceptor_config = SessionConfig::new("ACCEPTOR", "INITIATOR");
+    let ((mut initiator, initiator_sender, _initiator_receiver), (mut acceptor, _acceptor_sender, acceptor_receiver)) =
+        FixEngineFactory::create_loopback_pair(initiator_config, acceptor_config).unwrap();
+    let (sink, snapshots) = channel();
+    initiator.set_metrics_sink(Box::new(ChannelSink(sink)));
+
+    for _ in 0..ORDERS {
+        initiator_sender.send(create_new_order_single()).unwrap();
+    }
+    for _ in 0..ORDERS {
+        acceptor_receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    }
+    // The last write may still be finishing when the acceptor already has the order
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while initiator.metrics().app_messages_sent < ORDERS && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    let sent = initiator.metrics();
+    let received = acceptor.metrics();
+    assert_eq!((sent.app_messages_sent, received.app_messages_received), (ORDERS, ORDERS));
+    assert_eq!((sent.app_messages_received, received.app_messages_sent), (0, 0));
+    // Both logons, one each way
+    assert_eq!((sent.admin_messages_sent, sent.admin_messages_received), (1, 1));
+    assert_eq!((received.admin_messages_sent, received.admin_messages_received), (1, 1));
+    assert_eq!(sent.bytes_out, received.bytes_in);
+    assert_eq!(received.bytes_out, sent.bytes_in);
+    assert_eq!(sent.send_latency.iter().sum::<u64>(), ORDERS + 1);
+    assert_eq!((received.decode_errors, received.duplicates_dropped, received.resends_serviced), (0, 0, 0));
+
+    // A last snapshot is pushed at shutdown, including the Logout
+    initiator.shutdown();
+    let last = snapshots.try_iter().last().unwrap();
+    assert_eq!((last.app_messages_sent, last.admin_messages_sent), (ORDERS, 2));
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_throttle_spreads_application_messages_over_its_rate() {
+    const ORDERS: usize = 50;
+    // Both sides run on a clock the test moves, so the throttle's seconds pass without waiting them out
+    let clock = create_manual_clock();
+    let (initiator_stream, acceptor_stream) = duplex();
+    let initiator_config = SessionConfig { throttle: Some(ThrottlePolicy::new(10, 5)), ..SessionConfig::new("INITIATOR", "ACCEPTOR") };
+    let mut initiator = FixEngine::new(clock.clone(), FixEngineMode::Initiator, initiator_config);
+    let mut acceptor = FixEngine::new(clock.clone(), FixEngineMode::Acceptor, SessionConfig::new("ACCEPTOR", "INITIATOR"));
+    let (initiator_sender, initiator_outgoing) = channel();
+    let (initiator_incoming, _initiator_receiver) = channel();
+    let (_acceptor_sender, acceptor_outgoing) = channel();
+    let (acceptor_incoming, acceptor_receiver) = channel();
+    acceptor.start(acceptor_stream, acceptor_outgoing, acceptor_incoming).unwrap();
+    initiator.start(initiator_stream, initiator_outgoing, initiator_incoming).unwrap();
+    wait_for_state(&initiator, SessionState::LoggedOn);
+
+    for _ in 0..ORDERS {
+        initiator_sender.send(create_new_order_single()).unwrap();
+    }
+    let mut received = Vec::new();
+    let mut steps = 0;
+    while received.len() < ORDERS {
+        match acceptor_receiver.recv_timeout(Duration::from_millis(20)) {
+            Ok(message) => received.push(message),
+            Err(_) => {
+                steps += 1;
+                assert!(steps < 500, "Stuck after {} orders", received.len());
+                clock.advance(Duration::from_millis(100));
+            }
+        }
+    }
+    // A burst of 5 goes out at once and the other 45 follow at 10 a second of the engine's clock
+    let span = received.last().unwrap().received_at().unwrap() - received[0].received_at().unwrap();
+    assert!(span >= TimeDelta::milliseconds(4000), "{:?}", span);
+
+    let metrics = initiator.metrics();
+    assert_eq!(metrics.app_messages_sent, ORDERS as u64);
+    assert_eq!(metrics.messages_throttled, ORDERS as u64 - 5);
+    assert!(metrics.throttle_tokens.is_some() && metrics.throttle_delay <= Duration::from_millis(100));
+    assert_eq!(acceptor.metrics().throttle_tokens, None);
+
+    initiator.shutdown();
+    wait_for_state(&acceptor, SessionState::Disconnected);
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_saturated_throttle_refuses_under_the_reject_policy() {
+    let throttle = ThrottlePolicy { saturation: ThrottleSaturation::Reject, ..ThrottlePolicy::new(1, 1) };
+    let initiator_config = SessionConfig { throttle: Some(throttle), ..SessionConfig::new("INITIATOR", "ACCEPTOR") };
+    let acceptor_config = SessionConfig::new("ACCEPTOR", "INITIATOR");
+    let ((mut initiator, initiator_sender, _initiator_receiver), (mut acceptor, _acceptor_sender, acceptor_receiver)) =
+        FixEngineFactory::create_loopback_pair(initiator_config, acceptor_config).unwrap();
+    let events = initiator.take_events().unwrap();
+
+    let receipts: Vec<_> = (0..3)
+        .map(|_| {
+            let mut order = create_new_order_single();
+            let receipt = order.track();
+            initiator_sender.send(order).unwrap();
+            receipt
+        })
+        .collect();
+    assert_eq!(acceptor_receiver.recv_timeout(Duration::from_secs(5)).unwrap().header.get("34").unwrap(), "2");
+    assert_eq!(receipts[0].recv_timeout(Duration::from_secs(5)).unwrap().unwrap().seq_num, 2);
+    for receipt in &receipts[1..] {
+        assert!(matches!(receipt.recv_timeout(Duration::from_secs(5)).unwrap(), Err(SendFailure::Throttled)));
+        assert!(matches!(next_event(&events), EngineEvent::OutgoingThrottled { .. }));
+    }
+    assert!(acceptor_receiver.recv_timeout(Duration::from_millis(200)).is_err());
+    assert_eq!(initiator.next_sender_seq_num(), 3);
+
+    initiator.shutdown();
+    wait_for_state(&acceptor, SessionState::Disconnected);
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_tracked_message_reports_the_seq_num_it_went_out_with() {
+    let initiator_config = SessionConfig::new("INITIATOR", "ACCEPTOR");
+    let acceptor_config = SessionConfig::new("ACCEPTOR", "INITIATOR");
+    let ((mut initiator, initiator_sender, _initiator_receiver), (mut acceptor, _acceptor_sender, acceptor_receiver)) =
+        FixEngineFactory::create_loopback_pair(initiator_config, acceptor_config).unwrap();
+
+    let mut order = create_new_order_single();
+    let receipts = order.track();
+    // A clone is not tracked, so only the second message reports back
+    let before_send = Utc::now();
+    initiator_sender.send(order.clone()).unwrap();
+    initiator_sender.send(order).unwrap();
+
+    let receipt = receipts.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+    let after_receipt = Utc::now();
+    acceptor_receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    let observed = acceptor_receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(receipt.seq_num, 3);
+    assert_eq!(observed.header.get("34").unwrap(), &receipt.seq_num.to_string());
+    assert_eq!(receipt.bytes, observed.raw().unwrap().len());
+    // Taken once the write returned, which may be after the peer has already read the message
+    assert!(before_send <= receipt.sent_at && receipt.sent_at <= after_receipt);
+    assert!(receipts.recv_timeout(Duration::from_millis(100)).is_err());
+
+    initiator.shutdown();
+    wait_for_state(&acceptor, SessionState::Disconnected);
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_messages_arrive_whole_through_small_reads() {
+    let engine_config = EngineConfig::builder().read_chunk_size(16).build();
+    let config = |sender: &str, target: &str| FactoryOptions::new(SessionConfig::new(sender, target)).engine_config(engine_config.clone());
+    let ((mut initiator, initiator_sender, _initiator_receiver), (mut acceptor, _acceptor_sender, acceptor_receiver)) =
+        FixEngineFactory::create_loopback_pair(config("INITIATOR", "ACCEPTOR"), config("ACCEPTOR", "INITIATOR")).unwrap();
+
+    initiator_sender.send(create_new_order_single()).unwrap();
+    let order = acceptor_receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(order.header.get("35").unwrap(), "D");
+
+    initiator.shutdown();
+    wait_for_state(&acceptor, SessionState::Disconnected);
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_sofh_framing_between_engines() {
+    let config = |sender: &str, target: &str| SessionConfig { framing: Framing::Sofh, ..SessionConfig::new(sender, target) };
+    let ((mut initiator, initiator_sender, _initiator_receiver), (mut acceptor, _acceptor_sender, acceptor_receiver)) =
+        FixEngineFactory::create_loopback_pair(config("INITIATOR", "ACCEPTOR"), config("ACCEPTOR", "INITIATOR")).unwrap();
+
+    initiator_sender.send(create_new_order_single()).unwrap();
+    let order = acceptor_receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(order.header.get("35").unwrap(), "D");
+
+    initiator.shutdown();
+    wait_for_state(&acceptor, SessionState::Disconnected);
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_initiator_reports_connected_then_logged_on() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+
+    let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, SessionConfig::new("INITIATOR", "ACCEPTOR"));
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, SessionConfig::new("ACCEPTOR", "INITIATOR"));
+    let events = initiator.take_events().unwrap();
+
+    let (_initiator_sender, initiator_outgoing) = channel();
+    let (initiator_incoming, _initiator_receiver) = channel();
+    let (_acceptor_sender, acceptor_outgoing) = channel();
+    let (acceptor_incoming, _acceptor_receiver) = channel();
+    initiator.start(initiator_stream, initiator_outgoing, initiator_incoming).unwrap();
+    acceptor.start(acceptor_stream, acceptor_outgoing, acceptor_incoming).unwrap();
+
+    let next_lifecycle_event = || loop {
+        match events.recv_timeout(Duration::from_secs(5)).unwrap() {
+            EngineEvent::StateChanged { .. } => continue,
+            event => return event,
+        }
+    };
+    assert!(matches!(next_lifecycle_event(), EngineEvent::Connected { .. }));
+    assert!(matches!(next_lifecycle_event(), EngineEvent::LoggedOn));
+
+    // The acceptor logs out as it shuts down
+    acceptor.shutdown();
+    assert!(matches!(next_lifecycle_event(), EngineEvent::LoggedOut { .. }));
+    assert!(matches!(next_lifecycle_event(), EngineEvent::Disconnected { reason: DisconnectReason::Logout }));
+    initiator.shutdown();
+    assert!(matches!(next_lifecycle_event(), EngineEvent::ShutDown));
+}
+
+#[test]
+fn test_shutdown_reads_on_until_the_peer_confirms_the_logout() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, SessionConfig::default());
+    let events = acceptor.take_events().unwrap();
+    let (_sender, outgoing) = channel();
+    let (incoming, receiver) = channel();
+    acceptor.start(acceptor_stream, outgoing, incoming).unwrap();
+    write_message(&mut peer, peer_logon(30));
+    read_message(&mut peer);
+    wait_for_state(&acceptor, SessionState::LoggedOn);
+
+    let shutdown = thread::spawn(move || acceptor.shutdown());
+    assert_eq!(read_message(&mut peer).header.get("35").unwrap(), "5");
+    // What the peer still had on its way is taken in before its Logout
+    write_message(&mut peer, peer_message("D", 2));
+    write_message(&mut peer, peer_message("5", 3));
+    shutdown.join().unwrap();
+
+    assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap().header.get("34").unwrap(), "2");
+    assert!(matches!(next_event(&events), EngineEvent::LoggedOut { .. }));
+    assert!(matches!(next_event(&events), EngineEvent::Disconnected { reason: DisconnectReason::Logout }));
+}
+
+#[test]
+fn test_shutdown_closes_after_the_logout_timeout_without_a_reply() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let config = SessionConfig { logout_timeout: Duration::from_millis(200), ..SessionConfig::default() };
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, config);
+    let events = acceptor.take_events().unwrap();
+    let (_sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    acceptor.start(acceptor_stream, outgoing, incoming).unwrap();
+    write_message(&mut peer, peer_logon(30));
+    read_message(&mut peer);
+    wait_for_state(&acceptor, SessionState::LoggedOn);
+
+    acceptor.shutdown();
+    assert_eq!(read_message(&mut peer).header.get("35").unwrap(), "5");
+    assert!(matches!(next_event(&events), EngineEvent::Disconnected { reason: DisconnectReason::Shutdown }));
+}
+
+#[test]
+fn test_malformed_reject_is_not_answered_with_a_reject() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, SessionConfig::default());
+    let events = acceptor.take_events().unwrap();
+    let (_sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    acceptor.start(acceptor_stream, outgoing, incoming).unwrap();
+    write_message(&mut peer, peer_logon(30));
+    read_message(&mut peer);
+
+    // Resent from later than it was first sent, which is what makes it malformed
+    let mut reject = peer_message("3", 2);
+    reject.header.insert("43".to_string(), "Y".to_string());
+    reject.header.insert("122".to_string(), "20231016-12:31:00.000".to_string());
+    reject.body.insert("45".to_string(), "1".to_string());
+    write_message(&mut peer, reject);
+    let mut test_request = peer_message("1", 3);
+    test_request.body.insert("112".to_string(), "PING".to_string());
+    write_message(&mut peer, test_request);
+
+    assert!(matches!(next_event(&events), EngineEvent::MessageRejected { .. }));
+    // The TestRequest is the next thing answered
+    assert_eq!(read_message(&mut peer).header.get("35").unwrap(), "0");
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_peer_closing_the_connection_stops_both_threads() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let mut peer = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, SessionConfig::new("ENGINE", "PEER"));
+    let (_sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    initiator.start(initiator_stream, outgoing, incoming).unwrap();
+    read_message(&mut peer);
+    write_message(&mut peer, peer_logon(30));
+    wait_for_state(&initiator, SessionState::LoggedOn);
+
+    drop(peer);
+    wait_for_state(&initiator, SessionState::Disconnected);
+    for _ in 0..300 {
+        if !initiator.is_receiving() && !initiator.is_sending() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(!initiator.is_receiving(), "receive thread should exit at end of stream");
+    assert!(!initiator.is_sending(), "send thread should follow it");
+    initiator.shutdown();
+}
+
+#[test]
+fn test_shutdown_flushes_queued_messages_then_logs_out() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let mut peer = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, SessionConfig::default());
+    let (sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    initiator.start(initiator_stream, outgoing, incoming).unwrap();
+    read_message(&mut peer);
+    write_message(&mut peer, peer_message("A", 1));
+    wait_for_state(&initiator, SessionState::LoggedOn);
+
+    for i in 0..100 {
+        let mut order = create_new_order_single();
+        order.body.insert("11".to_string(), format!("ORDER-{}", i));
+        sender.send(order).unwrap();
+    }
+    let report = initiator.shutdown_with_timeout(Duration::from_secs(5));
+    assert_eq!(report.dropped, 0);
+
+    for i in 0..100 {
+        assert_eq!(read_message(&mut peer).body.get("11").unwrap(), &format!("ORDER-{}", i));
+    }
+    assert_eq!(read_message(&mut peer).header.get("35").unwrap(), "5");
+}
+
+// Logs an initiator on over TCP with a peer that never reads again, then queues far more than the socket
+// buffers hold, so the send thread ends up stuck writing
+fn start_with_stalled_peer(engine_config: EngineConfig) -> (FixEngine, TcpStream) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let mut peer = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, SessionConfig::default());
+    initiator.set_engine_config(engine_config);
+    let (sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    initiator.start(initiator_stream, outgoing, incoming).unwrap();
+    read_message(&mut peer);
+    write_message(&mut peer, peer_message("A", 1));
+    wait_for_state(&initiator, SessionState::LoggedOn);
+
+    let text = "x".repeat(64 * 1024);
+    for _ in 0..400 {
+        let mut order = create_new_order_single();
+        order.body.insert("58".to_string(), text.clone());
+        sender.send(order).unwrap();
+    }
+    (initiator, peer)
+}
+
+#[test]
+fn test_shutdown_returns_while_the_peer_has_stopped_reading() {
+    let (mut initiator, _peer) = start_with_stalled_peer(EngineConfig::default());
+    thread::sleep(Duration::from_millis(500));
+
+    let started = Instant::now();
+    let report = initiator.shutdown_with_timeout(Duration::from_secs(1));
+    assert!(started.elapsed() < Duration::from_secs(3), "shutdown took {:?}", started.elapsed());
+    assert!(report.dropped > 0);
+}
+
+#[test]
+fn test_stalled_write_ends_the_connection_after_the_write_timeout() {
+    let (mut initiator, _peer) = start_with_stalled_peer(EngineConfig::builder().write_timeout(Duration::from_millis(500)).build());
+    let events = initiator.take_events().unwrap();
+    let log = RecordingLog::default();
+    initiator.set_message_log(Box::new(log.clone()));
+
+    let mut io_error = None;
+    loop {
+        match events.recv_timeout(Duration::from_secs(5)).unwrap() {
+            EngineEvent::IoError(error) => io_error = Some(error),
+            EngineEvent::Disconnected { reason } => {
+                assert_eq!(reason, DisconnectReason::Io);
+                break;
+            }
+            _ => {}
+        }
+    }
+    assert_eq!(io_error.expect("IoError before the disconnect").kind(), std::io::ErrorKind::TimedOut);
+    initiator.shutdown();
+
+    // The message that never got through is logged as a failure, not as sent
+    let entries = log.0.lock().unwrap();
+    let failed = entries.iter().find_map(|entry| entry.strip_prefix("EVENT Failed to send MsgSeqNum ")).expect("the failed write is logged");
+    let seq_num = failed.split(':').next().unwrap();
+    assert!(!entries.iter().any(|entry| entry.starts_with("OUT") && entry.contains(&format!("\x0134={}\x01", seq_num))));
+}
+
+// Keeps what the engine logs in memory, messages as their raw text
+#[derive(Clone, Default)]
+struct RecordingLog(Arc<Mutex<Vec<String>>>);
+
+impl MessageLog for RecordingLog {
+    fn log_incoming(&mut self, raw: &[u8]) {
+        self.0.lock().unwrap().push(format!("IN {}", String::from_utf8_lossy(raw)));
+    }
+
+    fn log_outgoing(&mut self, raw: &[u8]) {
+        self.0.lock().unwrap().push(format!("OUT {}", String::from_utf8_lossy(raw)));
+    }
+
+    fn log_event(&mut self, text: &str) {
+        self.0.lock().unwrap().push(format!("EVENT {}", text));
+    }
+}
+
+#[test]
+fn test_start_over_a_closed_stream_fails_cleanly_and_can_be_retried() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let closed = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let _closed_peer = listener.accept().unwrap();
+    closed.shutdown(std::net::Shutdown::Both).unwrap();
+
+    let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, SessionConfig::default());
+    let (_sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    let error = initiator.start(closed, outgoing, incoming).unwrap_err();
+    assert!(error.to_string().starts_with("Failed opening the session"), "{}", error);
+    assert_eq!(initiator.state(), SessionState::Disconnected);
+    assert!(!initiator.is_sending() && !initiator.is_receiving());
+
+    // Nothing was left running, so the same engine starts over a good connection
+    let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let mut peer = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let (_sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    initiator.start(stream, outgoing, incoming).unwrap();
+    assert_eq!(read_message(&mut peer).header.get("35").unwrap(), "A");
+    initiator.shutdown();
+}
+
+#[test]
+fn test_shutdown_drops_what_a_session_never_logged_on_could_send() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let mut peer = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, SessionConfig::default());
+    let (sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    initiator.start(initiator_stream, outgoing, incoming).unwrap();
+    read_message(&mut peer);
+
+    for _ in 0..3 {
+        sender.send(create_new_order_single()).unwrap();
+    }
+    assert_eq!(initiator.shutdown_with_timeout(Duration::from_secs(5)), ShutdownReport { flushed: 0, dropped: 3 });
+    // No Logout either, only the end of the connection
+    assert_eq!(peer.read(&mut [0; 16]).unwrap(), 0);
+}
+
+#[test]
+fn test_state_transitions_through_logon_and_logout() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let mut peer = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, SessionConfig::new("ENGINE", "PEER"));
+    let events = initiator.take_events().unwrap();
+    let (_sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    initiator.start(initiator_stream, outgoing, incoming).unwrap();
+
+    read_message(&mut peer);
+    write_message(&mut peer, peer_message("A", 1));
+    wait_for_state(&initiator, SessionState::LoggedOn);
+    write_message(&mut peer, peer_message("5", 2));
+    assert_eq!(read_message(&mut peer).header.get("35").unwrap(), "5");
+    wait_for_state(&initiator, SessionState::Disconnected);
+
+    let transitions: Vec<(SessionState, SessionState)> = events.try_iter()
+        .filter_map(|event| match event {
+            EngineEvent::StateChanged { from, to, .. } => Some((from, to)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(transitions, [
+        (SessionState::Disconnected, SessionState::Connected),
+        (SessionState::Connected, SessionState::LogonSent),
+        (SessionState::LogonSent, SessionState::LoggedOn),
+        (SessionState::LoggedOn, SessionState::LogoutSent),
+        (SessionState::LogoutSent, SessionState::Disconnecting),
+        (SessionState::Disconnecting, SessionState::Disconnected),
+    ]);
+    initiator.shutdown();
+}
+
+#[test]
+fn test_begin_string_mismatch_fails_logon() {
+    // The acceptor speaks FIX.4.4 only
+    let config = SessionConfig { begin_string: BeginString::Fix4_4, ..SessionConfig::default() };
+    let (mut acceptor, _acceptor_sender, acceptor_receiver) = FixEngineFactory::create_acceptor_with_options("127.0.0.1:0", config).unwrap();
+    let events = acceptor.take_events().unwrap();
+    let address = acceptor.local_addr().unwrap().to_string();
+
+    let config = SessionConfig { begin_string: BeginString::Fix4_2, ..SessionConfig::default() };
+    let (mut engine, _sender, receiver) = FixEngineFactory::create_initiator_with_options(&address, config).unwrap();
+
+    match next_event(&events) {
+        EngineEvent::Error(EngineError::BeginStringMismatch { expected, received }) => {
+            assert_eq!(expected, BeginString::Fix4_4);
+            assert_eq!(received.as_deref(), Some("FIX.4.2"));
+        }
+        other => panic!("Unexpected event {:?}", other),
+    }
+    assert!(acceptor_receiver.recv_timeout(Duration::from_millis(200)).is_err(), "Mismatched logon must not reach the application");
+    acceptor.shutdown();
+
+    // The initiator never gets a logon back
+    assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+    assert_ne!(engine.state(), SessionState::LoggedOn);
+    engine.shutdown();
+}
+
+#[test]
+fn test_logon_in_another_begin_string_is_logged_out() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, SessionConfig::new("ENGINE", "PEER"));
+    let (_sender, outgoing) = channel();
+    let (incoming, receiver) = channel();
+    acceptor.start(acceptor_stream, outgoing, incoming).unwrap();
+    let mut logon = peer_logon(30);
+    logon.header.insert("8".to_string(), "FIX.4.2".to_string());
+    write_message(&mut peer, logon);
+
+    let logout = read_message(&mut peer);
+    assert_eq!(logout.header.get("35").unwrap(), "5");
+    assert_eq!(logout.body.get("58").unwrap(), "Incompatible BeginString");
+    assert_eq!(logout.header.get("8").unwrap(), "FIX.4.4");
+    assert_eq!(peer.read(&mut [0; 64]).unwrap(), 0, "The connection is dropped after the Logout");
+    wait_for_state(&acceptor, SessionState::Disconnected);
+    assert!(receiver.try_recv().is_err());
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_comp_id_mismatch_fails_logon() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+
+    let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, SessionConfig::new("STRANGER", "ACCEPTOR"));
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, SessionConfig::new("ACCEPTOR", "INITIATOR"));
+    let events = acceptor.take_events().unwrap();
+
+    let (_initiator_sender, initiator_outgoing) = channel();
+    let (initiator_incoming, _initiator_receiver) = channel();
+    let (_acceptor_sender, acceptor_outgoing) = channel();
+    let (acceptor_incoming, _acceptor_receiver) = channel();
+    initiator.start(initiator_stream, initiator_outgoing, initiator_incoming).unwrap();
+    acceptor.start(acceptor_stream, acceptor_outgoing, acceptor_incoming).unwrap();
+
+    match next_event(&events) {
+        EngineEvent::Error(EngineError::CompIDMismatch { tag, expected, received }) => {
+            assert_eq!(tag, 49);
+            assert_eq!(expected, "INITIATOR");
+            assert_eq!(received.as_deref(), Some("STRANGER"));
+        }
+        other => panic!("Unexpected event {:?}", other),
+    }
+    assert!(matches!(next_event(&events), EngineEvent::Disconnected { reason: DisconnectReason::Error }));
+    assert_eq!(acceptor.state(), SessionState::Disconnected);
+    assert_ne!(initiator.state(), SessionState::LoggedOn);
+
+    initiator.shutdown();
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_wrong_target_comp_id_is_rejected_then_logged_out() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, SessionConfig::new("ENGINE", "PEER"));
+    let events = acceptor.take_events().unwrap();
+    let (_sender, outgoing) = channel();
+    let (incoming, receiver) = channel();
+    acceptor.start(acceptor_stream, outgoing, incoming).unwrap();
+
+    write_message(&mut peer, peer_message("A", 1));
+    read_message(&mut peer);
+    let mut misrouted = peer_message("D", 2);
+    misrouted.header.insert("56".to_string(), "SOMEONE_ELSE".to_string());
+    write_message(&mut peer, misrouted);
+
+    let reject = read_message(&mut peer);
+    assert_eq!(reject.header.get("35").unwrap(), "3");
+    assert_eq!(reject.body.get("45").unwrap(), "2");
+    assert_eq!(reject.body.get("371").unwrap(), "56");
+    assert_eq!(reject.body.get("373").unwrap(), "9");
+    assert_eq!(read_message(&mut peer).header.get("35").unwrap(), "5");
+    assert_eq!(peer.read(&mut [0; 1]).unwrap(), 0, "connection should be closed");
+
+    assert!(matches!(next_event(&events), EngineEvent::MessageRejected { .. }));
+    match next_event(&events) {
+        EngineEvent::Error(EngineError::CompIDMismatch { tag, received, .. }) => {
+            assert_eq!(tag, 56);
+            assert_eq!(received.as_deref(), Some("SOMEONE_ELSE"));
+        }
+        other => panic!("Unexpected event {:?}", other),
+    }
+    assert!(matches!(next_event(&events), EngineEvent::Disconnected { reason: DisconnectReason::Error }));
+    assert!(receiver.try_recv().is_err());
+
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_silent_peer_gets_test_request_then_disconnect() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let mut peer = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let clock = create_manual_clock();
+    let mut initiator = FixEngine::new(clock.clone(), FixEngineMode::Initiator, SessionConfig::default());
+    let events = initiator.take_events().unwrap();
+    let (_sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    initiator.start(initiator_stream, outgoing, incoming).unwrap();
+
+    // Complete the logon, then go quiet
+    assert_eq!(read_message(&mut peer).header.get("35").unwrap(), "A");
+    write_message(&mut peer, peer_logon(30));
+    wait_for_state(&initiator, SessionState::LoggedOn);
+
+    // Nothing heard for HeartBtInt plus grace
+    clock.advance(Duration::from_secs(37));
+    let test_request = read_message(&mut peer);
+    assert_eq!(test_request.header.get("35").unwrap(), "1");
+    let test_req_id = test_request.body.get("112").unwrap().clone();
+
+    // No Heartbeat comes back within another interval
+    clock.advance(Duration::from_secs(30));
+    match next_event(&events) {
+        EngineEvent::Error(EngineError::TestRequestTimeout { test_req_id: id }) => assert_eq!(id, test_req_id),
+        other => panic!("Unexpected event {:?}", other),
+    }
+    assert!(matches!(next_event(&events), EngineEvent::Disconnected { reason: DisconnectReason::Error }));
+    assert_eq!(initiator.state(), SessionState::Disconnected);
+    initiator.shutdown();
+}
+
+#[test]
+fn test_oversized_garbage_disconnects() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, SessionConfig::default());
+    let events = acceptor.take_events().unwrap();
+    let (_sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    acceptor.start(acceptor_stream, outgoing, incoming).unwrap();
+
+    // 2 MiB without a checksum field; the engine hangs up part way through
+    let _ = peer.write_all(&vec![b'x'; 2 * 1024 * 1024]);
+
+    match next_event(&events) {
+        EngineEvent::Error(EngineError::MessageTooLarge { size, limit }) => {
+            assert_eq!(limit, 1024 * 1024);
+            assert!(size > limit);
+        }
+        other => panic!("Unexpected event {:?}", other),
+    }
+    assert!(matches!(next_event(&events), EngineEvent::Disconnected { reason: DisconnectReason::Error }));
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_outgoing_messages_are_numbered_by_the_engine() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let mut peer = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, SessionConfig::default());
+    let (sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    initiator.start(initiator_stream, outgoing, incoming).unwrap();
+
+    let logon = read_message(&mut peer);
+    assert_eq!(logon.header.get("34").unwrap(), "1");
+    write_message(&mut peer, peer_message("A", 1));
+    wait_for_state(&initiator, SessionState::LoggedOn);
+
+    // Whatever the caller puts in tag 34 is overridden
+    initiator.reset_sender_seq_num();
+    for _ in 0..3 {
+        sender.send(create_new_order_single()).unwrap();
+    }
+    for expected in ["1", "2", "3"] {
+        assert_eq!(read_message(&mut peer).header.get("34").unwrap(), expected);
+    }
+    assert_eq!(initiator.next_sender_seq_num(), 4);
+    initiator.shutdown();
+}
+
+#[test]
+fn test_sequence_gap_is_resent_before_delivery() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, SessionConfig::default());
+    let events = acceptor.take_events().unwrap();
+    let (_sender, outgoing) = channel();
+    let (incoming, receiver) = channel();
+    acceptor.start(acceptor_stream, outgoing, incoming).unwrap();
+
+    write_message(&mut peer, peer_message("A", 1));
+    assert_eq!(read_message(&mut peer).header.get("35").unwrap(), "A");
+
+    // The peer skips MsgSeqNum 2
+    write_message(&mut peer, peer_message("D", 3));
+    let resend_request = read_message(&mut peer);
+    assert!(matches!(next_event(&events), EngineEvent::SequenceGap { expected: 2, received: 3 }));
+    assert_eq!(resend_request.header.get("35").unwrap(), "2");
+    assert_eq!(resend_request.body.get("7").unwrap(), "2");
+    assert_eq!(resend_request.body.get("16").unwrap(), "2");
+    assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err(), "Nothing is released while the gap is open");
+    assert_eq!(acceptor.state(), SessionState::AwaitingResend);
+
+    // Filling the gap releases both messages in order
+    let mut resent = peer_message("F", 2);
+    resent.header.insert("43".to_string(), "Y".to_string());
+    write_message(&mut peer, resent);
+    assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap().header.get("34").unwrap(), "2");
+    assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap().header.get("34").unwrap(), "3");
+    wait_for_state(&acceptor, SessionState::LoggedOn);
+
+    // An old number without PossDupFlag ends the session
+    write_message(&mut peer, peer_message("D", 2));
+    let logout = read_message(&mut peer);
+    assert_eq!(logout.header.get("35").unwrap(), "5");
+    assert_eq!(logout.body.get("58").unwrap(), "MsgSeqNum too low, expecting 4 but received 2");
+    match next_event(&events) {
+        EngineEvent::Error(EngineError::MsgSeqNumTooLow { expected, received }) => assert_eq!((expected, received), (4, 2)),
+        other => panic!("Unexpected event {:?}", other),
+    }
+    acceptor.shutdown();
+}
+
+// Logs an initiator on with a hand-driven peer, returning the engine, the peer and the initiator's receiver
+fn logged_on_initiator(config: SessionConfig) -> (FixEngine, TcpStream, Receiver<FixMessage>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let mut peer = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, config);
+    let (_sender, outgoing) = channel();
+    let (incoming, receiver) = channel();
+    initiator.start(initiator_stream, outgoing, incoming).unwrap();
+    read_message(&mut peer);
+    write_message(&mut peer, peer_logon(30));
+    wait_for_state(&initiator, SessionState::LoggedOn);
+    (initiator, peer, receiver)
+}
+
+#[test]
+fn test_initiator_requests_each_missing_range() {
+    let (mut initiator, mut peer, receiver) = logged_on_initiator(SessionConfig::default());
+    let assert_resend_request = |peer: &mut TcpStream, begin: &str, end: &str| {
+        let resend_request = read_message(peer);
+        assert_eq!(resend_request.header.get("35").unwrap(), "2");
+        assert_eq!((resend_request.body.get("7").unwrap().as_str(), resend_request.body.get("16").unwrap().as_str()), (begin, end));
+    };
+
+    // The acceptor skips 2 and 3
+    write_message(&mut peer, peer_message("D", 4));
+    assert_resend_request(&mut peer, "2", "3");
+    // Following on from what is held asks for nothing more; a further jump asks for the new gap only
+    write_message(&mut peer, peer_message("D", 5));
+    write_message(&mut peer, peer_message("D", 7));
+    assert_resend_request(&mut peer, "6", "6");
+
+    for seq_num in [2, 3, 6] {
+        let mut resent = peer_message("D", seq_num);
+        resent.header.insert("43".to_string(), "Y".to_string());
+        write_message(&mut peer, resent);
+    }
+    for seq_num in 2..=7 {
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap().header.get("34").unwrap(), &seq_num.to_string());
+    }
+    wait_for_state(&initiator, SessionState::LoggedOn);
+    initiator.shutdown();
+}
+
+#[test]
+fn test_gap_is_only_reported_without_resend_on_gap() {
+    let config = SessionConfig { resend_on_gap: false, ..SessionConfig::default() };
+    let (mut initiator, mut peer, receiver) = logged_on_initiator(config);
+    let events = initiator.take_events().unwrap();
+
+    write_message(&mut peer, peer_message("D", 3));
+    assert!(matches!(next_event(&events), EngineEvent::SequenceGap { expected: 2, received: 3 }));
+    peer.set_read_timeout(Some(Duration::from_millis(300))).unwrap();
+    assert!(peer.read(&mut [0; 64]).is_err(), "No ResendRequest without resend_on_gap");
+    assert!(receiver.try_recv().is_err());
+
+    // The application asks for the gap itself
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    initiator.request_resend(2, 2);
+    let resend_request = read_message(&mut peer);
+    assert_eq!(resend_request.header.get("35").unwrap(), "2");
+    assert_eq!(resend_request.body.get("7").unwrap(), "2");
+    assert_eq!(resend_request.body.get("16").unwrap(), "2");
+    initiator.shutdown();
+}
+
+#[test]
+fn test_received_messages_carry_their_latency() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let mut peer = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let clock = create_manual_clock();
+    let mut initiator = FixEngine::new(clock.clone(), FixEngineMode::Initiator, SessionConfig::default());
+    let (_sender, outgoing) = channel();
+    let (incoming, receiver) = channel();
+    initiator.start(initiator_stream, outgoing, incoming).unwrap();
+    read_message(&mut peer);
+    write_message(&mut peer, peer_logon(30));
+    wait_for_state(&initiator, SessionState::LoggedOn);
+
+    // Sent at the fixed clock's time and read 250ms later by the engine's
+    clock.advance(Duration::from_millis(250));
+    write_message(&mut peer, peer_message("D", 2));
+    let order = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(order.received_at(), Some(clock.now_utc()));
+    assert_eq!(order.latency(), Some(TimeDelta::milliseconds(250)));
+    initiator.shutdown();
+}
+
+#[test]
+fn test_messages_sharing_one_write_are_all_delivered() {
+    let (mut initiator, mut peer, receiver) = logged_on_initiator(SessionConfig::default());
+    let clock = create_fixed_clock();
+    let mut bytes = peer_message("D", 2).encode(&clock);
+    bytes.push_str(&peer_message("D", 3).encode(&clock));
+    peer.write_all(bytes.as_bytes()).unwrap();
+
+    // The second message is handed over without waiting for another read
+    for seq_num in ["2", "3"] {
+        let message = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(message.header.get("34").unwrap(), seq_num);
+    }
+    initiator.shutdown();
+}
+
+#[test]
+fn test_acceptor_authenticates_logon_credentials() {
+    for (password, accepted) in [("secret", true), ("wrong", false)] {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let acceptor_stream = listener.accept().unwrap().0;
+
+        let config = SessionConfig {
+            username: Some("trader".to_string()),
+            password: Some(password.to_string()),
+            ..SessionConfig::new("INITIATOR", "ACCEPTOR")
+        };
+        let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, config);
+        let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, SessionConfig::new("ACCEPTOR", "INITIATOR"));
+        acceptor.set_logon_validator(Box::new(|session_id, logon| {
+            assert_eq!(session_id.target_comp_id, "INITIATOR");
+            match (logon.get_field(553), logon.get_field(554)) {
+                (Some("trader"), Some("secret")) => Ok(()),
+                _ => Err(LogoutReason::new("Invalid credentials")),
+            }
+        }));
+        let events = acceptor.take_events().unwrap();
+
+        let (_initiator_sender, initiator_outgoing) = channel();
+        let (initiator_incoming, _initiator_receiver) = channel();
+        let (_acceptor_sender, acceptor_outgoing) = channel();
+        let (acceptor_incoming, _acceptor_receiver) = channel();
+        initiator.start(initiator_stream, initiator_outgoing, initiator_incoming).unwrap();
+        acceptor.start(acceptor_stream, acceptor_outgoing, acceptor_incoming).unwrap();
+
+        if accepted {
+            wait_for_state(&initiator, SessionState::LoggedOn);
+            assert_eq!(acceptor.state(), SessionState::LoggedOn);
+        } else {
+            match next_event(&events) {
+                EngineEvent::Error(EngineError::AuthenticationFailed { username, reason }) => {
+                    assert_eq!((username.as_deref(), reason.as_str()), (Some("trader"), "Invalid credentials"));
+                }
+                other => panic!("Unexpected event {:?}", other),
+            }
+            wait_for_state(&initiator, SessionState::Disconnected);
+            wait_for_state(&acceptor, SessionState::Disconnected);
+        }
+
+        initiator.shutdown();
+        acceptor.shutdown();
+    }
+}
+
+#[test]
+fn test_logon_validator_refuses_with_its_reason() {
+    for (password, accepted) in [("secret", true), ("wrong", false)] {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let acceptor_stream = listener.accept().unwrap().0;
+        peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, SessionConfig::default());
+        acceptor.set_logon_validator(Box::new(|_, logon| {
+            match (logon.get_field(553), logon.get_field(554)) {
+                (Some("trader"), Some("secret")) => Ok(()),
+                _ => Err(LogoutReason::new("Unknown username or password")),
+            }
+        }));
+        let (_sender, outgoing) = channel();
+        let (incoming, receiver) = channel();
+        acceptor.start(acceptor_stream, outgoing, incoming).unwrap();
+
+        let mut logon = peer_message("A", 1);
+        logon.body.insert("553".to_string(), "trader".to_string());
+        logon.body.insert("554".to_string(), password.to_string());
+        write_message(&mut peer, logon);
+        let reply = read_message(&mut peer);
+
+        if accepted {
+            assert_eq!(reply.header.get("35").unwrap(), "A");
+            wait_for_state(&acceptor, SessionState::LoggedOn);
+        } else {
+            assert_eq!(reply.header.get("35").unwrap(), "5");
+            assert_eq!(reply.body.get("58").unwrap(), "Unknown username or password");
+            let _ = peer.write_all(peer_message("D", 2).encode(&create_fixed_clock()).as_bytes());
+            wait_for_state(&acceptor, SessionState::Disconnected);
+            assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+        }
+        acceptor.shutdown();
+    }
+}
+
+#[test]
+fn test_resend_request_replays_application_messages_and_gap_fills_admin() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let mut peer = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let clock = create_manual_clock();
+    let mut initiator = FixEngine::new(clock.clone(), FixEngineMode::Initiator, SessionConfig::default());
+    let (sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    initiator.start(initiator_stream, outgoing, incoming).unwrap();
+
+    read_message(&mut peer);
+    write_message(&mut peer, peer_message("A", 1));
+    wait_for_state(&initiator, SessionState::LoggedOn);
+
+    // Order 2, a Heartbeat answering a TestRequest as 3, then order 4
+    sender.send(create_new_order_single()).unwrap();
+    let original = read_message(&mut peer);
+    let mut test_request = peer_message("1", 2);
+    test_request.body.insert("112".to_string(), "PING".to_string());
+    write_message(&mut peer, test_request);
+    assert_eq!(read_message(&mut peer).header.get("35").unwrap(), "0");
+    sender.send(create_new_order_single()).unwrap();
+    read_message(&mut peer);
+
+    // The peer claims to have lost everything from 2 onwards
+    clock.advance(Duration::from_secs(5));
+    let mut resend_request = peer_message("2", 3);
+    resend_request.body.insert("7".to_string(), "2".to_string());
+    resend_request.body.insert("16".to_string(), "0".to_string());
+    write_message(&mut peer, resend_request);
+
+    let replayed = read_message(&mut peer);
+    assert_eq!(replayed.header.get("35").unwrap(), "D");
+    assert_eq!(replayed.header.get("34").unwrap(), "2");
+    assert_eq!(replayed.header.get("43").unwrap(), "Y");
+    assert_eq!(replayed.header.get("122"), original.header.get("52"));
+    assert_ne!(replayed.header.get("52"), original.header.get("52"));
+    assert_eq!(replayed.body, original.body);
+
+    let gap_fill = read_message(&mut peer);
+    assert_eq!(gap_fill.header.get("35").unwrap(), "4");
+    assert_eq!(gap_fill.header.get("34").unwrap(), "3");
+    assert_eq!(gap_fill.body.get("123").unwrap(), "Y");
+    assert_eq!(gap_fill.body.get("36").unwrap(), "4");
+
+    assert_eq!(read_message(&mut peer).header.get("34").unwrap(), "4");
+    assert_eq!(initiator.next_sender_seq_num(), 5, "Replays do not consume new sequence numbers");
+    initiator.shutdown();
+}
+
+#[test]
+fn test_sent_messages_are_kept_in_the_message_store() {
+    let (initiator_stream, acceptor_stream) = duplex();
+    let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, SessionConfig::new("INITIATOR", "ACCEPTOR"));
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, SessionConfig::new("ACCEPTOR", "INITIATOR"));
+    initiator.set_message_store(Box::new(MemoryMessageStore::new()));
+
+    let (initiator_sender, initiator_outgoing) = channel();
+    let (initiator_incoming, _initiator_receiver) = channel();
+    let (_acceptor_sender, acceptor_outgoing) = channel();
+    let (acceptor_incoming, acceptor_receiver) = channel();
+    initiator.start(initiator_stream, initiator_outgoing, initiator_incoming).unwrap();
+    acceptor.start(acceptor_stream, acceptor_outgoing, acceptor_incoming).unwrap();
+
+    let mut order = create_new_order_single();
+    order.body.insert("11".to_string(), "ORDER-1".to_string());
+    initiator_sender.send(order).unwrap();
+    acceptor_receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+
+    assert_eq!(initiator.sent_message(1).unwrap().header.get("35").unwrap(), "A");
+    let stored = initiator.sent_message(2).unwrap();
+    assert_eq!(stored.header.get("35").unwrap(), "D");
+    assert_eq!(stored.header.get("34").unwrap(), "2");
+    assert_eq!(stored.body.get("11").unwrap(), "ORDER-1");
+    assert!(initiator.sent_message(3).is_none());
+
+    initiator.shutdown();
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_outgoing_interceptor_stamps_or_drops_messages() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let mut peer = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, SessionConfig::default());
+    let events = initiator.take_events().unwrap();
+    // Every order gets the account; one without a symbol never goes out
+    initiator.set_outgoing_interceptor(Box::new(|message: &mut FixMessage| {
+        if message.header.get("35").map(String::as_str) != Some("D") {
+            return Ok(());
+        }
+        if !message.body.contains_key("55") {
+            return Err(DoNotSend);
+        }
+        message.body.insert("1".to_string(), "ACCOUNT".to_string());
+        Ok(())
+    }));
+    let (sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    initiator.start(initiator_stream, outgoing, incoming).unwrap();
+    read_message(&mut peer);
+    write_message(&mut peer, peer_logon(30));
+    wait_for_state(&initiator, SessionState::LoggedOn);
+
+    sender.send(create_new_order_single()).unwrap();
+    let mut order = create_new_order_single();
+    order.body.insert("55".to_string(), "ABC".to_string());
+    sender.send(order).unwrap();
+
+    match next_event(&events) {
+        EngineEvent::OutgoingDropped { message } => assert!(!message.body.contains_key("55")),
+        other => panic!("Unexpected event {:?}", other),
+    }
+    // The dropped order took no MsgSeqNum
+    let received = read_message(&mut peer);
+    assert_eq!(received.header.get("34").unwrap(), "2");
+    assert_eq!(received.body.get("55").unwrap(), "ABC");
+    assert_eq!(received.body.get("1").unwrap(), "ACCOUNT");
+    assert_eq!(initiator.next_sender_seq_num(), 3);
+    initiator.shutdown();
+}
+
+#[test]
+fn test_incoming_interceptor_keeps_messages_from_the_application() {
+    let (mut initiator, mut peer, receiver) = logged_on_initiator(SessionConfig::default());
+    let events = initiator.take_events().unwrap();
+    initiator.set_incoming_interceptor(Box::new(|message: &FixMessage| {
+        if message.body.get("58").map(String::as_str) == Some("test") { Err(DoNotDeliver) } else { Ok(()) }
+    }));
+
+    let mut vetoed = peer_message("D", 2);
+    vetoed.body.insert("58".to_string(), "test".to_string());
+    write_message(&mut peer, vetoed);
+    write_message(&mut peer, peer_message("D", 3));
+
+    match next_event(&events) {
+        EngineEvent::IncomingDropped { message } => assert_eq!(message.header.get("34").unwrap(), "2"),
+        other => panic!("Unexpected event {:?}", other),
+    }
+    // The session still took the vetoed message's MsgSeqNum, so no gap is seen
+    assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap().header.get("34").unwrap(), "3");
+    assert!(receiver.try_recv().is_err());
+    assert_eq!(initiator.state(), SessionState::LoggedOn);
+    initiator.shutdown();
+}
+
+#[test]
+fn test_message_log_records_both_directions_in_order() {
+    let directory = std::env::temp_dir().join(format!("fix_engine_message_log_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    let (initiator_stream, acceptor_stream) = duplex();
+    let initiator_config = SessionConfig { password: Some("secret".to_string()), ..SessionConfig::new("INITIATOR", "ACCEPTOR") };
+    let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, initiator_config);
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, SessionConfig::new("ACCEPTOR", "INITIATOR"));
+    initiator.set_message_log(Box::new(FileMessageLog::open(&directory, "INITIATOR", "ACCEPTOR").unwrap()));
+
+    let (initiator_sender, initiator_outgoing) = channel();
+    let (initiator_incoming, _initiator_receiver) = channel();
+    let (_acceptor_sender, acceptor_outgoing) = channel();
+    let (acceptor_incoming, acceptor_receiver) = channel();
+    acceptor.start(acceptor_stream, acceptor_outgoing, acceptor_incoming).unwrap();
+    initiator.start(initiator_stream, initiator_outgoing, initiator_incoming).unwrap();
+    wait_for_state(&initiator, SessionState::LoggedOn);
+    initiator_sender.send(create_new_order_single()).unwrap();
+    acceptor_receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    initiator.shutdown();
+    acceptor.shutdown();
+
+    // Flushed by the shutdown; each direction in the order it crossed the wire, with the events between them,
+    // all timestamped by the engine's clock
+    let contents = std::fs::read_to_string(directory.join("INITIATOR-ACCEPTOR.log")).unwrap();
+    assert!(contents.lines().all(|line| line.starts_with("20231016-12:30:00.123000 ")), "{}", contents);
+    let messages = |wanted: &str| -> Vec<String> {
+        contents.lines()
+            .filter_map(|line| {
+                let (_, entry) = line.split_once(' ')?;
+                let (direction, wire) = entry.split_once(' ')?;
+                let msg_type = wire.split('|').find_map(|field| field.strip_prefix("35="))?;
+                (direction == wanted).then(|| msg_type.to_string())
+            })
+            .collect()
+    };
+    assert_eq!(messages("OUT"), ["A", "D", "5"]);
+    assert_eq!(messages("IN"), ["A", "5"]);
+    assert!(contents.contains("|554=****|") && !contents.contains("secret"));
+    assert!(contents.contains(" EVENT Session state LoggedOn -> Disconnecting"));
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+fn replay_fixture() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/replay.log")
+}
+
+#[test]
+fn test_replay_runs_the_inbound_messages_of_a_capture_through_the_session() {
+    let (receiver, control) = FixEngineFactory::create_replay_with_speed(&replay_fixture(), ReplaySpeed::FullSpeed).unwrap();
+    let replayed: Vec<(String, String)> = receiver.iter()
+        .map(|message| (message.header.get("34").unwrap().clone(), message.body.get("17").unwrap().clone()))
+        .collect();
+
+    // The corrupt line is reported without ending the replay, and the session treats it as it did on the wire:
+    // it is rejected, using up its MsgSeqNum, and the heartbeat after it opens a gap that holds back what
+    // follows until the resend fills it. The logon and the heartbeat stay in the session layer, and the
+    // outbound lines and the event are passed over.
+    assert_eq!(replayed, [("2".to_string(), "EXEC-1".to_string()), ("4".to_string(), "EXEC-3".to_string()), ("6".to_string(), "EXEC-4".to_string())]);
+    assert!(matches!(control.errors().as_slice(), [ReplayError { line: 5, error: DecodeError::ChecksumMismatch { .. } }]), "{:?}", control.errors());
+    assert!(control.is_finished());
+
+    let missing = FixEngineFactory::create_replay(Path::new("no/such/capture.log"));
+    assert!(matches!(missing, Err(FixEngineError::Replay { .. })));
+}
+
+#[test]
+fn test_timed_replay_keeps_the_original_spacing_and_can_be_paused() {
+    let (receiver, control) = FixEngineFactory::create_replay(&replay_fixture()).unwrap();
+    assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap().body.get("17").unwrap(), "EXEC-1");
+    control.pause();
+    assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err(), "Nothing is fed while paused");
+
+    // The last execution report was sent 50ms after the resend; at a tenth of the speed it takes 500ms
+    control.set_speed(ReplaySpeed::Timed(0.1));
+    control.resume();
+    assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap().body.get("17").unwrap(), "EXEC-3");
+    let before_last = Instant::now();
+    assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap().body.get("17").unwrap(), "EXEC-4");
+    assert!(before_last.elapsed() >= Duration::from_millis(400));
+}
+
+#[test]
+fn test_restarted_engine_resumes_sequence_numbers_from_file_message_store() {
+    let directory = std::env::temp_dir().join(format!("fix_engine_restart_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    let seq_nums = || {
+        let store = FileMessageStore::open(&directory, "", "").unwrap();
+        (store.next_target_seq(), store.next_sender_seq())
+    };
+
+    // Runs one session over a fresh connection: logon, then one order each way
+    let run = |peer_seq_num: u64| -> u64 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let mut peer = listener.accept().unwrap().0;
+        peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, SessionConfig::default());
+        initiator.set_message_store(Box::new(FileMessageStore::open(&directory, "", "").unwrap()));
+        let (sender, outgoing) = channel();
+        let (incoming, receiver) = channel();
+        initiator.start(initiator_stream, outgoing, incoming).unwrap();
+
+        let logon_seq_num: u64 = read_message(&mut peer).header.get("34").unwrap().parse().unwrap();
+        write_message(&mut peer, peer_message("A", peer_seq_num));
+        write_message(&mut peer, peer_message("D", peer_seq_num + 1));
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap().header.get("34").unwrap(), &(peer_seq_num + 1).to_string());
+        sender.send(create_new_order_single()).unwrap();
+        assert_eq!(read_message(&mut peer).header.get("34").unwrap(), &(logon_seq_num + 1).to_string());
+        initiator.shutdown();
+        logon_seq_num
+    };
+
+    // Shutting down sends a Logout, which takes a MsgSeqNum as well
+    assert_eq!(run(1), 1);
+    assert_eq!(seq_nums(), (3, 4));
+    // A new engine over the same files carries on from there in both directions
+    assert_eq!(run(3), 4);
+    assert_eq!(seq_nums(), (5, 7));
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_file_message_store_answers_resend_request_after_restart() {
+    let directory = std::env::temp_dir().join(format!("fix_engine_resend_restart_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+
+    let connect = || {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let peer = listener.accept().unwrap().0;
+        peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, SessionConfig::new("ENGINE", "PEER"));
+        initiator.set_message_store(Box::new(FileMessageStore::open(&directory, "ENGINE", "PEER").unwrap()));
+        let (sender, outgoing) = channel();
+        let (incoming, _receiver) = channel();
+        initiator.start(initiator_stream, outgoing, incoming).unwrap();
+        (initiator, peer, sender)
+    };
+
+    let (mut initiator, mut peer, sender) = connect();
+    read_message(&mut peer);
+    write_message(&mut peer, peer_message("A", 1));
+    let mut order = create_new_order_single();
+    order.body.insert("11".to_string(), "BEFORE-RESTART".to_string());
+    sender.send(order).unwrap();
+    assert_eq!(read_message(&mut peer).header.get("34").unwrap(), "2");
+    initiator.shutdown();
+
+    let (mut initiator, mut peer, _sender) = connect();
+    // The Logout sent on shutdown was number 3
+    assert_eq!(read_message(&mut peer).header.get("34").unwrap(), "4");
+    write_message(&mut peer, peer_message("A", 2));
+    let mut resend_request = peer_message("2", 3);
+    resend_request.body.insert("7".to_string(), "2".to_string());
+    resend_request.body.insert("16".to_string(), "2".to_string());
+    write_message(&mut peer, resend_request);
+
+    let replayed = read_message(&mut peer);
+    assert_eq!(replayed.header.get("34").unwrap(), "2");
+    assert_eq!(replayed.header.get("43").unwrap(), "Y");
+    assert_eq!(replayed.body.get("11").unwrap(), "BEFORE-RESTART");
+    initiator.shutdown();
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+// A MemoryMessageStore that counts the updates to the inbound sequence number
+struct CountingStore(MemoryMessageStore, Arc<AtomicUsize>);
+
+impl MessageStore for CountingStore {
+    fn store(&mut self, seq_num: u64, raw: &[u8]) -> std::io::Result<()> {
+        self.0.store(seq_num, raw)
+    }
+
+    fn get_range(&self, begin: u64, end: u64) -> Vec<Vec<u8>> {
+        self.0.get_range(begin, end)
+    }
+
+    fn next_sender_seq(&self) -> u64 {
+        self.0.next_sender_seq()
+    }
+
+    fn next_target_seq(&self) -> u64 {
+        self.0.next_target_seq()
+    }
+
+    fn set_next_sender_seq(&mut self, seq_num: u64) -> std::io::Result<()> {
+        self.0.set_next_sender_seq(seq_num)
+    }
+
+    fn set_next_target_seq(&mut self, seq_num: u64) -> std::io::Result<()> {
+        self.1.fetch_add(1, Ordering::SeqCst);
+        self.0.set_next_target_seq(seq_num)
+    }
+
+    fn reset(&mut self) -> std::io::Result<()> {
+        self.0.reset()
+    }
+
+    fn creation_time(&self) -> chrono::DateTime<chrono::Utc> {
+        self.0.creation_time()
+    }
+}
+
+#[test]
+fn test_inbound_sequence_number_is_stored_once_per_message() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let updates = Arc::new(AtomicUsize::new(0));
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, SessionConfig::default());
+    acceptor.set_message_store(Box::new(CountingStore(MemoryMessageStore::new(), Arc::clone(&updates))));
+    let (_sender, outgoing) = channel();
+    let (incoming, receiver) = channel();
+    acceptor.start(acceptor_stream, outgoing, incoming).unwrap();
+    write_message(&mut peer, peer_message("A", 1));
+    read_message(&mut peer);
+
+    write_message(&mut peer, peer_message("D", 2));
+    let mut gap_fill = peer_message("4", 3);
+    gap_fill.body.insert("123".to_string(), "Y".to_string());
+    gap_fill.body.insert("36".to_string(), "5".to_string());
+    write_message(&mut peer, gap_fill);
+    write_message(&mut peer, peer_message("D", 5));
+    let mut test_request = peer_message("1", 6);
+    test_request.body.insert("112".to_string(), "DONE".to_string());
+    write_message(&mut peer, test_request);
+    receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap().header.get("34").unwrap(), "5");
+    assert_eq!(read_message(&mut peer).body.get("112").unwrap(), "DONE");
+    acceptor.shutdown();
+
+    // The logon, the two orders, the GapFill and the TestRequest
+    assert_eq!(updates.load(Ordering::SeqCst), 5);
+}
+
+#[test]
+fn test_next_expected_msg_seq_num_on_reconnect() {
+    let connect = |peer_expects: u64| {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let mut peer = listener.accept().unwrap().0;
+        peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        // Picks up from an earlier connection that sent up to 7, of which the peer only got up to 4
+        let mut store = MemoryMessageStore::new();
+        for seq_num in [5, 7] {
+            let mut order = create_new_order_single();
+            order.header.insert("34".to_string(), seq_num.to_string());
+            order.body.insert("11".to_string(), format!("ORDER-{}", seq_num));
+            store.store(seq_num, order.encode(&create_fixed_clock()).as_bytes()).unwrap();
+        }
+        store.set_next_sender_seq(8).unwrap();
+        store.set_next_target_seq(3).unwrap();
+
+        let mut config = SessionConfig::new("ENGINE", "PEER");
+        config.send_next_expected_msg_seq_num = true;
+        let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, config);
+        initiator.set_message_store(Box::new(store));
+        let (_sender, outgoing) = channel();
+        let (incoming, _receiver) = channel();
+        initiator.start(initiator_stream, outgoing, incoming).unwrap();
+
+        let logon = read_message(&mut peer);
+        assert_eq!(logon.header.get("34").unwrap(), "8");
+        assert_eq!(logon.body.get("789").unwrap(), "3");
+        let mut reply = peer_message("A", 3);
+        reply.body.insert("789".to_string(), peer_expects.to_string());
+        write_message(&mut peer, reply);
+        (initiator, peer)
+    };
+
+    // Behind: 5 to 7 are resent straight away, with the message that is no longer stored gap-filled
+    let (mut initiator, mut peer) = connect(5);
+    let resent: Vec<FixMessage> = (0..3).map(|_| read_message(&mut peer)).collect();
+    assert_eq!(resent[0].body.get("11").unwrap(), "ORDER-5");
+    assert_eq!(resent[0].header.get("43").unwrap(), "Y");
+    assert_eq!(resent[1].header.get("35").unwrap(), "4");
+    assert_eq!(resent[1].header.get("34").unwrap(), "6");
+    assert_eq!(resent[1].body.get("36").unwrap(), "7");
+    assert_eq!(resent[2].body.get("11").unwrap(), "ORDER-7");
+    assert_eq!(initiator.state(), SessionState::LoggedOn);
+    initiator.shutdown();
+
+    // Ahead: nothing we could resend brings the peer back in step
+    let (mut initiator, mut peer) = connect(12);
+    let logout = read_message(&mut peer);
+    assert_eq!(logout.header.get("35").unwrap(), "5");
+    assert!(logout.body.get("58").unwrap().contains("NextExpectedMsgSeqNum too high"));
+    wait_for_state(&initiator, SessionState::Disconnected);
+    initiator.shutdown();
+}
+
+#[test]
+fn test_initiator_reconnects_and_sends_what_was_queued_meanwhile() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let mut config = SessionConfig::new("ENGINE", "PEER");
+    config.reconnect = Some(ReconnectPolicy {
+        backoff: Backoff::Exponential { initial: Duration::from_millis(50), max: Duration::from_millis(200) },
+        max_attempts: Some(50),
+        queue: QueuePolicy::Buffer { capacity: 1 },
+    });
+    let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, config);
+    let events = initiator.take_events().unwrap();
+    let (sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    // Where the acceptor is listening, if anywhere
+    let acceptor_address = Arc::new(Mutex::new(Some(address)));
+    let connect_to = Arc::clone(&acceptor_address);
+    let connect = move || match *connect_to.lock().unwrap() {
+        Some(address) => TcpStream::connect(address),
+        None => Err(std::io::ErrorKind::ConnectionRefused.into()),
+    };
+    initiator.start_reconnecting(TcpStream::connect(address).unwrap(), connect, outgoing, incoming).unwrap();
+
+    let mut peer = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(read_message(&mut peer).header.get("34").unwrap(), "1");
+    write_message(&mut peer, peer_logon(30));
+    sender.send(create_new_order_single()).unwrap();
+    assert_eq!(read_message(&mut peer).header.get("34").unwrap(), "2");
+
+    // The acceptor goes away; nobody listens until it is back
+    *acceptor_address.lock().unwrap() = None;
+    drop(peer);
+    drop(listener);
+    wait_for_state(&initiator, SessionState::Disconnected);
+    assert!(matches!(next_event(&events), EngineEvent::Disconnected { reason: DisconnectReason::PeerClosed }));
+    let mut queued = create_new_order_single();
+    queued.body.insert("11".to_string(), "QUEUED".to_string());
+    sender.send(queued).unwrap();
+    // Beyond the buffer's capacity of one
+    sender.send(create_new_order_single()).unwrap();
+    assert!(matches!(next_event(&events), EngineEvent::OutgoingRejected { .. }));
+
+    // Back up again: the session resumes from the sequence numbers it stopped at
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    *acceptor_address.lock().unwrap() = Some(listener.local_addr().unwrap());
+    let mut peer = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(read_message(&mut peer).header.get("34").unwrap(), "3");
+    let mut logon = peer_logon(30);
+    logon.header.insert("34".to_string(), "2".to_string());
+    write_message(&mut peer, logon);
+
+    let resent = read_message(&mut peer);
+    assert_eq!(resent.header.get("34").unwrap(), "4");
+    assert_eq!(resent.body.get("11").unwrap(), "QUEUED");
+    assert_eq!(initiator.state(), SessionState::LoggedOn);
+    initiator.shutdown();
+}
+
+// Counts the connections made to the listener, answering each logon by closing the connection
+fn refuse_logons(listener: TcpListener) -> Arc<AtomicUsize> {
+    let connections = Arc::new(AtomicUsize::new(0));
+    let counted = Arc::clone(&connections);
     thread::spawn(move || {
-        let (mut engine, sender, receiver) = FixEngineFactory::create_acceptor(address);
+        for mut peer in listener.incoming().map_while(Result::ok) {
+            counted.fetch_add(1, Ordering::SeqCst);
+            peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            read_message(&mut peer);
+        }
+    });
+    connections
+}
 
-        // Receive the message (from initiator)
-        let message = receiver.recv().unwrap();
-        assert_eq!(message.header.get("35").unwrap(), "A"); // Logon message type
+// Fails unless the count stays put for a while
+fn assert_no_more_connections(connections: &AtomicUsize, expected: usize) {
+    let until = Instant::now() + Duration::from_millis(500);
+    while Instant::now() < until {
+        assert_eq!(connections.load(Ordering::SeqCst), expected);
+        thread::sleep(Duration::from_millis(10));
+    }
+}
 
-        // Send execution report
-        sender.send(create_execution_report()).unwrap();
-        engine.shutdown();
+#[test]
+fn test_initiator_gives_up_on_a_refused_logon_after_the_attempts_run_out() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let connections = refuse_logons(listener);
+    let mut config = SessionConfig::new("ENGINE", "PEER");
+    config.reconnect = Some(ReconnectPolicy {
+        backoff: Backoff::Fixed(Duration::from_millis(20)),
+        max_attempts: Some(2),
+        queue: QueuePolicy::Reject,
     });
+    let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, config);
+    let (_sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    initiator.start_reconnecting(TcpStream::connect(address).unwrap(), move || TcpStream::connect(address), outgoing, incoming).unwrap();
 
-    // Give the acceptor time to start listening
-    thread::sleep(Duration::from_millis(100));
+    // Every connection is accepted but never gets as far as a logon, so the attempts add up
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while connections.load(Ordering::SeqCst) < 3 {
+        assert!(Instant::now() < deadline, "the initiator stopped reconnecting early");
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_no_more_connections(&connections, 3);
+    initiator.shutdown();
+}
 
-    // Start the initiator
-    let (mut engine, sender, receiver) = FixEngineFactory::create_initiator(address);
+#[test]
+fn test_initiator_does_not_reconnect_after_a_logout() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let mut config = SessionConfig::new("ENGINE", "PEER");
+    config.reconnect = Some(ReconnectPolicy { backoff: Backoff::Fixed(Duration::from_millis(20)), ..ReconnectPolicy::default() });
+    let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, config);
+    let events = initiator.take_events().unwrap();
+    let (_sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    initiator.start_reconnecting(TcpStream::connect(address).unwrap(), move || TcpStream::connect(address), outgoing, incoming).unwrap();
 
-    // Create a logon message using the fixed clock
-    sender.send(create_logon_message()).unwrap(); // Send logon
+    let mut peer = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    read_message(&mut peer);
+    write_message(&mut peer, peer_logon(30));
+    wait_for_state(&initiator, SessionState::LoggedOn);
+    write_message(&mut peer, peer_message("5", 2));
+    assert_eq!(read_message(&mut peer).header.get("35").unwrap(), "5");
+    assert!(matches!(next_event(&events), EngineEvent::LoggedOut { .. }));
+    assert!(matches!(next_event(&events), EngineEvent::Disconnected { reason: DisconnectReason::Logout }));
+    drop(peer);
 
-    // Receive execution report from acceptor
-    let response = receiver.recv().unwrap();
-    assert_eq!(response.header.get("35").unwrap(), "8"); // Execution Report message type
+    assert_no_more_connections(&refuse_logons(listener), 0);
+    initiator.shutdown();
+}
 
-    engine.shutdown();
+#[test]
+fn test_possible_duplicates_are_delivered_once() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, SessionConfig::default());
+    let (_sender, outgoing) = channel();
+    let (incoming, receiver) = channel();
+    acceptor.start(acceptor_stream, outgoing, incoming).unwrap();
+    write_message(&mut peer, peer_message("A", 1));
+    read_message(&mut peer);
+
+    let resent_order = |seq_num: u64, orig_sending_time: &str| {
+        let mut order = peer_message("D", seq_num);
+        order.header.insert("43".to_string(), "Y".to_string());
+        order.header.insert("122".to_string(), orig_sending_time.to_string());
+        order.body.insert("11".to_string(), format!("ORDER-{}", seq_num));
+        order.encode(&create_fixed_clock())
+    };
+    // The same resent order arrives twice; the first fills MsgSeqNum 2 and the second is already processed
+    let encoded = resent_order(2, "20231016-12:29:00.000");
+    peer.write_all(encoded.as_bytes()).unwrap();
+    peer.write_all(encoded.as_bytes()).unwrap();
+    assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap().body.get("11").unwrap(), "ORDER-2");
+
+    // An OrigSendingTime after the SendingTime is rejected even on a duplicate
+    peer.write_all(resent_order(2, "20231016-12:31:00.000").as_bytes()).unwrap();
+    let reject = read_message(&mut peer);
+    assert_eq!(reject.header.get("35").unwrap(), "3");
+    assert_eq!(reject.body.get("45").unwrap(), "2");
+    assert_eq!(reject.body.get("371").unwrap(), "122");
+
+    let mut order = peer_message("D", 3);
+    order.body.insert("11".to_string(), "ORDER-3".to_string());
+    write_message(&mut peer, order);
+    assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap().body.get("11").unwrap(), "ORDER-3");
+    assert!(receiver.try_recv().is_err());
+    assert_eq!(acceptor.duplicates_suppressed(), 1);
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_unsupported_msg_type_gets_business_message_reject() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let config = SessionConfig { reject_unsupported_msg_types: vec![MsgType::QuoteRequest], ..SessionConfig::default() };
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, config);
+    let (_sender, outgoing) = channel();
+    let (incoming, receiver) = channel();
+    acceptor.start(acceptor_stream, outgoing, incoming).unwrap();
+    write_message(&mut peer, peer_message("A", 1));
+    read_message(&mut peer);
+
+    let mut quote_request = peer_message("R", 2);
+    quote_request.body.insert("131".to_string(), "QR-1".to_string());
+    write_message(&mut peer, quote_request);
+    let reject = read_message(&mut peer);
+    assert_eq!(reject.header.get("35").unwrap(), "j");
+    assert_eq!(reject.body.get("372").unwrap(), "R");
+    assert_eq!(reject.body.get("380").unwrap(), "3");
+    assert_eq!(reject.body.get("45").unwrap(), "2");
+    assert_eq!(reject.body.get("58").unwrap(), "Unsupported MsgType R");
+
+    // The rejected message still used up its MsgSeqNum and never reached the application
+    let mut order = peer_message("D", 3);
+    order.body.insert("11".to_string(), "ORDER-3".to_string());
+    write_message(&mut peer, order);
+    assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap().header.get("34").unwrap(), "3");
+    acceptor.shutdown();
+}
+
+// An acceptor logged on with a hand-driven peer, plus the peer's end and the acceptor's events
+fn logged_on_acceptor(config: SessionConfig) -> (FixEngine, TcpStream, Receiver<EngineEvent>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, config);
+    let events = acceptor.take_events().unwrap();
+    let (_sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    acceptor.start(acceptor_stream, outgoing, incoming).unwrap();
+    write_message(&mut peer, peer_message("A", 1));
+    read_message(&mut peer);
+    (acceptor, peer, events)
+}
+
+#[test]
+fn test_message_before_logon_disconnects() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, SessionConfig::default());
+    let events = acceptor.take_events().unwrap();
+    let (_sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    acceptor.start(acceptor_stream, outgoing, incoming).unwrap();
+
+    write_message(&mut peer, peer_message("D", 1));
+    match next_event(&events) {
+        EngineEvent::Error(EngineError::MessageBeforeLogon { msg_type }) => assert_eq!(msg_type.as_deref(), Some("D")),
+        other => panic!("Unexpected event {:?}", other),
+    }
+    wait_for_state(&acceptor, SessionState::Disconnected);
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_second_logon_is_rejected_or_resets_the_session() {
+    // Without the switch a second Logon is rejected and the session carries on
+    let (mut acceptor, mut peer, _events) = logged_on_acceptor(SessionConfig::default());
+    let mut logon = peer_message("A", 2);
+    logon.body.insert("141".to_string(), "Y".to_string());
+    write_message(&mut peer, logon);
+    let reject = read_message(&mut peer);
+    assert_eq!(reject.header.get("35").unwrap(), "3");
+    assert_eq!(reject.body.get("45").unwrap(), "2");
+    assert_eq!(reject.body.get("372").unwrap(), "A");
+    assert_eq!(acceptor.state(), SessionState::LoggedOn);
+    acceptor.shutdown();
+
+    // With it, a reset Logon starts both directions again from 1
+    let config = SessionConfig { accept_in_session_reset: true, ..SessionConfig::default() };
+    let (mut acceptor, mut peer, _events) = logged_on_acceptor(config);
+    write_message(&mut peer, peer_message("0", 2));
+    let mut logon = peer_message("A", 1);
+    logon.body.insert("141".to_string(), "Y".to_string());
+    write_message(&mut peer, logon);
+    let reply = read_message(&mut peer);
+    assert_eq!(reply.header.get("35").unwrap(), "A");
+    assert_eq!(reply.header.get("34").unwrap(), "1");
+    assert_eq!(reply.body.get("141").unwrap(), "Y");
+    let mut test_request = peer_message("1", 2);
+    test_request.body.insert("112".to_string(), "AFTER-RESET".to_string());
+    write_message(&mut peer, test_request);
+    let heartbeat = read_message(&mut peer);
+    assert_eq!(heartbeat.header.get("34").unwrap(), "2");
+    assert_eq!(heartbeat.body.get("112").unwrap(), "AFTER-RESET");
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_reset_logon_from_another_counterparty_leaves_the_sequence_numbers_alone() {
+    let config = SessionConfig { accept_in_session_reset: true, ..SessionConfig::default() };
+    let (mut acceptor, mut peer, events) = logged_on_acceptor(config);
+    let mut logon = peer_message("A", 1);
+    logon.header.insert("49".to_string(), "INTRUDER".to_string());
+    logon.body.insert("141".to_string(), "Y".to_string());
+    write_message(&mut peer, logon);
+
+    // Refused with the numbers the session already had, not ones started again from 1
+    let reject = read_message(&mut peer);
+    assert_eq!((reject.header.get("35").unwrap().as_str(), reject.header.get("34").unwrap().as_str()), ("3", "2"));
+    assert_eq!(read_message(&mut peer).header.get("34").unwrap(), "3");
+    assert!(matches!(next_event(&events), EngineEvent::MessageRejected { .. }));
+    assert!(matches!(next_event(&events), EngineEvent::Error(EngineError::CompIDMismatch { tag: 49, .. })));
+    assert_eq!(acceptor.next_sender_seq_num(), 4);
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_refused_reset_logon_leaves_the_stored_sequence_numbers_alone() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut store = MemoryMessageStore::new();
+    store.set_next_sender_seq(5).unwrap();
+    store.set_next_target_seq(7).unwrap();
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, SessionConfig::default());
+    acceptor.set_message_store(Box::new(store));
+    acceptor.set_logon_validator(Box::new(|_, logon| match logon.get_field(554) {
+        Some("secret") => Ok(()),
+        _ => Err(LogoutReason::new("Wrong password")),
+    }));
+    let (_sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    acceptor.start(acceptor_stream, outgoing, incoming).unwrap();
+
+    let mut logon = peer_message("A", 1);
+    logon.body.insert("141".to_string(), "Y".to_string());
+    logon.body.insert("554".to_string(), "wrong".to_string());
+    write_message(&mut peer, logon);
+    let logout = read_message(&mut peer);
+    assert_eq!((logout.header.get("35").unwrap().as_str(), logout.header.get("34").unwrap().as_str()), ("5", "5"));
+    wait_for_state(&acceptor, SessionState::Disconnected);
+    assert_eq!(acceptor.next_sender_seq_num(), 6);
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_logon_from_another_counterparty_is_refused() {
+    let (mut acceptor, mut peer, events) = logged_on_acceptor(SessionConfig::default());
+    let mut logon = peer_message("A", 2);
+    logon.header.insert("49".to_string(), "INTRUDER".to_string());
+    write_message(&mut peer, logon);
+
+    let reject = read_message(&mut peer);
+    assert_eq!(reject.header.get("35").unwrap(), "3");
+    assert_eq!(reject.body.get("373").unwrap(), "9");
+    assert_eq!(read_message(&mut peer).header.get("35").unwrap(), "5");
+    assert!(matches!(next_event(&events), EngineEvent::MessageRejected { .. }));
+    assert!(matches!(next_event(&events), EngineEvent::Error(EngineError::CompIDMismatch { tag: 49, .. })));
+    wait_for_state(&acceptor, SessionState::Disconnected);
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_message_without_msg_seq_num_ends_the_session() {
+    let (mut acceptor, mut peer, events) = logged_on_acceptor(SessionConfig::default());
+    let mut order = peer_message("D", 2);
+    order.header.remove("34");
+    write_message(&mut peer, order);
+
+    let logout = read_message(&mut peer);
+    assert_eq!(logout.header.get("35").unwrap(), "5");
+    assert_eq!(logout.body.get("58").unwrap(), "MsgSeqNum(34) missing");
+    match next_event(&events) {
+        EngineEvent::Error(EngineError::MsgSeqNumMissing { msg_type }) => assert_eq!(msg_type.as_deref(), Some("D")),
+        other => panic!("Unexpected event {:?}", other),
+    }
+    wait_for_state(&acceptor, SessionState::Disconnected);
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_heart_bt_int_within_bounds_is_echoed() {
+    let config = SessionConfig { min_heart_bt_int: Some(10), max_heart_bt_int: Some(60), ..SessionConfig::default() };
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, config);
+    let (_sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    acceptor.start(acceptor_stream, outgoing, incoming).unwrap();
+
+    write_message(&mut peer, peer_logon(45));
+    let logon = read_message(&mut peer);
+    assert_eq!(logon.header.get("35").unwrap(), "A");
+    assert_eq!(logon.body.get("108").unwrap(), "45");
+    wait_for_state(&acceptor, SessionState::LoggedOn);
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_heart_bt_int_outside_bounds_is_refused() {
+    let config = SessionConfig { min_heart_bt_int: Some(10), max_heart_bt_int: Some(60), ..SessionConfig::default() };
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, config);
+    let events = acceptor.take_events().unwrap();
+    let (_sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    acceptor.start(acceptor_stream, outgoing, incoming).unwrap();
+
+    write_message(&mut peer, peer_logon(5));
+    let logout = read_message(&mut peer);
+    assert_eq!(logout.header.get("35").unwrap(), "5");
+    assert_eq!(logout.body.get("58").unwrap(), "HeartBtInt 5 outside the allowed range 10 to 60");
+    match next_event(&events) {
+        EngineEvent::Error(EngineError::LogonRejected { reason }) => assert!(reason.starts_with("HeartBtInt 5")),
+        other => panic!("Unexpected event {:?}", other),
+    }
+    wait_for_state(&acceptor, SessionState::Disconnected);
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_fixt_logon_carries_default_appl_ver_id() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let mut peer = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut config = SessionConfig::new("ENGINE", "PEER");
+    config.begin_string = BeginString::FixT1_1;
+    config.default_appl_ver_id = Some("9".to_string()); // FIX50SP2
+    let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, config);
+    let (_sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    initiator.start(initiator_stream, outgoing, incoming).unwrap();
+
+    let logon = read_message(&mut peer);
+    assert_eq!(logon.header.get("8").unwrap(), "FIXT.1.1");
+    assert_eq!(logon.body.get("1137").unwrap(), "9");
+    assert_eq!(logon.default_appl_ver_id(), Some("9"));
+    initiator.shutdown();
+}
+
+#[test]
+fn test_initiator_warns_when_heart_bt_int_is_not_echoed() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let mut peer = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, SessionConfig::default());
+    let events = initiator.take_events().unwrap();
+    let (_sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    initiator.start(initiator_stream, outgoing, incoming).unwrap();
+
+    assert_eq!(read_message(&mut peer).body.get("108").unwrap(), "30");
+    write_message(&mut peer, peer_logon(60));
+    match next_event(&events) {
+        EngineEvent::HeartBtIntMismatch { proposed, received } => {
+            assert_eq!(proposed, 30);
+            assert_eq!(received, Some(60));
+        }
+        other => panic!("Unexpected event {:?}", other),
+    }
+    // The session still goes ahead
+    wait_for_state(&initiator, SessionState::LoggedOn);
+    initiator.shutdown();
+}
+
+#[test]
+fn test_bounded_incoming_channel_holds_back_the_receive_thread() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, SessionConfig::default());
+    let (_sender, outgoing) = bounded(1);
+    let (incoming, receiver) = bounded(1);
+    acceptor.start_bounded(acceptor_stream, outgoing, incoming).unwrap();
+    write_message(&mut peer, peer_message("A", 1));
+    read_message(&mut peer);
+
+    // Nobody is taking messages off the channel, so the engine stops after filling it
+    for seq_num in 2..=6 {
+        write_message(&mut peer, peer_message("D", seq_num));
+    }
+    // One queued and one waiting for room
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while receiver.len() != 2 {
+        assert!(Instant::now() < deadline, "the receive thread never filled the channel");
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    for seq_num in 2..=6 {
+        let order = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(order.header.get("34").unwrap(), &seq_num.to_string());
+    }
+    assert!(receiver.is_empty());
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_full_incoming_channel_keeps_heartbeats_going_and_does_not_hold_up_shutdown() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let clock = create_manual_clock();
+    let mut acceptor = FixEngine::new(clock.clone(), FixEngineMode::Acceptor, SessionConfig::default());
+    let (_sender, outgoing) = bounded(1);
+    let (incoming, receiver) = bounded(1);
+    acceptor.start_bounded(acceptor_stream, outgoing, incoming).unwrap();
+    write_message(&mut peer, peer_logon(30));
+    assert_eq!(read_message(&mut peer).header.get("35").unwrap(), "A");
+
+    write_message(&mut peer, peer_message("D", 2));
+    write_message(&mut peer, peer_message("D", 3));
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while receiver.len() != 2 {
+        assert!(Instant::now() < deadline, "the receive thread never filled the channel");
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    // Stuck on the full channel, the acceptor still tells the peer it is alive
+    clock.advance(Duration::from_secs(30));
+    assert_eq!(read_message(&mut peer).header.get("35").unwrap(), "0");
+
+    let started = Instant::now();
+    acceptor.shutdown();
+    assert!(started.elapsed() < Duration::from_secs(5), "shutdown waited on the application");
+    assert_eq!(receiver.len(), 1);
+}
+
+#[test]
+fn test_echo_acceptor_fills_each_order() {
+    let (mut acceptor, acceptor_sender, acceptor_receiver) = FixEngineFactory::create_acceptor_with_options("127.0.0.1:0", FactoryOptions::new(SessionConfig::new("VENUE", "CLIENT")).echo()).unwrap();
+    let address = acceptor.local_addr().unwrap().to_string();
+    let (mut initiator, sender, receiver) = FixEngineFactory::create_initiator_with_options(&address, SessionConfig::new("CLIENT", "VENUE")).unwrap();
+
+    let order = FixMessage::new_order_single(OrderSingleParams {
+        cl_ord_id: "CL-1".to_string(),
+        symbol: "VOD.L".to_string(),
+        side: Side::Buy,
+        order_qty: "300".parse().unwrap(),
+        ord_type: OrdType::Limit,
+        price: Some("99.5".parse().unwrap()),
+    });
+    sender.send(order.clone()).unwrap();
+    let fill = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(fill.header.get("35").unwrap(), "8");
+    assert_eq!(fill.header.get("49").unwrap(), "VENUE");
+    for tag in ["11", "55", "54", "38"] {
+        assert_eq!(fill.body.get(tag), order.body.get(tag), "tag {}", tag);
+    }
+    assert_eq!(fill.body.get("150").unwrap(), "F");
+    assert_eq!(fill.body.get("151").unwrap(), "0");
+    assert_eq!(fill.body.get("31").unwrap(), "99.5");
+    // The order was answered for the application, not handed to it
+    assert!(acceptor_receiver.try_recv().is_err());
+
+    // The fills do not keep the outgoing channel open once the application is done with it
+    drop(acceptor_sender);
+    for _ in 0..200 {
+        if !acceptor.is_sending() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(!acceptor.is_sending(), "send thread should exit once the sender is gone");
+    initiator.shutdown();
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_full_outgoing_channel_refuses_with_the_fail_policy() {
+    let config = SessionConfig { outgoing_overflow: OverflowPolicy::Fail, ..SessionConfig::default() };
+    // Nobody connects, so the send thread never takes anything off the channel
+    let (mut acceptor, sender, _receiver) = FixEngineFactory::create_acceptor_with_capacity("127.0.0.1:0", config, 2).unwrap();
+    assert_eq!(acceptor.outgoing_queue_len(), Some(0));
+
+    sender.send(create_new_order_single()).unwrap();
+    sender.send(create_new_order_single()).unwrap();
+    assert!(matches!(sender.send(create_new_order_single()), Err(TrySendError::Full(_))));
+    assert_eq!(acceptor.outgoing_queue_len(), Some(2));
+
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_send_thread_stops_when_the_outgoing_sender_is_dropped() {
+    for logout_on_outgoing_closed in [false, true] {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let acceptor_stream = listener.accept().unwrap().0;
+        peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let config = SessionConfig { logout_on_outgoing_closed, ..SessionConfig::default() };
+        let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, config);
+        let (sender, outgoing) = channel();
+        let (incoming, _receiver) = channel();
+        acceptor.start(acceptor_stream, outgoing, incoming).unwrap();
+        write_message(&mut peer, peer_message("A", 1));
+        read_message(&mut peer);
+        wait_for_state(&acceptor, SessionState::LoggedOn);
+
+        drop(sender);
+        for _ in 0..200 {
+            if !acceptor.is_sending() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(!acceptor.is_sending(), "send thread should exit once the sender is gone");
+
+        if logout_on_outgoing_closed {
+            let logout = read_message(&mut peer);
+            assert_eq!(logout.header.get("35").unwrap(), "5");
+            assert_eq!(acceptor.state(), SessionState::LogoutSent);
+        } else {
+            assert_eq!(acceptor.state(), SessionState::LoggedOn);
+        }
+        acceptor.shutdown();
+    }
+}
+
+#[test]
+fn test_end_of_session_logs_out_and_resets_sequence_numbers() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let mut peer = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    // The clock starts at 12:30 UTC, half an hour before the session closes
+    let clock = create_manual_clock();
+    let config = SessionConfig { schedule: Some(SessionSchedule::new(time(8, 0), time(13, 0))), ..SessionConfig::default() };
+    let mut initiator = FixEngine::new(clock.clone(), FixEngineMode::Initiator, config);
+    let (_sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    initiator.start(initiator_stream, outgoing, incoming).unwrap();
+
+    assert_eq!(read_message(&mut peer).header.get("35").unwrap(), "A");
+    write_message(&mut peer, peer_message("A", 1));
+    wait_for_state(&initiator, SessionState::LoggedOn);
+    assert_eq!(initiator.next_sender_seq_num(), 2);
+
+    clock.advance(Duration::from_secs(31 * 60));
+    let logout = read_message(&mut peer);
+    assert_eq!(logout.header.get("35").unwrap(), "5");
+    assert_eq!(logout.body.get("58").unwrap(), "End of session");
+    wait_for_state(&initiator, SessionState::Disconnected);
+    assert_eq!(initiator.next_sender_seq_num(), 1);
+    initiator.shutdown();
+}
+
+#[test]
+fn test_logon_outside_session_time_is_refused() {
+    // The fixed clock reads 12:30 UTC, before this session opens
+    let config = SessionConfig { schedule: Some(SessionSchedule::new(time(13, 0), time(17, 0))), ..SessionConfig::default() };
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let _initiator_peer = listener.accept().unwrap().0;
+    let (_sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, config.clone());
+    assert!(initiator.start(initiator_stream, outgoing, incoming).is_err());
+    assert_eq!(initiator.state(), SessionState::Disconnected);
+
+    let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, config);
+    let events = acceptor.take_events().unwrap();
+    let (_sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    acceptor.start(acceptor_stream, outgoing, incoming).unwrap();
+
+    write_message(&mut peer, peer_message("A", 1));
+    let logout = read_message(&mut peer);
+    assert_eq!(logout.header.get("35").unwrap(), "5");
+    assert_eq!(logout.body.get("58").unwrap(), "Logon outside of session time");
+    assert!(matches!(next_event(&events), EngineEvent::Error(EngineError::OutsideSessionTime)));
+    wait_for_state(&acceptor, SessionState::Disconnected);
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_sequence_reset_gap_fill_and_hard_reset() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, SessionConfig::default());
+    let (_sender, outgoing) = channel();
+    let (incoming, receiver) = channel();
+    acceptor.start(acceptor_stream, outgoing, incoming).unwrap();
+
+    write_message(&mut peer, peer_message("A", 1));
+    read_message(&mut peer);
+    let sequence_reset = |seq_num: u64, new_seq_no: &str, gap_fill: bool| {
+        let mut reset = peer_message("4", seq_num);
+        reset.body.insert("36".to_string(), new_seq_no.to_string());
+        if gap_fill {
+            reset.body.insert("123".to_string(), "Y".to_string());
+        }
+        reset
+    };
+    let next_delivered = || receiver.recv_timeout(Duration::from_secs(5)).unwrap().header.get("34").unwrap().clone();
+
+    // GapFill skips 2 to 4 without a ResendRequest
+    write_message(&mut peer, sequence_reset(2, "5", true));
+    write_message(&mut peer, peer_message("D", 5));
+    assert_eq!(next_delivered(), "5");
+
+    // A hard reset applies even backwards and regardless of its own MsgSeqNum
+    write_message(&mut peer, sequence_reset(99, "3", false));
+    write_message(&mut peer, peer_message("D", 3));
+    assert_eq!(next_delivered(), "3");
+
+    // A GapFill may not lower the expected number
+    write_message(&mut peer, sequence_reset(4, "2", true));
+    let reject = read_message(&mut peer);
+    assert_eq!(reject.header.get("35").unwrap(), "3");
+    assert_eq!(reject.body.get("45").unwrap(), "4");
+    assert_eq!(reject.body.get("373").unwrap(), "5");
+    write_message(&mut peer, peer_message("D", 5));
+    assert_eq!(next_delivered(), "5");
+    assert!(receiver.try_recv().is_err(), "SequenceResets never reach the application");
+
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_invalid_messages_are_rejected_and_use_up_their_seq_num() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, SessionConfig::default());
+    let events = acceptor.take_events().unwrap();
+    let (_sender, outgoing) = channel();
+    let (incoming, receiver) = channel();
+    acceptor.start(acceptor_stream, outgoing, incoming).unwrap();
+
+    write_message(&mut peer, peer_message("A", 1));
+    read_message(&mut peer);
+
+    let mut missing_msg_type = peer_message("D", 2);
+    missing_msg_type.header.remove("35");
+    write_message(&mut peer, missing_msg_type);
+    write_message(&mut peer, peer_message("Z?", 3));
+    let mut bad_sending_time = peer_message("D", 4);
+    bad_sending_time.header.insert("52".to_string(), "yesterday".to_string());
+    write_message(&mut peer, bad_sending_time);
+    let garbled = peer_message("D", 5).encode(&create_fixed_clock()).replace("10=", "10=x");
+    peer.write_all(garbled.as_bytes()).unwrap();
+
+    let expected = [("2", Some("35"), "1"), ("3", Some("35"), "11"), ("4", Some("52"), "6"), ("5", None, "6")];
+    for (ref_seq_num, ref_tag_id, reason) in expected {
+        let reject = read_message(&mut peer);
+        assert_eq!(reject.header.get("35").unwrap(), "3");
+        assert_eq!(reject.body.get("45").unwrap(), ref_seq_num);
+        assert_eq!(reject.body.get("371").map(String::as_str), ref_tag_id);
+        assert_eq!(reject.body.get("373").unwrap(), reason);
+        assert!(reject.body.contains_key("58"));
+    }
+
+    // The rejected messages used up 2 to 5, so 6 is delivered; a well-formed type the engine has no name for
+    // reaches the application too
+    write_message(&mut peer, peer_message("D", 6));
+    assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap().header.get("34").unwrap(), "6");
+    write_message(&mut peer, peer_message("AB", 7));
+    assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap().header.get("35").unwrap(), "AB");
+    assert!(receiver.try_recv().is_err());
+
+    let rejected: Vec<String> = events.try_iter()
+        .filter_map(|event| match event {
+            EngineEvent::MessageRejected { raw, .. } => Some(raw),
+            EngineEvent::DecodeFailed { raw, error } => {
+                assert!(matches!(error, DecodeError::MalformedChecksum { .. }), "{:?}", error);
+                assert_eq!(raw, garbled);
+                None
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(rejected.len(), 4);
+    assert_eq!(rejected[3], garbled);
+
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_empty_value_is_rejected_naming_the_tag() {
+    let (mut acceptor, mut peer, events) = logged_on_acceptor(SessionConfig::default());
+    let mut order = peer_message("D", 2);
+    order.body.insert("55".to_string(), String::new());
+    write_message(&mut peer, order);
+
+    let reject = read_message(&mut peer);
+    assert_eq!(reject.header.get("35").unwrap(), "3");
+    assert_eq!(reject.body.get("45").unwrap(), "2");
+    assert_eq!(reject.body.get("371").unwrap(), "55");
+    assert_eq!(reject.body.get("373").unwrap(), "4");
+    assert!(events.try_iter().any(|event| matches!(event, EngineEvent::DecodeFailed { error: DecodeError::EmptyValue { tag: 55 }, .. })));
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_unreadable_msg_seq_num_is_reported_with_its_raw_value() {
+    let (mut acceptor, mut peer, events) = logged_on_acceptor(SessionConfig::default());
+    let encoded = peer_message("D", 2).encode(&create_fixed_clock());
+    // Same length, so only the MsgSeqNum is wrong; with no number to name, it can't be rejected
+    let garbled = encoded.replacen("\x0134=2\x01", "\x0134=x\x01", 1);
+    let body = &garbled[..garbled.rfind("10=").unwrap()];
+    let checksum = body.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+    peer.write_all(format!("{}10={:03}\x01", body, checksum).as_bytes()).unwrap();
+    write_message(&mut peer, peer_message("1", 2));
+
+    let heartbeat = read_message(&mut peer);
+    assert_eq!(heartbeat.header.get("35").unwrap(), "0");
+    assert!(events.try_iter().any(|event| matches!(
+        &event,
+        EngineEvent::DecodeFailed { error: DecodeError::InvalidSeqNum { raw }, .. } if raw == "x"
+    )));
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_wrong_body_length_is_rejected_naming_body_length() {
+    let (mut acceptor, mut peer, events) = logged_on_acceptor(SessionConfig::default());
+    let encoded = peer_message("D", 2).encode(&create_fixed_clock());
+    let body_length: usize = encoded.split('\x01').nth(1).unwrap().strip_prefix("9=").unwrap().parse().unwrap();
+    let short = encoded.replacen(&format!("\x019={}\x01", body_length), &format!("\x019={}\x01", body_length - 1), 1);
+    peer.write_all(short.as_bytes()).unwrap();
+
+    let reject = read_message(&mut peer);
+    assert_eq!(reject.header.get("35").unwrap(), "3");
+    assert_eq!(reject.body.get("45").unwrap(), "2");
+    assert_eq!(reject.body.get("371").unwrap(), "9");
+    assert_eq!(reject.body.get("373").unwrap(), "6");
+    let declared = body_length - 1;
+    assert!(events.try_iter().any(|event| matches!(event,
+        EngineEvent::DecodeFailed { error: DecodeError::BodyLengthMismatch { declared: d, actual }, .. } if d == declared && actual == body_length)));
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_stale_sending_time_is_rejected_then_logged_out() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, SessionConfig::default());
+    let events = acceptor.take_events().unwrap();
+    let (_sender, outgoing) = channel();
+    let (incoming, receiver) = channel();
+    acceptor.start(acceptor_stream, outgoing, incoming).unwrap();
+
+    write_message(&mut peer, peer_message("A", 1));
+    read_message(&mut peer);
+
+    // A minute off is within the default two minute window
+    let mut late = peer_message("D", 2);
+    late.header.insert("52".to_string(), "20231016-12:29:00.123".to_string());
+    write_message(&mut peer, late);
+    assert!(receiver.recv_timeout(Duration::from_secs(5)).is_ok());
+
+    let mut stale = peer_message("D", 3);
+    stale.header.insert("52".to_string(), "20231016-12:00:00.000".to_string());
+    write_message(&mut peer, stale);
+
+    let reject = read_message(&mut peer);
+    assert_eq!(reject.header.get("35").unwrap(), "3");
+    assert_eq!(reject.body.get("45").unwrap(), "3");
+    assert_eq!(reject.body.get("371").unwrap(), "52");
+    assert_eq!(reject.body.get("373").unwrap(), "10");
+    assert_eq!(read_message(&mut peer).header.get("35").unwrap(), "5");
+
+    assert!(matches!(next_event(&events), EngineEvent::MessageRejected { .. }));
+    match next_event(&events) {
+        EngineEvent::Error(EngineError::SendingTimeAccuracy { sending_time }) => assert_eq!(sending_time, "20231016-12:00:00.000"),
+        other => panic!("Unexpected event {:?}", other),
+    }
+    assert!(receiver.try_recv().is_err());
+    acceptor.shutdown();
+}
+
+#[test]
+fn test_initiator_logon_resets_sequence_numbers() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let mut peer = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let config = SessionConfig { logon: LogonConfig { reset_seq_num: true, ..LogonConfig::default() }, ..SessionConfig::default() };
+    let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, config);
+    let (sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    initiator.start(initiator_stream, outgoing, incoming).unwrap();
+
+    let logon = read_message(&mut peer);
+    assert_eq!(logon.header.get("34").unwrap(), "1");
+    assert_eq!(logon.body.get("141").unwrap(), "Y");
+    let mut reply = peer_message("A", 1);
+    reply.body.insert("141".to_string(), "Y".to_string());
+    write_message(&mut peer, reply);
+    wait_for_state(&initiator, SessionState::LoggedOn);
+
+    sender.send(create_new_order_single()).unwrap();
+    assert_eq!(read_message(&mut peer).header.get("34").unwrap(), "2");
+    initiator.shutdown();
+}
+
+#[test]
+fn test_initiator_logon_follows_the_logon_config() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let mut peer = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut store = MemoryMessageStore::new();
+    store.set_next_sender_seq(5).unwrap();
+    let logon_config = LogonConfig { encrypt_method: EncryptMethod::None, reset_seq_num: true, heart_bt_int: 45 };
+    let config = SessionConfig { logon: logon_config, ..SessionConfig::default() };
+    let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, config);
+    initiator.set_message_store(Box::new(store));
+    let (_sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    initiator.start(initiator_stream, outgoing, incoming).unwrap();
+
+    // Our own outbound sequence starts again from 1 along with the counterparty's
+    let logon = read_message(&mut peer);
+    assert_eq!(logon.header.get("34").unwrap(), "1");
+    assert_eq!(logon.body.get("141").unwrap(), "Y");
+    assert_eq!(logon.body.get("108").unwrap(), "45");
+    assert_eq!(logon.body.get("98").unwrap(), "0");
+    assert_eq!(initiator.next_sender_seq_num(), 2);
+    initiator.shutdown();
+}
+
+#[test]
+fn test_unsupported_encrypt_method_fails_to_start() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let mut peer = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let config = SessionConfig { logon: LogonConfig { encrypt_method: EncryptMethod::Des, ..LogonConfig::default() }, ..SessionConfig::default() };
+    let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, config);
+    let (_sender, outgoing) = channel();
+    let (incoming, _receiver) = channel();
+    let error = initiator.start(initiator_stream, outgoing, incoming).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+    assert!(error.to_string().contains("EncryptMethod 2 is not supported"), "{}", error);
+
+    // Nothing was advertised before the connection was dropped
+    let mut buf = [0u8; 64];
+    assert_eq!(peer.read(&mut buf).unwrap(), 0);
+    assert_eq!(initiator.state(), SessionState::Disconnected);
+}
+
+#[test]
+fn test_peer_logout_is_confirmed_and_ends_the_session() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let mut peer = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, SessionConfig::default());
+    let events = initiator.take_events().unwrap();
+    let (_sender, outgoing) = channel();
+    let (incoming, receiver) = channel();
+    initiator.start(initiator_stream, outgoing, incoming).unwrap();
+
+    read_message(&mut peer);
+    write_message(&mut peer, peer_logon(30));
+    wait_for_state(&initiator, SessionState::LoggedOn);
+
+    let mut logout = peer_message("5", 2);
+    logout.body.insert("58".to_string(), "End of day".to_string());
+    write_message(&mut peer, logout);
+
+    let confirmation = read_message(&mut peer);
+    assert_eq!(confirmation.header.get("35").unwrap(), "5");
+    assert_eq!(confirmation.header.get("34").unwrap(), "2");
+    assert!(!confirmation.body.contains_key("58"));
+    assert_eq!(peer.read(&mut [0; 1]).unwrap(), 0, "connection should be closed");
+
+    match next_event(&events) {
+        EngineEvent::LoggedOut { text } => assert_eq!(text.as_deref(), Some("End of day")),
+        other => panic!("Unexpected event {:?}", other),
+    }
+    assert!(matches!(next_event(&events), EngineEvent::Disconnected { reason: DisconnectReason::Logout }));
+    assert_eq!(initiator.state(), SessionState::Disconnected);
+    assert!(receiver.try_recv().is_err(), "The Logout is not an application message");
+
+    initiator.shutdown();
+}
+
+#[test]
+fn test_acceptor_honours_reset_logon_and_manual_reset() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, SessionConfig::default());
+    let (sender, outgoing) = channel();
+    let (incoming, receiver) = channel();
+    acceptor.start(acceptor_stream, outgoing, incoming).unwrap();
+
+    let mut logon = peer_message("A", 1);
+    logon.body.insert("141".to_string(), "Y".to_string());
+    write_message(&mut peer, logon);
+    let reply = read_message(&mut peer);
+    assert_eq!(reply.header.get("34").unwrap(), "1");
+    assert_eq!(reply.body.get("141").unwrap(), "Y");
+
+    write_message(&mut peer, peer_message("D", 2));
+    assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap().header.get("34").unwrap(), "2");
+    sender.send(create_execution_report()).unwrap();
+    assert_eq!(read_message(&mut peer).header.get("34").unwrap(), "2");
+
+    // Both directions start again from 1 after an out-of-band reset
+    acceptor.reset_sequence_numbers();
+    assert_eq!(acceptor.next_sender_seq_num(), 1);
+    write_message(&mut peer, peer_message("D", 1));
+    assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap().header.get("34").unwrap(), "1");
+    sender.send(create_execution_report()).unwrap();
+    assert_eq!(read_message(&mut peer).header.get("34").unwrap(), "1");
+    acceptor.shutdown();
+}
+
+#[derive(Default)]
+struct CountingObserver {
+    sent: AtomicUsize,
+    received: AtomicUsize,
+    disconnects: AtomicUsize,
+}
+
+impl EngineObserver for CountingObserver {
+    fn on_sent(&self, _message: &FixMessage) {
+        self.sent.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn on_received(&self, _message: &FixMessage) {
+        self.received.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn on_state_change(&self, state: SessionState) {
+        if state == SessionState::Disconnected {
+            self.disconnects.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+#[test]
+fn test_observer_counts_sent_and_received_messages() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+
+    let initiator_observer = Arc::new(CountingObserver::default());
+    let acceptor_observer = Arc::new(CountingObserver::default());
+
+    let mut initiator = FixEngine::with_observer(create_fixed_clock(), FixEngineMode::Initiator, SessionConfig::new("INITIATOR", "ACCEPTOR"), initiator_observer.clone());
+    let mut acceptor = FixEngine::with_observer(create_fixed_clock(), FixEngineMode::Acceptor, SessionConfig::new("ACCEPTOR", "INITIATOR"), acceptor_observer.clone());
+
+    let (initiator_sender, initiator_outgoing) = channel();
+    let (initiator_incoming, initiator_receiver) = channel();
+    let (acceptor_sender, acceptor_outgoing) = channel();
+    let (acceptor_incoming, acceptor_receiver) = channel();
+    initiator.start(initiator_stream, initiator_outgoing, initiator_incoming).unwrap();
+    acceptor.start(acceptor_stream, acceptor_outgoing, acceptor_incoming).unwrap();
+
+    initiator_sender.send(create_new_order_single()).unwrap();
+    acceptor_receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    acceptor_sender.send(create_execution_report()).unwrap();
+    initiator_receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+
+    // Each side also sees the logon exchange
+    assert_eq!(initiator_observer.sent.load(Ordering::SeqCst), 2);
+    assert_eq!(initiator_observer.received.load(Ordering::SeqCst), 2);
+
+    initiator.shutdown();
+    wait_for_state(&acceptor, SessionState::Disconnected);
+    acceptor.shutdown();
+
+    // The initiator logged out as it shut down, and the acceptor confirmed
+    assert_eq!(initiator_observer.sent.load(Ordering::SeqCst), 3);
+    assert_eq!(initiator_observer.received.load(Ordering::SeqCst), 3);
+    assert_eq!(acceptor_observer.sent.load(Ordering::SeqCst), 3);
+    assert_eq!(acceptor_observer.received.load(Ordering::SeqCst), 3);
+    assert_eq!(initiator_observer.disconnects.load(Ordering::SeqCst), 1);
+}
+
+// Records each callback with the MsgType it was given; to_app refuses orders marked with Text(58)=HOLD
+#[derive(Default)]
+struct RecordingApplication {
+    calls: Mutex<Vec<String>>,
+}
+
+impl RecordingApplication {
+    fn record(&self, callback: &str, message: Option<&FixMessage>) {
+        let call = match message {
+            Some(message) => format!("{} {}", callback, message.header.get("35").unwrap()),
+            None => callback.to_string(),
+        };
+        self.calls.lock().unwrap().push(call);
+    }
+
+    fn wait_for(&self, call: &str) {
+        for _ in 0..500 {
+            if self.calls.lock().unwrap().iter().any(|recorded| recorded == call) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("Application never saw {:?}", call);
+    }
+
+    fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+impl FixApplication for RecordingApplication {
+    fn on_logon(&self, _session_id: &SessionID) {
+        self.record("on_logon", None);
+    }
+
+    fn on_logout(&self, _session_id: &SessionID) {
+        self.record("on_logout", None);
+    }
+
+    fn to_admin(&self, message: &mut FixMessage, _session_id: &SessionID) {
+        self.record("to_admin", Some(message));
+    }
+
+    fn from_admin(&self, message: &FixMessage, _session_id: &SessionID) {
+        self.record("from_admin", Some(message));
+    }
+
+    fn to_app(&self, message: &mut FixMessage, _session_id: &SessionID) -> Result<(), DoNotSend> {
+        self.record("to_app", Some(message));
+        match message.body.get("58").map(String::as_str) {
+            Some("HOLD") => Err(DoNotSend),
+            _ => Ok(()),
+        }
+    }
+
+    fn from_app(&self, message: &FixMessage, _session_id: &SessionID) {
+        self.record("from_app", Some(message));
+    }
+
+    fn on_decode_error(&self, _raw: &str, error: &DecodeError, _session_id: &SessionID) {
+        self.record(&format!("on_decode_error {:?}", error), None);
+    }
+}
+
+#[test]
+fn test_application_callbacks_during_logon_and_order_exchange() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let initiator_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+
+    let initiator_application = Arc::new(RecordingApplication::default());
+    let acceptor_application = Arc::new(RecordingApplication::default());
+    let mut initiator = FixEngine::new(create_fixed_clock(), FixEngineMode::Initiator, SessionConfig::new("INITIATOR", "ACCEPTOR"));
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, SessionConfig::new("ACCEPTOR", "INITIATOR"));
+    let initiator_sender = initiator.start_with_application(initiator_stream, initiator_application.clone()).unwrap();
+    let acceptor_sender = acceptor.start_with_application(acceptor_stream, acceptor_application.clone()).unwrap();
+
+    initiator_application.wait_for("on_logon");
+    let mut held = create_new_order_single();
+    held.body.insert("58".to_string(), "HOLD".to_string());
+    initiator_sender.send(held).unwrap();
+    initiator_sender.send(create_new_order_single()).unwrap();
+
+    acceptor_application.wait_for("from_app D");
+    acceptor_sender.send(create_execution_report()).unwrap();
+    initiator_application.wait_for("from_app 8");
+
+    initiator.shutdown();
+    wait_for_state(&acceptor, SessionState::Disconnected);
+    acceptor.shutdown();
+
+    assert_eq!(initiator_application.calls(), ["to_admin A", "from_admin A", "on_logon", "to_app D", "to_app D", "from_app 8", "on_logout", "to_admin 5", "from_admin 5"]);
+    assert_eq!(acceptor_application.calls(), ["from_admin A", "to_admin A", "on_logon", "from_app D", "to_app 8", "from_admin 5", "to_admin 5", "on_logout"]);
+    // The refused order never took a MsgSeqNum; the Logout took 3
+    assert_eq!(initiator.next_sender_seq_num(), 4);
+}
+
+#[test]
+fn test_application_hears_of_a_message_that_cannot_be_decoded() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let acceptor_stream = listener.accept().unwrap().0;
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let application = Arc::new(RecordingApplication::default());
+    let mut acceptor = FixEngine::new(create_fixed_clock(), FixEngineMode::Acceptor, SessionConfig::default());
+    let _sender = acceptor.start_with_application(acceptor_stream, application.clone()).unwrap();
+    write_message(&mut peer, peer_message("A", 1));
+    read_message(&mut peer);
+
+    let garbled = peer_message("D", 2).encode(&create_fixed_clock()).replace("10=", "10=x");
+    peer.write_all(garbled.as_bytes()).unwrap();
+    let checksum = garbled.rsplit("10=").next().unwrap().trim_end_matches('\x01');
+    application.wait_for(&format!("on_decode_error {:?}", DecodeError::MalformedChecksum { raw: checksum.to_string() }));
+    assert_eq!(read_message(&mut peer).header.get("35").unwrap(), "3");
+    assert!(!application.calls().iter().any(|call| call.starts_with("from_app")));
+
+    acceptor.shutdown();
+}
+
+fn read_message(stream: &mut TcpStream) -> FixMessage {
+    let mut buffer = Vec::new();
+    let mut byte = [0; 1];
+    // Read up to the SOH that terminates the checksum field
+    while !String::from_utf8_lossy(&buffer).rsplit('\x01').nth(1).is_some_and(|field| field.starts_with("10=")) {
+        stream.read_exact(&mut byte).unwrap();
+        buffer.push(byte[0]);
+    }
+    FixMessage::decode(&String::from_utf8(buffer).unwrap()).unwrap()
+}
+
+fn write_message(stream: &mut TcpStream, mut message: FixMessage) {
+    stream.write_all(message.encode(&create_fixed_clock()).as_bytes()).unwrap();
+}
+
+// A message as a hand-driven counterparty would send it
+fn peer_message(msg_type: &str, seq_num: u64) -> FixMessage {
+    let mut message = FixMessage::new();
+    message.header.insert("35".to_string(), msg_type.to_string());
+    message.header.insert("49".to_string(), "PEER".to_string());
+    message.header.insert("56".to_string(), "ENGINE".to_string());
+    message.header.insert("34".to_string(), seq_num.to_string());
+    message
+}
+
+// A logon as the hand-driven counterparty would send it, carrying HeartBtInt(108)
+fn peer_logon(heart_bt_int: u64) -> FixMessage {
+    let mut logon = peer_message("A", 1);
+    logon.body.insert("108".to_string(), heart_bt_int.to_string());
+    logon
+}
+
+// Next event after the Connected and LoggedOn every session starts with, skipping state transitions
+fn next_event(events: &Receiver<EngineEvent>) -> EngineEvent {
+    loop {
+        match events.recv_timeout(Duration::from_secs(5)).unwrap() {
+            EngineEvent::Connected { .. } | EngineEvent::LoggedOn | EngineEvent::StateChanged { .. } => continue,
+            event => return event,
+        }
+    }
+}
+
+fn time(hour: u32, minute: u32) -> NaiveTime {
+    NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+}
+
+fn wait_for_state(engine: &FixEngine, state: SessionState) {
+    for _ in 0..500 {
+        if engine.state() == state {
+            return;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    panic!("Engine never reached {:?}", state);
 }
 
-fn create_logon_message() -> FixMessage {
+fn create_new_order_single() -> FixMessage {
     let fixed_clock = create_fixed_clock();
     let mut msg = FixMessage::new();
     msg.header.insert("8".to_string(), "FIX.4.4".to_string());  // BeginString
-    msg.header.insert("35".to_string(), "A".to_string());       // MsgType (Logon)
+    msg.header.insert("35".to_string(), "D".to_string());       // MsgType (NewOrderSingle)
     msg.header.insert("49".to_string(), "INITIATOR".to_string());  // SenderCompID
     msg.header.insert("56".to_string(), "ACCEPTOR".to_string());  // TargetCompID
-    msg.header.insert("34".to_string(), "1".to_string());       // MsgSeqNum
+    msg.header.insert("34".to_string(), "2".to_string());       // MsgSeqNum
     msg.header.insert("52".to_string(), fixed_clock.now());     // SendingTime
     msg
 }
@@ -0,0 +1,30 @@
+use fix_engine_2::tag::{CompID, FixField, FixTag, PossDupFlag};
+use std::str::FromStr;
+
+#[test]
+fn test_comp_id_can_be_constructed_outside_the_crate() {
+    let sender = FixTag::SenderCompID(CompID::new("SENDER".to_string()).unwrap());
+    assert_eq!(sender.tag_id(), "49");
+    assert_eq!(sender.value(), "SENDER");
+
+    let target = CompID::new("TARGET".to_string()).unwrap();
+    assert_eq!(target.to_string(), "TARGET");
+    assert_eq!(FixTag::TargetCompID(target).tag_id(), "56");
+}
+
+#[test]
+fn test_comp_id_rejects_invalid_values() {
+    assert!(CompID::new("".to_string()).is_err());
+    assert!(CompID::new("SEN\x01DER".to_string()).is_err());
+    assert!(CompID::new("SEN=DER".to_string()).is_err());
+}
+
+#[test]
+fn test_poss_dup_flag_can_be_parsed_outside_the_crate() {
+    assert_eq!(PossDupFlag::from_str("Y"), Ok(PossDupFlag::Yes));
+    assert_eq!("N".parse::<PossDupFlag>(), Ok(PossDupFlag::No));
+    assert!(PossDupFlag::from_str("X").is_err());
+
+    assert_eq!(PossDupFlag::Yes.to_string(), "Y");
+    assert_eq!(FixTag::PossDupFlag(PossDupFlag::No).value(), "N");
+}